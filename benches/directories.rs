@@ -0,0 +1,155 @@
+// timings for reading a large directory, as a baseline for work on the
+// shell's directory code (see "support large directories" in main.rs):
+//
+//     cargo bench --bench directories
+//     EXT2_BENCH_ENTRIES=20000 cargo bench --bench directories
+//
+// the image is built by mke2fs from a tree with one big directory. the
+// library's `Volume` is timed in process, the shell's `ls`, `cd` and `stat`
+// (read_dir_inode and resolve) by running it with --time. a directory
+// bigger than its direct blocks is where the shell stops, lookups past them
+// fail and are reported as such
+
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const BLOCK_SIZE: usize = 4096;
+
+fn main() {
+    let entries: usize = std::env::var("EXT2_BENCH_ENTRIES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(5000);
+    let dir = std::env::temp_dir().join(format!("ext2-bench-{}", std::process::id()));
+    let tree = dir.join("tree");
+    fs::create_dir_all(tree.join("big")).unwrap();
+    for i in 1..=entries {
+        fs::write(tree.join(format!("big/entry-{}", i)), b"").unwrap();
+    }
+    let image = dir.join("bench.ext2");
+    // a block per 16 entries is plenty, on top of the inode tables
+    let size = format!("{}k", 16 * 1024 + entries / 4);
+    let made = Command::new("mke2fs")
+        .args(["-q", "-F", "-t", "ext2", "-b", &BLOCK_SIZE.to_string()])
+        .args(["-N", &(entries + 64).to_string()])
+        .arg("-d")
+        .arg(&tree)
+        .arg(&image)
+        .arg(&size)
+        .output();
+    if !made.is_ok_and(|output| output.status.success()) {
+        eprintln!("mke2fs not available or failed, nothing to measure");
+        fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    let bytes = fs::read(&image).unwrap();
+    let volume = Volume::open(&bytes[..]).unwrap();
+    let big = volume.resolve(b"/big").unwrap();
+    println!(
+        "{} entries in /big, {} byte blocks, {} bytes of directory",
+        entries,
+        BLOCK_SIZE,
+        volume.metadata(big).unwrap().size
+    );
+
+    let first = b"/big/entry-1".to_vec();
+    let last = format!("/big/entry-{}", entries).into_bytes();
+    measure("Volume::read_dir /big", 100, || {
+        volume.read_dir(big).unwrap();
+    });
+    measure("Volume::resolve first entry", 100, || {
+        volume.resolve(&first).unwrap();
+    });
+    measure("Volume::resolve last entry", 100, || {
+        volume.resolve(&last).unwrap();
+    });
+    measure("Volume::lookup missing name", 100, || {
+        assert!(volume.lookup(big, b"missing").is_err());
+    });
+    measure("Volume::read_dir /", 1000, || {
+        volume.read_dir(InodeNo::ROOT).unwrap();
+    });
+
+    shell(&image, "ls /big", 20);
+    shell(&image, "cd /big", 20);
+    shell(&image, "stat /big/entry-1", 20);
+    shell(&image, &format!("stat /big/entry-{}", entries), 20);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// run `f` `runs` times and print the time each run took on average
+fn measure(name: &str, runs: u32, mut f: impl FnMut()) {
+    // one run first, so caches are warm for all the timed ones
+    f();
+    let started = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    report(name, runs, started.elapsed() / runs);
+}
+
+// run `command` `runs` times in one shell with --time, and average the
+// times it prints. the shell's output is part of what's timed, as it is
+// for anyone using it
+fn shell(image: &Path, command: &str, runs: u32) {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_ext2"))
+        .arg("--time")
+        .arg(image)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let script = format!("{}\n", command).repeat(runs as usize);
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = shell.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let times: Vec<Duration> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("time: "))
+        .filter_map(|line| line.split(" took ").nth(1))
+        .filter_map(parse_duration)
+        .collect();
+    if times.is_empty() {
+        println!("shell {:<32} no timings, did the shell start?", command);
+        return;
+    }
+    let name = format!("shell {}", command);
+    let failed = stdout.lines().any(|line| line.starts_with("unable to"));
+    report(
+        &name,
+        times.len() as u32,
+        times.iter().sum::<Duration>() / times.len() as u32,
+    );
+    if failed {
+        println!("    (the command failed)");
+    }
+}
+
+fn report(name: &str, runs: u32, per_run: Duration) {
+    println!("{:<40} {:>6} runs {:>12.3?} per run", name, runs, per_run);
+}
+
+// a Duration as its Debug impl prints it, e.g. 6.549ms
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| c.is_alphabetic())?;
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let scale = match unit {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "µs" => 1e-6,
+        "ns" => 1e-9,
+        _ => return None,
+    };
+    Some(Duration::from_secs_f64(number * scale))
+}
@@ -0,0 +1,124 @@
+use crate::{now, Ext2};
+use ext2::error::Ext2Error;
+use ext2::metadata::FileType;
+use ext2::structs::{Inode, InodeNo};
+
+impl Ext2 {
+    /// Walk the data blocks of `node`, see `ext2::volume::BlockPtrIter`
+    pub fn block_ptrs<'a>(
+        &'a self,
+        node: &'a Inode,
+    ) -> impl Iterator<Item = Result<(usize, usize), Ext2Error>> + 'a {
+        self.volume
+            .block_ptrs(node)
+            .map(|pointer| pointer.map(|(logical, block)| (logical as usize, block as usize)))
+    }
+
+    // write block number `pointer` into slot `slot` of indirect block `block`
+    pub(crate) fn set_pointer(&mut self, block: usize, slot: usize, pointer: usize) {
+        self.block_mut(block)[slot * 4..slot * 4 + 4]
+            .copy_from_slice(&(pointer as u32).to_le_bytes());
+    }
+
+    // one more block in the sectors of `inode`
+    fn count_block(&mut self, inode: InodeNo) -> Result<(), Ext2Error> {
+        let block_size = self.block_size as u32;
+        self.volume.update_inode(inode, |node| {
+            let sectors = node.sectors_count.get();
+            node.sectors_count.set(sectors + block_size / 512);
+        })
+    }
+
+    // a new block for `inode`, counted in its sectors
+    fn allocate_block_for(&mut self, inode: InodeNo) -> Result<usize, Ext2Error> {
+        let block = self.allocate_block()?;
+        self.count_block(inode)?;
+        Ok(block)
+    }
+
+    // the block number in slot `slot` of indirect block `block`
+    pub(crate) fn pointer_at(&self, block: usize, slot: usize) -> Result<usize, Ext2Error> {
+        let pointer = &self.block(block)?[slot * 4..][..4];
+        Ok(u32::from_le_bytes(pointer.try_into().unwrap()) as usize)
+    }
+
+    // make logical block `logical` of `inode` be `block`, allocating the
+    // indirect blocks on the way down to it that aren't there yet
+    pub fn set_block_number(
+        &mut self,
+        inode: InodeNo,
+        logical: usize,
+        block: usize,
+    ) -> Result<(), Ext2Error> {
+        if logical < 12 {
+            return self
+                .volume
+                .update_inode(inode, |node| node.direct_pointer[logical].set(block as u32));
+        }
+        let Some((tree, path, slot)) = self.volume.indirect_path(logical as u64) else {
+            return Err(Ext2Error::Unsupported(format!(
+                "logical block {}, past what a triply indirect block reaches",
+                logical
+            )));
+        };
+        let node = self.get_inode(inode)?;
+        let mut parent = match tree {
+            1 => node.indirect_pointer.get(),
+            2 => node.doubly_indirect.get(),
+            _ => node.triply_indirect.get(),
+        } as usize;
+        if parent == 0 {
+            parent = self.allocate_block_for(inode)?;
+            self.volume.update_inode(inode, |node| match tree {
+                1 => node.indirect_pointer.set(parent as u32),
+                2 => node.doubly_indirect.set(parent as u32),
+                _ => node.triply_indirect.set(parent as u32),
+            })?;
+        }
+        for index in path {
+            let mut next = self.pointer_at(parent, index)?;
+            if next == 0 {
+                next = self.allocate_block_for(inode)?;
+                self.set_pointer(parent, index, next);
+            }
+            parent = next;
+        }
+        self.set_pointer(parent, slot, block);
+        Ok(())
+    }
+
+    /// Give the empty regular file `inode` the contents `data`, going
+    /// through the indirect blocks past the 12 direct ones
+    pub fn write_file_bytes(&mut self, inode: InodeNo, data: &[u8]) -> Result<(), Ext2Error> {
+        self.check_flags(inode, &inode.to_string(), true)?;
+        let node = self.get_inode(inode)?;
+        if FileType::from_mode(node.type_perm.get()) != FileType::Regular {
+            return Err(Ext2Error::Unsupported(
+                "writing data to anything but a regular file".to_string(),
+            ));
+        }
+        if node.size() != 0 || node.sectors_count.get() != 0 {
+            return Err(Ext2Error::Unsupported(
+                "writing over a file's data".to_string(),
+            ));
+        }
+        // everything allocated hangs from the inode as soon as it is, so
+        // on an error freeing the inode gives it all back
+        for (logical, chunk) in data.chunks(self.block_size).enumerate() {
+            let block = self.allocate_block()?;
+            if let Err(e) = self.set_block_number(inode, logical, block) {
+                self.free_block(block)?;
+                return Err(e);
+            }
+            self.count_block(inode)?;
+            // allocate_block zeroed it, so the end of the last one is zeros
+            self.block_mut(block)[..chunk.len()].copy_from_slice(chunk);
+        }
+        let now = now();
+        self.volume.update_inode(inode, |node| {
+            node.size_low.set(data.len() as u32);
+            node.size_high.set((data.len() as u64 >> 32) as u32);
+            node.mtime.set(now);
+        })
+    }
+}
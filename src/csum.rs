@@ -0,0 +1,328 @@
+//! Checking the metadata_csum checksums ext4 puts on an image's metadata.
+//! mke2fs can turn the feature on for an ext2 file system too, and since
+//! nothing here updates the checksums, such an image can only be read.
+//! The checksums are crc32c, seeded from the file system's UUID, over the
+//! superblock, the group descriptors, the bitmaps, each inode and each
+//! directory block. See
+//! https://www.kernel.org/doc/html/latest/filesystems/ext4/checksums.html
+
+use crate::error::Ext2Error;
+use crate::metadata::Metadata;
+use crate::structs::{InodeNo, Superblock, EXT2_START_OF_SUPERBLOCK};
+use crate::volume::{BlockDevice, Volume};
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The ro_compat bit of metadata_csum
+pub const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+// the checksum seed is kept in the superblock rather than worked out from
+// the UUID, so the UUID can change
+const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+// descriptors bigger than 32 bytes, which this crate doesn't read
+const INCOMPAT_64BIT: u32 = 0x80;
+
+// where things are in the parts of the superblock `Superblock` stops short of
+const SB_CHECKSUM_TYPE: usize = 0x175;
+const SB_CHECKSUM_SEED: usize = 0x270;
+const SB_CHECKSUM: usize = 0x3FC;
+
+// and in a group descriptor: bg_flags, the bitmaps' checksums and the
+// descriptor's own
+const BG_FLAGS: usize = 0x12;
+const BG_BLOCK_BITMAP_CSUM: usize = 0x18;
+const BG_INODE_BITMAP_CSUM: usize = 0x1A;
+const BG_CHECKSUM: usize = 0x1E;
+const BG_INODE_UNINIT: u16 = 0x1;
+const BG_BLOCK_UNINIT: u16 = 0x2;
+
+// and in an inode. the high half of the checksum is only there when
+// i_extra_isize covers it
+const I_CHECKSUM_LO: usize = 0x7C;
+const I_EXTRA_ISIZE: usize = 0x80;
+const I_CHECKSUM_HI: usize = 0x82;
+
+// the fake entry at the end of a directory block that holds its checksum
+const DIR_TAIL_LEN: usize = 12;
+const DIR_TAIL_FILE_TYPE: u8 = 0xDE;
+
+/// Whether the file system has metadata_csum checksums
+pub fn has_metadata_csum(superblock: &Superblock) -> bool {
+    superblock.features_ronly.get() & RO_COMPAT_METADATA_CSUM != 0
+}
+
+// crc32c's table, one entry per byte value
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// crc32c of `data` carrying on from `crc`, the way Linux's `crc32c_le`
+/// works: no inversion going in or coming out, so start from `!0` for a
+/// standard crc32c and chain calls to checksum several pieces
+pub fn crc32c(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc = TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// A piece of metadata with a checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksummed {
+    Superblock,
+    GroupDescriptor(usize),
+    BlockBitmap(usize),
+    InodeBitmap(usize),
+    Inode(u32),
+    /// Block `block` of the directory at inode `inode`, counting from 0
+    DirectoryBlock {
+        inode: u32,
+        block: u64,
+    },
+}
+
+impl fmt::Display for Checksummed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Checksummed::Superblock => write!(f, "superblock"),
+            Checksummed::GroupDescriptor(group) => write!(f, "group {} descriptor", group),
+            Checksummed::BlockBitmap(group) => write!(f, "group {} block bitmap", group),
+            Checksummed::InodeBitmap(group) => write!(f, "group {} inode bitmap", group),
+            Checksummed::Inode(inode) => write!(f, "inode {}", inode),
+            Checksummed::DirectoryBlock { inode, block } => {
+                write!(f, "directory inode {} block {}", inode, block)
+            }
+        }
+    }
+}
+
+/// A checksum that doesn't match what's on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    pub what: Checksummed,
+    /// The checksum on disk. Some are only the low 16 bits of the crc
+    pub stored: u32,
+    /// The checksum of what's there now, cut down the same way
+    pub computed: u32,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: checksum is 0x{:08x}, should be 0x{:08x}",
+            self.what, self.stored, self.computed
+        )
+    }
+}
+
+/// What `verify` found
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumReport {
+    /// How many checksums were compared
+    pub checked: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ChecksumReport {
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn compare(&mut self, what: Checksummed, stored: u32, computed: u32) {
+        self.checked += 1;
+        if stored != computed {
+            self.mismatches.push(Mismatch {
+                what,
+                stored,
+                computed,
+            });
+        }
+    }
+}
+
+fn le16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// Compare every checksum on a metadata_csum file system with what the
+/// metadata it covers adds up to now. Groups whose bitmaps and inodes are
+/// marked uninitialized are skipped, as are unused inodes and directory
+/// blocks without a checksum tail (the index blocks of a hashed directory)
+pub fn verify<D: BlockDevice>(device: D) -> Result<ChecksumReport, Ext2Error> {
+    let mut raw = vec![0; 1024];
+    device.read_at(EXT2_START_OF_SUPERBLOCK as u64, &mut raw)?;
+    let volume = Volume::open(&device)?;
+    let superblock = volume.superblock();
+    if !has_metadata_csum(superblock) {
+        return Err(Ext2Error::Unsupported(
+            "checking the checksums of a file system without metadata_csum".to_string(),
+        ));
+    }
+    if raw[SB_CHECKSUM_TYPE] != 1 {
+        return Err(Ext2Error::Unsupported(format!(
+            "checksum type {}, only crc32c (1) is known",
+            raw[SB_CHECKSUM_TYPE]
+        )));
+    }
+    if superblock.features_req.get() & INCOMPAT_64BIT != 0 {
+        return Err(Ext2Error::Unsupported(
+            "64 byte group descriptors".to_string(),
+        ));
+    }
+    let mut report = ChecksumReport::default();
+    report.compare(
+        Checksummed::Superblock,
+        le32(&raw, SB_CHECKSUM),
+        crc32c(!0, &raw[..SB_CHECKSUM]),
+    );
+    let seed = if superblock.features_req.get() & INCOMPAT_CSUM_SEED != 0 {
+        le32(&raw, SB_CHECKSUM_SEED)
+    } else {
+        crc32c(!0, &superblock.fs_id)
+    };
+
+    let block_size = volume.block_size() as u64;
+    let inodes_per_group = superblock.inodes_per_group.get() as usize;
+    let inode_size = superblock.inode_size();
+    let table = (superblock.first_data_block.get() as u64 + 1) * block_size;
+    let mut descriptor = [0; 32];
+    let mut block_bitmap = vec![0; superblock.blocks_per_group.get() as usize / 8];
+    let mut inode_bitmap = vec![0; inodes_per_group / 8];
+    let mut inode = vec![0; inode_size];
+    // checked once the bitmaps and inodes are, through `volume`
+    let mut directories = Vec::new();
+    for (group, parsed) in volume.block_groups().iter().enumerate() {
+        device.read_at(table + (group * descriptor.len()) as u64, &mut descriptor)?;
+        let le_group = (group as u32).to_le_bytes();
+        let crc = crc32c(seed, &le_group);
+        let crc = crc32c(crc, &descriptor[..BG_CHECKSUM]);
+        report.compare(
+            Checksummed::GroupDescriptor(group),
+            le16(&descriptor, BG_CHECKSUM) as u32,
+            crc32c(crc, &[0, 0]) & 0xFFFF,
+        );
+
+        let flags = le16(&descriptor, BG_FLAGS);
+        if flags & BG_BLOCK_UNINIT == 0 {
+            device.read_at(
+                parsed.block_usage_addr.get() as u64 * block_size,
+                &mut block_bitmap,
+            )?;
+            report.compare(
+                Checksummed::BlockBitmap(group),
+                le16(&descriptor, BG_BLOCK_BITMAP_CSUM) as u32,
+                crc32c(seed, &block_bitmap) & 0xFFFF,
+            );
+        }
+        if flags & BG_INODE_UNINIT != 0 {
+            continue;
+        }
+        device.read_at(
+            parsed.inode_usage_addr.get() as u64 * block_size,
+            &mut inode_bitmap,
+        )?;
+        report.compare(
+            Checksummed::InodeBitmap(group),
+            le16(&descriptor, BG_INODE_BITMAP_CSUM) as u32,
+            crc32c(seed, &inode_bitmap) & 0xFFFF,
+        );
+
+        let table = parsed.inode_table_block.get() as u64 * block_size;
+        for index in 0..inodes_per_group {
+            if inode_bitmap[index / 8] & (1 << (index % 8)) == 0 {
+                continue;
+            }
+            let number = (group * inodes_per_group + index + 1) as u32;
+            device.read_at(table + (index * inode_size) as u64, &mut inode)?;
+            let (stored, computed) = inode_checksums(seed, number, &inode);
+            report.compare(Checksummed::Inode(number), stored, computed);
+            if le16(&inode, 0) & 0xF000 == 0x4000 {
+                directories.push(number);
+            }
+        }
+    }
+
+    for number in directories {
+        let inode = InodeNo::new(number as usize, superblock)?;
+        let node = volume.read_inode(inode)?;
+        let inode_seed = crc32c(
+            crc32c(seed, &number.to_le_bytes()),
+            &node.gen_number.get().to_le_bytes(),
+        );
+        let mut block = vec![0; block_size as usize];
+        let size = Metadata::from_inode(inode, &node).size;
+        for n in 0..size.div_ceil(block_size) {
+            let len = volume.read_file(inode, n * block_size, &mut block)?;
+            let tail = &block[block.len() - DIR_TAIL_LEN..];
+            let is_tail = len == block.len()
+                && le32(tail, 0) == 0
+                && le16(tail, 4) as usize == DIR_TAIL_LEN
+                && tail[6] == 0
+                && tail[7] == DIR_TAIL_FILE_TYPE;
+            if is_tail {
+                report.compare(
+                    Checksummed::DirectoryBlock {
+                        inode: number,
+                        block: n,
+                    },
+                    le32(tail, 8),
+                    crc32c(inode_seed, &block[..block.len() - DIR_TAIL_LEN]),
+                );
+            }
+        }
+    }
+    Ok(report)
+}
+
+// an inode's stored and computed checksums. the crc covers the inode with
+// its checksum fields as zeros, seeded with the inode number and
+// generation. 128 byte inodes, or ones whose extra space doesn't reach the
+// high half, only keep the low 16 bits
+fn inode_checksums(seed: u32, number: u32, inode: &[u8]) -> (u32, u32) {
+    let generation = &inode[0x64..0x68];
+    let crc = crc32c(crc32c(seed, &number.to_le_bytes()), generation);
+    let crc = crc32c(crc, &inode[..I_CHECKSUM_LO]);
+    let crc = crc32c(crc, &[0, 0]);
+    let mut crc = crc32c(crc, &inode[I_CHECKSUM_LO + 2..128]);
+    let mut stored = le16(inode, I_CHECKSUM_LO) as u32;
+    let mut has_hi = false;
+    if inode.len() > 128 {
+        crc = crc32c(crc, &inode[128..I_CHECKSUM_HI]);
+        let mut rest = I_CHECKSUM_HI;
+        if 128 + le16(inode, I_EXTRA_ISIZE) as usize >= I_CHECKSUM_HI + 2 {
+            crc = crc32c(crc, &[0, 0]);
+            stored |= (le16(inode, I_CHECKSUM_HI) as u32) << 16;
+            has_hi = true;
+            rest += 2;
+        }
+        crc = crc32c(crc, &inode[rest..]);
+    }
+    if has_hi {
+        (stored, crc)
+    } else {
+        (stored, crc & 0xFFFF)
+    }
+}
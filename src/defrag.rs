@@ -0,0 +1,158 @@
+use crate::Ext2;
+use ext2::error::Ext2Error;
+use ext2::metadata::FileType;
+use ext2::structs::InodeNo;
+use std::collections::BTreeMap;
+
+/// What `Ext2::defragment` did with a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Defrag {
+    /// The data and indirect blocks now take the `len` blocks from `first`
+    /// on, the data first
+    Moved { first: usize, len: usize },
+    /// The data was in one run already, or there is none
+    Contiguous,
+    /// No group has `needed` free blocks in a row, so nothing was moved
+    NoRun { needed: usize },
+}
+
+impl Ext2 {
+    // the runs of free blocks in `group`'s bitmap, as their first block and
+    // length. runs don't cross from one group into the next, where the
+    // group's own metadata is anyway
+    pub fn free_runs(&self, group: usize) -> Result<Vec<(usize, usize)>, Ext2Error> {
+        let blocks_per_group = self.superblock().blocks_per_group.get() as usize;
+        let first_data_block = self.superblock().first_data_block.get() as usize;
+        let first = first_data_block + group * blocks_per_group;
+        let (start, limit) = self.allocatable_bits(group);
+        let bitmap = self.block(self.block_groups()[group].block_usage_addr.get() as usize)?;
+        let mut runs = Vec::new();
+        let mut run = 0;
+        for i in start..limit {
+            if bitmap[i / 8] & (1 << (i % 8)) == 0 {
+                run += 1;
+                continue;
+            }
+            if run > 0 {
+                runs.push((first + i - run, run));
+            }
+            run = 0;
+        }
+        if run > 0 {
+            runs.push((first + limit - run, run));
+        }
+        Ok(runs)
+    }
+
+    // the first block of `len` free blocks in a row
+    fn find_free_run(&self, len: usize) -> Result<Option<usize>, Ext2Error> {
+        for (group, descriptor) in self.block_groups().iter().enumerate() {
+            if (descriptor.free_blocks_count.get() as usize) < len {
+                continue;
+            }
+            let runs = self.free_runs(group)?;
+            if let Some(&(first, _)) = runs.iter().find(|&&(_, run)| run >= len) {
+                return Ok(Some(first));
+            }
+        }
+        Ok(None)
+    }
+
+    // mark a block `find_free_run` found as used and zero it, the way
+    // `allocate_block` does with the one it finds
+    fn claim_block(&mut self, block: usize) -> Result<(), Ext2Error> {
+        let blocks_per_group = self.superblock().blocks_per_group.get() as usize;
+        let first_data_block = self.superblock().first_data_block.get() as usize;
+        let group = (block - first_data_block) / blocks_per_group;
+        let index = (block - first_data_block) % blocks_per_group;
+        let bitmap = self.block_mut(self.block_groups()[group].block_usage_addr.get() as usize);
+        bitmap[index / 8] |= 1 << (index % 8);
+        self.count_free_blocks(group, -1)?;
+        self.block_mut(block).fill(0);
+        self.usage.blocks_allocated += 1;
+        Ok(())
+    }
+
+    // move a regular file's data into one run of free blocks, with the
+    // indirect blocks it needs right after it, then free the blocks it had.
+    // holes stay holes, and pointers past the size are dropped
+    pub fn defragment(&mut self, inode: InodeNo) -> Result<Defrag, Ext2Error> {
+        // moving the data moves the block pointers, which are the inode's
+        self.check_flags(inode, &inode.to_string(), false)?;
+        let node = self.get_inode(inode)?;
+        if FileType::from_mode(node.type_perm.get()) != FileType::Regular {
+            return Err(Ext2Error::Unsupported(
+                "defragmenting anything but a regular file".to_string(),
+            ));
+        }
+        let data = self.block_ptrs(&node).collect::<Result<Vec<_>, _>>()?;
+        if data.windows(2).all(|pair| pair[1].1 == pair[0].1 + 1) {
+            return Ok(Defrag::Contiguous);
+        }
+
+        // every indirect block on the way to a data block, keyed by its
+        // tree and the slots leading to it
+        let mut indirect = BTreeMap::new();
+        for &(logical, _) in &data {
+            if let Some((tree, path, _)) = self.volume.indirect_path(logical as u64) {
+                for depth in 0..=path.len() {
+                    indirect.insert((tree, path[..depth].to_vec()), 0);
+                }
+            }
+        }
+        let needed = data.len() + indirect.len();
+        let Some(first) = self.find_free_run(needed)? else {
+            return Ok(Defrag::NoRun { needed });
+        };
+        let old_blocks = self.inode_blocks(inode);
+
+        for block in first..first + needed {
+            self.claim_block(block)?;
+        }
+        for (i, &(_, old)) in data.iter().enumerate() {
+            let contents = self.block(old)?.to_vec();
+            self.block_mut(first + i).copy_from_slice(&contents);
+        }
+        for (i, block) in indirect.values_mut().enumerate() {
+            *block = first + data.len() + i;
+        }
+
+        // the inode's new pointers, written in one go at the end
+        let mut direct = [0u32; 12];
+        let mut trees = [0u32; 3];
+        // hang each indirect block from the one above it, or the inode
+        for ((tree, path), &block) in &indirect {
+            match path.split_last() {
+                Some((slot, above)) => {
+                    let parent = indirect[&(*tree, above.to_vec())];
+                    self.set_pointer(parent, *slot, block);
+                }
+                None => trees[tree - 1] = block as u32,
+            }
+        }
+        for (i, &(logical, _)) in data.iter().enumerate() {
+            match self.volume.indirect_path(logical as u64) {
+                Some((tree, path, slot)) => {
+                    let parent = indirect[&(tree, path)];
+                    self.set_pointer(parent, slot, first + i);
+                }
+                None => direct[logical] = (first + i) as u32,
+            }
+        }
+        let sectors = (needed * (self.block_size / 512)) as u32;
+        self.volume.update_inode(inode, |node| {
+            for (pointer, block) in node.direct_pointer.iter_mut().zip(direct) {
+                pointer.set(block);
+            }
+            node.indirect_pointer.set(trees[0]);
+            node.doubly_indirect.set(trees[1]);
+            node.triply_indirect.set(trees[2]);
+            node.sectors_count.set(sectors);
+        })?;
+
+        for block in old_blocks {
+            self.free_block(block as usize)?;
+        }
+        Ok(Defrag::Moved { first, len: needed })
+    }
+}
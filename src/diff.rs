@@ -0,0 +1,284 @@
+//! Comparing the trees of two volumes, for `ext2diff`. Entries are paired
+//! by path, so inode numbers can differ between the images without it
+//! showing. The differences come out sorted by path, one per line, so two
+//! runs over the same images print the same thing
+
+use crate::error::Ext2Error;
+use crate::metadata::{escape_name, FileType, Metadata};
+use crate::structs::InodeNo;
+use crate::volume::{BlockDevice, Volume};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// What to compare
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    /// Read and compare the contents of files and symlinks whose sizes match
+    pub content: bool,
+    /// Compare permissions, owners and link counts
+    pub metadata: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            content: true,
+            metadata: true,
+        }
+    }
+}
+
+/// One difference between the two trees, at `path` in the first
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Difference {
+    /// Raw names joined by `/`, starting with a `/`
+    pub path: Vec<u8>,
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    /// The path is only in the first tree
+    Removed,
+    /// The path is only in the second tree
+    Added,
+    /// A hard link that has this other path in the second tree. The inode
+    /// is the same one, it's known by a name both trees share
+    Renamed(Vec<u8>),
+    /// Different kinds of file, by `FileType::name`
+    Type(&'static str, &'static str),
+    Size(u64, u64),
+    /// Same size, different bytes. For symlinks, the target
+    Content,
+    Mode(u16, u16),
+    Uid(u16, u16),
+    Gid(u16, u16),
+    Links(u16, u16),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = escape_name(&self.path);
+        match &self.change {
+            Change::Removed => write!(f, "- {}", path),
+            Change::Added => write!(f, "+ {}", path),
+            Change::Renamed(to) => write!(f, "R {} -> {}", path, escape_name(to)),
+            Change::Type(a, b) => write!(f, "T {} {} -> {}", path, a, b),
+            Change::Size(a, b) => write!(f, "S {} {} -> {}", path, a, b),
+            Change::Content => write!(f, "C {}", path),
+            Change::Mode(a, b) => write!(f, "M {} mode {:04o} -> {:04o}", path, a, b),
+            Change::Uid(a, b) => write!(f, "M {} uid {} -> {}", path, a, b),
+            Change::Gid(a, b) => write!(f, "M {} gid {} -> {}", path, a, b),
+            Change::Links(a, b) => write!(f, "M {} links {} -> {}", path, a, b),
+        }
+    }
+}
+
+// every path in a volume's tree, and the paths of each inode that has
+// more than one
+struct Tree {
+    entries: BTreeMap<Vec<u8>, Metadata>,
+    links: HashMap<u32, Vec<Vec<u8>>>,
+}
+
+impl Tree {
+    fn new<D: BlockDevice>(volume: &Volume<D>) -> Result<Tree, Ext2Error> {
+        let mut tree = Tree {
+            entries: BTreeMap::new(),
+            links: HashMap::new(),
+        };
+        tree.add(volume, InodeNo::ROOT, b"/".to_vec(), &mut HashSet::new())?;
+        Ok(tree)
+    }
+
+    // `visited` holds the directories on the way down, a directory linked
+    // into its own subtree would otherwise be walked forever
+    fn add<D: BlockDevice>(
+        &mut self,
+        volume: &Volume<D>,
+        inode: InodeNo,
+        path: Vec<u8>,
+        visited: &mut HashSet<u32>,
+    ) -> Result<(), Ext2Error> {
+        let metadata = volume.metadata(inode)?;
+        if metadata.is_dir() {
+            if !visited.insert(inode.get()) {
+                return Err(Ext2Error::Corrupt(format!(
+                    "directory inode {} is linked into its own subtree",
+                    inode
+                )));
+            }
+            for entry in volume.read_dir(inode)? {
+                if entry.name_bytes == b"." || entry.name_bytes == b".." {
+                    continue;
+                }
+                let mut child = path.clone();
+                if child.len() > 1 {
+                    child.push(b'/');
+                }
+                child.extend_from_slice(&entry.name_bytes);
+                self.add(volume, entry.inode, child, visited)?;
+            }
+            visited.remove(&inode.get());
+        } else if metadata.nlink > 1 {
+            self.links
+                .entry(inode.get())
+                .or_default()
+                .push(path.clone());
+        }
+        self.entries.insert(path, metadata);
+        Ok(())
+    }
+}
+
+/// Everything that differs between the trees of `a` and `b`, sorted by path
+pub fn diff<A: BlockDevice, B: BlockDevice>(
+    a: &Volume<A>,
+    b: &Volume<B>,
+    options: DiffOptions,
+) -> Result<Vec<Difference>, Ext2Error> {
+    let tree_a = Tree::new(a)?;
+    let tree_b = Tree::new(b)?;
+    let mut differences = Vec::new();
+    // contents compared so far, by inode pair, so each hard linked file is
+    // only read once
+    let mut same_contents = HashMap::new();
+    // paths only in the second tree that turned out to be renamed links
+    let mut renamed = HashSet::new();
+
+    for (path, meta_a) in &tree_a.entries {
+        let Some(meta_b) = tree_b.entries.get(path) else {
+            match renamed_to(&tree_a, &tree_b, path, meta_a, &renamed) {
+                Some(to) => {
+                    renamed.insert(to.clone());
+                    differences.push(Difference {
+                        path: path.clone(),
+                        change: Change::Renamed(to),
+                    });
+                }
+                None => differences.push(Difference {
+                    path: path.clone(),
+                    change: Change::Removed,
+                }),
+            }
+            continue;
+        };
+        let mut push = |change| {
+            differences.push(Difference {
+                path: path.clone(),
+                change,
+            })
+        };
+        if meta_a.file_type != meta_b.file_type {
+            push(Change::Type(
+                meta_a.file_type.name(),
+                meta_b.file_type.name(),
+            ));
+            continue;
+        }
+        // a directory's size is just how many blocks its entries take
+        if !meta_a.is_dir() && meta_a.size != meta_b.size {
+            push(Change::Size(meta_a.size, meta_b.size));
+        } else if options.content && (meta_a.is_file() || meta_a.is_symlink()) {
+            let key = (meta_a.ino.get(), meta_b.ino.get());
+            let same = match same_contents.get(&key) {
+                Some(&same) => same,
+                None => {
+                    let same = contents_match(a, meta_a, b, meta_b)?;
+                    same_contents.insert(key, same);
+                    same
+                }
+            };
+            if !same {
+                push(Change::Content);
+            }
+        }
+        if options.metadata {
+            if meta_a.perms != meta_b.perms {
+                push(Change::Mode(meta_a.perms, meta_b.perms));
+            }
+            if meta_a.uid != meta_b.uid {
+                push(Change::Uid(meta_a.uid, meta_b.uid));
+            }
+            if meta_a.gid != meta_b.gid {
+                push(Change::Gid(meta_a.gid, meta_b.gid));
+            }
+            if meta_a.nlink != meta_b.nlink {
+                push(Change::Links(meta_a.nlink, meta_b.nlink));
+            }
+        }
+    }
+    for path in tree_b.entries.keys() {
+        if !tree_a.entries.contains_key(path) && !renamed.contains(path) {
+            differences.push(Difference {
+                path: path.clone(),
+                change: Change::Added,
+            });
+        }
+    }
+    differences.sort();
+    Ok(differences)
+}
+
+// the path in the second tree a hard link at `path` was renamed to, if it
+// was. that's when another link of the same inode is at a path both trees
+// have, and the inode at that path in the second tree has a link that's
+// only in the second tree
+fn renamed_to(
+    tree_a: &Tree,
+    tree_b: &Tree,
+    path: &[u8],
+    meta_a: &Metadata,
+    taken: &HashSet<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    let links_a = tree_a.links.get(&meta_a.ino.get())?;
+    for shared in links_a {
+        let Some(meta_b) = tree_b.entries.get(shared) else {
+            continue;
+        };
+        if shared == path || meta_b.file_type != meta_a.file_type {
+            continue;
+        }
+        let Some(links_b) = tree_b.links.get(&meta_b.ino.get()) else {
+            continue;
+        };
+        // sorted, since the tree was walked in directory order
+        let mut candidates: Vec<&Vec<u8>> = links_b
+            .iter()
+            .filter(|&to| !tree_a.entries.contains_key(to) && !taken.contains(to))
+            .collect();
+        candidates.sort();
+        if let Some(to) = candidates.first() {
+            return Some(to.to_vec());
+        }
+    }
+    None
+}
+
+// whether two files or symlinks of the same size hold the same bytes
+fn contents_match<A: BlockDevice, B: BlockDevice>(
+    a: &Volume<A>,
+    meta_a: &Metadata,
+    b: &Volume<B>,
+    meta_b: &Metadata,
+) -> Result<bool, Ext2Error> {
+    if meta_a.file_type == FileType::Symlink {
+        let mut target_a = vec![0; meta_a.size as usize];
+        let mut target_b = vec![0; meta_b.size as usize];
+        let len_a = a.read_link(meta_a.ino, &mut target_a)?;
+        let len_b = b.read_link(meta_b.ino, &mut target_b)?;
+        return Ok(target_a[..len_a] == target_b[..len_b]);
+    }
+    let mut buf_a = vec![0; 64 * 1024];
+    let mut buf_b = vec![0; 64 * 1024];
+    let mut offset = 0;
+    while offset < meta_a.size {
+        let read_a = a.read_file(meta_a.ino, offset, &mut buf_a)?;
+        let read_b = b.read_file(meta_b.ino, offset, &mut buf_b[..read_a])?;
+        if read_a == 0 || read_a != read_b || buf_a[..read_a] != buf_b[..read_b] {
+            return Ok(read_a == 0 && read_b == 0);
+        }
+        offset += read_a as u64;
+    }
+    Ok(true)
+}
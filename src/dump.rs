@@ -0,0 +1,197 @@
+//! Owned copies of a file system's metadata that serde can serialize, for
+//! `dump --json`. The on-disk structs are left alone: their fields are
+//! little endian wrappers and raw byte arrays, which make for poor JSON
+
+use crate::error::Ext2Error;
+use crate::metadata::{escape_name, Metadata};
+use crate::structs::{BlockGroupDescriptor, InodeNo, Superblock};
+use crate::volume::{BlockDevice, Volume};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Everything `dump` shows
+#[derive(Serialize)]
+pub struct Dump {
+    pub superblock: SuperblockInfo,
+    pub groups: Vec<GroupInfo>,
+    /// Only there if a tree was asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<TreeEntry>,
+}
+
+#[derive(Serialize)]
+pub struct SuperblockInfo {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub reserved_blocks_count: u32,
+    pub free_blocks_count: u32,
+    pub free_inodes_count: u32,
+    pub first_data_block: u32,
+    pub block_size: usize,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: usize,
+    pub rev_major: u32,
+    pub rev_minor: u16,
+    /// Last mount and write times, in POSIX time
+    pub mtime: u32,
+    pub wtime: u32,
+    pub mount_count: u16,
+    pub max_mount_count: i16,
+    pub state: u16,
+    pub errors: u16,
+    pub features_compat: u32,
+    pub features_incompat: u32,
+    pub features_ro_compat: u32,
+    /// The file system id in the usual 8-4-4-4-12 form
+    pub uuid: String,
+    pub volume_name: String,
+}
+
+#[derive(Serialize)]
+pub struct GroupInfo {
+    pub index: usize,
+    pub block_bitmap: u32,
+    pub inode_bitmap: u32,
+    pub inode_table: u32,
+    pub free_blocks_count: u16,
+    pub free_inodes_count: u16,
+    pub dirs_count: u16,
+}
+
+#[derive(Serialize)]
+pub struct TreeEntry {
+    /// The name as `escape_name` shows it
+    pub name: String,
+    pub inode: u32,
+    /// One of file, dir, symlink, char, block, fifo, socket or unknown
+    #[serde(rename = "type")]
+    pub file_type: &'static str,
+    pub size: u64,
+    pub atime: u32,
+    pub mtime: u32,
+    pub ctime: u32,
+    /// What a directory holds, `.` and `..` left out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<TreeEntry>>,
+}
+
+impl SuperblockInfo {
+    pub fn new(superblock: &Superblock) -> SuperblockInfo {
+        let id = superblock.fs_id;
+        let uuid = id
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| {
+                let dash = if matches!(i, 4 | 6 | 8 | 10) { "-" } else { "" };
+                format!("{}{:02x}", dash, byte)
+            })
+            .collect();
+        let name_len = superblock
+            .volume_name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(superblock.volume_name.len());
+        SuperblockInfo {
+            inodes_count: superblock.inodes_count.get(),
+            blocks_count: superblock.blocks_count.get(),
+            reserved_blocks_count: superblock.r_blocks_count.get(),
+            free_blocks_count: superblock.free_blocks_count.get(),
+            free_inodes_count: superblock.free_inodes_count.get(),
+            first_data_block: superblock.first_data_block.get(),
+            block_size: superblock.block_size(),
+            blocks_per_group: superblock.blocks_per_group.get(),
+            inodes_per_group: superblock.inodes_per_group.get(),
+            inode_size: superblock.inode_size(),
+            rev_major: superblock.rev_major.get(),
+            rev_minor: superblock.rev_minor.get(),
+            mtime: superblock.mtime.get(),
+            wtime: superblock.wtime.get(),
+            mount_count: superblock.mnt_count.get(),
+            max_mount_count: superblock.max_mnt_count.get(),
+            state: superblock.state.get(),
+            errors: superblock.errors.get(),
+            features_compat: superblock.features_opt.get(),
+            features_incompat: superblock.features_req.get(),
+            features_ro_compat: superblock.features_ronly.get(),
+            uuid,
+            volume_name: escape_name(&superblock.volume_name[..name_len]),
+        }
+    }
+}
+
+impl GroupInfo {
+    pub fn new(index: usize, group: &BlockGroupDescriptor) -> GroupInfo {
+        GroupInfo {
+            index,
+            block_bitmap: group.block_usage_addr.get(),
+            inode_bitmap: group.inode_usage_addr.get(),
+            inode_table: group.inode_table_block.get(),
+            free_blocks_count: group.free_blocks_count.get(),
+            free_inodes_count: group.free_inodes_count.get(),
+            dirs_count: group.dirs_count.get(),
+        }
+    }
+}
+
+/// The superblock and descriptor table of `volume`, and the tree at `tree`
+/// if it's given. The root of the tree is called `name`
+pub fn dump<D: BlockDevice>(
+    volume: &Volume<D>,
+    tree: Option<(InodeNo, &[u8])>,
+) -> Result<Dump, Ext2Error> {
+    let tree = match tree {
+        Some((inode, name)) => Some(tree_entry(volume, inode, name, &mut HashSet::new())?),
+        None => None,
+    };
+    Ok(Dump {
+        superblock: SuperblockInfo::new(volume.superblock()),
+        groups: volume
+            .block_groups()
+            .iter()
+            .enumerate()
+            .map(|(i, group)| GroupInfo::new(i, group))
+            .collect(),
+        tree,
+    })
+}
+
+// `visited` holds the directories on the way down, so a directory linked
+// into its own subtree is reported instead of recursing forever
+fn tree_entry<D: BlockDevice>(
+    volume: &Volume<D>,
+    inode: InodeNo,
+    name: &[u8],
+    visited: &mut HashSet<u32>,
+) -> Result<TreeEntry, Ext2Error> {
+    let metadata: Metadata = volume.metadata(inode)?;
+    let children = if metadata.is_dir() {
+        if !visited.insert(inode.get()) {
+            return Err(Ext2Error::Corrupt(format!(
+                "directory inode {} is linked into its own subtree",
+                inode
+            )));
+        }
+        let mut children = Vec::new();
+        for entry in volume.read_dir(inode)? {
+            if entry.name_bytes == b"." || entry.name_bytes == b".." {
+                continue;
+            }
+            children.push(tree_entry(volume, entry.inode, &entry.name_bytes, visited)?);
+        }
+        visited.remove(&inode.get());
+        Some(children)
+    } else {
+        None
+    };
+    Ok(TreeEntry {
+        name: escape_name(name),
+        inode: inode.get(),
+        file_type: metadata.file_type.name(),
+        size: metadata.size,
+        atime: metadata.atime,
+        mtime: metadata.mtime,
+        ctime: metadata.ctime,
+        children,
+    })
+}
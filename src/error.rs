@@ -0,0 +1,87 @@
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// Errors returned by the file system API
+#[derive(Debug)]
+pub enum Ext2Error {
+    /// A path component does not exist
+    NotFound(String),
+    /// A path component that has to be a directory is not one
+    NotADirectory(String),
+    /// The path does not name anything (e.g. it is empty)
+    InvalidPath(String),
+    /// The target name is already taken
+    AlreadyExists(String),
+    /// A directory that has to be empty still has entries
+    DirectoryNotEmpty(String),
+    /// A directory can't be removed while the shell is in it or below it
+    Busy(String),
+    /// The inode's flags don't allow the change, see `InodeFlags`
+    NotPermitted(String),
+    /// The permission bits don't let the user do it, see `Metadata::permits`
+    PermissionDenied(String),
+    /// There are no free inodes or blocks left
+    NoSpace,
+    /// The on-disk structures are inconsistent (e.g. a bad superblock field)
+    Corrupt(String),
+    /// An inode number outside `1..=inodes_count`, with `inodes_count`
+    InodeOutOfRange(usize, u32),
+    /// The block device could not read what was asked of it
+    Device(String),
+    /// More than one thing fits where one was wanted, e.g. several
+    /// partitions hold a file system
+    Ambiguous(String),
+    /// The file system uses something this crate can't handle (yet)
+    Unsupported(String),
+    /// Reading the underlying file system failed
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for Ext2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ext2Error::NotFound(name) => write!(f, "{}: No such file or directory", name),
+            Ext2Error::NotADirectory(name) => write!(f, "{}: Not a directory", name),
+            Ext2Error::InvalidPath(path) => write!(f, "invalid path: {:?}", path),
+            Ext2Error::AlreadyExists(name) => write!(f, "{}: File exists", name),
+            Ext2Error::DirectoryNotEmpty(name) => write!(f, "{}: Directory not empty", name),
+            Ext2Error::Busy(name) => write!(f, "{}: Device or resource busy", name),
+            Ext2Error::NotPermitted(name) => write!(f, "{}: Operation not permitted", name),
+            Ext2Error::PermissionDenied(name) => write!(f, "{}: Permission denied", name),
+            Ext2Error::NoSpace => write!(f, "No space left on device"),
+            Ext2Error::Corrupt(what) => write!(f, "corrupt file system: {}", what),
+            Ext2Error::InodeOutOfRange(inode, count) => {
+                write!(f, "inode {} out of range 1..={}", inode, count)
+            }
+            Ext2Error::Device(what) => write!(f, "device error: {}", what),
+            Ext2Error::Ambiguous(what) => write!(f, "ambiguous: {}", what),
+            Ext2Error::Unsupported(what) => write!(f, "not supported: {}", what),
+            #[cfg(feature = "std")]
+            Ext2Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Ext2Error {}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Ext2Error {
+    fn from(e: io::Error) -> Ext2Error {
+        Ext2Error::Io(e)
+    }
+}
+
+// so functions returning io::Result can use `?` on the file system API
+#[cfg(feature = "std")]
+impl From<Ext2Error> for io::Error {
+    fn from(e: Ext2Error) -> io::Error {
+        match e {
+            Ext2Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::Other, e),
+        }
+    }
+}
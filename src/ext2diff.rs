@@ -0,0 +1,69 @@
+// compare the trees of two ext2 images, built on `Volume`:
+//
+//     cargo run --bin ext2diff -- [--content | --metadata-only] a.ext2 b.ext2
+//
+// prints one line per difference, sorted by path:
+//
+//     - path              only in a
+//     + path              only in b
+//     R path -> path      a hard link that was renamed
+//     T path dir -> file  different kinds of file
+//     S path 10 -> 20     different sizes
+//     C path              same size, different contents
+//     M path mode 0644 -> 0755 (or uid, gid, links)
+//
+// --content leaves out the M lines, --metadata-only doesn't read contents.
+// like diff(1), the exit status is 0 for no differences, 1 for some and 2
+// for trouble. with the gzip feature either image can be gzipped
+
+use ext2::diff::{diff, DiffOptions};
+use ext2::error::Ext2Error;
+use ext2::image;
+use ext2::volume::Volume;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+fn main() {
+    let usage = "usage: ext2diff [--content | --metadata-only] a.ext2 b.ext2";
+    let mut options = DiffOptions::default();
+    let mut images = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--content" => options.metadata = false,
+            "--metadata-only" => options.content = false,
+            _ if arg.starts_with("--") => {
+                eprintln!("{}", usage);
+                std::process::exit(2);
+            }
+            _ => images.push(arg),
+        }
+    }
+    if images.len() != 2 || !(options.content || options.metadata) {
+        eprintln!("{}", usage);
+        std::process::exit(2);
+    }
+
+    let open = |path: &String| {
+        image::open(Path::new(path))
+            .map_err(Ext2Error::from)
+            .and_then(Volume::open)
+            .unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(2);
+            })
+    };
+    let (a, b) = (open(&images[0]), open(&images[1]));
+    let differences = match diff(&a, &b, options) {
+        Ok(differences) => differences,
+        Err(e) => {
+            eprintln!("ext2diff: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let mut out = BufWriter::new(io::stdout().lock());
+    for difference in &differences {
+        writeln!(out, "{}", difference).unwrap();
+    }
+    out.flush().unwrap();
+    std::process::exit(if differences.is_empty() { 0 } else { 1 });
+}
@@ -0,0 +1,107 @@
+//! Reading a regular file of a `Volume` through `std::io::Read`, a buffer at
+//! a time, so a file of any size can be copied or hashed without holding all
+//! of it
+
+use crate::error::Ext2Error;
+use crate::structs::InodeNo;
+use crate::volume::{BlockDevice, Volume};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+
+/// How much `copy_file` reads at a time
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// An open regular file. Reads start where the last one stopped, like a
+/// `std::fs::File`, and go through `Volume::read_file`, so reading one from
+/// start to end gets the volume's readahead
+pub struct Ext2File<'v, D> {
+    volume: &'v Volume<D>,
+    inode: InodeNo,
+    size: u64,
+    position: u64,
+}
+
+impl<'v, D: BlockDevice> Ext2File<'v, D> {
+    /// Open `inode`, which has to be a regular file, at its start
+    pub fn open(volume: &'v Volume<D>, inode: InodeNo) -> Result<Self, Ext2Error> {
+        let metadata = volume.metadata(inode)?;
+        if !metadata.is_file() {
+            return Err(Ext2Error::Unsupported(format!(
+                "reading inode {}, which is a {} and not a file",
+                inode,
+                metadata.file_type.name()
+            )));
+        }
+        Ok(Ext2File {
+            volume,
+            inode,
+            size: metadata.size,
+            position: 0,
+        })
+    }
+
+    /// Size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl<D: BlockDevice> Read for Ext2File<'_, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.volume.read_file(self.inode, self.position, buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<D: BlockDevice> Seek for Ext2File<'_, D> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        // like a file, past the end is fine and reads nothing
+        let Some(position) = position else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to before the start of the file",
+            ));
+        };
+        self.position = position;
+        Ok(position)
+    }
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// The whole of regular file `inode` at once, read through `copy_file`.
+    /// For files known to be small, `copy_file` handles any size
+    pub fn read_file_bytes(&self, inode: InodeNo) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        copy_file(self, inode, &mut data)?;
+        Ok(data)
+    }
+
+    /// Regular file `inode` as text, with anything that isn't UTF-8
+    /// replaced by U+FFFD so it can always be printed
+    pub fn read_file_string(&self, inode: InodeNo) -> io::Result<String> {
+        Ok(String::from_utf8_lossy(&self.read_file_bytes(inode)?).into_owned())
+    }
+}
+
+/// Copy the whole of regular file `inode` to `out`, `CHUNK_SIZE` bytes at a
+/// time, returning how many bytes that was
+pub fn copy_file<D: BlockDevice, W: Write + ?Sized>(
+    volume: &Volume<D>,
+    inode: InodeNo,
+    out: &mut W,
+) -> Result<u64, Ext2Error> {
+    let file = Ext2File::open(volume, inode)?;
+    // io::copy reads straight into a BufReader's buffer, so this is the
+    // only one
+    let copied = io::copy(&mut BufReader::with_capacity(CHUNK_SIZE, file), out)?;
+    Ok(copied)
+}
@@ -0,0 +1,285 @@
+//! Making a new, empty ext2 file system: what mke2fs does, cut down to
+//! what's needed for an image the shell, e2fsck and Linux are happy with.
+//! The result is a revision 1 file system with the sparse_super,
+//! large_file and filetype features, a root directory and lost+found
+
+use crate::error::Ext2Error;
+use crate::structs::{
+    BlockGroupDescriptor, Inode, Superblock, TypeIndicator, EXT2_MAGIC, EXT2_START_OF_SUPERBLOCK,
+};
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+
+// feature bits, see https://www.nongnu.org/ext2-doc/ext2.html
+const INCOMPAT_FILETYPE: u32 = 0x2;
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+
+/// Inode of lost+found, the first one that isn't reserved
+const LOST_AND_FOUND: u32 = 11;
+
+/// What `format` makes
+pub struct FormatOptions {
+    /// Size of the image in bytes, rounded down to whole blocks
+    pub size: u64,
+    /// 1024, 2048 or 4096
+    pub block_size: usize,
+    /// How many inodes to make, rounded up to fill whole inode table
+    /// blocks. None for one per 4K of space, like mke2fs on small disks
+    pub inodes: Option<u32>,
+    /// 128 or 256
+    pub inode_size: usize,
+    pub uuid: [u8; 16],
+    /// At most 16 bytes
+    pub volume_name: Vec<u8>,
+    /// POSIX time to stamp on the superblock and the two directories
+    pub now: u32,
+}
+
+/// Whether block group `group` holds a copy of the superblock and the
+/// descriptor table. With sparse_super only groups 0, 1 and powers of 3,
+/// 5 and 7 do, otherwise they all do
+pub fn has_superblock_backup(sparse_super: bool, group: usize) -> bool {
+    !sparse_super
+        || group <= 1
+        || [3, 5, 7].iter().any(|&p| {
+            let mut n = group;
+            while n % p == 0 {
+                n /= p;
+            }
+            n == 1
+        })
+}
+
+// set or clear bit `bit` of a bitmap
+fn set_bit(bitmap: &mut [u8], bit: usize, value: bool) {
+    if value {
+        bitmap[bit / 8] |= 1 << (bit % 8);
+    } else {
+        bitmap[bit / 8] &= !(1 << (bit % 8));
+    }
+}
+
+/// A new file system image of `options.size` bytes
+pub fn format(options: &FormatOptions) -> Result<Vec<u8>, Ext2Error> {
+    let block_size = options.block_size;
+    if ![1024, 2048, 4096].contains(&block_size) {
+        return Err(Ext2Error::Unsupported(format!(
+            "a block size of {}",
+            block_size
+        )));
+    }
+    if ![128, 256].contains(&options.inode_size) {
+        return Err(Ext2Error::Unsupported(format!(
+            "an inode size of {}",
+            options.inode_size
+        )));
+    }
+    if options.volume_name.len() > 16 {
+        return Err(Ext2Error::Unsupported(
+            "a volume name longer than 16 bytes".to_string(),
+        ));
+    }
+    let first_data_block = if block_size == 1024 { 1 } else { 0 };
+    let blocks_per_group = 8 * block_size;
+    let inodes_per_block = block_size / options.inode_size;
+    let mut blocks_count = cmp::min(options.size / block_size as u64, u32::MAX as u64) as usize;
+
+    // the last group has to have room for its metadata and some data, if it
+    // doesn't it's dropped like mke2fs does. that leaves the group count and
+    // inode count depending on each other, so go round until they settle
+    let mut groups;
+    let mut inodes_per_group;
+    let mut table_blocks;
+    let mut inode_table_blocks;
+    loop {
+        if blocks_count <= first_data_block {
+            return Err(Ext2Error::NoSpace);
+        }
+        groups = (blocks_count - first_data_block).div_ceil(blocks_per_group);
+        let inodes = match options.inodes {
+            Some(inodes) => inodes as usize,
+            None => blocks_count * block_size / 4096,
+        };
+        // whole inode table blocks, and whole bytes of the inode bitmap
+        inodes_per_group = inodes
+            .div_ceil(groups)
+            .next_multiple_of(cmp::max(inodes_per_block, 8))
+            .clamp(16, block_size * 8);
+        table_blocks = (groups * mem::size_of::<BlockGroupDescriptor>()).div_ceil(block_size);
+        inode_table_blocks = inodes_per_group / inodes_per_block;
+        let last = groups - 1;
+        let last_len = blocks_count - first_data_block - last * blocks_per_group;
+        let overhead = has_superblock_backup(true, last) as usize * (1 + table_blocks)
+            + 2
+            + inode_table_blocks;
+        if last_len >= overhead + 50 {
+            break;
+        }
+        if groups == 1 {
+            return Err(Ext2Error::NoSpace);
+        }
+        blocks_count = first_data_block + last * blocks_per_group;
+    }
+    let inodes_count = u32::try_from(groups * inodes_per_group)
+        .map_err(|_| Ext2Error::Unsupported(format!("{} groups of inodes", groups)))?;
+
+    let mut image = Vec::new();
+    if image.try_reserve_exact(blocks_count * block_size).is_err() {
+        return Err(Ext2Error::NoSpace);
+    }
+    image.resize(blocks_count * block_size, 0);
+    let block = |n: usize| n * block_size..(n + 1) * block_size;
+    let table = (first_data_block + 1) * block_size;
+    let group_start = |group: usize| first_data_block + group * blocks_per_group;
+    let group_len = |group: usize| cmp::min(blocks_per_group, blocks_count - group_start(group));
+
+    let mut free_blocks = 0;
+    let mut root_block = 0;
+    for group in 0..groups {
+        let start = group_start(group);
+        let len = group_len(group);
+        let mut next = start;
+        if has_superblock_backup(true, group) {
+            next += 1 + table_blocks;
+        }
+        let (block_bitmap, inode_bitmap, inode_table) = (next, next + 1, next + 2);
+        let mut used = inode_table + inode_table_blocks - start;
+        if group == 0 {
+            // the root directory and lost+found get a block each
+            root_block = start + used;
+            used += 2;
+        }
+        let bitmap = &mut image[block(block_bitmap)];
+        for bit in (0..used).chain(len..block_size * 8) {
+            set_bit(bitmap, bit, true);
+        }
+        let bitmap = &mut image[block(inode_bitmap)];
+        let reserved = if group == 0 {
+            LOST_AND_FOUND as usize
+        } else {
+            0
+        };
+        for bit in (0..reserved).chain(inodes_per_group..block_size * 8) {
+            set_bit(bitmap, bit, true);
+        }
+
+        let descriptor = unsafe {
+            &mut *(image
+                .as_mut_ptr()
+                .add(table + group * mem::size_of::<BlockGroupDescriptor>())
+                as *mut BlockGroupDescriptor)
+        };
+        descriptor.block_usage_addr.set(block_bitmap as u32);
+        descriptor.inode_usage_addr.set(inode_bitmap as u32);
+        descriptor.inode_table_block.set(inode_table as u32);
+        descriptor.free_blocks_count.set((len - used) as u16);
+        descriptor
+            .free_inodes_count
+            .set((inodes_per_group - reserved) as u16);
+        descriptor.dirs_count.set(if group == 0 { 2 } else { 0 });
+        free_blocks += len - used;
+    }
+
+    // inode 2 is the root, and lost+found is the first unreserved inode.
+    // both are in group 0's inode table
+    let inode_table = unsafe {
+        (*(image.as_ptr().add(table) as *const BlockGroupDescriptor))
+            .inode_table_block
+            .get() as usize
+    };
+    for (inode, data, perms, links) in [
+        (2, root_block, 0o755, 3),
+        (LOST_AND_FOUND, root_block + 1, 0o700, 2),
+    ] {
+        let at = inode_table * block_size + (inode as usize - 1) * options.inode_size;
+        let node = unsafe { &mut *(image.as_mut_ptr().add(at) as *mut Inode) };
+        node.type_perm.set(0x4000 | perms);
+        node.size_low.set(block_size as u32);
+        node.atime.set(options.now);
+        node.ctime.set(options.now);
+        node.mtime.set(options.now);
+        node.hard_links.set(links);
+        node.sectors_count.set((block_size / 512) as u32);
+        node.direct_pointer[0].set(data as u32);
+        if options.inode_size > 128 {
+            // the extra space after the 128 byte inode that's in use,
+            // i_extra_isize. 32 bytes covers the extra timestamps
+            image[at + 128..at + 130].copy_from_slice(&32u16.to_le_bytes());
+        }
+    }
+    let root_entries: [(u32, &[u8]); 3] = [(2, b"."), (2, b".."), (LOST_AND_FOUND, b"lost+found")];
+    write_entries(&mut image[block(root_block)], &root_entries);
+    write_entries(
+        &mut image[block(root_block + 1)],
+        &[(LOST_AND_FOUND, b"."), (2, b"..")],
+    );
+
+    let superblock =
+        unsafe { &mut *(image.as_mut_ptr().add(EXT2_START_OF_SUPERBLOCK) as *mut Superblock) };
+    superblock.inodes_count.set(inodes_count);
+    superblock.blocks_count.set(blocks_count as u32);
+    // 5% for root, as mke2fs does
+    superblock.r_blocks_count.set((blocks_count / 20) as u32);
+    superblock.free_blocks_count.set(free_blocks as u32);
+    superblock
+        .free_inodes_count
+        .set(inodes_count - LOST_AND_FOUND);
+    superblock.first_data_block.set(first_data_block as u32);
+    let log_block_size = block_size.trailing_zeros() - 10;
+    superblock.log_block_size.set(log_block_size);
+    superblock.log_frag_size.set(log_block_size as i32);
+    superblock.blocks_per_group.set(blocks_per_group as u32);
+    superblock.frags_per_group.set(blocks_per_group as u32);
+    superblock.inodes_per_group.set(inodes_per_group as u32);
+    superblock.wtime.set(options.now);
+    superblock.lastcheck.set(options.now);
+    // no checks forced by mount count
+    superblock.max_mnt_count.set(-1);
+    superblock.magic.set(EXT2_MAGIC);
+    // clean, and carry on when there are errors
+    superblock.state.set(1);
+    superblock.errors.set(1);
+    superblock.rev_major.set(1);
+    superblock.first_inode.set(LOST_AND_FOUND);
+    superblock.inode_size.set(options.inode_size as u16);
+    superblock.features_req.set(INCOMPAT_FILETYPE);
+    superblock
+        .features_ronly
+        .set(RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE);
+    superblock.fs_id = options.uuid;
+    superblock.volume_name[..options.volume_name.len()].copy_from_slice(&options.volume_name);
+
+    // the backups are copies of the primary superblock and table
+    let primary = image[EXT2_START_OF_SUPERBLOCK..EXT2_START_OF_SUPERBLOCK + 1024].to_vec();
+    let descriptors = image[table..table + table_blocks * block_size].to_vec();
+    for group in (1..groups).filter(|&group| has_superblock_backup(true, group)) {
+        let at = group_start(group) * block_size;
+        image[at..at + primary.len()].copy_from_slice(&primary);
+        let backup = unsafe { &mut *(image.as_mut_ptr().add(at) as *mut Superblock) };
+        backup.block_group.set(group as u16);
+        image[at + block_size..at + block_size + descriptors.len()].copy_from_slice(&descriptors);
+    }
+    Ok(image)
+}
+
+// fill a directory block with entries, the last one stretched to the end
+fn write_entries(block: &mut [u8], entries: &[(u32, &[u8])]) {
+    let mut offset = 0;
+    for (i, (inode, name)) in entries.iter().enumerate() {
+        let len = if i == entries.len() - 1 {
+            block.len() - offset
+        } else {
+            (8 + name.len()).next_multiple_of(4)
+        };
+        block[offset..offset + 4].copy_from_slice(&inode.to_le_bytes());
+        block[offset + 4..offset + 6].copy_from_slice(&(len as u16).to_le_bytes());
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = TypeIndicator::Directory as u8;
+        block[offset + 8..offset + 8 + name.len()].copy_from_slice(name);
+        offset += len;
+    }
+}
@@ -0,0 +1,677 @@
+use crate::Ext2;
+use ext2::metadata::{escape_name, FileType};
+use ext2::structs::{InodeNo, TypeIndicator};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+// the superblock feature that says directory entries record their inode's
+// type in the byte after the name length
+
+/// How bad a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The file system is damaged and writing to it can make things worse
+    Error,
+    /// A summary or bookkeeping field is off, no data is at risk
+    Warning,
+}
+
+/// One inconsistency found by `Ext2::check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// An inode points at a block outside the file system
+    BlockOutOfRange { inode: InodeNo, block: u32 },
+    /// An inode uses a block that the block bitmap says is free
+    BlockNotMarkedUsed { inode: InodeNo, block: u32 },
+    /// A block is pointed at more than once (by two inodes, or twice by one)
+    BlockClaimedTwice {
+        block: u32,
+        first: InodeNo,
+        second: InodeNo,
+    },
+    /// A group descriptor's free count disagrees with the group's bitmap
+    GroupFreeCount {
+        group: usize,
+        what: &'static str,
+        recorded: u32,
+        counted: u32,
+    },
+    /// The superblock's free count disagrees with the sum over all groups
+    SuperblockFreeCount {
+        what: &'static str,
+        recorded: u32,
+        counted: u32,
+    },
+    /// A directory's first entry is not `.` pointing at the directory itself
+    BadDot { dir: InodeNo },
+    /// A directory's second entry is not `..` pointing at its parent
+    BadDotDot { dir: InodeNo, parent: InodeNo },
+    /// A directory entry is too short or runs past the end of its block
+    BadDirEntry {
+        dir: InodeNo,
+        block: u32,
+        offset: usize,
+    },
+    /// A directory entry names an inode number that does not exist
+    EntryOutOfRange {
+        dir: InodeNo,
+        name: String,
+        inode: u32,
+    },
+    /// A directory entry names an inode that the inode bitmap says is free
+    EntryToFreeInode {
+        dir: InodeNo,
+        name: String,
+        inode: InodeNo,
+    },
+    /// A directory entry's type byte says something other than the type of
+    /// the inode it points at
+    TypeMismatch {
+        dir: InodeNo,
+        name: String,
+        inode: InodeNo,
+        entry: FileType,
+        actual: FileType,
+    },
+    /// An inode's link count is not the number of entries referencing it
+    LinkCount {
+        inode: InodeNo,
+        recorded: u16,
+        counted: u32,
+    },
+    /// An inode's sector count does not match the blocks it points at
+    SectorCount {
+        inode: InodeNo,
+        recorded: u32,
+        counted: u32,
+    },
+    /// An inode's size does not fit the data blocks it points at
+    SizeMismatch {
+        inode: InodeNo,
+        size: u64,
+        blocks: u32,
+    },
+}
+
+impl Finding {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Finding::GroupFreeCount { .. }
+            | Finding::SuperblockFreeCount { .. }
+            | Finding::SectorCount { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::BlockOutOfRange { inode, block } => {
+                write!(
+                    f,
+                    "inode {} points at block {} outside the file system",
+                    inode, block
+                )
+            }
+            Finding::BlockNotMarkedUsed { inode, block } => write!(
+                f,
+                "block {} is used by inode {} but free in the bitmap",
+                block, inode
+            ),
+            Finding::BlockClaimedTwice {
+                block,
+                first,
+                second,
+            } => write!(
+                f,
+                "block {} is claimed by inode {} and inode {}",
+                block, first, second
+            ),
+            Finding::GroupFreeCount {
+                group,
+                what,
+                recorded,
+                counted,
+            } => write!(
+                f,
+                "group {} records {} free {}, its bitmap has {}",
+                group, recorded, what, counted
+            ),
+            Finding::SuperblockFreeCount {
+                what,
+                recorded,
+                counted,
+            } => write!(
+                f,
+                "superblock records {} free {}, the groups have {}",
+                recorded, what, counted
+            ),
+            Finding::BadDot { dir } => {
+                write!(f, "directory inode {} has no valid `.` entry", dir)
+            }
+            Finding::BadDotDot { dir, parent } => write!(
+                f,
+                "directory inode {} has no `..` entry pointing at inode {}",
+                dir, parent
+            ),
+            Finding::BadDirEntry { dir, block, offset } => write!(
+                f,
+                "directory inode {} has a broken entry in block {} at offset {}",
+                dir, block, offset
+            ),
+            Finding::EntryOutOfRange { dir, name, inode } => write!(
+                f,
+                "entry {:?} in directory inode {} points at invalid inode {}",
+                name, dir, inode
+            ),
+            Finding::EntryToFreeInode { dir, name, inode } => write!(
+                f,
+                "entry {:?} in directory inode {} points at free inode {}",
+                name, dir, inode
+            ),
+            Finding::TypeMismatch {
+                dir,
+                name,
+                inode,
+                entry,
+                actual,
+            } => write!(
+                f,
+                "entry {:?} in directory inode {} is marked as a {}, inode {} is a {}",
+                name, dir, entry, inode, actual
+            ),
+            Finding::LinkCount {
+                inode,
+                recorded,
+                counted,
+            } => write!(
+                f,
+                "inode {} has link count {}, {} entries reference it",
+                inode, recorded, counted
+            ),
+            Finding::SectorCount {
+                inode,
+                recorded,
+                counted,
+            } => write!(
+                f,
+                "inode {} records {} sectors, it uses {}",
+                inode, recorded, counted
+            ),
+            Finding::SizeMismatch {
+                inode,
+                size,
+                blocks,
+            } => write!(
+                f,
+                "inode {} has size {} but {} data blocks",
+                inode, size, blocks
+            ),
+        }
+    }
+}
+
+/// Everything `Ext2::check` found, in the order it was found
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub findings: Vec<Finding>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.severity() == severity)
+    }
+}
+
+impl fmt::Display for FsckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no problems found");
+        }
+        let mut first = true;
+        for (severity, heading) in [(Severity::Error, "errors"), (Severity::Warning, "warnings")] {
+            let mut findings = self.with_severity(severity).peekable();
+            if findings.peek().is_none() {
+                continue;
+            }
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            write!(f, "{}:", heading)?;
+            for finding in findings {
+                write!(f, "\n  {}", finding)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the blocks an inode points at, split into data blocks (in file order,
+// holes skipped) and the indirect blocks holding the pointers
+#[derive(Default)]
+struct InodeBlocks {
+    data: Vec<u32>,
+    indirect: Vec<u32>,
+    // one past the last logical block that is mapped
+    logical_end: u64,
+}
+
+impl Ext2 {
+    /// Run the classic fsck passes over everything reachable from the root
+    /// and report what is inconsistent. Nothing is modified
+    pub fn check(&self) -> FsckReport {
+        let mut report = FsckReport::default();
+        let mut owners: HashMap<u32, InodeNo> = HashMap::new();
+        let mut references: HashMap<InodeNo, u32> = HashMap::new();
+        let mut visited = HashSet::from([InodeNo::ROOT]);
+        // (inode, directory it was found in)
+        let mut queue = VecDeque::from([(InodeNo::ROOT, InodeNo::ROOT)]);
+
+        // passes 1 and 2: blocks and sizes of each inode, then the entries of
+        // each directory, walking breadth first from the root
+        while let Some((inode, parent)) = queue.pop_front() {
+            let blocks = self.check_inode_blocks(inode, &mut owners, &mut report);
+            match self.get_inode(inode) {
+                Ok(node) if FileType::from_mode(node.type_perm.get()) == FileType::Directory => {}
+                _ => continue,
+            }
+            for (name, child) in self.check_dir_entries(inode, parent, &blocks, &mut report) {
+                *references.entry(child).or_insert(0) += 1;
+                if name != "." && name != ".." && visited.insert(child) {
+                    queue.push_back((child, inode));
+                }
+            }
+        }
+
+        // pass 4: link counts
+        let mut counted: Vec<_> = references.into_iter().collect();
+        counted.sort_by_key(|(inode, _)| inode.get());
+        for (inode, counted) in counted {
+            let recorded = match self.get_inode(inode) {
+                Ok(node) => node.hard_links.get(),
+                Err(_) => continue,
+            };
+            if recorded as u32 != counted {
+                report.findings.push(Finding::LinkCount {
+                    inode,
+                    recorded,
+                    counted,
+                });
+            }
+        }
+
+        // pass 5: group summaries
+        self.check_free_counts(&mut report);
+        report
+    }
+
+    // a block of the image, if it is inside the file system
+    fn block_data(&self, block: u32) -> Option<&[u8]> {
+        self.block(block as usize).ok()
+    }
+
+    fn bit_is_set(&self, bitmap_block: u32, bit: usize) -> bool {
+        match self.block_data(bitmap_block) {
+            Some(bitmap) => bitmap[bit / 8] & (1 << (bit % 8)) != 0,
+            None => false,
+        }
+    }
+
+    fn block_in_use(&self, block: u32) -> bool {
+        let first_data_block = self.superblock().first_data_block.get();
+        let blocks_per_group = self.superblock().blocks_per_group.get();
+        let group = ((block - first_data_block) / blocks_per_group) as usize;
+        let bit = ((block - first_data_block) % blocks_per_group) as usize;
+        self.bit_is_set(self.block_groups()[group].block_usage_addr.get(), bit)
+    }
+
+    pub(crate) fn inode_in_use(&self, inode: InodeNo) -> bool {
+        let inodes_per_group = self.superblock().inodes_per_group.get();
+        let group = ((inode.get() - 1) / inodes_per_group) as usize;
+        let bit = ((inode.get() - 1) % inodes_per_group) as usize;
+        self.bit_is_set(self.block_groups()[group].inode_usage_addr.get(), bit)
+    }
+
+    // every block `inode` holds, data and indirect, i.e. what freeing it
+    // gives back. pointers out of range aren't blocks and are left out
+    pub(crate) fn inode_blocks(&self, inode: InodeNo) -> Vec<u32> {
+        let blocks =
+            self.check_inode_blocks(inode, &mut HashMap::new(), &mut FsckReport::default());
+        let mut all = blocks.data;
+        all.extend(blocks.indirect);
+        all.sort_unstable();
+        all
+    }
+
+    // the data blocks of `inode` in file order, holes skipped. devices,
+    // fifos and fast symlinks have none. pointers out of range are left out
+    // and so is anything past a bad indirect block
+    pub(crate) fn data_blocks_for_inode(&self, inode: InodeNo) -> Vec<u32> {
+        let Ok(node) = self.get_inode(inode) else {
+            return Vec::new();
+        };
+        if !self.volume.has_block_pointers(&node) {
+            return Vec::new();
+        }
+        let first = self.superblock().first_data_block.get() as usize;
+        self.block_ptrs(&node)
+            .map_while(Result::ok)
+            .map(|(_, block)| block)
+            .filter(|&block| block >= first && block < self.blocks_count())
+            .map(|block| block as u32)
+            .collect()
+    }
+
+    // pass 1 for one inode: every block it points at has to be in range,
+    // marked used, and not claimed by anything else. the sector count and
+    // size have to agree with what was found
+    fn check_inode_blocks(
+        &self,
+        inode: InodeNo,
+        owners: &mut HashMap<u32, InodeNo>,
+        report: &mut FsckReport,
+    ) -> InodeBlocks {
+        let node = match self.get_inode(inode) {
+            Ok(node) => node,
+            Err(_) => return InodeBlocks::default(),
+        };
+        let file_type = FileType::from_mode(node.type_perm.get());
+        let mut blocks = InodeBlocks::default();
+        if !self.volume.has_block_pointers(&node) {
+            return blocks;
+        }
+        let xattr_sectors = self.volume.xattr_sectors(&node);
+
+        let pointers_per_block = self.pointers_per_block() as u64;
+        let mut logical = 0;
+        for pointer in node.direct_pointer {
+            self.walk_block_tree(
+                inode,
+                pointer.get(),
+                0,
+                logical,
+                owners,
+                &mut blocks,
+                report,
+            );
+            logical += 1;
+        }
+        let trees = [
+            node.indirect_pointer.get(),
+            node.doubly_indirect.get(),
+            node.triply_indirect.get(),
+        ];
+        let mut span = pointers_per_block;
+        for (depth, pointer) in trees.into_iter().enumerate() {
+            self.walk_block_tree(
+                inode,
+                pointer,
+                depth + 1,
+                logical,
+                owners,
+                &mut blocks,
+                report,
+            );
+            logical += span;
+            span *= pointers_per_block;
+        }
+
+        let used = (blocks.data.len() + blocks.indirect.len()) as u32;
+        let counted = used * (self.block_size / 512) as u32 + xattr_sectors;
+        if node.sectors_count.get() != counted {
+            report.findings.push(Finding::SectorCount {
+                inode,
+                recorded: node.sectors_count.get(),
+                counted,
+            });
+        }
+
+        let block_size = self.block_size as u64;
+        let size = node.size();
+        // directories are always a whole number of blocks with no holes, a
+        // file can be sparse but can't have blocks past its end
+        let consistent = match file_type {
+            FileType::Directory => size == blocks.data.len() as u64 * block_size,
+            _ => blocks.logical_end * block_size < size + block_size,
+        };
+        if !consistent {
+            report.findings.push(Finding::SizeMismatch {
+                inode,
+                size,
+                blocks: blocks.data.len() as u32,
+            });
+        }
+        blocks
+    }
+
+    // claim `block` for `inode`; at depth > 0 it is an indirect block whose
+    // pointers are followed too
+    #[allow(clippy::too_many_arguments)]
+    fn walk_block_tree(
+        &self,
+        inode: InodeNo,
+        block: u32,
+        depth: usize,
+        logical: u64,
+        owners: &mut HashMap<u32, InodeNo>,
+        blocks: &mut InodeBlocks,
+        report: &mut FsckReport,
+    ) {
+        // 0 is a hole
+        if block == 0 {
+            return;
+        }
+        if block < self.superblock().first_data_block.get()
+            || block >= self.superblock().blocks_count.get()
+        {
+            report
+                .findings
+                .push(Finding::BlockOutOfRange { inode, block });
+            return;
+        }
+        if !self.block_in_use(block) {
+            report
+                .findings
+                .push(Finding::BlockNotMarkedUsed { inode, block });
+        }
+        if let Some(&first) = owners.get(&block) {
+            report.findings.push(Finding::BlockClaimedTwice {
+                block,
+                first,
+                second: inode,
+            });
+            // don't follow the pointers of a block that belongs to someone else
+            return;
+        }
+        owners.insert(block, inode);
+
+        if depth == 0 {
+            blocks.data.push(block);
+            blocks.logical_end = logical + 1;
+            return;
+        }
+        blocks.indirect.push(block);
+        let data = match self.block_data(block) {
+            Some(data) => data,
+            None => return,
+        };
+        let span = (self.pointers_per_block() as u64).pow(depth as u32 - 1);
+        for (i, pointer) in data.chunks_exact(4).enumerate() {
+            let pointer = u32::from_le_bytes(pointer.try_into().unwrap());
+            let logical = logical + i as u64 * span;
+            self.walk_block_tree(inode, pointer, depth - 1, logical, owners, blocks, report);
+        }
+    }
+
+    // pass 2 for one directory: walk the raw entries of each block, checking
+    // that they tile the block and that `.` and `..` come first. returns the
+    // (name, inode) of every entry that points at an in-use inode
+    fn check_dir_entries(
+        &self,
+        dir: InodeNo,
+        parent: InodeNo,
+        blocks: &InodeBlocks,
+        report: &mut FsckReport,
+    ) -> Vec<(String, InodeNo)> {
+        let mut entries = Vec::new();
+        let mut index = 0;
+        let mut dot_ok = false;
+        let mut dot_dot_ok = false;
+        let has_types = self.superblock().has_file_types();
+        for &block in &blocks.data {
+            let data = match self.block_data(block) {
+                Some(data) => data,
+                None => continue,
+            };
+            let mut offset = 0;
+            while offset < data.len() {
+                let header = &data[offset..];
+                let entry_size = if header.len() >= 8 {
+                    u16::from_le_bytes([header[4], header[5]]) as usize
+                } else {
+                    0
+                };
+                let name_length = if header.len() >= 8 {
+                    header[6] as usize
+                } else {
+                    0
+                };
+                if entry_size < 8
+                    || entry_size % 4 != 0
+                    || entry_size > header.len()
+                    || 8 + name_length > entry_size
+                {
+                    report
+                        .findings
+                        .push(Finding::BadDirEntry { dir, block, offset });
+                    // the rest of this block can't be trusted
+                    break;
+                }
+                let number = u32::from_le_bytes(header[..4].try_into().unwrap());
+                let name = escape_name(&header[8..8 + name_length]);
+                offset += entry_size;
+
+                match index {
+                    0 => dot_ok = name == "." && number == dir.get(),
+                    1 => dot_dot_ok = name == ".." && number == parent.get(),
+                    _ => {}
+                }
+                index += 1;
+                // inode 0 marks an unused entry
+                if number == 0 {
+                    continue;
+                }
+                let inode = match InodeNo::new(number as usize, self.superblock()) {
+                    Ok(inode) => inode,
+                    Err(_) => {
+                        report.findings.push(Finding::EntryOutOfRange {
+                            dir,
+                            name,
+                            inode: number,
+                        });
+                        continue;
+                    }
+                };
+                if !self.inode_in_use(inode) {
+                    report
+                        .findings
+                        .push(Finding::EntryToFreeInode { dir, name, inode });
+                    continue;
+                }
+                if let (true, Ok(node)) = (has_types, self.get_inode(inode)) {
+                    let entry = FileType::from_type_indicator(TypeIndicator::from_byte(header[7]));
+                    let actual = FileType::from_mode(node.type_perm.get());
+                    if entry != actual {
+                        report.findings.push(Finding::TypeMismatch {
+                            dir,
+                            name: name.clone(),
+                            inode,
+                            entry,
+                            actual,
+                        });
+                    }
+                }
+                entries.push((name, inode));
+            }
+        }
+        if !dot_ok {
+            report.findings.push(Finding::BadDot { dir });
+        }
+        if !dot_dot_ok {
+            report.findings.push(Finding::BadDotDot { dir, parent });
+        }
+        entries
+    }
+
+    // pass 5: the free counts in each descriptor have to match its bitmaps,
+    // and the superblock's have to match the sum over the descriptors
+    fn check_free_counts(&self, report: &mut FsckReport) {
+        let blocks_per_group = self.superblock().blocks_per_group.get() as usize;
+        let inodes_per_group = self.superblock().inodes_per_group.get() as usize;
+        let first_data_block = self.superblock().first_data_block.get() as usize;
+        let blocks_count = self.superblock().blocks_count.get() as usize;
+        let mut free_blocks = 0;
+        let mut free_inodes = 0;
+        for (group, descriptor) in self.block_groups().iter().enumerate() {
+            // the last group can be shorter than the rest
+            let group_blocks = std::cmp::min(
+                blocks_per_group,
+                blocks_count - first_data_block - group * blocks_per_group,
+            );
+            let counted = (0..group_blocks)
+                .filter(|&bit| !self.bit_is_set(descriptor.block_usage_addr.get(), bit))
+                .count() as u32;
+            if descriptor.free_blocks_count.get() as u32 != counted {
+                report.findings.push(Finding::GroupFreeCount {
+                    group,
+                    what: "blocks",
+                    recorded: descriptor.free_blocks_count.get() as u32,
+                    counted,
+                });
+            }
+            free_blocks += descriptor.free_blocks_count.get() as u32;
+
+            let counted = (0..inodes_per_group)
+                .filter(|&bit| !self.bit_is_set(descriptor.inode_usage_addr.get(), bit))
+                .count() as u32;
+            if descriptor.free_inodes_count.get() as u32 != counted {
+                report.findings.push(Finding::GroupFreeCount {
+                    group,
+                    what: "inodes",
+                    recorded: descriptor.free_inodes_count.get() as u32,
+                    counted,
+                });
+            }
+            free_inodes += descriptor.free_inodes_count.get() as u32;
+        }
+
+        for (what, recorded, counted) in [
+            (
+                "blocks",
+                self.superblock().free_blocks_count.get(),
+                free_blocks,
+            ),
+            (
+                "inodes",
+                self.superblock().free_inodes_count.get(),
+                free_inodes,
+            ),
+        ] {
+            if recorded != counted {
+                report.findings.push(Finding::SuperblockFreeCount {
+                    what,
+                    recorded,
+                    counted,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// a read-only FUSE mount of an ext2 image, built on `Volume`:
+//
+//     cargo run --features fuse --bin ext2-fuse -- myfs.ext2 /mnt/point
+//
+// unmount with `fusermount -u /mnt/point`. inode numbers are the image's own,
+// except that FUSE wants the root to be 1 where ext2 has it at 2 (1 is the
+// bad blocks inode, which no directory links to). with the gzip feature the
+// image can be gzipped
+
+use ext2::error::Ext2Error;
+use ext2::image::{self, ImageFile};
+use ext2::metadata::{FileType as Ext2FileType, Metadata};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    Request, FUSE_ROOT_ID,
+};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+// nothing changes under a read-only mount, so the kernel can keep what it's
+// told for a while
+const TTL: Duration = Duration::from_secs(60);
+
+struct Ext2Fuse {
+    volume: Volume<ImageFile>,
+}
+
+impl Ext2Fuse {
+    fn inode(&self, ino: u64) -> Result<InodeNo, Ext2Error> {
+        let n = if ino == FUSE_ROOT_ID {
+            InodeNo::ROOT.get() as u64
+        } else {
+            ino
+        };
+        InodeNo::new(n as usize, self.volume.superblock())
+    }
+
+    fn attr(&self, inode: InodeNo) -> Result<(FileAttr, u64), Ext2Error> {
+        let node = self.volume.read_inode(inode)?;
+        let metadata = Metadata::from_inode(inode, &node);
+        let time = |secs: u32| UNIX_EPOCH + Duration::from_secs(secs as u64);
+        let attr = FileAttr {
+            ino: fuse_ino(inode),
+            size: metadata.size,
+            blocks: metadata.blocks as u64,
+            atime: time(metadata.atime),
+            mtime: time(metadata.mtime),
+            ctime: time(metadata.ctime),
+            // ext2 doesn't record when a file was made
+            crtime: time(metadata.ctime),
+            kind: file_type(metadata.file_type),
+            perm: metadata.perms,
+            nlink: metadata.nlink as u32,
+            uid: metadata.uid as u32,
+            gid: metadata.gid as u32,
+            rdev: 0,
+            blksize: self.volume.block_size() as u32,
+            flags: 0,
+        };
+        Ok((attr, node.gen_number.get() as u64))
+    }
+}
+
+fn fuse_ino(inode: InodeNo) -> u64 {
+    if inode == InodeNo::ROOT {
+        FUSE_ROOT_ID
+    } else {
+        inode.get() as u64
+    }
+}
+
+fn file_type(file_type: Ext2FileType) -> FileType {
+    match file_type {
+        Ext2FileType::Fifo => FileType::NamedPipe,
+        Ext2FileType::CharDevice => FileType::CharDevice,
+        Ext2FileType::Directory => FileType::Directory,
+        Ext2FileType::BlockDevice => FileType::BlockDevice,
+        Ext2FileType::Symlink => FileType::Symlink,
+        Ext2FileType::Socket => FileType::Socket,
+        Ext2FileType::Regular | Ext2FileType::Unknown => FileType::RegularFile,
+    }
+}
+
+fn errno(e: &Ext2Error) -> libc::c_int {
+    match e {
+        Ext2Error::NotFound(_) | Ext2Error::InodeOutOfRange(_, _) => libc::ENOENT,
+        Ext2Error::NotADirectory(_) => libc::ENOTDIR,
+        Ext2Error::InvalidPath(_) => libc::EINVAL,
+        Ext2Error::NotPermitted(_) => libc::EPERM,
+        Ext2Error::PermissionDenied(_) => libc::EACCES,
+        Ext2Error::Io(e) => e.raw_os_error().unwrap_or(libc::EIO),
+        _ => libc::EIO,
+    }
+}
+
+impl Filesystem for Ext2Fuse {
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let found = self
+            .inode(parent)
+            .and_then(|dir| self.volume.lookup(dir, name.as_bytes()))
+            .and_then(|inode| self.attr(inode));
+        match found {
+            Ok((attr, generation)) => reply.entry(&TTL, &attr, generation),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
+        match self.inode(ino).and_then(|inode| self.attr(inode)) {
+            Ok((attr, _)) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        let target = self.inode(ino).and_then(|inode| {
+            let len = self.volume.metadata(inode)?.size as usize;
+            let mut target = vec![0; len];
+            let read = self.volume.read_link(inode, &mut target)?;
+            target.truncate(read);
+            Ok(target)
+        });
+        match target {
+            Ok(target) => reply.data(&target),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let data = self.inode(ino).and_then(|inode| {
+            let mut data = vec![0; size as usize];
+            let read = self.volume.read_file(inode, offset as u64, &mut data)?;
+            data.truncate(read);
+            Ok(data)
+        });
+        match data {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let entries = match self.inode(ino).and_then(|dir| self.volume.read_dir(dir)) {
+            Ok(entries) => entries,
+            Err(e) => return reply.error(errno(&e)),
+        };
+        // the offset handed back with each entry is where to carry on after it
+        for (i, entry) in entries.iter().enumerate().skip(offset as usize) {
+            let kind = match self.volume.metadata(entry.inode) {
+                Ok(metadata) => file_type(metadata.file_type),
+                Err(e) => return reply.error(errno(&e)),
+            };
+            let name = OsStr::from_bytes(&entry.name_bytes);
+            if reply.add(fuse_ino(entry.inode), i as i64 + 1, kind, name) {
+                // the reply buffer is full
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <image> <mountpoint>", args[0]);
+        std::process::exit(2);
+    }
+    let volume = image::open(Path::new(&args[1]))
+        .map_err(Ext2Error::from)
+        .and_then(Volume::open);
+    let volume = match volume {
+        Ok(volume) => volume,
+        Err(e) => {
+            eprintln!("{}: {}", args[1], e);
+            std::process::exit(1);
+        }
+    };
+    let options = [
+        MountOption::RO,
+        MountOption::FSName(args[1].clone()),
+        MountOption::Subtype("ext2".to_string()),
+        MountOption::DefaultPermissions,
+    ];
+    if let Err(e) = fuser::mount2(Ext2Fuse { volume }, &args[2], &options) {
+        eprintln!("mount: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,115 @@
+use ext2::metadata::{unescape_name, DirEntry};
+
+/// Whether `s` contains any of the glob metacharacters `*`, `?` or `[`
+pub fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// The names in a directory listing matched by a shell-style `pattern`,
+/// sorted. Like a shell, `*`, `?` and `[...]` don't match a leading `.`
+/// unless the pattern starts with one, and `.` and `..` are never returned.
+/// Matching is done on the raw bytes of the names, the names returned are
+/// the escaped ones that can be typed back in
+pub fn expand(pattern: &str, entries: &[DirEntry]) -> Vec<String> {
+    let pattern = unescape_name(pattern);
+    let mut matched: Vec<&DirEntry> = entries
+        .iter()
+        .filter(|entry| entry.name_bytes != b"." && entry.name_bytes != b"..")
+        .filter(|entry| !entry.name_bytes.starts_with(b".") || pattern.starts_with(b"."))
+        .filter(|entry| matches(&pattern, &entry.name_bytes))
+        .collect();
+    matched.sort_by(|a, b| a.name_bytes.cmp(&b.name_bytes));
+    matched
+        .into_iter()
+        .map(|entry| entry.name.clone())
+        .collect()
+}
+
+/// Whether the whole of `name` matches `pattern`. `*` matches any run of
+/// bytes, `?` any one byte, and `[abc]`, `[a-z]` or `[!abc]` one byte from
+/// (or not from) a class. A `[` without a closing `]` is matched literally
+pub fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    let mut p = 0;
+    let mut n = 0;
+    // where to resume if the current attempt fails: the position after the
+    // last `*`, and the name position that `*` has swallowed up to
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    backtrack = Some((p + 1, n));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+                b'[' => match match_class(pattern, p, name[n]) {
+                    Some((true, next)) => {
+                        p = next;
+                        n += 1;
+                        continue;
+                    }
+                    Some((false, _)) => {}
+                    None if name[n] == b'[' => {
+                        p += 1;
+                        n += 1;
+                        continue;
+                    }
+                    None => {}
+                },
+                c if c == name[n] => {
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        // mismatch: let the last `*` swallow one more character and retry
+        match backtrack {
+            Some((after_star, swallowed)) => {
+                p = after_star;
+                n = swallowed + 1;
+                backtrack = Some((after_star, swallowed + 1));
+            }
+            None => return false,
+        }
+    }
+    // trailing stars can match nothing
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// match `c` against the class starting at pattern[start] == '['. returns
+// whether it matched and the index just past the closing `]`, or None if
+// the class is never closed
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some(b'!') | Some(b'^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let lo = *pattern.get(i)?;
+        // a `]` right at the start is part of the class, not its end
+        if lo == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some(b'-'), Some(&hi)) if hi != b']' => {
+                matched |= lo <= c && c <= hi;
+                i += 3;
+            }
+            _ => {
+                matched |= lo == c;
+                i += 1;
+            }
+        }
+    }
+}
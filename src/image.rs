@@ -0,0 +1,167 @@
+//! Opening an image file as a `BlockDevice`, for the shell, ext2diff and
+//! ext2-fuse alike. A gzipped image is known by its magic bytes and
+//! decompressed first: into memory when it's small, into a temporary file
+//! that's already unlinked when it isn't. Either way the compressed file is
+//! only ever read.
+//!
+//! The path can also be a device node like `/dev/sdb1`. It's read with
+//! pread like any other file, and its size comes from seeking to its end,
+//! see `BlockDevice for File`
+
+use crate::error::Ext2Error;
+use crate::volume::BlockDevice;
+use std::fs::{File, Metadata};
+use std::io;
+use std::os::unix::fs::{FileExt, FileTypeExt};
+use std::path::Path;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Images that decompress to at most this many bytes are kept in memory
+pub const IN_MEMORY_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// An opened image file
+pub enum ImageFile {
+    /// Not compressed, read as it is
+    Plain(File),
+    /// Decompressed into memory
+    Memory(Vec<u8>),
+    /// Decompressed into a temporary file, gone once this is dropped
+    Spilled(File),
+}
+
+impl ImageFile {
+    /// Whether the file was compressed, so changes can't be written back
+    /// to it
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self, ImageFile::Plain(_))
+    }
+
+    /// Whether this is a block or character device rather than an image
+    /// file
+    pub fn is_device(&self) -> bool {
+        match self {
+            ImageFile::Plain(file) => file.metadata().is_ok_and(|m| is_device(&m)),
+            _ => false,
+        }
+    }
+
+    /// The whole image in memory, read in if it isn't already
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self {
+            ImageFile::Memory(bytes) => Ok(bytes),
+            ImageFile::Plain(file) | ImageFile::Spilled(file) => {
+                let mut bytes = vec![0; BlockDevice::size(&file) as usize];
+                file.read_exact_at(&mut bytes, 0)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+impl BlockDevice for ImageFile {
+    fn size(&self) -> u64 {
+        match self {
+            ImageFile::Plain(file) | ImageFile::Spilled(file) => BlockDevice::size(file),
+            ImageFile::Memory(bytes) => bytes.size(),
+        }
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        match self {
+            ImageFile::Plain(file) | ImageFile::Spilled(file) => {
+                BlockDevice::read_at(file, offset, buf)
+            }
+            ImageFile::Memory(bytes) => bytes.read_at(offset, buf),
+        }
+    }
+}
+
+/// Whether `file` starts with gzip's magic bytes
+pub fn is_gzip(file: &File) -> io::Result<bool> {
+    let mut magic = [0; 2];
+    match file.read_exact_at(&mut magic, 0) {
+        Ok(()) => Ok(magic == GZIP_MAGIC),
+        // too short to be anything much, let the superblock check say so
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Open the image at `path` for reading, decompressing it if it's gzipped
+pub fn open(path: &Path) -> io::Result<ImageFile> {
+    open_with(path, IN_MEMORY_LIMIT)
+}
+
+/// `open`, keeping decompressed images of up to `in_memory_limit` bytes in
+/// memory
+pub fn open_with(path: &Path, in_memory_limit: u64) -> io::Result<ImageFile> {
+    let file = File::open(path).map_err(|e| explain(path, e))?;
+    if !is_gzip(&file)? {
+        return Ok(ImageFile::Plain(file));
+    }
+    decompress(file, in_memory_limit)
+}
+
+fn is_device(metadata: &Metadata) -> bool {
+    let kind = metadata.file_type();
+    kind.is_block_device() || kind.is_char_device()
+}
+
+// a device node most users can't read without help, say what that help is
+fn explain(path: &Path, e: io::Error) -> io::Error {
+    if e.kind() != io::ErrorKind::PermissionDenied || !path.metadata().is_ok_and(|m| is_device(&m))
+    {
+        return e;
+    }
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "permission denied reading the device {}. try sudo or add yourself to the disk group",
+            path.display()
+        ),
+    )
+}
+
+#[cfg(feature = "gzip")]
+fn decompress(file: File, in_memory_limit: u64) -> io::Result<ImageFile> {
+    use flate2::read::MultiGzDecoder;
+    use std::io::{BufReader, Read};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // the last 4 bytes are the decompressed size, mod 2^32. a file too big
+    // for that to be the whole size is taken to be big
+    let len = file.metadata()?.len();
+    let mut trailer = [0; 4];
+    file.read_exact_at(&mut trailer, len.saturating_sub(4))?;
+    let size = u32::from_le_bytes(trailer) as u64;
+    let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+    if size <= in_memory_limit && len < u32::MAX as u64 {
+        let mut bytes = Vec::with_capacity(size as usize);
+        decoder.read_to_end(&mut bytes)?;
+        return Ok(ImageFile::Memory(bytes));
+    }
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "ext2-{}-{}.img",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let mut spilled = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    // the open file keeps it around until it's dropped
+    std::fs::remove_file(&path)?;
+    io::copy(&mut decoder, &mut spilled)?;
+    Ok(ImageFile::Spilled(spilled))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn decompress(_file: File, _in_memory_limit: u64) -> io::Result<ImageFile> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the image is gzipped, and this was built without the gzip feature",
+    ))
+}
@@ -0,0 +1,30 @@
+//! The on-disk structures of ext2 and a read-only `Volume` on top of any
+//! `BlockDevice`. Everything here only needs `core` and `alloc`, so with the
+//! `std` feature turned off it can be used from a kernel or a bootloader.
+//! The shell in main.rs builds on it with `std`
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod csum;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "json")]
+pub mod dump;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod file;
+pub mod format;
+#[cfg(feature = "std")]
+pub mod image;
+pub mod metadata;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod partition;
+pub mod path;
+pub mod structs;
+#[cfg(feature = "std")]
+pub mod tar;
+pub mod validate;
+pub mod volume;
+pub mod xattr;
@@ -1,681 +1,4008 @@
-#![feature(int_roundings)]
-
-mod structs;
-use crate::structs::{
-    BlockGroupDescriptor, DirectoryEntry, Inode, Superblock, TypeIndicator, TypePerm,
+mod blocks;
+mod defrag;
+mod fsck;
+mod glob;
+mod plan;
+mod walk;
+use crate::defrag::Defrag;
+use crate::fsck::Severity;
+use crate::plan::Change;
+use ext2::csum::{self, has_metadata_csum};
+use ext2::error::Ext2Error;
+use ext2::file::{copy_file, CHUNK_SIZE};
+use ext2::format::{format, has_superblock_backup, FormatOptions};
+use ext2::metadata::{escape_name, unescape_name, Access, DirEntry, FileType, Metadata};
+use ext2::partition::{choose_partition, holds_ext2, partitions};
+use ext2::path::{self, Lookup};
+use ext2::structs::{
+    BlockGroupDescriptor, DirectoryEntry, Inode, InodeFlags, InodeNo, Superblock, TypeIndicator,
+    EXT2_END_OF_SUPERBLOCK, EXT2_START_OF_SUPERBLOCK,
 };
-use null_terminated::NulStr;
-use rustyline::{DefaultEditor, Result};
+use ext2::volume::{BlockDevice, BlockDeviceMut, Volume};
+use ext2::xattr::{drop_reference, parse_block, parse_in_inode, Xattr};
+use rustyline::DefaultEditor;
 use std::cmp;
-use std::collections::VecDeque;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::fs;
 use std::mem;
-use std::slice;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
-use zerocopy::AsBytes;
-use zerocopy::ByteSlice;
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
 
-#[repr(C)]
-#[derive(Debug)]
 pub struct Ext2 {
-    pub superblock: &'static Superblock,
-    pub block_groups: &'static [BlockGroupDescriptor],
-    pub blocks: Vec<&'static [u8]>,
+    // the file system on the image the shell owns, which holds its
+    // `blocks_count` blocks, block n at n * block_size. blocks are read
+    // through `block` and written through `block_mut`, the superblock,
+    // descriptors and inodes through the volume's update methods
+    volume: Volume<Image>,
     pub block_size: usize,
     pub uuid: Uuid,
-    pub block_offset: usize, // <- our "device data" actually starts at this index'th block of the device
-                             // so we have to subtract this number before indexing blocks[]
+    // `--dry-run`: rm and mv print what they would change and change
+    // nothing, and everything else that writes refuses to
+    pub dry_run: bool,
+    // `--uid`/`--gid`: who commands check permission bits for, see
+    // `check_access`. without them nothing is checked
+    pub identity: Option<Identity>,
+    // look names up in a hash of each directory's entries, built the first
+    // time one is looked up in it. `--no-name-index` scans the entries
+    // instead, which is what very large directories are slow at
+    pub name_index: bool,
+    // what the allocators handed out and took back since the last
+    // `take_usage`, which the shell prints after each command
+    pub usage: Usage,
+    // directory blocks written back since the shell last looked, which
+    // `--time` prints along with how long the command took
+    pub dir_blocks_written: usize,
+    // the same for directory blocks read, which the cache below saves
+    pub dir_blocks_read: AtomicUsize,
+    // paths `path_of_inode` found, forgotten whenever an entry is added or
+    // removed anywhere
+    paths: Mutex<HashMap<InodeNo, String>>,
+    // the live entries of each directory `dir_entries` parsed, forgotten
+    // whenever that directory changes, see `forget_dir`
+    entries: Mutex<HashMap<InodeNo, Arc<CachedDir>>>,
+}
+
+// a directory's live entries in on-disk order, and the same by raw name
+// once `lookup` has needed it
+#[derive(Debug)]
+struct CachedDir {
+    entries: Vec<DirEntry>,
+    by_name: OnceLock<HashMap<Vec<u8>, InodeNo>>,
+}
+
+/// The user and group `--uid` and `--gid` have commands act as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    pub uid: u16,
+    pub gid: u16,
+}
+
+/// Inodes and blocks allocated and freed by a command
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub inodes_allocated: Vec<InodeNo>,
+    pub inodes_freed: Vec<InodeNo>,
+    pub blocks_allocated: usize,
+    pub blocks_freed: usize,
+}
+
+impl Usage {
+    pub fn is_empty(&self) -> bool {
+        *self == Usage::default()
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |n: usize| format!("{} block{}", n, if n == 1 { "" } else { "s" });
+        let mut parts = Vec::new();
+        for (verb, inodes, blocks) in [
+            ("allocated", &self.inodes_allocated, self.blocks_allocated),
+            ("freed", &self.inodes_freed, self.blocks_freed),
+        ] {
+            let numbers: Vec<String> = inodes.iter().map(|inode| inode.to_string()).collect();
+            match inodes.len() {
+                0 if blocks == 0 => {}
+                0 => parts.push(format!("{} {}", verb, count(blocks))),
+                1 => parts.push(format!("{} inode {}, {}", verb, numbers[0], count(blocks))),
+                _ => parts.push(format!(
+                    "{} inodes {}, {}",
+                    verb,
+                    numbers.join(", "),
+                    count(blocks)
+                )),
+            }
+        }
+        write!(f, "{}", parts.join("; "))
+    }
 }
 
-const EXT2_MAGIC: u16 = 0xef53;
-const EXT2_START_OF_SUPERBLOCK: usize = 1024;
-const EXT2_END_OF_SUPERBLOCK: usize = 2048;
+// Ext2 is Send + Sync because its fields are, no unsafe impl needed. the
+// image is only written through &mut self, so a shared &Ext2 can only read
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Ext2>();
+};
+
+// name of the directory at the root that `rm --trash` moves things into
+const TRASH_NAME: &str = ".trash";
 
 impl Ext2 {
-    pub fn new<B: ByteSlice + std::fmt::Debug>(device_bytes: B, start_addr: usize) -> Ext2 {
+    pub fn new(image: Image) -> Result<Ext2, Ext2Error> {
         // https://wiki.osdev.org/Ext2#Superblock
-        // parse into Ext2 struct - without copying
+        // Volume::open checks the superblock and the descriptor table
+        let mut volume = Volume::open(image)?;
+        // the blocks of the file system, without anything after them on the
+        // device. check_superblock made sure blocks_count of them fit on it
+        let block_size = volume.block_size();
+        let blocks_count = volume.superblock().blocks_count.get() as usize;
+        volume.device_mut().truncate(blocks_count * block_size);
 
-        // the superblock goes from bytes 1024 -> 2047
-        let header_body_bytes = device_bytes.split_at(EXT2_END_OF_SUPERBLOCK);
+        let uuid = Uuid::from_bytes(volume.superblock().fs_id);
+        Ok(Ext2 {
+            volume,
+            block_size,
+            uuid,
+            dry_run: false,
+            identity: None,
+            name_index: true,
+            usage: Usage::default(),
+            dir_blocks_written: 0,
+            dir_blocks_read: AtomicUsize::new(0),
+            paths: Mutex::default(),
+            entries: Mutex::default(),
+        })
+    }
 
-        let superblock = unsafe {
-            &*(header_body_bytes
-                .0
-                .split_at(EXT2_START_OF_SUPERBLOCK)
-                .1
-                .as_ptr() as *const Superblock)
-        };
-        assert_eq!(superblock.magic, EXT2_MAGIC);
-        // at this point, we strongly suspect these bytes are indeed an ext2 filesystem
+    pub fn superblock(&self) -> &Superblock {
+        self.volume.superblock()
+    }
 
-        println!("superblock:\n{:?}", superblock);
-        println!("size of Inode struct: {}", mem::size_of::<Inode>());
+    pub fn block_groups(&self) -> &[BlockGroupDescriptor] {
+        self.volume.block_groups()
+    }
 
-        let block_group_count = superblock
-            .blocks_count
-            .div_ceil(superblock.blocks_per_group) as usize;
+    // the disk the image is on, to flush a mapping
+    #[cfg(feature = "mmap")]
+    pub fn into_disk(self) -> Disk {
+        self.volume.into_device().disk
+    }
 
-        let block_size: usize = 1024 << superblock.log_block_size;
-        println!(
-            "there are {} block groups and block_size = {}",
-            block_group_count, block_size
-        );
-        let block_groups_rest_bytes = header_body_bytes.1.split_at(block_size);
+    // whether the image is a file mapped with changes going back to it
+    #[cfg(feature = "mmap")]
+    fn is_shared_mapping(&self) -> bool {
+        matches!(&self.volume.device().disk, Disk::Mapped(mapping) if mapping.is_shared())
+    }
 
-        let block_groups = unsafe {
-            std::slice::from_raw_parts(
-                block_groups_rest_bytes.0.as_ptr() as *const BlockGroupDescriptor,
-                block_group_count,
-            )
-        };
+    // given a (1-indexed) inode number, return a copy of that #'s inode
+    // structure. InodeNo::new checks the number against inodes_count, this
+    // checks it again in case it was made for another file system
+    pub fn get_inode(&self, inode: InodeNo) -> Result<Inode, Ext2Error> {
+        self.volume.read_inode(inode)
+    }
 
-        println!("block group 0: {:?}", block_groups[0]);
+    // where an inode lives: its block group, the block of the inode table
+    // holding it and its byte offset in that block
+    pub fn inode_location(&self, inode: InodeNo) -> Result<(usize, usize, usize), Ext2Error> {
+        let offset = self.volume.inode_offset(inode)? as usize;
+        let group = (inode.get() as usize - 1) / self.superblock().inodes_per_group.get() as usize;
+        Ok((group, offset / self.block_size, offset % self.block_size))
+    }
 
-        let blocks = unsafe {
-            std::slice::from_raw_parts(
-                block_groups_rest_bytes.1.as_ptr() as *mut u8,
-                // would rather use: device_bytes.as_ptr(),
-                superblock.blocks_count as usize * block_size,
-            )
-        }
-        .chunks(block_size)
-        .collect::<Vec<_>>();
+    // how many blocks the file system has, block 0 included
+    fn blocks_count(&self) -> usize {
+        self.superblock().blocks_count.get() as usize
+    }
 
-        let offset_bytes = (blocks[0].as_ptr() as usize) - start_addr;
-        let block_offset = offset_bytes / block_size;
-        let uuid = Uuid::from_bytes(superblock.fs_id);
-        Ext2 {
-            superblock,
-            block_groups,
-            blocks,
-            block_size,
-            uuid,
-            block_offset,
+    // a whole block, or an error for a block number from disk that is past
+    // the end of the file system
+    fn block(&self, block: usize) -> Result<&[u8], Ext2Error> {
+        if block >= self.blocks_count() {
+            return Err(Ext2Error::Corrupt(format!(
+                "block {} is past the end of the file system",
+                block
+            )));
         }
+        Ok(&self.volume.device()[block * self.block_size..][..self.block_size])
     }
 
-    // given a (1-indexed) inode number, return that #'s inode structure
-    pub fn get_inode(&self, inode: usize) -> &Inode {
-        let group: usize = (inode - 1) / self.superblock.inodes_per_group as usize;
-        let index: usize = (inode - 1) % self.superblock.inodes_per_group as usize;
+    // the blocks of a directory in order, as the block number and the
+    // block. nothing is copied, the slices are the image's
+    fn dir_blocks(&self, inode: InodeNo) -> Result<Vec<(usize, &[u8])>, Ext2Error> {
+        let blocks = self.volume.dir_blocks(inode)?;
+        self.dir_blocks_read
+            .fetch_add(blocks.len(), Ordering::Relaxed);
+        let image = &self.volume.device()[..];
+        Ok(blocks
+            .into_iter()
+            .map(|block| {
+                let block = block as usize;
+                (block, &image[block * self.block_size..][..self.block_size])
+            })
+            .collect())
+    }
 
-        // println!("in get_inode, inode num = {}, index = {}, group = {}", inode, index, group);
-        let inode_table_block =
-            (self.block_groups[group].inode_table_block) as usize - self.block_offset;
-        // println!("in get_inode, block number of inode table {}", inode_table_block);
-        let inode_table = unsafe {
-            std::slice::from_raw_parts(
-                self.blocks[inode_table_block].as_ptr() as *const Inode,
-                self.superblock.inodes_per_group as usize,
-            )
-        };
-        let node = &inode_table[index];
-        // println!("inode permissions: {}", node.type_perm.bits());
-        // probably want a Vec of BlockGroups in our Ext structure so we don't have to slice each time,
-        // but this works for now.
-        // println!("{:?}", inode_table);
-        &inode_table[index]
+    // a block of a directory, for changing entries in it. every call
+    // counts as a block written
+    fn dir_block_mut(&mut self, block: usize) -> &mut [u8] {
+        self.dir_blocks_written += 1;
+        self.block_mut(block)
     }
 
-    pub fn read_dir_entry_block(
-        &self,
-        contiguous_data: &mut Vec<u8>,
-        direct_pointer: *const u8,
-        whole_size: u64,
-        bytes_read: u64,
-    ) -> std::io::Result<isize> {
-        let bytes_to_read = cmp::min(self.block_size, (whole_size as usize - bytes_read as usize));
-        // read all the bytes in that block
-        let new_data = unsafe { slice::from_raw_parts(direct_pointer, bytes_to_read) };
-        contiguous_data.extend_from_slice(new_data);
-        Ok(bytes_to_read as isize)
-    }
-
-    pub fn contiguous_data_from_dir_inode(&self, inode: usize) -> std::io::Result<Vec<u8>> {
-        let root = self.get_inode(inode);
-        if root.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
+    // add an entry called `name` for inode `child` to the end of directory `inode`
+    pub fn insert_dir_entry(
+        &mut self,
+        inode: InodeNo,
+        name: &str,
+        child: InodeNo,
+        kind: TypeIndicator,
+    ) -> std::io::Result<()> {
+        self.check_dir_change(inode, name, true)?;
+        self.forget_dir(inode);
+        let root = self.get_inode(inode)?;
+        if FileType::from_mode(root.type_perm.get()) != FileType::Directory {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "inode is not a directory",
             ));
         }
-
-        let whole_size: u64 = ((root.size_high as u64) << 32) + root.size_low as u64;
-        let mut contiguous_data: Vec<u8> = Vec::new();
-        let mut i = 0;
-        let mut bytes_read: isize = 0;
-        // get all the direct pointer blocks
-        while i < 12 && bytes_read < whole_size as isize {
-            let entry_ptr =
-                self.blocks[root.direct_pointer[i] as usize - self.block_offset].as_ptr();
-            let ret: isize = match self.read_dir_entry_block(
-                &mut contiguous_data,
-                entry_ptr,
-                whole_size,
-                bytes_read as u64,
-            ) {
-                Ok(num_bytes_read) => num_bytes_read,
-                Err(_) => {
-                    panic!("Error reading inode");
+        let kind = self.stored_kind(kind);
+        // names are typed in escaped form, see escape_name
+        let typed = name;
+        let name = &unescape_name(name)[..];
+        if name.len() > 255 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "file name too long",
+            ));
+        }
+        // find the first entry with room for the new one after it: the last
+        // of a block is padded out to the end of it, and removing entries
+        // leaves slack behind the ones before them
+        let entry_size = dir_entry_size(name.len());
+        let mut gap = None;
+        for (index, (number, block)) in self.dir_blocks(inode)?.into_iter().enumerate() {
+            for entry in DirectoryEntry::entries(block, inode, index * self.block_size) {
+                let (byte_offset, entry, entry_name) = entry?;
+                let rec_len = entry.entry_size.get() as usize;
+                // a second entry with the same name would hide the first one
+                if entry.inode.get() != 0 && entry_name == name {
+                    return Err(Ext2Error::AlreadyExists(typed.to_string()).into());
                 }
-            };
-            bytes_read += ret;
-            i += 1;
+                // an unused entry (inode 0) can be overwritten completely
+                let used = if entry.inode.get() == 0 {
+                    0
+                } else {
+                    dir_entry_size(entry.name_length as usize)
+                };
+                // a corrupt entry can claim less room than its own name takes
+                let slack = rec_len.checked_sub(used);
+                if gap.is_none() && slack.is_some_and(|slack| slack >= entry_size) {
+                    gap = Some((number, byte_offset, used, rec_len));
+                }
+            }
         }
-        for i in (0..contiguous_data.len()).rev() {
-            if contiguous_data[i] != 0 {
-                contiguous_data.truncate(i + 1);
-                // println!("contiguous data after trim: {:?}", contiguous_data);
-                return Ok(contiguous_data);
+
+        // only the block the new entry lands in changes, so only that one
+        // is written
+        match gap {
+            Some((number, offset, used, rec_len)) => {
+                // shrink the entry to what it needs and put the new one in the slack
+                let new_entry = dir_entry_bytes(child, (rec_len - used) as u16, name, kind);
+                let block = self.dir_block_mut(number);
+                if used != 0 {
+                    block[offset + 4..offset + 6].copy_from_slice(&(used as u16).to_le_bytes());
+                }
+                block[offset + used..offset + used + new_entry.len()].copy_from_slice(&new_entry);
+            }
+            None => {
+                // no room left in the last block, give the directory another one
+                let number = self
+                    .add_dir_block(inode)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+                let new_entry = dir_entry_bytes(child, self.block_size as u16, name, kind);
+                self.dir_block_mut(number)[..new_entry.len()].copy_from_slice(&new_entry);
             }
         }
+        Ok(())
+    }
 
-        return Ok(contiguous_data);
+    // the type to write into a new entry: none on file systems without the
+    // filetype feature, like revision 0 ones
+    fn stored_kind(&self, kind: TypeIndicator) -> TypeIndicator {
+        if self.superblock().has_file_types() {
+            kind
+        } else {
+            TypeIndicator::Unknown
+        }
     }
 
-    pub fn read_dir_inode(&self, inode: usize) -> std::io::Result<Vec<(usize, &NulStr)>> {
-        let mut ret_vec = Vec::new();
-        // get data from inode data as a contiguous vector
-        let contiguous_data = match self.contiguous_data_from_dir_inode(inode) {
-            Ok(data_vector) => data_vector,
-            Err(_) => panic!("Whoopsies"),
-        };
+    // point the `..` of directory `dir` at `parent`. it is the second entry
+    // of the first block, right after `.`
+    pub fn set_dot_dot(&mut self, dir: InodeNo, parent: InodeNo) -> Result<(), Ext2Error> {
+        self.forget_dir(dir);
+        let block = self.get_inode(dir)?.direct_pointer[0].get() as usize;
+        let dot = self.block(block)?;
+        let offset = u16::from_le_bytes([dot[4], dot[5]]) as usize;
+        if offset < 12 || offset + 12 > self.block_size {
+            return Err(Ext2Error::Corrupt(format!(
+                "`.` of directory inode {} has record length {}",
+                dir, offset
+            )));
+        }
+        self.block_mut(block)[offset..offset + 4].copy_from_slice(&parent.get().to_le_bytes());
+        Ok(())
+    }
 
-        let root = self.get_inode(inode);
-        if root.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "inode is not a directory",
+    // give directory `dir` another block, holding one unused entry that
+    // spans all of it. returns the block
+    fn add_dir_block(&mut self, dir: InodeNo) -> Result<usize, Ext2Error> {
+        let index = self.get_inode(dir)?.size() as usize / self.block_size;
+        if index >= 12 {
+            return Err(Ext2Error::Unsupported(
+                "growing a directory past its 12 direct blocks".to_string(),
             ));
         }
+        let block = self.allocate_block()?;
+        // allocate_block zeroed it, so this is inode 0 with no name
+        let rec_len = (self.block_size as u16).to_le_bytes();
+        self.block_mut(block)[4..6].copy_from_slice(&rec_len);
+        let block_size = self.block_size as u32;
+        self.volume.update_inode(dir, |node| {
+            node.direct_pointer[index].set(block as u32);
+            let size = node.size_low.get();
+            node.size_low.set(size + block_size);
+            let sectors = node.sectors_count.get();
+            node.sectors_count.set(sectors + block_size / 512);
+        })?;
+        Ok(block)
+    }
 
-        let whole_size: u64 = ((root.size_high as u64) << 32) + root.size_low as u64;
-        let data_ptr = contiguous_data.as_ptr();
-        let mut byte_offset: isize = 0;
-        while byte_offset < contiguous_data.len() as isize {
-            let directory = unsafe { &*(data_ptr.offset(byte_offset) as *const DirectoryEntry) };
-            byte_offset += directory.entry_size as isize;
-            ret_vec.push((directory.inode as usize, &directory.name));
-            println!("{:?}", &directory.name);
-            println!("In read_dir_inode : {:?}", directory)
+    // grow directory `dir` to at least `blocks` blocks, the way mke2fs makes
+    // lost+found, so later entries go in without allocating anything
+    pub fn preallocate_dir(&mut self, dir: InodeNo, blocks: usize) -> Result<(), Ext2Error> {
+        if !self.metadata(dir)?.is_dir() {
+            return Err(Ext2Error::NotADirectory(dir.to_string()));
+        }
+        while (self.get_inode(dir)?.size() as usize / self.block_size) < blocks {
+            self.add_dir_block(dir)?;
         }
-        Ok(ret_vec)
+        Ok(())
     }
 
-    pub fn write_dir_entry_block(
-        &self,
-        contiguous_data: &mut Vec<u8>,
-        direct_pointer: *mut u8,
-        whole_size: u64,
-        bytes_written: u64,
-    ) -> std::io::Result<isize> {
-        let bytes_to_write = cmp::min(
-            self.block_size,
-            whole_size as usize - bytes_written as usize,
+    // an (absolute) block, for writing into the image
+    fn block_mut(&mut self, block: usize) -> &mut [u8] {
+        let block_size = self.block_size;
+        &mut self.volume.device_mut()[block * block_size..][..block_size]
+    }
+
+    // find the first clear bit in [start, limit) of a bitmap block, set it and return its index
+    fn claim_bit(&mut self, bitmap_block: usize, start: usize, limit: usize) -> Option<usize> {
+        let bitmap = self.block_mut(bitmap_block);
+        for i in start..limit {
+            let mask = 1u8 << (i % 8);
+            if bitmap[i / 8] & mask == 0 {
+                bitmap[i / 8] |= mask;
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    // the first inode that isn't reserved
+    fn first_inode(&self) -> usize {
+        self.superblock().first_ino()
+    }
+
+    // mark the first free inode as used and return its number
+    pub fn allocate_inode(&mut self, is_dir: bool) -> Result<InodeNo, Ext2Error> {
+        let inodes_per_group = self.superblock().inodes_per_group.get() as usize;
+        // inodes below first_inode are reserved, they all live in group 0
+        let first_inode = self.first_inode();
+        for group in 0..self.block_groups().len() {
+            let descriptor = &self.block_groups()[group];
+            if descriptor.free_inodes_count.get() == 0 {
+                continue;
+            }
+            let start = first_inode
+                .saturating_sub(1)
+                .saturating_sub(group * inodes_per_group);
+            let bitmap = descriptor.inode_usage_addr.get() as usize;
+            if let Some(index) = self.claim_bit(bitmap, start, inodes_per_group) {
+                self.volume.update_block_group(group, |descriptor| {
+                    let free = descriptor.free_inodes_count.get();
+                    descriptor.free_inodes_count.set(free - 1);
+                    if is_dir {
+                        let dirs = descriptor.dirs_count.get();
+                        descriptor.dirs_count.set(dirs + 1);
+                    }
+                })?;
+                self.volume.update_superblock(|superblock| {
+                    let free = superblock.free_inodes_count.get();
+                    superblock.free_inodes_count.set(free.saturating_sub(1));
+                })?;
+                let inode = InodeNo::new(group * inodes_per_group + index + 1, self.superblock())?;
+                self.usage.inodes_allocated.push(inode);
+                return Ok(inode);
+            }
+        }
+        Err(Ext2Error::NoSpace)
+    }
+
+    // mark the first free block as used, zero it, and return its number
+    pub fn allocate_block(&mut self) -> Result<usize, Ext2Error> {
+        let blocks_per_group = self.superblock().blocks_per_group.get() as usize;
+        let first_data_block = self.superblock().first_data_block.get() as usize;
+        for group in 0..self.block_groups().len() {
+            let descriptor = &self.block_groups()[group];
+            if descriptor.free_blocks_count.get() == 0 {
+                continue;
+            }
+            let (start, limit) = self.allocatable_bits(group);
+            let bitmap = descriptor.block_usage_addr.get() as usize;
+            if let Some(index) = self.claim_bit(bitmap, start, limit) {
+                self.count_free_blocks(group, -1)?;
+                let block = first_data_block + group * blocks_per_group + index;
+                self.block_mut(block).fill(0);
+                self.usage.blocks_allocated += 1;
+                return Ok(block);
+            }
+        }
+        Err(Ext2Error::NoSpace)
+    }
+
+    // add `delta` to the free block counts of `group` and the superblock
+    fn count_free_blocks(&mut self, group: usize, delta: i32) -> Result<(), Ext2Error> {
+        self.volume.update_block_group(group, |descriptor| {
+            let free = descriptor.free_blocks_count.get() as i32;
+            descriptor.free_blocks_count.set((free + delta) as u16);
+        })?;
+        self.volume.update_superblock(|superblock| {
+            let free = superblock.free_blocks_count.get() as i64;
+            superblock
+                .free_blocks_count
+                .set((free + delta as i64).max(0) as u32);
+        })
+    }
+
+    // the range of bits in `group`'s block bitmap that can be handed out
+    fn allocatable_bits(&self, group: usize) -> (usize, usize) {
+        let blocks_per_group = self.superblock().blocks_per_group.get() as usize;
+        let first_data_block = self.superblock().first_data_block.get() as usize;
+        let blocks_count = self.superblock().blocks_count.get() as usize;
+        // never hand out the superblock or the descriptor table, even if a
+        // corrupt bitmap says they are free
+        let first_free =
+            first_data_block + 1 + mem::size_of_val(self.block_groups()).div_ceil(self.block_size);
+        let start = first_free.saturating_sub(first_data_block + group * blocks_per_group);
+        // the last group can be shorter than the rest
+        let limit = cmp::min(
+            blocks_per_group,
+            blocks_count - first_data_block - group * blocks_per_group,
         );
+        (start, limit)
+    }
+
+    // what was allocated and freed since the last call, starting afresh
+    pub fn take_usage(&mut self) -> Usage {
+        mem::take(&mut self.usage)
+    }
+
+    // a copy of the image grown to `blocks_count` blocks. the last group is
+    // filled out first, then new groups are added after it, empty apart from
+    // their bitmaps, inode table and (where sparse_super wants one) a backup
+    // of the superblock and descriptor table. the descriptor table has to fit
+    // in the blocks it already has, there is nowhere to move it to
+    pub fn grown(&self, blocks_count: u32) -> Result<Vec<u8>, Ext2Error> {
+        // feature bits, see https://www.nongnu.org/ext2-doc/ext2.html
+        const COMPAT_RESIZE_INODE: u32 = 0x10;
+        const INCOMPAT_META_BG: u32 = 0x10;
+        const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+
+        let superblock = self.superblock();
+        let old_count = superblock.blocks_count.get();
+        let first_data_block = superblock.first_data_block.get() as usize;
+        let blocks_per_group = superblock.blocks_per_group.get() as usize;
+        let inodes_per_group = superblock.inodes_per_group.get() as usize;
+        let block_size = self.block_size;
+        if blocks_count <= old_count {
+            return Err(Ext2Error::Unsupported(format!(
+                "shrinking a file system of {} blocks to {}",
+                old_count, blocks_count
+            )));
+        }
+        // the resize inode reserves descriptor table blocks in every backup
+        // group, and meta_bg spreads the table over the groups
+        if superblock.features_opt.get() & COMPAT_RESIZE_INODE != 0 {
+            return Err(Ext2Error::Unsupported(
+                "growing a file system with the resize_inode feature".to_string(),
+            ));
+        }
+        if superblock.features_req.get() & INCOMPAT_META_BG != 0 {
+            return Err(Ext2Error::Unsupported(
+                "growing a file system with the meta_bg feature".to_string(),
+            ));
+        }
 
-        let data_ptr = (contiguous_data as *const Vec<u8>) as *const u8;
-        // get subarray of data to be written back
-        let vec_to_write = unsafe {
-            std::slice::from_raw_parts(data_ptr.offset(bytes_written as isize), bytes_to_write)
+        let old_groups = self.block_groups().len();
+        let groups = (blocks_count as usize - first_data_block).div_ceil(blocks_per_group);
+        let descriptor_size = mem::size_of::<BlockGroupDescriptor>();
+        let table_blocks = mem::size_of_val(self.block_groups()).div_ceil(block_size);
+        if groups * descriptor_size > table_blocks * block_size {
+            return Err(Ext2Error::Unsupported(format!(
+                "{} block groups need a bigger descriptor table than the {} block(s) there are",
+                groups, table_blocks
+            )));
+        }
+        let inodes_count = u32::try_from(groups * inodes_per_group).map_err(|_| {
+            Ext2Error::Unsupported(format!("{} groups have too many inodes", groups))
+        })?;
+        let inode_table_blocks = (inodes_per_group * superblock.inode_size()).div_ceil(block_size);
+        let sparse = superblock.features_ronly.get() & RO_COMPAT_SPARSE_SUPER != 0;
+        let has_backup = |group: usize| has_superblock_backup(sparse, group);
+        let group_start = |group: usize| first_data_block + group * blocks_per_group;
+        let group_len =
+            |group: usize| cmp::min(blocks_per_group, blocks_count as usize - group_start(group));
+
+        let len = blocks_count as usize * block_size;
+        let mut image = Vec::new();
+        if image.try_reserve_exact(len).is_err() {
+            return Err(Ext2Error::NoSpace);
+        }
+        image.extend_from_slice(self.image());
+        image.resize(len, 0);
+        let table = (first_data_block + 1) * block_size;
+        let set_bit = |image: &mut Vec<u8>, bitmap: usize, bit: usize, value: bool| {
+            let byte = &mut image[bitmap * block_size + bit / 8];
+            if value {
+                *byte |= 1 << (bit % 8);
+            } else {
+                *byte &= !(1 << (bit % 8));
+            }
         };
+        let mut new_free_blocks = 0;
+
+        // the blocks past the old end of the last group were marked used as
+        // padding, they are real now
+        let last = old_groups - 1;
+        let old_len = old_count as usize - group_start(last);
+        let bitmap = self.block_groups()[last].block_usage_addr.get() as usize;
+        for bit in old_len..group_len(last) {
+            set_bit(&mut image, bitmap, bit, false);
+        }
+        let added = (group_len(last) - old_len) as u16;
+        let descriptor: &mut BlockGroupDescriptor =
+            struct_at(&mut image, table + last * descriptor_size);
+        let free = descriptor.free_blocks_count.get();
+        descriptor.free_blocks_count.set(free + added);
+        new_free_blocks += added as u32;
 
-        // then write vec_to_write to self.blocks
-        for i in 0..vec_to_write.len() {
-            unsafe {
-                direct_pointer
-                    .offset(i as isize)
-                    .write_bytes(contiguous_data[(bytes_written + i as u64) as usize], 1)
+        for group in old_groups..groups {
+            let start = group_start(group);
+            let len = group_len(group);
+            let mut next = start;
+            if has_backup(group) {
+                next += 1 + table_blocks;
+            }
+            let (block_bitmap, inode_bitmap, inode_table) = (next, next + 1, next + 2);
+            let used = inode_table + inode_table_blocks - start;
+            if used >= len {
+                return Err(Ext2Error::Unsupported(format!(
+                    "a last block group of {} blocks, too few for its own metadata",
+                    len
+                )));
             }
+            // the metadata is in use, and so are the bits past the end of the
+            // group or past the group's inodes
+            for bit in (0..used).chain(len..block_size * 8) {
+                set_bit(&mut image, block_bitmap, bit, true);
+            }
+            for bit in inodes_per_group..block_size * 8 {
+                set_bit(&mut image, inode_bitmap, bit, true);
+            }
+            let descriptor: &mut BlockGroupDescriptor =
+                struct_at(&mut image, table + group * descriptor_size);
+            descriptor.block_usage_addr.set(block_bitmap as u32);
+            descriptor.inode_usage_addr.set(inode_bitmap as u32);
+            descriptor.inode_table_block.set(inode_table as u32);
+            descriptor.free_blocks_count.set((len - used) as u16);
+            descriptor.free_inodes_count.set(inodes_per_group as u16);
+            descriptor.dirs_count.set(0);
+            new_free_blocks += (len - used) as u32;
         }
 
-        Ok(bytes_to_write as isize)
+        let new_superblock: &mut Superblock = struct_at(&mut image, EXT2_START_OF_SUPERBLOCK);
+        // keep the same share of blocks reserved for root
+        let reserved =
+            superblock.r_blocks_count.get() as u64 * blocks_count as u64 / old_count as u64;
+        new_superblock.r_blocks_count.set(reserved as u32);
+        new_superblock.blocks_count.set(blocks_count);
+        new_superblock.inodes_count.set(inodes_count);
+        let free = superblock.free_blocks_count.get();
+        new_superblock.free_blocks_count.set(free + new_free_blocks);
+        let free = superblock.free_inodes_count.get();
+        new_superblock
+            .free_inodes_count
+            .set(free + ((groups - old_groups) * inodes_per_group) as u32);
+
+        // and every backup, old or new, gets the new superblock and table
+        let primary = image[EXT2_START_OF_SUPERBLOCK..EXT2_END_OF_SUPERBLOCK].to_vec();
+        let descriptors = image[table..table + table_blocks * block_size].to_vec();
+        for group in (1..groups).filter(|&group| has_backup(group)) {
+            let at = group_start(group) * block_size;
+            image[at..at + primary.len()].copy_from_slice(&primary);
+            let backup: &mut Superblock = struct_at(&mut image, at);
+            backup.block_group.set(group as u16);
+            image[at + block_size..at + block_size + descriptors.len()]
+                .copy_from_slice(&descriptors);
+        }
+        Ok(image)
     }
 
-    pub fn write_dir_inode(
-        &self,
-        inode: usize,
-        data: &mut Vec<u8>,
-        new_entry_size: u16,
-    ) -> std::io::Result<()> {
-        let root = self.get_inode(inode);
-        if root.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "inode is not a directory",
+    // allocate and initialise a new inode of the given type and link it into
+    // `parent` as `name`. this is the one place new files, directories etc. come from
+    pub fn create_inode(
+        &mut self,
+        parent: InodeNo,
+        name: &str,
+        file_type: FileType,
+        perms: u16,
+    ) -> Result<InodeNo, Ext2Error> {
+        let is_dir = file_type == FileType::Directory;
+        let inode = self.allocate_inode(is_dir)?;
+        // a listing from whatever had the number before doesn't apply
+        self.forget_dir(inode);
+        let now = now();
+        // freed inodes keep their old contents, so start from scratch
+        let slot = self.volume.inode_offset(inode)? as usize;
+        let inode_size = self.volume.inode_size();
+        self.volume.device_mut()[slot..slot + inode_size].fill(0);
+        self.volume.update_inode(inode, |node| {
+            node.type_perm.set(file_type.mode_bits() | (perms & 0o7777));
+            node.atime.set(now);
+            node.ctime.set(now);
+            node.mtime.set(now);
+            node.hard_links.set(1);
+        })?;
+
+        if is_dir {
+            // a directory starts out with one block holding `.` and `..`
+            let block = match self.allocate_block() {
+                Ok(block) => block,
+                Err(e) => {
+                    self.free_inode(inode)?;
+                    return Err(e);
+                }
+            };
+            let kind = self.stored_kind(TypeIndicator::Directory);
+            let mut data = dir_entry_bytes(inode, 12, b".", kind);
+            data.extend(dir_entry_bytes(
+                parent,
+                (self.block_size - 12) as u16,
+                b"..",
+                kind,
             ));
+            self.block_mut(block)[..data.len()].copy_from_slice(&data);
+            let block_size = self.block_size as u32;
+            self.volume.update_inode(inode, |node| {
+                node.direct_pointer[0].set(block as u32);
+                node.size_low.set(block_size);
+                node.sectors_count.set(block_size / 512);
+                // one link from the parent's entry, one from our own `.`
+                node.hard_links.set(2);
+            })?;
         }
 
-        let whole_size: u64 = data.len() as u64;
-
-        let mut i = 0;
-        let mut bytes_written: isize = 0;
-        // write to all the direct pointer blocks
-        while i < 12 && bytes_written < whole_size as isize && root.direct_pointer[i] != 0 {
-            let entry_ptr = self.blocks[root.direct_pointer[i] as usize - self.block_offset];
-            let ret: isize = match self.write_dir_entry_block(
-                data,
-                entry_ptr.as_ptr() as *mut u8,
-                whole_size,
-                bytes_written as u64,
-            ) {
-                Ok(dir_listing) => dir_listing,
-                Err(_) => {
-                    panic!("Opps");
+        if let Err(e) = self.insert_dir_entry(parent, name, inode, file_type.type_indicator()) {
+            // nothing links to the new inode, so don't leave it allocated
+            self.free_inode(inode)?;
+            return Err(e.into());
+        }
+        if is_dir {
+            // the new directory's `..` is another link to the parent
+            let links = self.get_inode(parent)?.hard_links.get();
+            self.adjust_links(parent, 1)?;
+            debug_assert_eq!(self.get_inode(parent)?.hard_links.get(), links + 1);
+        }
+        Ok(inode)
+    }
+
+    // the first path from the root that leads to `target`, looking through
+    // the tree a level at a time. none if nothing reachable links to it
+    pub fn path_of_inode(&self, target: InodeNo) -> Option<String> {
+        if target == InodeNo::ROOT {
+            return Some("/".to_string());
+        }
+        if let Some(path) = self.paths.lock().unwrap().get(&target) {
+            return Some(path.clone());
+        }
+        let mut seen = HashSet::from([InodeNo::ROOT]);
+        let mut queue = VecDeque::from([(InodeNo::ROOT, String::new())]);
+        while let Some((dir, path)) = queue.pop_front() {
+            // a directory that can't be read just isn't looked through
+            for entry in self.dir_entries(dir).unwrap_or_default() {
+                if entry.name == "." || entry.name == ".." {
+                    continue;
+                }
+                let child = format!("{}/{}", path, entry.name);
+                if entry.inode == target {
+                    self.paths.lock().unwrap().insert(target, child.clone());
+                    return Some(child);
+                }
+                let is_dir = self.metadata(entry.inode).is_ok_and(|m| m.is_dir());
+                if is_dir && seen.insert(entry.inode) {
+                    queue.push_back((entry.inode, child));
                 }
-            };
-            bytes_written += ret;
-            i += 1;
-        }
-
-        assert!(bytes_written as u64 == whole_size);
-        return Ok(());
-    }
-
-    pub fn insert_dir_entry(&self, inode: usize, name: &str) -> std::io::Result<()> {
-        // read in data from directory entry
-        let mut contiguous_data = match self.contiguous_data_from_dir_inode(inode) {
-            Ok(data_vector) => data_vector,
-            Err(_) => panic!("Whoopsies"),
-        };
-
-        // update second to last directory entry's size
-        let data_ptr = contiguous_data.as_ptr() as *mut u8;
-        let mut byte_offset: isize = 0;
-        // find second to last directory entry
-        let mut num_dir_entry = 0;
-        let mut last_dir_entry_name_length = 0;
-        while byte_offset < contiguous_data.len() as isize {
-            let directory = unsafe { &*(data_ptr.offset(byte_offset) as *const DirectoryEntry) };
-            byte_offset += directory.entry_size as isize;
-            last_dir_entry_name_length = directory.name_length;
-            num_dir_entry += 1;
-        }
-        // update entry size for second to last directory entry
-        let mut i = 0;
-        byte_offset = 0;
-        let mut entry_size = last_dir_entry_name_length as usize;
-        while byte_offset < contiguous_data.len() as isize {
-            i += 1;
-            if i == num_dir_entry {
-                entry_size += (mem::size_of::<u32>()
-                    + mem::size_of::<u16>()
-                    + mem::size_of::<u8>()
-                    + mem::size_of::<TypeIndicator>());
-                unsafe {
-                    data_ptr
-                        .offset(byte_offset + 4)
-                        .write_bytes(entry_size.as_bytes()[0], 1)
-                };
-                unsafe {
-                    data_ptr
-                        .offset(byte_offset + 5)
-                        .write_bytes(entry_size.as_bytes()[1], 1)
-                };
             }
-            let directory = unsafe { &*(data_ptr.offset(byte_offset) as *const DirectoryEntry) };
-            byte_offset += directory.entry_size as isize;
-        }
-
-        // add the new directory entry to the end as bytes
-        contiguous_data.extend_from_slice((inode as u32).as_bytes());
-        // calculate size of new entry
-        let entry_size = mem::size_of::<u32>()
-            + mem::size_of::<u16>()
-            + mem::size_of::<u8>()
-            + mem::size_of::<TypeIndicator>()
-            + name.len()
-            + 1;
-
-        contiguous_data.extend_from_slice((entry_size as u16).as_bytes());
-        let name_size = name.len() + 1;
-        contiguous_data.extend((name_size as u8).as_bytes());
-        // type is directory entry
-        contiguous_data.push(2);
-        contiguous_data.extend_from_slice(name.as_bytes());
-        let null = "\0";
-        contiguous_data.extend_from_slice(null.as_bytes());
-        let root = self.get_inode(inode);
-        if root.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "inode is not a directory",
-            ));
         }
+        None
+    }
+
+    // entries are about to change, so the paths found so far may not lead
+    // where they did
+    fn forget_paths(&mut self) {
+        self.paths.get_mut().unwrap().clear();
+    }
+
+    // the entries of directory `dir` are about to change, or the inode is
+    // about to stop being that directory: its listing has to be parsed
+    // again, and paths through it may lead elsewhere
+    fn forget_dir(&mut self, dir: InodeNo) {
+        self.entries.get_mut().unwrap().remove(&dir);
+        self.forget_paths();
+    }
+
+    // walk `path` from `base`, creating each directory that doesn't exist yet.
+    // directories that are already there are fine, anything else in the way is
+    // an error. returns the last directory
+    pub fn create_dir_all(&mut self, base: InodeNo, path: &str) -> Result<InodeNo, Ext2Error> {
+        let mut dir = if path.starts_with('/') {
+            InodeNo::ROOT
+        } else {
+            base
+        };
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            dir = match self.lookup(dir, name) {
+                Ok(inode) if self.metadata(inode)?.is_dir() => inode,
+                Ok(_) => return Err(Ext2Error::NotADirectory(name.to_string())),
+                Err(Ext2Error::NotFound(_)) => {
+                    self.create_inode(dir, name, FileType::Directory, 0o755)?
+                }
+                Err(e) => return Err(e),
+            };
+        }
+        Ok(dir)
+    }
 
-        // write data back out
-        self.write_dir_inode(inode, &mut contiguous_data, entry_size as u16)
-            .expect("write_dir_inode fails");
+    // refuse a change to `inode` its flags don't allow. an immutable inode
+    // can't change at all, and an append-only one can only grow: `adding`
+    // says the change only adds to it, like a new entry in a directory.
+    // `name` is what the error calls it
+    pub fn check_flags(&self, inode: InodeNo, name: &str, adding: bool) -> Result<(), Ext2Error> {
+        let flags = self.get_inode(inode)?.flags();
+        if flags.contains(InodeFlags::IMMUTABLE)
+            || (!adding && flags.contains(InodeFlags::APPEND_ONLY))
+        {
+            return Err(Ext2Error::NotPermitted(name.to_string()));
+        }
+        Ok(())
+    }
 
-        // make the entry size correct
-        // add to inode table
-        return Ok(());
+    // with an identity, refuse `access` to `inode` that its permission bits
+    // don't give it. `name` is what the error calls it
+    pub fn check_access(
+        &self,
+        inode: InodeNo,
+        name: &str,
+        access: Access,
+    ) -> Result<(), Ext2Error> {
+        let Some(Identity { uid, gid }) = self.identity else {
+            return Ok(());
+        };
+        if !self.metadata(inode)?.permits(uid, gid, access) {
+            return Err(Ext2Error::PermissionDenied(name.to_string()));
+        }
+        Ok(())
     }
 
-    pub fn follow_path(&self, path: &str, dirs: Vec<(usize, &NulStr)>) -> Option<usize> {
-        let mut candidate_directories: VecDeque<&str> = path.split('/').collect();
-        let mut dirs: Vec<(usize, &NulStr)> = dirs;
-        let mut possible_inode: usize = 2;
-        // directory where the call is made from
-        let initial_dir = dirs[0].0;
-        let mut candidate = None;
+    // adding or removing an entry of directory `dir` takes write and search
+    // permission on it, and flags that allow it, see `check_flags`
+    pub fn check_dir_change(
+        &self,
+        dir: InodeNo,
+        name: &str,
+        adding: bool,
+    ) -> Result<(), Ext2Error> {
+        self.check_flags(dir, name, adding)?;
+        self.check_access(dir, name, Access::Write)?;
+        self.check_access(dir, name, Access::Execute)
+    }
 
-        while candidate_directories.len() > 0 {
-            candidate = Some(candidate_directories.pop_front().unwrap());
-            let mut found = false;
-            // find next directory
-            for dir in &dirs {
-                if dir.1.to_string().eq(candidate.unwrap()) {
-                    found = true;
-                    // update inode of current directory
-                    possible_inode = dir.0;
-                    break;
+    // take the entry called `name` out of directory `dir` and return the inode
+    // it pointed at. the space goes to the entry before it in the same block,
+    // or if it is first in its block the entry is just marked unused
+    pub fn remove_dir_entry(&mut self, dir: InodeNo, name: &str) -> Result<InodeNo, Ext2Error> {
+        if name == "." || name == ".." {
+            return Err(Ext2Error::InvalidPath(name.to_string()));
+        }
+        self.check_dir_change(dir, name, false)?;
+        self.forget_dir(dir);
+        let wanted = unescape_name(name);
+        // entries never span blocks, so neither does merging, and only the
+        // block the entry is in is written
+        let mut found = None;
+        'blocks: for (index, (number, block)) in self.dir_blocks(dir)?.into_iter().enumerate() {
+            let mut previous: Option<usize> = None;
+            for entry in DirectoryEntry::entries(block, dir, index * self.block_size) {
+                let (byte_offset, entry, entry_name) = entry?;
+                let inode = entry.inode.get();
+                if inode != 0 && entry_name == &wanted[..] {
+                    let entry_size = entry.entry_size.get() as usize;
+                    found = Some((number, byte_offset, entry_size, previous, inode));
+                    break 'blocks;
                 }
+                previous = Some(byte_offset);
             }
-            if !found {
-                println!("unable to locate {}", candidate.unwrap());
-                return None;
-            } else {
-                let inode = self.get_inode(possible_inode);
-                // check type permission of inode, for last inode can be not a directory (for cat)
-                if inode.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY
-                    && candidate_directories.len() != 0
-                {
-                    println!("not a directory: {}", candidate.unwrap());
-                    return Some(initial_dir);
-                } else {
-                    if candidate_directories.len() > 0 {
-                        // update current directory
-                        dirs = match self.read_dir_inode(possible_inode) {
-                            Ok(dir_listing) => dir_listing,
-                            Err(_) => {
-                                println!("unable to read directory");
-                                break;
-                            }
-                        }
-                    }
+        }
+        if let Some((number, byte_offset, entry_size, previous, inode)) = found {
+            let block = self.dir_block_mut(number);
+            match previous {
+                Some(previous) => {
+                    let merged = (byte_offset - previous + entry_size) as u16;
+                    block[previous + 4..previous + 6].copy_from_slice(&merged.to_le_bytes());
                 }
+                None => block[byte_offset..byte_offset + 4].fill(0),
             }
+            self.release_empty_tail(dir)?;
+            return InodeNo::new(inode as usize, self.superblock());
         }
-        return Some(possible_inode);
+        Err(Ext2Error::NotFound(name.to_string()))
     }
 
-    pub fn read_file_inode(&self, inode: usize) -> std::io::Result<Vec<&NulStr>> {
-        let mut ret = Vec::new();
-        let root = self.get_inode(inode);
-        // make sure we are reading a file
-        if root.type_perm & TypePerm::FILE != TypePerm::FILE {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "inode is not a file",
-            ));
+    // give back the blocks at the end of directory `dir` that hold nothing
+    // but one unused entry, which removing entries can leave behind. the
+    // first block, with `.` and `..` in it, always stays
+    fn release_empty_tail(&mut self, dir: InodeNo) -> Result<(), Ext2Error> {
+        loop {
+            let node = self.get_inode(dir)?;
+            let blocks = node.size() as usize / self.block_size;
+            if blocks <= 1 || blocks > 12 {
+                return Ok(());
+            }
+            let block = node.direct_pointer[blocks - 1].get() as usize;
+            let data = self.block(block)?;
+            let rec_len = u16::from_le_bytes([data[4], data[5]]) as usize;
+            if data[..4] != [0; 4] || rec_len != self.block_size {
+                return Ok(());
+            }
+            self.free_block(block)?;
+            let block_size = self.block_size as u32;
+            self.volume.update_inode(dir, |node| {
+                node.direct_pointer[blocks - 1].set(0);
+                let size = node.size_low.get();
+                node.size_low.set(size - block_size);
+                let sectors = node.sectors_count.get();
+                node.sectors_count.set(sectors - block_size / 512);
+            })?;
         }
+    }
 
-        // go through all the direct pointers
-        for cont in root.direct_pointer {
-            // <- todo, support large directories
-            // if this is 0, then that means the pointer is nullptr and we are done
-            if cont != 0 {
-                let directory = unsafe {
-                    &*(self.blocks[cont as usize - self.block_offset].as_ptr() as *const NulStr)
-                };
-                ret.push(directory);
-            }
+    // clear the bitmap bit of a block and give it back to the free counts
+    fn free_block(&mut self, block: usize) -> Result<(), Ext2Error> {
+        let blocks_per_group = self.superblock().blocks_per_group.get() as usize;
+        let first_data_block = self.superblock().first_data_block.get() as usize;
+        // a pointer from a corrupt inode can be anything, there is nothing to
+        // give back for a block that isn't in any group
+        if block < first_data_block || block >= self.blocks_count() {
+            return Ok(());
         }
-        Ok(ret)
+        let group = (block - first_data_block) / blocks_per_group;
+        let index = (block - first_data_block) % blocks_per_group;
+        let bitmap = self.block_mut(self.block_groups()[group].block_usage_addr.get() as usize);
+        // a block that is already free (a corrupt inode sharing it, say)
+        // mustn't be counted twice
+        let mask = 1u8 << (index % 8);
+        if bitmap[index / 8] & mask == 0 {
+            return Ok(());
+        }
+        bitmap[index / 8] &= !mask;
+        self.count_free_blocks(group, 1)?;
+        self.usage.blocks_freed += 1;
+        Ok(())
     }
 
-    pub fn ls(&self, dirs: Vec<(usize, &NulStr)>, command: String) -> Option<()> {
-        let elts: Vec<&str> = command.split(' ').collect();
-        if elts.len() == 1 {
-            println!("IN first if in ls function.");
-            for dir in &dirs {
-                print!("{}\t", dir.1);
+    // how many block numbers fit in an indirect block
+    pub fn pointers_per_block(&self) -> usize {
+        self.block_size / mem::size_of::<u32>()
+    }
+
+    // free `block` and, `depth` levels down, every block it points at
+    fn free_block_tree(&mut self, block: usize, depth: usize) -> Result<(), Ext2Error> {
+        if block == 0 || block >= self.blocks_count() {
+            return Ok(());
+        }
+        if depth > 0 {
+            for i in 0..self.pointers_per_block() {
+                self.free_block_tree(self.pointer_at(block, i)?, depth - 1)?;
             }
-            println!();
-        } else {
-            let paths = elts[1];
-            let inode = self.follow_path(paths, dirs);
-            if inode.is_none() {
-                println!("unable to follow path");
-            }
-            let possible_inode = self.get_inode(inode.unwrap());
-            if possible_inode.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
-                println!("not a directory: {}", paths);
-            }
-            // get directories for
-            let dirs_to_show: Option<Vec<(usize, &NulStr)>> =
-                match self.read_dir_inode(inode.unwrap()) {
-                    Ok(dir_listing) => Some(dir_listing),
-                    Err(_) => None,
-                };
-            if dirs_to_show.is_none() {
-                println!("unable to read directory in ls");
-                return None;
+        }
+        self.free_block(block)
+    }
+
+    // give an inode with no links left, and all its blocks, back to the free lists
+    pub fn free_inode(&mut self, inode: InodeNo) -> Result<(), Ext2Error> {
+        self.forget_dir(inode);
+        let node = self.get_inode(inode)?;
+        let file_type = FileType::from_mode(node.type_perm.get());
+        if self.volume.has_block_pointers(&node) {
+            let direct_pointer = node.direct_pointer;
+            let trees = [
+                node.indirect_pointer.get(),
+                node.doubly_indirect.get(),
+                node.triply_indirect.get(),
+            ];
+            for pointer in direct_pointer {
+                self.free_block_tree(pointer.get() as usize, 0)?;
             }
-            for dir in &dirs_to_show.unwrap() {
-                print!("{}\t", dir.1);
+            for (depth, pointer) in trees.into_iter().enumerate() {
+                self.free_block_tree(pointer as usize, depth + 1)?;
             }
-            println!();
         }
-        return Some(());
-    }
+        // inodes with the same attributes can share a block of them, only
+        // the last one to go frees it
+        let xattr_block = node.ext_attribute_block.get() as usize;
+        if xattr_block != 0
+            && xattr_block < self.blocks_count()
+            && drop_reference(self.block_mut(xattr_block))
+        {
+            self.free_block(xattr_block)?;
+        }
 
-    pub fn cd(&self, dirs: Vec<(usize, &NulStr)>, command: String) -> Option<usize> {
-        // `cd` with no arguments, cd goes back to root
-        // `cd dir_name` moves cwd to that directory
-        let elts: Vec<&str> = command.split(' ').collect();
-        if elts.len() == 1 {
-            return Some(2);
-        } else {
-            let paths = elts[1];
-            let inode = self.follow_path(paths, dirs);
-            if inode.is_none() {
-                println!("cd: unable to find directory: {}", paths);
-                return None;
-            } else {
-                let possible_inode = self.get_inode(inode.unwrap());
-                // let possible_inode = ext2.get_inode(inode);
-                if possible_inode.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
-                    println!("not a directory: {}", paths);
+        let inodes_per_group = self.superblock().inodes_per_group.get() as usize;
+        let group = (inode.get() as usize - 1) / inodes_per_group;
+        let index = (inode.get() as usize - 1) % inodes_per_group;
+        let bitmap = self.block_mut(self.block_groups()[group].inode_usage_addr.get() as usize);
+        // only give back an inode that was in use, or the counts drift
+        let mask = 1u8 << (index % 8);
+        if bitmap[index / 8] & mask != 0 {
+            bitmap[index / 8] &= !mask;
+            self.volume.update_block_group(group, |descriptor| {
+                let free = descriptor.free_inodes_count.get();
+                descriptor.free_inodes_count.set(free + 1);
+                if file_type == FileType::Directory {
+                    let dirs = descriptor.dirs_count.get();
+                    descriptor.dirs_count.set(dirs.saturating_sub(1));
                 }
-                return Some(inode.unwrap());
-            }
+            })?;
+            self.volume.update_superblock(|superblock| {
+                let free = superblock.free_inodes_count.get();
+                superblock.free_inodes_count.set(free + 1);
+            })?;
+            self.usage.inodes_freed.push(inode);
         }
+        let now = now();
+        self.volume.update_inode(inode, |node| {
+            node.hard_links.set(0);
+            node.ext_attribute_block.set(0);
+            node.dtime.set(now);
+        })
     }
 
-    pub fn mkdir(&self, dirs: Vec<(usize, &NulStr)>, inode: usize, command: String) -> Option<()> {
-        // `mkdir childname`
-        // create a directory with the given name, add a link to cwd
-        // consider supporting `-p path/to_file` to create a path of directories
-        let elts: Vec<&str> = command.split(' ').collect();
-        if elts.len() == 1 {
-            print!("must pass file to mkdir");
-        }
-        let name = elts[1];
+    // add `delta` to the link count of an inode
+    fn adjust_links(&mut self, inode: InodeNo, delta: i32) -> Result<(), Ext2Error> {
+        self.volume.update_inode(inode, |node| {
+            let links = node.hard_links.get() as i32;
+            node.hard_links.set((links + delta).max(0) as u16);
+        })
+    }
 
-        self.insert_dir_entry(inode, name)
-            .expect("insert_dir_entry failed");
-        Some(())
+    // remove the entry `name` from `parent`, freeing the inode once nothing
+    // links to it. directories have to be empty
+    pub fn unlink(&mut self, parent: InodeNo, name: &str) -> Result<(), Ext2Error> {
+        let plan = self.plan_unlink(parent, name)?;
+        self.apply(&plan)
     }
 
-    pub fn cat(&self, dirs: Vec<(usize, &NulStr)>, command: String) -> Option<()> {
-        // `cat filename`
-        // print the contents of filename to stdout
-        // if it's a directory, print a nice error
-        let elts: Vec<&str> = command.split(' ').collect();
-        if elts.len() == 1 {
-            print!("must pass file to show");
-        } else {
-            let paths = elts[1];
-            // get inode of potential file
-            let possible_inode = self.follow_path(paths, dirs);
-            if possible_inode.is_none() {
-                println!("unable to follow path");
-            } else {
-                let inode = self.get_inode(possible_inode.unwrap());
-                if inode.type_perm & TypePerm::FILE != TypePerm::FILE {
-                    println!("not a file: {}", paths);
-                    return None;
-                } else {
-                    let file_contents: Vec<&NulStr> =
-                        match self.read_file_inode(possible_inode.unwrap()) {
-                            Ok(file_data) => file_data,
-                            Err(_) => {
-                                println!("unable to cat file: {}", paths);
-                                return None;
-                            }
-                        };
+    // unlink `name` from `parent`, emptying it first if it is a directory
+    pub fn remove_tree(&mut self, parent: InodeNo, name: &str) -> Result<(), Ext2Error> {
+        self.remove_tree_visiting(parent, name, &mut HashSet::new())
+    }
 
-                    for cont in &file_contents {
-                        print!("{}\t", cont);
-                    }
+    // remove_tree, remembering the directories on the way down so a corrupt
+    // entry pointing back up can't send it round forever
+    fn remove_tree_visiting(
+        &mut self,
+        parent: InodeNo,
+        name: &str,
+        visited: &mut HashSet<InodeNo>,
+    ) -> Result<(), Ext2Error> {
+        let child = self.lookup(parent, name)?;
+        if self.metadata(child)?.is_dir() {
+            if !visited.insert(child) {
+                return Err(Ext2Error::Corrupt(format!(
+                    "directory loop through inode {}",
+                    child
+                )));
+            }
+            for entry in self.dir_entries(child)? {
+                if entry.name != "." && entry.name != ".." {
+                    self.remove_tree_visiting(child, &entry.name, visited)?;
                 }
             }
         }
-        return Some(());
+        self.unlink(parent, name)
     }
 
-    pub fn rm(&self, dirs: Vec<(usize, &NulStr)>, command: String) -> Option<()> {
-        // `rm target`
-        // unlink a file or empty directory
-        println!("rm not yet implemented");
-        return None;
+    // move the entry `src_name` of `src_dir` to `dst_dir` as `dst_name`. the
+    // inode stays where it is, only the directory entries change
+    pub fn rename(
+        &mut self,
+        src_dir: InodeNo,
+        src_name: &str,
+        dst_dir: InodeNo,
+        dst_name: &str,
+    ) -> Result<(), Ext2Error> {
+        let plan = self.plan_rename(src_dir, src_name, dst_dir, dst_name)?;
+        self.apply(&plan)
     }
 
-    pub fn mount(&self, dirs: Vec<(usize, &NulStr)>, command: String) -> Option<()> {
-        // `mount host_filename mountpoint`
-        // mount an ext2 filesystem over an existing empty directory
-        println!("mount not yet implemented");
-        return None;
+    // the hidden directory `rm --trash` moves things into, made on first use
+    fn trash_dir(&mut self) -> Result<InodeNo, Ext2Error> {
+        match self.lookup(InodeNo::ROOT, TRASH_NAME) {
+            Ok(trash) => Ok(trash),
+            Err(Ext2Error::NotFound(_)) => {
+                self.create_inode(InodeNo::ROOT, TRASH_NAME, FileType::Directory, 0o700)
+            }
+            Err(e) => Err(e),
+        }
     }
 
-    pub fn link(
-        &self,
-        current_working_inode: usize,
-        dirs: Vec<(usize, &NulStr)>,
-        command: String,
-    ) -> Option<()> {
-        // `link arg_1 arg_2`
-        // create a hard link from arg_1 to arg_2
-        // consider what to do if arg2 does- or does-not end in "/"
-        // and/or if arg2 is an existing directory name
+    // move `name` out of `parent` into the trash, as `name.1`, `name.2`, ...
+    // if the trash already has something by that name. returns the new name
+    pub fn move_to_trash(&mut self, parent: InodeNo, name: &str) -> Result<String, Ext2Error> {
+        let trash = self.trash_dir()?;
+        let trash_name = self.trash_name(trash, name);
+        self.rename(parent, name, trash, &trash_name)?;
+        Ok(trash_name)
+    }
 
-        let elts: Vec<&str> = command.split(' ').collect();
-        if elts.len() != 3 {
-            println!("usage: link arg_1 arg_2 ...");
-            return None;
+    // the first of `name`, `name.1`, `name.2`, ... that isn't in the trash
+    fn trash_name(&self, trash: InodeNo, name: &str) -> String {
+        let mut trash_name = name.to_string();
+        let mut suffix = 0;
+        while self.lookup(trash, &trash_name).is_ok() {
+            suffix += 1;
+            trash_name = format!("{}.{}", name, suffix);
         }
+        trash_name
+    }
 
-        let arg_1 = elts[1];
-        // for right now assume that arg_2 is not a path
-        let arg_2 = elts[2];
-        // first make sure that arg_1 does in fact exist
-        let inode_number = self.follow_path(arg_1, dirs);
-        if inode_number.is_none() {
-            println!("unable to follow path to arg_1");
-            return None;
-        }
-        // in parent directory of arg_1 we need to make a new directory entry with arg_1 that corresponds to the same inode number as arg_2
-        let inode = self.get_inode(inode_number.unwrap());
-        let parent_directory = self.read_dir_inode(current_working_inode);
-        let test_string = parent_directory.unwrap().pop().unwrap().1;
+    // resolve `path` to an inode number, see `path::resolve`. `..` only goes
+    // back through a directory, and each directory looked in takes search
+    // permission
+    pub fn resolve(&self, base: InodeNo, path: &str) -> Result<InodeNo, Ext2Error> {
+        path::resolve(self, base, &unescape_name(path))
+    }
 
-        let mut entry_type: TypeIndicator;
-        if inode.type_perm & TypePerm::DIRECTORY != TypePerm::DIRECTORY {
-            entry_type = TypeIndicator::Directory;
-        } else if inode.type_perm & TypePerm::FILE != TypePerm::FILE {
-            entry_type = TypeIndicator::Regular;
-        }
-        println!("link not yet implemented");
-        return None;
+    // `resolve`, and then `check_access` on what it leads to
+    pub fn resolve_for(
+        &self,
+        base: InodeNo,
+        path: &str,
+        access: Access,
+    ) -> Result<InodeNo, Ext2Error> {
+        let inode = self.resolve(base, path)?;
+        self.check_access(inode, path, access)?;
+        Ok(inode)
     }
-}
 
-impl fmt::Debug for Inode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.size_low == 0 && self.size_high == 0 {
-            f.debug_struct("").finish()
-        } else {
-            f.debug_struct("Inode")
-                .field("type_perm", &self.type_perm)
-                .field("size_low", &self.size_low)
-                .field("direct_pointers", &self.direct_pointer)
-                .field("indirect_pointer", &self.indirect_pointer)
-                .finish()
+    // `path::resolve_parent`, with the name as it was typed
+    pub fn resolve_parent<'p>(
+        &self,
+        base: InodeNo,
+        path: &'p str,
+    ) -> Result<(InodeNo, &'p str), Ext2Error> {
+        let (parent, _) = path::resolve_parent(self, base, &unescape_name(path))?;
+        let trimmed = path.trim_end_matches('/');
+        let name = &trimmed[trimmed.rfind('/').map_or(0, |i| i + 1)..];
+        Ok((parent, name))
+    }
+
+    // a new entry of type `file_type` can only be made at `path` if it
+    // doesn't end in a slash or is a directory, like POSIX says
+    fn trailing_slash_ok(path: &str, file_type: FileType) -> Result<(), Ext2Error> {
+        if path.ends_with('/') && file_type != FileType::Directory {
+            return Err(Ext2Error::NotADirectory(path.to_string()));
         }
+        Ok(())
     }
-}
-fn main() -> Result<()> {
-    // load disk at runtime rather than compile time
-    let disk = fs::read("myfs.ext2").expect("Couldn't find FS");
-    // let disk = include_bytes!("../largefs.ext2");
-    let start_addr: usize = disk.as_ptr() as usize;
-    let ext2 = Ext2::new(&disk[..], start_addr);
 
-    let mut current_working_inode: usize = 2;
+    // find the entry called `name` inside directory `dir`
+    fn lookup(&self, dir: InodeNo, name: &str) -> Result<InodeNo, Ext2Error> {
+        Lookup::lookup(self, dir, &unescape_name(name))
+    }
 
-    let mut rl = DefaultEditor::new()?;
-    loop {
-        // fetch the children of the current working directory
-        let dirs = match ext2.read_dir_inode(current_working_inode) {
-            Ok(dir_listing) => dir_listing,
-            Err(_) => {
-                println!("unable to read cwd");
-                break;
-            }
-        };
+    // the live entries of a directory, in on-disk order, with owned names
+    pub fn dir_entries(&self, dir: InodeNo) -> Result<Vec<DirEntry>, Ext2Error> {
+        Ok(self.cached_entries(dir)?.entries.clone())
+    }
 
-        let buffer = rl.readline(":> ");
-        if let Ok(line) = buffer {
-            if line.starts_with("ls") {
-                let success = ext2.ls(dirs, line);
-                if success.is_none() {
-                    println!("unable to read directory in ls");
-                }
-            } else if line.starts_with("cd") {
-                let possible_working_inode = ext2.cd(dirs, line);
-                if possible_working_inode.is_none() {
-                    println!("unable to read directory in cd");
-                } else {
-                    current_working_inode = possible_working_inode.unwrap();
-                }
-            } else if line.starts_with("mkdir") {
-                let success = ext2.mkdir(dirs, current_working_inode, line);
-                if success.is_none() {
-                    println!("unable to create directory in mkdir");
+    // `dir_entries`, parsing the directory's blocks only the first time
+    fn cached_entries(&self, dir: InodeNo) -> Result<Arc<CachedDir>, Ext2Error> {
+        if let Some(cached) = self.entries.lock().unwrap().get(&dir) {
+            return Ok(cached.clone());
+        }
+        let cached = Arc::new(CachedDir {
+            entries: self.parse_dir_entries(dir)?,
+            by_name: OnceLock::new(),
+        });
+        self.entries.lock().unwrap().insert(dir, cached.clone());
+        Ok(cached)
+    }
+
+    fn parse_dir_entries(&self, dir: InodeNo) -> Result<Vec<DirEntry>, Ext2Error> {
+        let mut entries = Vec::new();
+        for (index, (_, block)) in self.dir_blocks(dir)?.into_iter().enumerate() {
+            for entry in DirectoryEntry::entries(block, dir, index * self.block_size) {
+                let (_, directory, name) = entry?;
+                // inode 0 marks an unused entry
+                if directory.inode.get() != 0 {
+                    entries.push(DirEntry {
+                        // entries come from disk, so check the inode number is sane
+                        inode: InodeNo::new(directory.inode.get() as usize, self.superblock())?,
+                        name: escape_name(name),
+                        name_bytes: name.to_vec(),
+                        kind: directory.kind(),
+                    });
                 }
-            } else if line.starts_with("cat") {
-                let success = ext2.cat(dirs, line);
-                if success.is_none() {
-                    println!("unable to cat file");
+            }
+        }
+        Ok(entries)
+    }
+
+    // replace every argument whose last component is a glob with the
+    // matching paths. like a shell, a glob that matches nothing is kept as is
+    pub fn expand_globs(&self, cwd: InodeNo, args: &[&str]) -> Result<Vec<String>, Ext2Error> {
+        let mut paths = Vec::new();
+        for &arg in args {
+            let (dir_path, pattern) = match arg.rfind('/') {
+                Some(i) => (&arg[..=i], &arg[i + 1..]),
+                None => ("", arg),
+            };
+            if !glob::is_pattern(pattern) {
+                paths.push(arg.to_string());
+                continue;
+            }
+            let dir = self.resolve(cwd, if dir_path.is_empty() { "." } else { dir_path })?;
+            let matches = glob::expand(pattern, &self.cached_entries(dir)?.entries);
+            if matches.is_empty() {
+                paths.push(arg.to_string());
+            }
+            paths.extend(
+                matches
+                    .into_iter()
+                    .map(|name| format!("{}{}", dir_path, name)),
+            );
+        }
+        Ok(paths)
+    }
+
+    // set (or with `set` false clear) `flags` of an inode, leaving the
+    // others as they are, bits this shell has no name for included
+    pub fn set_inode_flags(
+        &mut self,
+        inode: InodeNo,
+        flags: InodeFlags,
+        set: bool,
+    ) -> Result<(), Ext2Error> {
+        let now = now();
+        self.volume.update_inode(inode, |node| {
+            let old = node.flags.get();
+            let new = if set {
+                old | flags.bits()
+            } else {
+                old & !flags.bits()
+            };
+            node.flags.set(new);
+            node.ctime.set(now);
+        })
+    }
+
+    // overwrite the access and modification times of an inode in place,
+    // leaving its size and block pointers untouched
+    pub fn set_inode_times(
+        &mut self,
+        inode: InodeNo,
+        atime: u32,
+        mtime: u32,
+    ) -> std::io::Result<()> {
+        self.volume.update_inode(inode, |node| {
+            node.atime.set(atime);
+            node.mtime.set(mtime);
+        })?;
+        Ok(())
+    }
+
+    // decoded information about an inode, what stat(2) would report
+    pub fn metadata(&self, inode: InodeNo) -> Result<Metadata, Ext2Error> {
+        Ok(Metadata::from_inode(inode, &self.get_inode(inode)?))
+    }
+
+    pub fn ls(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `ls [-a] [-l] [-R] [-S|-t] [-r] [--depth=N] [path ...]`
+        // paths can be globs (`*.txt`, `file?`, `[abc]*`) matched in their directory.
+        // -a shows entries starting with `.`, -l shows one entry per line with
+        // its metadata, -R lists subdirectories too, at most N levels deep.
+        // entries are sorted by name, or with -S largest first, or with -t
+        // newest first. -r reverses the order
+        let mut all = false;
+        let mut long = false;
+        let mut recursive = false;
+        let mut sort = SortBy::Name;
+        let mut reverse = false;
+        let mut depth = None;
+        let mut args = Vec::new();
+        for arg in command.split_whitespace().skip(1) {
+            if let Some(n) = arg.strip_prefix("--depth=") {
+                match n.parse::<usize>() {
+                    Ok(n) => depth = Some(n),
+                    Err(_) => {
+                        println!("ls: invalid depth: {}", n);
+                        return None;
+                    }
+                }
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                for flag in arg.chars().skip(1) {
+                    match flag {
+                        'a' => all = true,
+                        'l' => long = true,
+                        'R' => recursive = true,
+                        'S' => sort = SortBy::Size,
+                        't' => sort = SortBy::Time,
+                        'r' => reverse = true,
+                        _ => {
+                            println!("ls: unknown option: -{}", flag);
+                            return None;
+                        }
+                    }
+                }
+            } else {
+                args.push(arg);
+            }
+        }
+        // `ls` with no path lists the current directory
+        if args.is_empty() {
+            args.push(".");
+        }
+        let paths = match self.expand_globs(cwd, &args) {
+            Ok(paths) => paths,
+            Err(e) => {
+                println!("ls: {}", e);
+                return None;
+            }
+        };
+
+        let options = ListOptions {
+            all,
+            long,
+            sort,
+            reverse,
+        };
+        let mut ok = true;
+        for (i, path) in paths.iter().enumerate() {
+            let inode = match self.resolve(cwd, path) {
+                Ok(inode) => inode,
+                Err(e) => {
+                    println!("ls: {}", e);
+                    ok = false;
+                    continue;
+                }
+            };
+            let possible_inode = match self.get_inode(inode) {
+                Ok(node) => node,
+                Err(e) => {
+                    println!("ls: {}", e);
+                    ok = false;
+                    continue;
+                }
+            };
+            if FileType::from_mode(possible_inode.type_perm.get()) != FileType::Directory {
+                // a file lists as just its name, or its one line with -l
+                if long {
+                    print_long(&Metadata::from_inode(inode, &possible_inode), path);
+                } else {
+                    println!("{}", path);
+                }
+                continue;
+            }
+            // several directories each get a header, like the sections of -R
+            if paths.len() > 1 && !recursive {
+                if i > 0 {
+                    println!();
+                }
+                println!("{}:", path);
+            }
+
+            let result = if recursive {
+                let mut visited = HashSet::new();
+                self.ls_recursive(inode, path.to_string(), &options, depth, &mut visited)
+            } else {
+                self.check_access(inode, path, Access::Read)
+                    .and_then(|()| self.print_listing(inode, &options).map(|_| ()))
+            };
+            if let Err(e) = result {
+                println!("ls: {}", e);
+                ok = false;
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    // print the entries of one directory and hand them back for -R
+    fn print_listing(
+        &self,
+        dir: InodeNo,
+        options: &ListOptions,
+    ) -> Result<Vec<DirEntry>, Ext2Error> {
+        let mut entries = Vec::new();
+        for entry in self.dir_entries(dir)? {
+            if options.all || !entry.name.starts_with('.') {
+                let metadata = self.metadata(entry.inode)?;
+                entries.push((entry, metadata));
+            }
+        }
+        // sorting by name first leaves ties in size or time in name order,
+        // since sort_by is stable
+        entries.sort_by(|(a, _), (b, _)| a.name_bytes.cmp(&b.name_bytes));
+        match options.sort {
+            SortBy::Name => {}
+            SortBy::Size => entries.sort_by_key(|(_, metadata)| Reverse(metadata.len())),
+            SortBy::Time => entries.sort_by_key(|(_, metadata)| Reverse(metadata.mtime)),
+        }
+        if options.reverse {
+            entries.reverse();
+        }
+
+        for (entry, metadata) in &entries {
+            if options.long {
+                print_long(metadata, &entry.name);
+            } else {
+                print!("{}\t", entry.name);
+            }
+        }
+        if !options.long {
+            println!();
+        }
+        Ok(entries.into_iter().map(|(entry, _)| entry).collect())
+    }
+
+    // `ls -R`: a `path:` header and the listing for each directory, then the
+    // same for each subdirectory in turn, never following `.`/`..` or
+    // visiting a directory twice
+    fn ls_recursive(
+        &self,
+        dir: InodeNo,
+        path: String,
+        options: &ListOptions,
+        depth: Option<usize>,
+        visited: &mut HashSet<InodeNo>,
+    ) -> Result<(), Ext2Error> {
+        visited.insert(dir);
+        println!("{}:", path);
+        self.check_access(dir, &path, Access::Read)?;
+        let entries = self.print_listing(dir, options)?;
+        if depth == Some(0) {
+            return Ok(());
+        }
+        for entry in entries {
+            if entry.name == "." || entry.name == ".." || !self.metadata(entry.inode)?.is_dir() {
+                continue;
+            }
+            let child_path = format!("{}/{}", path.trim_end_matches('/'), entry.name);
+            if visited.contains(&entry.inode) {
+                println!();
+                println!("ls: {}: not listing already-listed directory", child_path);
+                continue;
+            }
+            println!();
+            self.ls_recursive(
+                entry.inode,
+                child_path,
+                options,
+                depth.map(|d| d - 1),
+                visited,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn cd(&self, cwd: InodeNo, command: String) -> Option<InodeNo> {
+        // `cd` with no arguments, cd goes back to root
+        // `cd dir_name` moves cwd to that directory
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() == 1 {
+            return Some(InodeNo::ROOT);
+        }
+        let paths = elts[1];
+        let inode = match self.resolve_for(cwd, paths, Access::Execute) {
+            Ok(inode) => inode,
+            Err(e) => {
+                println!("cd: {}", e);
+                return None;
+            }
+        };
+        let possible_inode = match self.get_inode(inode) {
+            Ok(node) => node,
+            Err(e) => {
+                println!("cd: {}", e);
+                return None;
+            }
+        };
+        // a file or a symlink can't be the cwd, leave it where it is
+        if FileType::from_mode(possible_inode.type_perm.get()) != FileType::Directory {
+            println!("cd: {}", Ext2Error::NotADirectory(paths.to_string()));
+            return None;
+        }
+        Some(inode)
+    }
+
+    pub fn mkdir(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `mkdir [-p] [--blocks n] childname`
+        // create a directory with the given name, add a link to cwd.
+        // `-p path/to/dir` creates every missing directory along the path.
+        // `--blocks n` gives the directory n blocks to start with, like
+        // lost+found has, so it can take many entries without allocating
+        self.writable("mkdir")?;
+        let mut parents = false;
+        let mut blocks = None;
+        let mut paths = None;
+        let mut args = command.split_whitespace().skip(1);
+        while let Some(arg) = args.next() {
+            match arg {
+                "-p" => parents = true,
+                "--blocks" => match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if (1..=12).contains(&n) => blocks = Some(n),
+                    _ => {
+                        println!("mkdir: --blocks takes a number from 1 to 12");
+                        return None;
+                    }
+                },
+                _ if paths.is_none() => paths = Some(arg),
+                _ => {
+                    println!("usage: mkdir [-p] [--blocks n] childname");
+                    return None;
+                }
+            }
+        }
+        let Some(paths) = paths else {
+            println!("must pass file to mkdir");
+            return None;
+        };
+        let made = if parents {
+            self.create_dir_all(cwd, paths)
+        } else {
+            self.resolve_parent(cwd, paths).and_then(|(parent, name)| {
+                self.create_inode(parent, name, FileType::Directory, 0o755)
+            })
+        };
+        let result = made.and_then(|dir| match blocks {
+            Some(blocks) => self.preallocate_dir(dir, blocks),
+            None => Ok(()),
+        });
+        match result {
+            Ok(()) => Some(()),
+            Err(e) => {
+                println!("mkdir: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn mknod(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `mknod name type`
+        // create an empty inode of the given type (file, dir, fifo, symlink,
+        // char, block or socket) and link it into the directory. devices
+        // get device number 0
+        self.writable("mknod")?;
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() != 3 {
+            println!("usage: mknod name file|dir|fifo|symlink|char|block|socket");
+            return None;
+        }
+        let (file_type, perms) = match elts[2] {
+            "file" | "f" => (FileType::Regular, 0o644),
+            "dir" | "d" => (FileType::Directory, 0o755),
+            "fifo" | "p" => (FileType::Fifo, 0o644),
+            "symlink" | "l" => (FileType::Symlink, 0o777),
+            "char" | "c" => (FileType::CharDevice, 0o644),
+            "block" | "b" => (FileType::BlockDevice, 0o644),
+            "socket" | "s" => (FileType::Socket, 0o644),
+            other => {
+                println!("mknod: unknown type: {}", other);
+                return None;
+            }
+        };
+        let found = Self::trailing_slash_ok(elts[1], file_type)
+            .and_then(|()| self.resolve_parent(cwd, elts[1]));
+        let (parent, name) = match found {
+            Ok(found) => found,
+            Err(e) => {
+                println!("mknod: {}", e);
+                return None;
+            }
+        };
+        match self.create_inode(parent, name, file_type, perms) {
+            Ok(_) => Some(()),
+            Err(e) => {
+                println!("mknod: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn write(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `write host_file path`
+        // copy host_file from the host into a new regular file at path
+        self.writable("write")?;
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, host_file, path] = elts[..] else {
+            println!("usage: write host_file path");
+            return None;
+        };
+        let data = match fs::read(host_file) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("write: {}: {}", host_file, e);
+                return None;
+            }
+        };
+        let found = Self::trailing_slash_ok(path, FileType::Regular)
+            .and_then(|()| self.resolve_parent(cwd, path));
+        let (parent, name) = match found {
+            Ok(found) => found,
+            Err(e) => {
+                println!("write: {}", e);
+                return None;
+            }
+        };
+        let written = self
+            .create_inode(parent, name, FileType::Regular, 0o644)
+            .and_then(|inode| {
+                let written = self.write_file_bytes(inode, &data);
+                if written.is_err() {
+                    // don't leave half a file behind
+                    let _ = self.remove_dir_entry(parent, name);
+                    let _ = self.free_inode(inode);
+                }
+                written
+            });
+        match written {
+            Ok(()) => Some(()),
+            Err(e) => {
+                println!("write: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn cat(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `cat filename ...`
+        // print the contents of each filename to stdout, one after the other
+        // if one is a directory, print a nice error and carry on with the rest
+        let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+        if args.is_empty() {
+            println!("must pass file to show");
+            return None;
+        }
+        let paths = match self.expand_globs(cwd, &args) {
+            Ok(paths) => paths,
+            Err(e) => {
+                println!("cat: {}", e);
+                return None;
+            }
+        };
+        let mut ok = true;
+        for path in &paths {
+            // get inode of potential file
+            let possible_inode = match self.resolve_for(cwd, path, Access::Read) {
+                Ok(inode) => inode,
+                Err(e) => {
+                    println!("cat: {}", e);
+                    ok = false;
+                    continue;
+                }
+            };
+            let metadata = match self.metadata(possible_inode) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    println!("cat: {}", e);
+                    ok = false;
+                    continue;
+                }
+            };
+            // the type is the top four bits as a whole: a symlink has the
+            // regular file's bit set too
+            if metadata.file_type != FileType::Regular {
+                println!("not a file: {}", path);
+                ok = false;
+                continue;
+            }
+            // exactly the file's bytes: it needn't be text, have a newline
+            // at the end, or stop at a zero byte. they go out a chunk at a
+            // time, however big the file is
+            let mut stdout = std::io::stdout().lock();
+            let written = copy_file(&self.volume, possible_inode, &mut stdout)
+                .and_then(|_| Ok(std::io::Write::flush(&mut stdout)?));
+            if let Err(e) = written {
+                println!("cat: {}: {}", path, e);
+                ok = false;
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    pub fn touch(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `touch [-c] filename`
+        // set the access and modification times of filename to the current time
+        // `-c` means do not create filename if it does not already exist
+        self.writable("touch")?;
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let no_create = elts.len() > 1 && elts[1] == "-c";
+        let paths = if no_create { elts.get(2) } else { elts.get(1) };
+        if paths.is_none() {
+            println!("usage: touch [-c] filename");
+            return None;
+        }
+        let paths = *paths.unwrap();
+
+        let possible_inode = match self.resolve(cwd, paths) {
+            Ok(inode) => inode,
+            Err(Ext2Error::NotFound(_)) if no_create => {
+                // nothing to do, just like unix touch -c
+                return Some(());
+            }
+            Err(Ext2Error::NotFound(_)) => {
+                let found = Self::trailing_slash_ok(paths, FileType::Regular)
+                    .and_then(|()| self.resolve_parent(cwd, paths));
+                let (parent, name) = match found {
+                    Ok(found) => found,
+                    Err(e) => {
+                        println!("touch: {}", e);
+                        return None;
+                    }
+                };
+                // a brand new file already has the current time
+                return match self.create_inode(parent, name, FileType::Regular, 0o644) {
+                    Ok(_) => Some(()),
+                    Err(e) => {
+                        println!("touch: {}", e);
+                        None
+                    }
+                };
+            }
+            Err(e) => {
+                println!("touch: {}", e);
+                return None;
+            }
+        };
+
+        // only to the current time, which append-only allows
+        let allowed = self
+            .check_flags(possible_inode, paths, true)
+            .and_then(|()| self.check_access(possible_inode, paths, Access::Write));
+        if let Err(e) = allowed {
+            println!("touch: {}", e);
+            return None;
+        }
+        let now = now();
+        match self.set_inode_times(possible_inode, now, now) {
+            Ok(()) => Some(()),
+            Err(_) => {
+                println!("unable to update times for {}", paths);
+                None
+            }
+        }
+    }
+
+    pub fn chattr(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `chattr +i|-i|+a|-a path ...`
+        // set (+) or clear (-) the immutable (i) or append-only (a) flag of
+        // each path. both can go together, as in `+ia`. paths can be globs
+        self.writable("chattr")?;
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let parsed = match elts[..] {
+            [_, mode, ref paths @ ..] if !paths.is_empty() => mode
+                .strip_prefix('+')
+                .map(|letters| (true, letters))
+                .or_else(|| mode.strip_prefix('-').map(|letters| (false, letters)))
+                .filter(|(_, letters)| !letters.is_empty())
+                .map(|(set, letters)| (set, letters, paths)),
+            _ => None,
+        };
+        let Some((set, letters, paths)) = parsed else {
+            println!("usage: chattr +i|-i|+a|-a path ...");
+            return None;
+        };
+        // like on Linux, only root can set or clear them
+        if matches!(self.identity, Some(Identity { uid, .. }) if uid != 0) {
+            println!("chattr: {}", Ext2Error::NotPermitted(paths.join(" ")));
+            return None;
+        }
+        let mut flags = InodeFlags::empty();
+        for letter in letters.chars() {
+            flags |= match letter {
+                'i' => InodeFlags::IMMUTABLE,
+                'a' => InodeFlags::APPEND_ONLY,
+                _ => {
+                    println!("chattr: unknown flag {}", letter);
+                    return None;
+                }
+            };
+        }
+        let paths = match self.expand_globs(cwd, paths) {
+            Ok(paths) => paths,
+            Err(e) => {
+                println!("chattr: {}", e);
+                return None;
+            }
+        };
+        let mut ok = true;
+        for path in &paths {
+            let result = self
+                .resolve(cwd, path)
+                .and_then(|inode| self.set_inode_flags(inode, flags, set));
+            if let Err(e) = result {
+                println!("chattr: {}", e);
+                ok = false;
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    pub fn stat(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `stat path`
+        // print what the inode behind path says about it
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() == 1 {
+            println!("usage: stat path");
+            return None;
+        }
+        let paths = elts[1];
+        let metadata = match self
+            .resolve(cwd, paths)
+            .and_then(|inode| self.metadata(inode))
+        {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("stat: {}", e);
+                return None;
+            }
+        };
+        println!("  File: {}", paths);
+        println!(
+            "  Size: {}\tBlocks: {}\t{}",
+            metadata.len(),
+            metadata.blocks,
+            metadata.file_type
+        );
+        println!("Inode: {}\tLinks: {}", metadata.ino, metadata.nlink);
+        println!(
+            "Access: ({:04o}/{})\tUid: {}\tGid: {}",
+            metadata.perms,
+            metadata.mode_string(),
+            metadata.uid,
+            metadata.gid
+        );
+        println!("Access: {}", metadata.atime);
+        println!("Modify: {}", metadata.mtime);
+        println!("Change: {}", metadata.ctime);
+        Some(())
+    }
+
+    pub fn getxattr(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `getxattr path`
+        // print the extended attributes of path, one `name="value"` a line,
+        // the ones kept in the inode before the ones in its attribute block
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, path] = elts[..] else {
+            println!("usage: getxattr path");
+            return None;
+        };
+        match self.resolve(cwd, path).and_then(|inode| self.xattrs(inode)) {
+            Ok(xattrs) => {
+                for xattr in xattrs {
+                    println!(
+                        "{}=\"{}\"",
+                        escape_name(&xattr.name),
+                        escape_name(&xattr.value)
+                    );
+                }
+                Some(())
+            }
+            Err(e) => {
+                println!("getxattr: {}", e);
+                None
+            }
+        }
+    }
+
+    // every extended attribute of an inode: the ones in the space after a
+    // big inode's extra fields, then the ones in its attribute block
+    pub fn xattrs(&self, inode: InodeNo) -> Result<Vec<Xattr>, Ext2Error> {
+        let inode_size = self.superblock().inode_size();
+        let mut xattrs = Vec::new();
+        if inode_size > 128 {
+            let (_, block, offset) = self.inode_location(inode)?;
+            let raw = &self.block(block)?[offset..offset + inode_size];
+            let extra_isize = u16::from_le_bytes([raw[128], raw[129]]) as usize;
+            if 128 + extra_isize <= inode_size {
+                xattrs = parse_in_inode(&raw[128 + extra_isize..])?;
+            }
+        }
+        let block = self.get_inode(inode)?.ext_attribute_block.get() as usize;
+        if block != 0 {
+            xattrs.extend(parse_block(self.block(block)?)?);
+        }
+        Ok(xattrs)
+    }
+
+    pub fn lsinodes(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `lsinodes [--all]`
+        // list every inode the bitmaps say is in use with its type, size and
+        // link count. the reserved inodes below first_inode are left out,
+        // all but the root, unless `--all` is given
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let all = match elts[..] {
+            [_] => false,
+            [_, "--all"] => true,
+            _ => {
+                println!("usage: lsinodes [--all]");
+                return None;
+            }
+        };
+        let first_inode = self.first_inode();
+        println!("inode\ttype\tsize\tlinks");
+        for n in 1..=self.superblock().inodes_count.get() as usize {
+            let inode = InodeNo::new(n, self.superblock()).ok()?;
+            let reserved = n < first_inode && inode != InodeNo::ROOT;
+            if !self.inode_in_use(inode) || (reserved && !all) {
+                continue;
+            }
+            match self.metadata(inode) {
+                Ok(metadata) => println!(
+                    "{}\t{}\t{}\t{}",
+                    n,
+                    metadata.file_type.name(),
+                    metadata.len(),
+                    metadata.nlink
+                ),
+                Err(e) => {
+                    println!("lsinodes: {}", e);
+                    return None;
+                }
+            }
+        }
+        Some(())
+    }
+
+    pub fn orphans(&self, _cwd: InodeNo, _command: String) -> Option<()> {
+        // `orphans`
+        // list inodes that are in use and linked, but that no directory
+        // entry reachable from the root leads to: leaked by a bad unlink
+        let orphans = match self.find_orphans() {
+            Ok(orphans) => orphans,
+            Err(e) => {
+                println!("orphans: {}", e);
+                return None;
+            }
+        };
+        for metadata in &orphans {
+            println!(
+                "inode {}: {}, {} bytes, {} links, not in any directory",
+                metadata.ino,
+                metadata.file_type.name(),
+                metadata.len(),
+                metadata.nlink
+            );
+        }
+        println!("{} orphaned inodes", orphans.len());
+        if orphans.is_empty() {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    // the inodes the bitmaps say are in use, with a link count, that the
+    // tree doesn't reach. the walk goes through `Volume`, which reads every
+    // block of a directory, so big directories don't hide anything
+    pub fn find_orphans(&self) -> Result<Vec<Metadata>, Ext2Error> {
+        let mut reachable = HashSet::from([InodeNo::ROOT]);
+        let mut queue = vec![InodeNo::ROOT];
+        while let Some(dir) = queue.pop() {
+            for entry in self.volume.read_dir(dir)? {
+                if reachable.insert(entry.inode) && self.volume.metadata(entry.inode)?.is_dir() {
+                    queue.push(entry.inode);
+                }
+            }
+        }
+        let first_inode = self.first_inode();
+        let mut orphans = Vec::new();
+        for n in first_inode..=self.superblock().inodes_count.get() as usize {
+            let inode = InodeNo::new(n, self.superblock())?;
+            if !self.inode_in_use(inode) || reachable.contains(&inode) {
+                continue;
+            }
+            let metadata = self.metadata(inode)?;
+            if metadata.nlink > 0 {
+                orphans.push(metadata);
+            }
+        }
+        Ok(orphans)
+    }
+
+    // link each orphan into /lost+found as `#<inode>`, the way e2fsck does,
+    // making lost+found if there isn't one. what's inside an orphaned
+    // directory comes along with it, so only the tops of orphaned trees get
+    // an entry. returns the inodes reconnected and their new names
+    pub fn reconnect_orphans(&mut self) -> Result<Vec<(InodeNo, String)>, Ext2Error> {
+        let orphans = self.find_orphans()?;
+        if orphans.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut inside = HashSet::new();
+        let mut queue: Vec<InodeNo> = orphans
+            .iter()
+            .filter(|metadata| metadata.is_dir())
+            .map(|metadata| metadata.ino)
+            .collect();
+        let mut visited: HashSet<InodeNo> = queue.iter().copied().collect();
+        while let Some(dir) = queue.pop() {
+            for entry in self.dir_entries(dir)? {
+                if entry.name_bytes == b"." || entry.name_bytes == b".." {
+                    continue;
+                }
+                inside.insert(entry.inode);
+                if visited.insert(entry.inode) && self.metadata(entry.inode)?.is_dir() {
+                    queue.push(entry.inode);
+                }
+            }
+        }
+
+        let lost_found = match self.lookup(InodeNo::ROOT, "lost+found") {
+            Ok(inode) if self.metadata(inode)?.is_dir() => inode,
+            Ok(_) => return Err(Ext2Error::NotADirectory("lost+found".to_string())),
+            Err(Ext2Error::NotFound(_)) => {
+                self.create_inode(InodeNo::ROOT, "lost+found", FileType::Directory, 0o700)?
+            }
+            Err(e) => return Err(e),
+        };
+        let mut reconnected = Vec::new();
+        for metadata in orphans {
+            if inside.contains(&metadata.ino) {
+                continue;
+            }
+            let name = format!("#{}", metadata.ino);
+            // insert_dir_entry gives lost+found another block once it's full
+            self.insert_dir_entry(
+                lost_found,
+                &name,
+                metadata.ino,
+                metadata.file_type.type_indicator(),
+            )?;
+            if metadata.is_dir() {
+                // `..` still points at the directory it was cut out of
+                let old_parent = self.lookup(metadata.ino, "..")?;
+                self.set_dot_dot(metadata.ino, lost_found)?;
+                if old_parent != lost_found
+                    && self.inode_in_use(old_parent)
+                    && self.metadata(old_parent)?.is_dir()
+                {
+                    self.adjust_links(old_parent, -1)?;
+                }
+                self.adjust_links(lost_found, 1)?;
+            }
+            reconnected.push((metadata.ino, name));
+        }
+        Ok(reconnected)
+    }
+
+    pub fn resolve_trace(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `resolve path`
+        // show each step resolve takes: the component, the inode it led to
+        // and that inode's type, ending with where it failed if it did
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() == 1 {
+            println!("usage: resolve path");
+            return None;
+        }
+        let path = elts[1];
+        if path.starts_with('/') {
+            println!("/\t{}\t{}", InodeNo::ROOT, FileType::Directory);
+        } else {
+            match self.metadata(cwd) {
+                Ok(metadata) => println!(".\t{}\t{}", cwd, metadata.file_type),
+                Err(e) => {
+                    println!("resolve: {}", e);
+                    return None;
+                }
+            }
+        }
+        // the steps are printed as path::resolve takes them, so the trace is
+        // of exactly what every other command does with the path
+        match path::resolve(&Trace(self), cwd, &unescape_name(path)) {
+            Ok(inode) => println!("=> inode {}", inode),
+            Err(e) => {
+                println!("=> failed: {}", e);
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    pub fn bmap(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `bmap path n`
+        // print the block holding logical block n of path's data, like
+        // debugfs does. 0 means a hole
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, path, logical] = elts[..] else {
+            println!("usage: bmap path n");
+            return None;
+        };
+        let Ok(logical) = logical.parse::<usize>() else {
+            println!("bmap: {} is not a block number", logical);
+            return None;
+        };
+        let node = match self
+            .resolve(cwd, path)
+            .and_then(|inode| self.get_inode(inode))
+        {
+            Ok(node) => node,
+            Err(e) => {
+                println!("bmap: {}", e);
+                return None;
+            }
+        };
+        if !self.volume.has_block_pointers(&node) {
+            println!("bmap: {} has no data blocks", path);
+            return None;
+        }
+        let per_block = self.pointers_per_block();
+        let addressable = 12 + per_block + per_block.pow(2) + per_block.pow(3);
+        if logical >= addressable {
+            println!(
+                "bmap: an inode has {} logical blocks, {} is past them",
+                addressable, logical
+            );
+            return None;
+        }
+        match self.volume.block_at(&node, logical as u64) {
+            Ok(block) => {
+                println!("{}", block);
+                Some(())
+            }
+            Err(e) => {
+                println!("bmap: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn frag(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `frag path`
+        // how contiguous path's data is: its blocks, the runs of consecutive
+        // block numbers they make, and the most blocks between two runs
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, path] = elts[..] else {
+            println!("usage: frag path");
+            return None;
+        };
+        let inode = match self.resolve(cwd, path) {
+            Ok(inode) => inode,
+            Err(e) => {
+                println!("frag: {}", e);
+                return None;
+            }
+        };
+        let blocks = self.data_blocks_for_inode(inode);
+        if blocks.is_empty() {
+            println!("{}: no data blocks", path);
+            return Some(());
+        }
+        let mut fragments = 1;
+        let mut largest_gap = 0;
+        for pair in blocks.windows(2) {
+            if pair[1] != pair[0] + 1 {
+                fragments += 1;
+                // a run can also start before the last one, so the gap is
+                // how far the next block is from where it would have been
+                largest_gap = largest_gap.max(pair[1].abs_diff(pair[0] + 1));
+            }
+        }
+        println!(
+            "{}: {} blocks in {} fragments, largest gap {} blocks",
+            path,
+            blocks.len(),
+            fragments,
+            largest_gap
+        );
+        Some(())
+    }
+
+    pub fn defrag(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `defrag path`
+        // move path's data into one run of newly allocated blocks and free
+        // the old ones. with no free run big enough the file stays as it is
+        self.writable("defrag")?;
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, path] = elts[..] else {
+            println!("usage: defrag path");
+            return None;
+        };
+        let inode = match self.resolve(cwd, path) {
+            Ok(inode) => inode,
+            Err(e) => {
+                println!("defrag: {}", e);
+                return None;
+            }
+        };
+        match self.defragment(inode) {
+            Ok(Defrag::Moved { first, len }) => {
+                println!("{}: moved to blocks {}-{}", path, first, first + len - 1)
+            }
+            Ok(Defrag::Contiguous) => println!("{}: already in one fragment", path),
+            Ok(Defrag::NoRun { needed }) => println!(
+                "{}: no run of {} free blocks to move it to, leaving it as it is",
+                path, needed
+            ),
+            Err(e) => {
+                println!("defrag: {}", e);
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    pub fn freemap(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `freemap`
+        // for each group, how many blocks are free and the longest run of
+        // them, which is the most `defrag` can move into it
+        if command.split_whitespace().count() != 1 {
+            println!("usage: freemap");
+            return None;
+        }
+        let mut largest: Option<(usize, usize)> = None;
+        for group in 0..self.block_groups().len() {
+            let runs = match self.free_runs(group) {
+                Ok(runs) => runs,
+                Err(e) => {
+                    println!("freemap: {}", e);
+                    return None;
+                }
+            };
+            let free: usize = runs.iter().map(|&(_, len)| len).sum();
+            // the first of the longest, like defrag would pick
+            let Some(&(first, len)) = runs.iter().rev().max_by_key(|&&(_, len)| len) else {
+                println!("group {}: no free blocks", group);
+                continue;
+            };
+            let blocks = if free == 1 { "block" } else { "blocks" };
+            println!(
+                "group {}: {} free {}, largest run {} (blocks {}-{})",
+                group,
+                free,
+                blocks,
+                len,
+                first,
+                first + len - 1
+            );
+            if largest.map_or(true, |(_, most)| len > most) {
+                largest = Some((group, len));
+            }
+        }
+        match largest {
+            Some((group, len)) => println!("largest free run: {} blocks, in group {}", len, group),
+            None => println!("largest free run: none, the file system is full"),
+        }
+        Some(())
+    }
+
+    pub fn bitmap(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `bitmap [-i] [group]`
+        // draw a group's block bitmap, or with `-i` its inode bitmap, as rows
+        // of 64 cells: `#` for used and `.` for free. group 0 by default
+        let mut inodes = false;
+        let mut group = None;
+        for arg in command.split_whitespace().skip(1) {
+            if arg == "-i" {
+                inodes = true;
+            } else if let (None, Ok(n)) = (group, arg.parse::<usize>()) {
+                group = Some(n);
+            } else {
+                println!("usage: bitmap [-i] [group]");
+                return None;
+            }
+        }
+        let group = group.unwrap_or(0);
+        let Some(descriptor) = self.block_groups().get(group) else {
+            println!(
+                "bitmap: there is no group {}, there are {}",
+                group,
+                self.block_groups().len()
+            );
+            return None;
+        };
+        // what the bitmap covers, the number of the first thing in it, and
+        // how many of them the descriptor says are free
+        let (kind, bitmap, first, len, free) = if inodes {
+            let per_group = self.superblock().inodes_per_group.get() as usize;
+            (
+                "inode",
+                descriptor.inode_usage_addr.get(),
+                group * per_group + 1,
+                per_group,
+                descriptor.free_inodes_count.get(),
+            )
+        } else {
+            let (_, limit) = self.allocatable_bits(group);
+            let per_group = self.superblock().blocks_per_group.get() as usize;
+            let first_data_block = self.superblock().first_data_block.get() as usize;
+            (
+                "block",
+                descriptor.block_usage_addr.get(),
+                first_data_block + group * per_group,
+                limit,
+                descriptor.free_blocks_count.get(),
+            )
+        };
+        let bitmap = match self.block(bitmap as usize) {
+            Ok(bitmap) => bitmap,
+            Err(e) => {
+                println!("bitmap: {}", e);
+                return None;
+            }
+        };
+        if len > bitmap.len() * 8 {
+            println!(
+                "bitmap: a group of {} {}s doesn't fit in one bitmap block",
+                len, kind
+            );
+            return None;
+        }
+        println!(
+            "{} bitmap of group {}, {}s {}-{}:",
+            kind,
+            group,
+            kind,
+            first,
+            first + len - 1
+        );
+        let width = (first + len - 1).to_string().len();
+        let mut used = 0;
+        for row in (0..len).step_by(64) {
+            let cells: String = (row..cmp::min(row + 64, len))
+                .map(|i| {
+                    if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                        used += 1;
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            println!("{:>width$} {}", first + row, cells, width = width);
+        }
+        print!(
+            "group {}: {} of {} {}s used, {} free",
+            group,
+            used,
+            len,
+            kind,
+            len - used
+        );
+        if free as usize != len - used {
+            print!(" (the descriptor says {} free)", free);
+        }
+        println!();
+        Some(())
+    }
+
+    pub fn pwd(&self, cwd: InodeNo, _command: String) -> Option<()> {
+        // `pwd`
+        // print the path from the root to the current directory
+        match self.path_of_inode(cwd) {
+            Some(path) => println!("{}", path),
+            None => {
+                println!("pwd: inode {} can't be reached from the root", cwd);
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    pub fn find(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `find [--jobs N] [path]`
+        // print path and every path below it, one per line, sorted. with
+        // --jobs the directories are walked on N threads, which needs the
+        // rayon feature. the output is the same either way
+        let mut args = command.split_whitespace().skip(1);
+        let mut jobs = 1;
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            match arg {
+                "--jobs" => match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) if n > 0 => jobs = n,
+                    _ => {
+                        println!("usage: find [--jobs N] [path]");
+                        return None;
+                    }
+                },
+                _ if path.is_none() => path = Some(arg),
+                _ => {
+                    println!("usage: find [--jobs N] [path]");
+                    return None;
+                }
+            }
+        }
+        let path = path.unwrap_or(".");
+        if jobs > 1 && cfg!(not(feature = "rayon")) {
+            println!("find: built without the rayon feature, walking with one thread");
+        }
+        let inode = match self.resolve(cwd, path) {
+            Ok(inode) => inode,
+            Err(e) => {
+                println!("find: {}", e);
+                return None;
+            }
+        };
+        let is_dir = match self.metadata(inode) {
+            Ok(metadata) => metadata.is_dir(),
+            Err(e) => {
+                println!("find: {}", e);
+                return None;
+            }
+        };
+        println!("{}", path);
+        if !is_dir {
+            return Some(());
+        }
+        match self.walk_tree(inode, path, jobs) {
+            Ok(found) => {
+                for (path, _) in found {
+                    println!("{}", path);
+                }
+                Some(())
+            }
+            Err(e) => {
+                println!("find: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn ncheck(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `ncheck inode ...`
+        // print a path to each inode, like debugfs does. the numbers can be
+        // given as they are or in debugfs's `<12>` form
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() < 2 {
+            println!("usage: ncheck inode ...");
+            return None;
+        }
+        let mut ok = true;
+        println!("Inode\tPathname");
+        for number in &elts[1..] {
+            let digits = number
+                .strip_prefix('<')
+                .and_then(|n| n.strip_suffix('>'))
+                .unwrap_or(number);
+            let Ok(n) = digits.parse::<usize>() else {
+                println!("ncheck: {} is not an inode number", number);
+                ok = false;
+                continue;
+            };
+            match InodeNo::new(n, self.superblock()).map(|inode| self.path_of_inode(inode)) {
+                Ok(Some(path)) => println!("{}\t{}", n, path),
+                Ok(None) => {
+                    println!("ncheck: inode {} can't be reached from the root", n);
+                    ok = false;
+                }
+                Err(e) => {
+                    println!("ncheck: {}", e);
+                    ok = false;
+                }
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    pub fn rev(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `rev`
+        // print the superblock's revision, and what depends on it: revision
+        // 0 has no first_ino or inode_size fields and always uses 11 and 128
+        if command.split_whitespace().count() != 1 {
+            println!("usage: rev");
+            return None;
+        }
+        let revision = self.superblock().rev_major.get();
+        let name = match revision {
+            0 => "good old",
+            1 => "dynamic",
+            _ => "unknown",
+        };
+        println!("revision: {} ({})", revision, name);
+        println!("minor revision: {}", self.superblock().rev_minor.get());
+        println!("first inode: {}", self.superblock().first_ino());
+        println!("inode size: {}", self.superblock().inode_size());
+        Some(())
+    }
+
+    pub fn lookup_name(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `lookup dir name`
+        // print the inode number the entry `name` of directory `dir` has
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, dir, name] = elts[..] else {
+            println!("usage: lookup dir name");
+            return None;
+        };
+        match self
+            .resolve(cwd, dir)
+            .and_then(|dir| self.lookup(dir, name))
+        {
+            Ok(inode) => {
+                println!("{}", inode);
+                Some(())
+            }
+            Err(e) => {
+                println!("lookup: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn rlookup(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `rlookup dir inode`
+        // print the name of each entry of `dir` that points at inode, one
+        // per line. hard links give a file more than one. the number can be
+        // given as it is or in debugfs's `<12>` form
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, dir, number] = elts[..] else {
+            println!("usage: rlookup dir inode");
+            return None;
+        };
+        let digits = number
+            .strip_prefix('<')
+            .and_then(|n| n.strip_suffix('>'))
+            .unwrap_or(number);
+        let Ok(n) = digits.parse::<usize>() else {
+            println!("rlookup: {} is not an inode number", number);
+            return None;
+        };
+        let found = InodeNo::new(n, self.superblock()).and_then(|inode| {
+            let dir_inode = self.resolve(cwd, dir)?;
+            if !self.metadata(dir_inode)?.is_dir() {
+                return Err(Ext2Error::NotADirectory(dir.to_string()));
+            }
+            Ok(self
+                .dir_entries(dir_inode)?
+                .into_iter()
+                .filter(|entry| entry.inode == inode)
+                .collect::<Vec<_>>())
+        });
+        let entries = match found {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("rlookup: {}", e);
+                return None;
+            }
+        };
+        if entries.is_empty() {
+            println!("rlookup: no entry of {} points at inode {}", dir, n);
+            return None;
+        }
+        for entry in entries {
+            println!("{}", entry.name);
+        }
+        Some(())
+    }
+
+    pub fn imap(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `imap inode`
+        // print where inode lives on disk, like debugfs does. the number can
+        // be given as it is or in debugfs's `<12>` form
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, number] = elts[..] else {
+            println!("usage: imap inode");
+            return None;
+        };
+        let digits = number
+            .strip_prefix('<')
+            .and_then(|n| n.strip_suffix('>'))
+            .unwrap_or(number);
+        let Ok(n) = digits.parse::<usize>() else {
+            println!("imap: {} is not an inode number", number);
+            return None;
+        };
+        let location =
+            InodeNo::new(n, self.superblock()).and_then(|inode| self.inode_location(inode));
+        match location {
+            Ok((group, block, offset)) => {
+                println!("Inode {} is part of block group {}", n, group);
+                println!("\tlocated at block {}, offset 0x{:04x}", block, offset);
+                Some(())
+            }
+            Err(e) => {
+                println!("imap: {}", e);
+                None
+            }
+        }
+    }
+
+    // the whole image as it is now, changes included
+    pub fn image(&self) -> &[u8] {
+        self.volume.device()
+    }
+
+    pub fn tar(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `tar path output.tar`
+        // write the tree at path to a tar archive on the host. entries are
+        // named after path's last component, or relative to it for `/`, `.`
+        // and `..`
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() != 3 {
+            println!("usage: tar path output.tar");
+            return None;
+        }
+        let (path, output) = (elts[1], elts[2]);
+        // only what path leads to is checked, not everything under it
+        let root = match self.resolve_for(cwd, path, Access::Read) {
+            Ok(root) => root,
+            Err(e) => {
+                println!("tar: {}", e);
+                return None;
+            }
+        };
+        let name = match path.trim_end_matches('/').rsplit('/').next() {
+            Some(".") | Some("..") | Some("") | None => Vec::new(),
+            Some(name) => unescape_name(name),
+        };
+        let written = fs::File::create(output)
+            .map_err(Ext2Error::from)
+            .and_then(|file| {
+                ext2::tar::write_tar(&self.volume, root, &name, std::io::BufWriter::new(file))
+            });
+        if let Err(e) = written {
+            println!("tar: {}", e);
+            return None;
+        }
+        Some(())
+    }
+
+    pub fn export(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `export path host_file`
+        // copy the regular file at path to a new file on the host, a chunk
+        // at a time so it can be bigger than memory. like clone, an
+        // existing file is never overwritten
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let [_, path, output] = elts[..] else {
+            println!("usage: export path host_file");
+            return None;
+        };
+        let inode = match self.resolve_for(cwd, path, Access::Read) {
+            Ok(inode) => inode,
+            Err(e) => {
+                println!("export: {}", e);
+                return None;
+            }
+        };
+        let created = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(output);
+        let written = created.map_err(Ext2Error::from).and_then(|file| {
+            let mut out = std::io::BufWriter::with_capacity(CHUNK_SIZE, file);
+            copy_file(&self.volume, inode, &mut out)?;
+            std::io::Write::flush(&mut out)?;
+            Ok(())
+        });
+        match written {
+            Ok(()) => Some(()),
+            Err(e) => {
+                // don't leave half a file behind, but only one this made
+                if !matches!(&e, Ext2Error::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists)
+                {
+                    let _ = fs::remove_file(output);
+                }
+                println!("export: {}", e);
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "sha256")]
+    pub fn sha256(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `sha256 path ...`
+        // print the SHA-256 of each regular file like sha256sum does, the
+        // file read a chunk at a time. paths can be globs
+        use ext2::file::Ext2File;
+        use sha2::{Digest, Sha256};
+        use std::io::Read;
+        let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+        if args.is_empty() {
+            println!("usage: sha256 path ...");
+            return None;
+        }
+        let paths = match self.expand_globs(cwd, &args) {
+            Ok(paths) => paths,
+            Err(e) => {
+                println!("sha256: {}", e);
+                return None;
+            }
+        };
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut ok = true;
+        for path in &paths {
+            let hashed = self.resolve_for(cwd, path, Access::Read).and_then(|inode| {
+                let mut file = Ext2File::open(&self.volume, inode)?;
+                let mut hasher = Sha256::new();
+                loop {
+                    let read = file.read(&mut buf)?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buf[..read]);
+                }
+                Ok(hasher.finalize())
+            });
+            match hashed {
+                Ok(hash) => {
+                    let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    println!("{}  {}", hex, path);
+                }
+                Err(e) => {
+                    println!("sha256: {}", e);
+                    ok = false;
+                }
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(feature = "sha256"))]
+    pub fn sha256(&self, _cwd: InodeNo, _command: String) -> Option<()> {
+        println!("sha256: built without the sha256 feature");
+        None
+    }
+
+    pub fn clone_image(&self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `clone out.ext2`
+        // write the image as it is now, changes and all, to a new file on the
+        // host. changes are made straight to the image in memory, so there's
+        // nothing to flush first. an existing file is never overwritten, it
+        // could be the image itself
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() != 2 {
+            println!("usage: clone out.ext2");
+            return None;
+        }
+        let written = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(elts[1])
+            .and_then(|mut file| {
+                use std::io::Write;
+                file.write_all(self.image())?;
+                file.sync_all()
+            });
+        if let Err(e) = written {
+            println!("clone: {}: {}", elts[1], e);
+            return None;
+        }
+        Some(())
+    }
+
+    pub fn dump(&self, cwd: InodeNo, command: String) -> Option<()> {
+        // `dump --json [--tree] [--pretty] [path]`
+        // print the superblock and block group descriptors as one line of
+        // JSON, or indented with --pretty. --tree adds everything under path,
+        // `/` if it isn't given
+        let elts: Vec<&str> = command.split_whitespace().skip(1).collect();
+        let mut json = false;
+        let mut tree = false;
+        let mut pretty = false;
+        let mut path = None;
+        for elt in elts {
+            match elt {
+                "--json" => json = true,
+                "--tree" => tree = true,
+                "--pretty" => pretty = true,
+                _ if elt.starts_with('-') => {
+                    println!("dump: unknown option {}", elt);
+                    return None;
+                }
+                _ if path.is_none() => path = Some(elt),
+                _ => {
+                    println!("usage: dump --json [--tree] [--pretty] [path]");
+                    return None;
+                }
+            }
+        }
+        if !json {
+            println!("usage: dump --json [--tree] [--pretty] [path]");
+            return None;
+        }
+        self.dump_json(cwd, path.unwrap_or("/"), tree, pretty)
+    }
+
+    #[cfg(feature = "json")]
+    fn dump_json(&self, cwd: InodeNo, path: &str, tree: bool, pretty: bool) -> Option<()> {
+        let root = if tree {
+            self.resolve(cwd, path).map(Some)
+        } else {
+            Ok(None)
+        };
+        let dumped = root.and_then(|root| {
+            let name = match path.trim_end_matches('/').rsplit('/').next() {
+                Some("") | None => "/".as_bytes().to_vec(),
+                Some(name) => unescape_name(name),
+            };
+            ext2::dump::dump(&self.volume, root.map(|inode| (inode, &name[..])))
+        });
+        let dump = match dumped {
+            Ok(dump) => dump,
+            Err(e) => {
+                println!("dump: {}", e);
+                return None;
+            }
+        };
+        let json = if pretty {
+            serde_json::to_string_pretty(&dump)
+        } else {
+            serde_json::to_string(&dump)
+        };
+        match json {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                println!("dump: {}", e);
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn dump_json(&self, _cwd: InodeNo, _path: &str, _tree: bool, _pretty: bool) -> Option<()> {
+        println!("dump: built without the json feature");
+        None
+    }
+
+    pub fn resizefs(&self, _cwd: InodeNo, command: String) -> Option<Vec<u8>> {
+        // `resizefs n_blocks`
+        // grow the file system to n_blocks blocks. this only makes the new
+        // image, main swaps it in for the old one
+        self.writable("resizefs")?;
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        let blocks_count = match elts[..] {
+            [_, n] => match n.parse::<u32>() {
+                Ok(n) => n,
+                Err(_) => {
+                    println!("resizefs: {} is not a number of blocks", n);
+                    return None;
+                }
+            },
+            _ => {
+                println!("usage: resizefs n_blocks");
+                return None;
+            }
+        };
+        match self.grown(blocks_count) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                println!("resizefs: {}", e);
+                None
+            }
+        }
+    }
+
+    // the shell doesn't update metadata_csum checksums, so changing an
+    // image that has them would leave it corrupt for e2fsck and Linux
+    fn writable(&self, command: &str) -> Option<()> {
+        if self.dry_run {
+            println!(
+                "{}: this is a dry run, and only rm and mv can say what they would change",
+                command
+            );
+            return None;
+        }
+        if has_metadata_csum(self.superblock()) {
+            println!(
+                "{}: the file system has metadata_csum checksums, which this shell can't update, so it is read-only",
+                command
+            );
+            return None;
+        }
+        Some(())
+    }
+
+    pub fn csum_check(&self, _cwd: InodeNo, _command: String) -> Option<()> {
+        // `csum-check`
+        // check the metadata_csum checksums of the superblock, descriptors,
+        // bitmaps, inodes and directory blocks, changing nothing
+        if !has_metadata_csum(self.superblock()) {
+            println!("csum-check: the file system has no metadata_csum checksums");
+            return Some(());
+        }
+        match csum::verify(self.image()) {
+            Ok(report) => {
+                for mismatch in &report.mismatches {
+                    println!("{}", mismatch);
+                }
+                println!(
+                    "{} checksums checked, {} wrong",
+                    report.checked,
+                    report.mismatches.len()
+                );
+                if report.is_valid() {
+                    Some(())
+                } else {
+                    None
+                }
+            }
+            Err(e) => {
+                println!("csum-check: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn fsck(&mut self, _cwd: InodeNo, command: String) -> Option<()> {
+        // `fsck [--reconnect]`
+        // check the whole file system for inconsistencies, changing nothing.
+        // with --reconnect, orphaned inodes are first linked into lost+found
+        let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+        match args[..] {
+            [] => {}
+            ["--reconnect"] => {
+                self.writable("fsck")?;
+                let reconnected = match self.reconnect_orphans() {
+                    Ok(reconnected) => reconnected,
+                    Err(e) => {
+                        println!("fsck: {}", e);
+                        return None;
+                    }
+                };
+                for (inode, name) in &reconnected {
+                    println!("reconnected inode {} as /lost+found/{}", inode, name);
+                }
+            }
+            _ => {
+                println!("usage: fsck [--reconnect]");
+                return None;
+            }
+        }
+        let report = self.check();
+        println!("{}", report);
+        // warnings alone don't make the file system unsafe to use
+        if report.with_severity(Severity::Error).next().is_none() {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    pub fn rm(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `rm [-r] [--trash] [--dry-run] target ...`
+        // unlink a file or empty directory. targets can be globs.
+        // `-r` removes a directory with everything in it.
+        // `--trash` moves the targets into /.trash instead, see `empty-trash`.
+        // `--dry-run` prints what would change for each target, changing nothing.
+        // the current directory and the ones above it are never removed
+        let mut recursive = false;
+        let mut trash = false;
+        let mut dry_run = self.dry_run;
+        let mut args = Vec::new();
+        for arg in command.split_whitespace().skip(1) {
+            if arg == "-r" {
+                recursive = true;
+            } else if arg == "--trash" {
+                trash = true;
+            } else if arg == "--dry-run" {
+                dry_run = true;
+            } else if arg.starts_with('-') {
+                // not a name, `./-x` is how to remove a file called -x
+                println!("rm: unknown option {}", arg);
+                return None;
+            } else {
+                args.push(arg);
+            }
+        }
+        if args.is_empty() {
+            println!("usage: rm [-r] [--trash] [--dry-run] target ...");
+            return None;
+        }
+        if !dry_run {
+            self.writable("rm")?;
+        }
+        let targets = match self.expand_globs(cwd, &args) {
+            Ok(targets) => targets,
+            Err(e) => {
+                println!("rm: {}", e);
+                return None;
+            }
+        };
+        let mut ok = true;
+        for target in &targets {
+            let result = self.resolve_parent(cwd, target).and_then(|(parent, name)| {
+                self.check_not_cwd(cwd, parent, name, target)?;
+                if dry_run {
+                    let plan = if trash {
+                        self.plan_move_to_trash(parent, name)?
+                    } else if recursive {
+                        // the plan for a directory depends on its children
+                        // being gone, which a plan doesn't do
+                        return Err(Ext2Error::Unsupported("a dry run of rm -r".to_string()));
+                    } else {
+                        self.plan_unlink(parent, name)?
+                    };
+                    print_plan(target, &plan);
+                    Ok(())
+                } else if trash {
+                    self.move_to_trash(parent, name).map(|_| ())
+                } else if recursive {
+                    self.remove_tree(parent, name)
+                } else {
+                    self.unlink(parent, name)
+                }
+            });
+            if let Err(e) = result {
+                println!("rm: {}", e);
+                ok = false;
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    // refuse to remove the entry `name` of `parent` when it is the current
+    // directory or one above it, which would leave the shell nowhere
+    fn check_not_cwd(
+        &self,
+        cwd: InodeNo,
+        parent: InodeNo,
+        name: &str,
+        target: &str,
+    ) -> Result<(), Ext2Error> {
+        let child = self.lookup(parent, name)?;
+        if self.metadata(child)?.is_dir() && self.is_within(cwd, child)? {
+            return Err(Ext2Error::Busy(target.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn rmdir(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `rmdir dir ...`
+        // remove empty directories, and nothing else. targets can be globs
+        let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+        if args.is_empty() {
+            println!("usage: rmdir dir ...");
+            return None;
+        }
+        self.writable("rmdir")?;
+        let targets = match self.expand_globs(cwd, &args) {
+            Ok(targets) => targets,
+            Err(e) => {
+                println!("rmdir: {}", e);
+                return None;
+            }
+        };
+        let mut ok = true;
+        for target in &targets {
+            let result = self.resolve_parent(cwd, target).and_then(|(parent, name)| {
+                let child = self.lookup(parent, name)?;
+                if !self.metadata(child)?.is_dir() {
+                    return Err(Ext2Error::NotADirectory(target.to_string()));
+                }
+                self.check_not_cwd(cwd, parent, name, target)?;
+                self.unlink(parent, name)
+            });
+            if let Err(e) = result {
+                println!("rmdir: {}", e);
+                ok = false;
+            }
+        }
+        if ok {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    // what `move_to_trash` does. the trash is only made when something is
+    // first moved into it, so before then there's nothing to plan against
+    fn plan_move_to_trash(&self, parent: InodeNo, name: &str) -> Result<Vec<Change>, Ext2Error> {
+        let trash = match self.lookup(InodeNo::ROOT, TRASH_NAME) {
+            Ok(trash) => trash,
+            Err(Ext2Error::NotFound(_)) => {
+                return Err(Ext2Error::Unsupported(format!(
+                    "a dry run of moving into /{} before it exists",
+                    TRASH_NAME
+                )))
+            }
+            Err(e) => return Err(e),
+        };
+        self.plan_rename(parent, name, trash, &self.trash_name(trash, name))
+    }
+
+    pub fn empty_trash(&mut self, _cwd: InodeNo, _command: String) -> Option<()> {
+        // `empty-trash`
+        // permanently delete everything `rm --trash` moved into /.trash
+        self.writable("empty-trash")?;
+        let trash = match self.lookup(InodeNo::ROOT, TRASH_NAME) {
+            Ok(trash) => trash,
+            // nothing has been trashed yet
+            Err(Ext2Error::NotFound(_)) => return Some(()),
+            Err(e) => {
+                println!("empty-trash: {}", e);
+                return None;
+            }
+        };
+        let result = self.dir_entries(trash).and_then(|entries| {
+            entries
+                .iter()
+                .filter(|entry| entry.name != "." && entry.name != "..")
+                .try_for_each(|entry| self.remove_tree(trash, &entry.name))
+        });
+        match result {
+            Ok(()) => Some(()),
+            Err(e) => {
+                println!("empty-trash: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn mv(&mut self, cwd: InodeNo, command: String) -> Option<()> {
+        // `mv [--dry-run] source target`
+        // move or rename source. if target is a directory, source is moved into it.
+        // `--dry-run` prints what would change, changing nothing
+        let mut dry_run = self.dry_run;
+        let elts: Vec<&str> = command
+            .split_whitespace()
+            .filter(|&arg| {
+                let flag = arg == "--dry-run";
+                dry_run |= flag;
+                !flag
+            })
+            .collect();
+        if elts.len() != 3 {
+            println!("usage: mv [--dry-run] source target");
+            return None;
+        }
+        if !dry_run {
+            self.writable("mv")?;
+        }
+        let result = self
+            .resolve_parent(cwd, elts[1])
+            .and_then(|(src_dir, src_name)| {
+                let (dst_dir, dst_name) = match self.resolve(cwd, elts[2]) {
+                    Ok(dir) if self.metadata(dir)?.is_dir() => (dir, src_name),
+                    Ok(_) => return Err(Ext2Error::AlreadyExists(elts[2].to_string())),
+                    Err(Ext2Error::NotFound(_)) => {
+                        let source = self.metadata(self.lookup(src_dir, src_name)?)?;
+                        Self::trailing_slash_ok(elts[2], source.file_type)?;
+                        self.resolve_parent(cwd, elts[2])?
+                    }
+                    Err(e) => return Err(e),
+                };
+                if dry_run {
+                    let plan = self.plan_rename(src_dir, src_name, dst_dir, dst_name)?;
+                    print_plan(elts[1], &plan);
+                    Ok(())
+                } else {
+                    self.rename(src_dir, src_name, dst_dir, dst_name)
+                }
+            });
+        match result {
+            Ok(()) => Some(()),
+            Err(e) => {
+                println!("mv: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn mount(&self, _cwd: InodeNo, _command: String) -> Option<()> {
+        // `mount host_filename mountpoint`
+        // mount an ext2 filesystem over an existing empty directory
+        println!("mount not yet implemented");
+        None
+    }
+
+    pub fn link(&self, current_working_inode: InodeNo, command: String) -> Option<()> {
+        // `link arg_1 arg_2`
+        // create a hard link from arg_1 to arg_2
+        // consider what to do if arg2 does- or does-not end in "/"
+        // and/or if arg2 is an existing directory name
+
+        let elts: Vec<&str> = command.split_whitespace().collect();
+        if elts.len() != 3 {
+            println!("usage: link arg_1 arg_2 ...");
+            return None;
+        }
+
+        let arg_1 = elts[1];
+        // first make sure that arg_1 does in fact exist
+        if let Err(e) = self.resolve(current_working_inode, arg_1) {
+            println!("link: {}", e);
+            return None;
+        }
+        // in parent directory of arg_1 we need to make a new directory entry
+        // with arg_1 that corresponds to the same inode number as arg_2
+        println!("link not yet implemented");
+        None
+    }
+}
+
+// the shell's path resolution: names are found through the entry cache, and
+// every directory looked in has to be searchable by the shell's user
+impl Lookup for Ext2 {
+    fn lookup(&self, dir: InodeNo, name: &[u8]) -> Result<InodeNo, Ext2Error> {
+        if !self.metadata(dir)?.is_dir() {
+            return Err(Ext2Error::NotADirectory(escape_name(name)));
+        }
+        let cached = self.cached_entries(dir)?;
+
+        let found = if self.name_index {
+            // names are unique within a directory, so the hash has them all
+            let by_name = cached.by_name.get_or_init(|| {
+                cached
+                    .entries
+                    .iter()
+                    .map(|entry| (entry.name_bytes.clone(), entry.inode))
+                    .collect()
+            });
+            by_name.get(name).copied()
+        } else {
+            cached
+                .entries
+                .iter()
+                .find(|entry| entry.name_bytes == name)
+                .map(|entry| entry.inode)
+        };
+        found.ok_or_else(|| Ext2Error::NotFound(escape_name(name)))
+    }
+
+    fn is_dir(&self, inode: InodeNo) -> Result<bool, Ext2Error> {
+        Ok(self.metadata(inode)?.is_dir())
+    }
+
+    fn may_search(&self, dir: InodeNo, path: &[u8]) -> Result<(), Ext2Error> {
+        self.check_access(dir, &escape_name(path), Access::Execute)
+    }
+}
+
+// the shell's path resolution, printing each component as it is followed
+struct Trace<'a>(&'a Ext2);
+
+impl Lookup for Trace<'_> {
+    fn lookup(&self, dir: InodeNo, name: &[u8]) -> Result<InodeNo, Ext2Error> {
+        Lookup::lookup(self.0, dir, name)
+    }
+
+    fn is_dir(&self, inode: InodeNo) -> Result<bool, Ext2Error> {
+        Lookup::is_dir(self.0, inode)
+    }
+
+    fn may_search(&self, dir: InodeNo, path: &[u8]) -> Result<(), Ext2Error> {
+        Lookup::may_search(self.0, dir, path)
+    }
+
+    fn step(&self, component: &[u8], inode: InodeNo) {
+        let name = escape_name(component);
+        match self.0.metadata(inode) {
+            Ok(metadata) => println!("{}\t{}\t{}", name, inode, metadata.file_type),
+            Err(e) => println!("{}\t{}\tfailed: {}", name, inode, e),
+        }
+    }
+}
+
+// how `ls` orders the entries of a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortBy {
+    Name,
+    Size,
+    Time,
+}
+
+// the flags of `ls` that affect how one directory is printed
+struct ListOptions {
+    all: bool,
+    long: bool,
+    sort: SortBy,
+    reverse: bool,
+}
+
+// current time in POSIX seconds, the way inodes store it
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970")
+        .as_secs() as u32
+}
+
+// one line of `ls -l`
+fn print_long(metadata: &Metadata, name: &str) {
+    println!(
+        "{} {:>3} {:>5} {:>5} {:>8} {:>10} {}",
+        metadata.mode_string(),
+        metadata.nlink,
+        metadata.uid,
+        metadata.gid,
+        metadata.len(),
+        metadata.mtime,
+        name
+    );
+}
+
+// what a dry run prints: the target, then each change it would make
+fn print_plan(target: &str, plan: &[Change]) {
+    println!("{}:", target);
+    for change in plan {
+        println!("  would {}", change);
+    }
+}
+
+// bytes a directory entry with a name of name_length bytes needs, entries are 4 byte aligned
+fn dir_entry_size(name_length: usize) -> usize {
+    (8 + name_length + 3) & !3
+}
+
+// the on-disk bytes of a directory entry, padded with zeros out to entry_size
+fn dir_entry_bytes(inode: InodeNo, entry_size: u16, name: &[u8], kind: TypeIndicator) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(entry_size as usize);
+    bytes.extend_from_slice(&inode.get().to_le_bytes());
+    bytes.extend_from_slice(&entry_size.to_le_bytes());
+    bytes.push(name.len() as u8);
+    bytes.push(kind as u8);
+    bytes.extend_from_slice(name);
+    bytes.resize(entry_size as usize, 0);
+    bytes
+}
+
+// `ext2 mkfs [--size 8M] [--block-size 1024] [--inodes n] [--label name] output.img`
+// write a new, empty file system to a new file on the host. sizes can end
+// in K, M or G
+fn mkfs(args: &[String]) -> Option<()> {
+    let usage =
+        "usage: ext2 mkfs [--size 8M] [--block-size 1024] [--inodes n] [--label name] output.img";
+    let mut options = FormatOptions {
+        size: 8 << 20,
+        block_size: 1024,
+        inodes: None,
+        inode_size: 256,
+        uuid: [0; 16],
+        volume_name: Vec::new(),
+        now: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32),
+    };
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--size" | "--block-size" | "--inodes" | "--label" => match args.next() {
+                Some(value) => value,
+                None => {
+                    println!("{}", usage);
+                    return None;
+                }
+            },
+            _ if output.is_none() && !arg.starts_with("--") => {
+                output = Some(arg);
+                continue;
+            }
+            _ => {
+                println!("{}", usage);
+                return None;
+            }
+        };
+        let number = || {
+            let (digits, shift) = match value.as_bytes().last() {
+                Some(b'K' | b'k') => (&value[..value.len() - 1], 10),
+                Some(b'M' | b'm') => (&value[..value.len() - 1], 20),
+                Some(b'G' | b'g') => (&value[..value.len() - 1], 30),
+                _ => (&value[..], 0),
+            };
+            let n = digits
+                .parse::<u64>()
+                .ok()
+                .and_then(|n| n.checked_mul(1 << shift));
+            if n.is_none() {
+                println!("mkfs: {} is not a size", value);
+            }
+            n
+        };
+        match arg.as_str() {
+            "--size" => options.size = number()?,
+            "--block-size" => options.block_size = number()? as usize,
+            "--inodes" => options.inodes = Some(u32::try_from(number()?).ok()?),
+            _ => options.volume_name = value.as_bytes().to_vec(),
+        }
+    }
+    let Some(output) = output else {
+        println!("{}", usage);
+        return None;
+    };
+
+    // a random (version 4) uuid, like mke2fs gives every new file system
+    let mut random = [0; 16];
+    if let Err(e) = fs::File::open("/dev/urandom")
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut random))
+    {
+        println!("mkfs: no random numbers for the uuid: {}", e);
+        return None;
+    }
+    options.uuid = *uuid::Builder::from_random_bytes(random)
+        .into_uuid()
+        .as_bytes();
+
+    let image = match format(&options) {
+        Ok(image) => image,
+        Err(e) => {
+            println!("mkfs: {}", e);
+            return None;
+        }
+    };
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output)
+        .and_then(|mut file| {
+            use std::io::Write;
+            file.write_all(&image)?;
+            file.sync_all()
+        });
+    if let Err(e) = written {
+        println!("mkfs: {}: {}", output, e);
+        return None;
+    }
+    Some(())
+}
+
+// the struct at byte `at` of `bytes`, to change it in place
+fn struct_at<T: FromBytes + AsBytes + Unaligned>(bytes: &mut [u8], at: usize) -> &mut T {
+    let (view, _) = LayoutVerified::<_, T>::new_unaligned_from_prefix(&mut bytes[at..]).unwrap();
+    view.into_mut()
+}
+
+// the bytes of the image the shell works on: read into memory or, with the
+// mmap feature, mapped from the file
+pub enum Disk {
+    Read(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(ext2::mmap::Mapping),
+}
+
+impl std::ops::Deref for Disk {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Disk::Read(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Disk::Mapped(mapping) => mapping,
+        }
+    }
+}
+
+impl std::ops::DerefMut for Disk {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Disk::Read(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Disk::Mapped(mapping) => mapping,
+        }
+    }
+}
+
+// the file system on a `Disk`: the partition --partition picked, or all of
+// the disk, less anything after the file system's last block
+pub struct Image {
+    disk: Disk,
+    range: std::ops::Range<usize>,
+}
+
+impl Image {
+    fn whole(disk: Disk) -> Image {
+        let range = 0..disk.len();
+        Image { disk, range }
+    }
+
+    // leave out everything past the first `len` bytes
+    fn truncate(&mut self, len: usize) {
+        self.range.end = cmp::min(self.range.end, self.range.start + len);
+    }
+}
+
+impl std::ops::Deref for Image {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.disk[self.range.clone()]
+    }
+}
+
+impl std::ops::DerefMut for Image {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.disk[self.range.clone()]
+    }
+}
+
+impl BlockDevice for Image {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        self[..].read_at(offset, buf)
+    }
+}
+
+impl BlockDeviceMut for Image {
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), Ext2Error> {
+        self[..].write_at(offset, buf)
+    }
+}
+
+// read the image, or map it if `mmap` says how: Some(false) maps it copy on
+// write, so changes stay in memory as they do for a read image, and
+// Some(true) maps it shared, so they go back to the file. an image another
+// shell is writing to is mapped copy on write instead, and reading it is
+// the fallback when it can't be mapped at all
+fn load(image: &str, mmap: Option<bool>, allow_write_device: bool) -> Disk {
+    let opened = match ext2::image::open(std::path::Path::new(image)) {
+        Ok(opened) => opened,
+        Err(e) => {
+            println!("unable to open {}: {}", image, e);
+            std::process::exit(1);
+        }
+    };
+    // a gzipped image is decompressed into memory, there's nothing to map
+    // and nowhere to write changes back to. a device is only written with
+    // an explicit go ahead
+    if mmap == Some(true) && opened.is_compressed() {
+        println!(
+            "{} is compressed, so changes can't be written back to it. leave out --mmap-write",
+            image
+        );
+        std::process::exit(1);
+    }
+    if mmap == Some(true) && opened.is_device() && !allow_write_device {
+        println!(
+            "{} is a device, so it is only read. add --allow-write-device to write changes to it",
+            image
+        );
+        std::process::exit(1);
+    }
+    #[cfg(feature = "mmap")]
+    if let (Some(shared), false) = (mmap, opened.is_compressed()) {
+        let path = std::path::Path::new(image);
+        let mapped = ext2::mmap::Mapping::open(path, shared).or_else(|e| {
+            if e.kind() != std::io::ErrorKind::WouldBlock {
+                return Err(e);
+            }
+            println!(
+                "{} is locked: {}. changes will not be written back to it",
+                image, e
+            );
+            ext2::mmap::Mapping::open(path, false)
+        });
+        match mapped {
+            Ok(mapping) => return Disk::Mapped(mapping),
+            Err(e) => println!("unable to map {}, reading it instead: {}", image, e),
+        }
+    }
+    #[cfg(not(feature = "mmap"))]
+    if mmap.is_some() {
+        println!("built without the mmap feature, reading {} instead", image);
+    }
+    // read with pread, a device's size found by seeking to its end
+    match opened.into_bytes() {
+        Ok(bytes) => Disk::Read(bytes),
+        Err(e) => {
+            println!("unable to read {}: {}", image, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() -> rustyline::Result<()> {
+    // `ext2 mkfs ...` makes a new image instead of opening one
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("mkfs") {
+        let made = mkfs(&args[2..]);
+        std::process::exit(if made.is_some() { 0 } else { 1 });
+    }
+
+    // load disk at runtime rather than compile time. the image to use can be
+    // passed as the first argument, e.g. myfs_4k.ext2 for 4K blocks.
+    // `--time` prints how long each command took after it runs, and how
+    // many directory blocks it read and wrote,
+    // `--mmap` or `--mmap-write` map the image instead of reading it, and
+    // `--partition n` mounts partition n of a whole-disk image, and
+    // `--dry-run` has rm and mv only say what they would change, and
+    // `--no-name-index` looks names up by scanning each directory, and
+    // `--uid n` and `--gid n` have commands check permission bits for that
+    // user and group, nobody's (65534) for the one left out. a gzipped
+    // image is decompressed first, and a device like /dev/sdb1 is only
+    // written to with `--allow-write-device` as well, see `load`
+    let flag = |name: &str| args.iter().skip(1).any(|arg| arg == name);
+    let time = flag("--time");
+    let mmap = if flag("--mmap-write") {
+        Some(true)
+    } else if flag("--mmap") {
+        Some(false)
+    } else {
+        None
+    };
+    let usage = || -> ! {
+        println!(
+            "usage: ext2 [--time] [--dry-run] [--no-name-index] [--uid n] [--gid n] [--mmap | --mmap-write [--allow-write-device]] [--partition n] [image]"
+        );
+        std::process::exit(1);
+    };
+    let mut partition = None;
+    let mut uid = None;
+    let mut gid = None;
+    let mut image = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if arg == "--partition" {
+            let Some(n) = rest.next().and_then(|n| n.parse::<usize>().ok()) else {
+                usage();
+            };
+            partition = Some(n);
+        } else if arg == "--uid" || arg == "--gid" {
+            let Some(n) = rest.next().and_then(|n| n.parse::<u16>().ok()) else {
+                usage();
+            };
+            if arg == "--uid" {
+                uid = Some(n);
+            } else {
+                gid = Some(n);
+            }
+        } else if !arg.starts_with("--") && image.is_none() {
+            image = Some(arg.clone());
+        }
+    }
+    let image = image.unwrap_or_else(|| "myfs.ext2".to_string());
+    let disk = load(&image, mmap, flag("--allow-write-device"));
+    // let disk = include_bytes!("../largefs.ext2");
+    let device = &disk[..];
+    let range = match choose_partition(&device, partition) {
+        // choose_partition made sure the partition is on the disk
+        Ok(Some(chosen)) => chosen.start as usize..(chosen.start + chosen.len) as usize,
+        Ok(None) => 0..device.len(),
+        Err(e) => {
+            println!("unable to mount file system: {}", e);
+            if let Ext2Error::Ambiguous(_) = e {
+                for candidate in partitions(&device).unwrap_or_default() {
+                    if holds_ext2(&device, &candidate) {
+                        println!("{}", candidate);
+                    }
+                }
+                println!("pick one with --partition n");
+            }
+            std::process::exit(1);
+        }
+    };
+    let mut ext2 = match Ext2::new(Image { disk, range }) {
+        Ok(ext2) => ext2,
+        Err(e) => {
+            println!("unable to mount file system: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    ext2.dry_run = flag("--dry-run");
+    ext2.name_index = !flag("--no-name-index");
+    if uid.is_some() || gid.is_some() {
+        ext2.identity = Some(Identity {
+            uid: uid.unwrap_or(65534),
+            gid: gid.unwrap_or(65534),
+        });
+    }
+
+    let mut current_working_inode = InodeNo::ROOT;
+
+    let mut rl = DefaultEditor::new()?;
+    // commands read the cwd themselves when they need it, so one that can't
+    // be read only fails the commands that look at it
+    loop {
+        let buffer = rl.readline(":> ");
+        if let Ok(line) = buffer {
+            let started = Instant::now();
+            ext2.dir_blocks_read.store(0, Ordering::Relaxed);
+            // the first word names the command, exactly: `lsof` isn't `ls`.
+            // commands split their arguments on single spaces, so they get
+            // the words joined back up that way
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let command = words.first().copied().unwrap_or("").to_string();
+            let line = words.join(" ");
+            match command.as_str() {
+                "lsinodes" => {
+                    let success = ext2.lsinodes(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to list inodes");
+                    }
+                }
+                "ls" => {
+                    let success = ext2.ls(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to read directory in ls");
+                    }
+                }
+                "clone" => {
+                    let success = ext2.clone_image(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to clone image");
+                    }
+                }
+                "cd" => match ext2.cd(current_working_inode, line) {
+                    Some(inode) => current_working_inode = inode,
+                    None => println!("unable to read directory in cd"),
+                },
+                "mkdir" => {
+                    let success = ext2.mkdir(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to create directory in mkdir");
+                    }
+                }
+                "cat" => {
+                    let success = ext2.cat(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to cat file");
+                    }
+                    // println!("cat not yet implemented");
+                }
+                "tar" => {
+                    let success = ext2.tar(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to write tar archive");
+                    }
+                }
+                "touch" => {
+                    let success = ext2.touch(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to touch file");
+                    }
+                }
+                "chattr" => {
+                    let success = ext2.chattr(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to change file flags");
+                    }
+                }
+                "export" => {
+                    let success = ext2.export(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to export file");
+                    }
+                }
+                "sha256" => {
+                    let success = ext2.sha256(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to hash file");
+                    }
+                }
+                "stat" => {
+                    let success = ext2.stat(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to stat file");
+                    }
+                }
+                "getxattr" => {
+                    let success = ext2.getxattr(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to read extended attributes");
+                    }
+                }
+                "bmap" => {
+                    let success = ext2.bmap(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to map block");
+                    }
+                }
+                "defrag" => {
+                    let success = ext2.defrag(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to defragment");
+                    }
+                }
+                "frag" => {
+                    let success = ext2.frag(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to report fragmentation");
+                    }
+                }
+                "freemap" => {
+                    let success = ext2.freemap(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to map free space");
+                    }
+                }
+                "bitmap" => {
+                    let success = ext2.bitmap(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to show bitmap");
+                    }
+                }
+                "pwd" => {
+                    let success = ext2.pwd(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to print working directory");
+                    }
+                }
+                "find" => {
+                    let success = ext2.find(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to find files");
+                    }
+                }
+                "ncheck" => {
+                    let success = ext2.ncheck(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to find a path to every inode");
+                    }
+                }
+                "rev" => {
+                    let success = ext2.rev(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to show the revision");
+                    }
+                }
+                "lookup" => {
+                    let success = ext2.lookup_name(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to look up name");
+                    }
+                }
+                "rlookup" => {
+                    let success = ext2.rlookup(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to look up inode");
+                    }
+                }
+                "imap" => {
+                    let success = ext2.imap(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to map inode");
+                    }
+                }
+                "orphans" => {
+                    let success = ext2.orphans(current_working_inode, line);
+                    if success.is_none() {
+                        println!("file system has orphaned inodes");
+                    }
+                }
+                "resolve" => {
+                    let success = ext2.resolve_trace(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to resolve path");
+                    }
+                }
+                "mknod" => {
+                    let success = ext2.mknod(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to create inode in mknod");
+                    }
+                }
+                "write" => {
+                    let success = ext2.write(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to write file");
+                    }
+                }
+                "fsck" => {
+                    let success = ext2.fsck(current_working_inode, line);
+                    if success.is_none() {
+                        println!("file system has errors");
+                    }
+                }
+                "csum-check" => {
+                    let success = ext2.csum_check(current_working_inode, line);
+                    if success.is_none() {
+                        println!("checksums do not validate");
+                    }
+                }
+                "resizefs" => {
+                    let grown = ext2
+                        .resizefs(current_working_inode, line)
+                        .and_then(|grown| match Ext2::new(Image::whole(Disk::Read(grown))) {
+                            Ok(new) => Some(new),
+                            Err(e) => {
+                                println!("resizefs: {}", e);
+                                None
+                            }
+                        });
+                    match grown {
+                        Some(new) => {
+                            #[cfg(feature = "mmap")]
+                            if ext2.is_shared_mapping() {
+                                println!("the grown image is only in memory, clone saves it");
+                            }
+                            ext2 = new;
+                        }
+                        None => println!("unable to resize file system"),
+                    }
+                }
+                "rm" => {
+                    let success = ext2.rm(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to remove directory in rm");
+                    }
+                }
+                "rmdir" => {
+                    let success = ext2.rmdir(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to remove directory in rmdir");
+                    }
+                }
+                "dump" => {
+                    let success = ext2.dump(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to dump file system");
+                    }
+                }
+                "empty-trash" => {
+                    let success = ext2.empty_trash(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to empty trash");
+                    }
+                }
+                "mv" => {
+                    let success = ext2.mv(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to move file in mv");
+                    }
                 }
-                // println!("cat not yet implemented");
-            } else if line.starts_with("rm") {
-                let success = ext2.rm(dirs, line);
-                if success.is_none() {
-                    println!("unable to remove directory in rm");
+                "mount" => {
+                    let success = ext2.mount(current_working_inode, line);
+                    if success.is_none() {
+                        println!("unable to mount directory in rm");
+                    }
+                }
+                "link" => {
+                    let success = ext2.link(current_working_inode, line);
+                    if success.is_none() {
+                        println!("link to mount directory in rm");
+                    }
                 }
-            } else if line.starts_with("mount") {
-                let success = ext2.mount(dirs, line);
-                if success.is_none() {
-                    println!("unable to mount directory in rm");
+                "quit" | "exit" => break,
+                "" => {}
+                _ => println!("{}: command not found", command),
+            }
+            // e.g. `rm: freed inode 34, 3 blocks`
+            let usage = ext2.take_usage();
+            if !usage.is_empty() {
+                println!("{}: {}", command, usage);
+            }
+            let written = mem::take(&mut ext2.dir_blocks_written);
+            let read = ext2.dir_blocks_read.swap(0, Ordering::Relaxed);
+            if time {
+                print!("time: {} took {:.3?}", command, started.elapsed());
+                let noun = |n: usize| if n == 1 { "block" } else { "blocks" };
+                if read > 0 {
+                    print!(", read {} directory {}", read, noun(read));
                 }
-            } else if line.starts_with("link") {
-                let success = ext2.link(current_working_inode, dirs, line);
-                if success.is_none() {
-                    println!("link to mount directory in rm");
+                if written > 0 {
+                    print!(", wrote {} directory {}", written, noun(written));
                 }
-            } else if line.starts_with("quit") || line.starts_with("exit") {
-                break;
+                println!();
             }
         } else {
             println!("bye!");
             break;
         }
     }
+    #[cfg(feature = "mmap")]
+    if let Disk::Mapped(mapping) = ext2.into_disk() {
+        if let Err(e) = mapping.flush() {
+            println!("unable to write changes back to {}: {}", image, e);
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+    use std::thread;
+
+    // a hash of the contents of each of `names` in the root directory
+    fn hash_files(ext2: &Ext2, names: &[String]) -> Vec<u64> {
+        names
+            .iter()
+            .map(|name| {
+                let inode = ext2.resolve(InodeNo::ROOT, name).unwrap();
+                let contents = ext2.volume.read_file_bytes(inode).unwrap();
+                let mut hasher = DefaultHasher::new();
+                hasher.write(&contents);
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    // a shared &Ext2 only reads, so threads looking files up and reading
+    // them through one at the same time see what a single thread does
+    #[test]
+    fn threads_read_what_one_thread_does() {
+        let image = format(&FormatOptions {
+            size: 4 << 20,
+            block_size: 1024,
+            inodes: None,
+            inode_size: 256,
+            uuid: [0; 16],
+            volume_name: Vec::new(),
+            now: 0,
+        })
+        .unwrap();
+        let mut ext2 = Ext2::new(Image::whole(Disk::Read(image))).unwrap();
+        let names: Vec<String> = (0..100).map(|i| format!("file{}", i)).collect();
+        for (i, name) in names.iter().enumerate() {
+            let inode = ext2
+                .create_inode(InodeNo::ROOT, name, FileType::Regular, 0o644)
+                .unwrap();
+            // from empty to past the 12 direct blocks
+            let data: Vec<u8> = (0..i * 331).map(|n| (n * 7 + i) as u8).collect();
+            ext2.write_file_bytes(inode, &data).unwrap();
+        }
+        let expected = hash_files(&ext2, &names);
+
+        let ext2 = &ext2;
+        let names = &names;
+        let hashes: Vec<Vec<u64>> = thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(move || hash_files(ext2, names)))
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect()
+        });
+        for hashed in hashes {
+            assert_eq!(hashed, expected);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::structs::{Inode, InodeFlags, InodeNo, TypeIndicator};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str;
+
+/// The kind of object an inode holds, decoded from the top bits of its mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Fifo,
+    CharDevice,
+    Directory,
+    BlockDevice,
+    Regular,
+    Symlink,
+    Socket,
+    Unknown,
+}
+
+impl FileType {
+    /// Decode the type from a raw inode mode. The type is a 4 bit number, not
+    /// a set of flags, so it has to be compared as a whole
+    pub fn from_mode(mode: u16) -> FileType {
+        match mode & 0xF000 {
+            0x1000 => FileType::Fifo,
+            0x2000 => FileType::CharDevice,
+            0x4000 => FileType::Directory,
+            0x6000 => FileType::BlockDevice,
+            0x8000 => FileType::Regular,
+            0xA000 => FileType::Symlink,
+            0xC000 => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+
+    /// The type bits of an inode mode for this type
+    pub fn mode_bits(&self) -> u16 {
+        match self {
+            FileType::Fifo => 0x1000,
+            FileType::CharDevice => 0x2000,
+            FileType::Directory => 0x4000,
+            FileType::BlockDevice => 0x6000,
+            FileType::Regular => 0x8000,
+            FileType::Symlink => 0xA000,
+            FileType::Socket => 0xC000,
+            FileType::Unknown => 0,
+        }
+    }
+
+    /// The type byte stored in directory entries pointing at this type
+    pub fn type_indicator(&self) -> TypeIndicator {
+        match self {
+            FileType::Fifo => TypeIndicator::Fifo,
+            FileType::CharDevice => TypeIndicator::Character,
+            FileType::Directory => TypeIndicator::Directory,
+            FileType::BlockDevice => TypeIndicator::Block,
+            FileType::Regular => TypeIndicator::Regular,
+            FileType::Symlink => TypeIndicator::Symlink,
+            FileType::Socket => TypeIndicator::Socket,
+            FileType::Unknown => TypeIndicator::Unknown,
+        }
+    }
+
+    /// The type a directory entry's type byte says its inode has, the
+    /// other way from `type_indicator`
+    pub fn from_type_indicator(kind: TypeIndicator) -> FileType {
+        match kind {
+            TypeIndicator::Fifo => FileType::Fifo,
+            TypeIndicator::Character => FileType::CharDevice,
+            TypeIndicator::Directory => FileType::Directory,
+            TypeIndicator::Block => FileType::BlockDevice,
+            TypeIndicator::Regular => FileType::Regular,
+            TypeIndicator::Symlink => FileType::Symlink,
+            TypeIndicator::Socket => FileType::Socket,
+            TypeIndicator::Unknown => FileType::Unknown,
+        }
+    }
+
+    /// The character `ls -l` uses for this type
+    pub fn mode_char(&self) -> char {
+        match self {
+            FileType::Fifo => 'p',
+            FileType::CharDevice => 'c',
+            FileType::Directory => 'd',
+            FileType::BlockDevice => 'b',
+            FileType::Regular => '-',
+            FileType::Symlink => 'l',
+            FileType::Socket => 's',
+            FileType::Unknown => '?',
+        }
+    }
+
+    /// A one word name for this type, for output that's read by programs
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileType::Fifo => "fifo",
+            FileType::CharDevice => "char",
+            FileType::Directory => "dir",
+            FileType::BlockDevice => "block",
+            FileType::Regular => "file",
+            FileType::Symlink => "symlink",
+            FileType::Socket => "socket",
+            FileType::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FileType::Fifo => "fifo",
+            FileType::CharDevice => "character special file",
+            FileType::Directory => "directory",
+            FileType::BlockDevice => "block special file",
+            FileType::Regular => "regular file",
+            FileType::Symlink => "symbolic link",
+            FileType::Socket => "socket",
+            FileType::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What a permission check is for, see `Metadata::permits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Running a file, or looking a name up in a directory
+    Execute,
+}
+
+/// Decoded view of an inode, for anything that wants to show or compare
+/// file information without poking at the on-disk struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub ino: InodeNo,
+    pub file_type: FileType,
+    /// Permission bits, including setuid, setgid and sticky
+    pub perms: u16,
+    pub nlink: u16,
+    pub uid: u16,
+    pub gid: u16,
+    /// Size in bytes
+    pub size: u64,
+    /// Count of 512 byte sectors in use
+    pub blocks: u32,
+    pub atime: u32,
+    pub mtime: u32,
+    pub ctime: u32,
+    pub flags: InodeFlags,
+}
+
+impl Metadata {
+    pub fn from_inode(ino: InodeNo, inode: &Inode) -> Metadata {
+        let mode = inode.type_perm.get();
+        let file_type = FileType::from_mode(mode);
+        Metadata {
+            ino,
+            file_type,
+            perms: mode & 0o7777,
+            nlink: inode.hard_links.get(),
+            uid: inode.uid.get(),
+            gid: inode.gid.get(),
+            size: inode.size(),
+            blocks: inode.sectors_count.get(),
+            atime: inode.atime.get(),
+            mtime: inode.mtime.get(),
+            ctime: inode.ctime.get(),
+            flags: inode.flags(),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileType::Regular
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type == FileType::Symlink
+    }
+
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Whether user `uid` in group `gid` may `access` the inode by its
+    /// permission bits: the owner's bits if `uid` owns it, else the
+    /// group's if `gid` is its group, else everyone else's. Only that one
+    /// set counts, like on Unix, so an owner without read permission can't
+    /// read even when everyone else can. uid 0 may do anything
+    pub fn permits(&self, uid: u16, gid: u16, access: Access) -> bool {
+        if uid == 0 {
+            return true;
+        }
+        let shift = if uid == self.uid {
+            6
+        } else if gid == self.gid {
+            3
+        } else {
+            0
+        };
+        let bit = match access {
+            Access::Read => 0o4,
+            Access::Write => 0o2,
+            Access::Execute => 0o1,
+        };
+        (self.perms >> shift) & bit != 0
+    }
+
+    /// Type and permissions the way `ls -l` prints them, e.g. `drwxr-xr-x`
+    pub fn mode_string(&self) -> String {
+        let mut mode = String::with_capacity(10);
+        mode.push(self.file_type.mode_char());
+        // (read bit, write bit, execute bit, special bit, special char)
+        let triples = [
+            (0o400, 0o200, 0o100, 0o4000, 's'),
+            (0o040, 0o020, 0o010, 0o2000, 's'),
+            (0o004, 0o002, 0o001, 0o1000, 't'),
+        ];
+        for (r, w, x, special, special_char) in triples {
+            mode.push(if self.perms & r != 0 { 'r' } else { '-' });
+            mode.push(if self.perms & w != 0 { 'w' } else { '-' });
+            mode.push(match (self.perms & x != 0, self.perms & special != 0) {
+                (true, true) => special_char,
+                (false, true) => special_char.to_ascii_uppercase(),
+                (true, false) => 'x',
+                (false, false) => '-',
+            });
+        }
+        mode
+    }
+}
+
+/// One live entry of a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub inode: InodeNo,
+    /// The name as it is shown and typed, see `escape_name`
+    pub name: String,
+    /// The name exactly as it is stored, names are arbitrary bytes
+    pub name_bytes: Vec<u8>,
+    /// The type the entry claims its inode has
+    pub kind: TypeIndicator,
+}
+
+/// Make a raw file name printable: valid UTF-8 is kept, while control
+/// characters, whitespace and bytes that aren't UTF-8 become `\xNN` and `\`
+/// becomes `\\`. `unescape_name` turns the result back into the same bytes,
+/// and the shell splits its arguments on whitespace, so a name can be typed
+/// back in the way `ls` shows it
+pub fn escape_name(bytes: &[u8]) -> String {
+    fn push_escaped(out: &mut String, bytes: &[u8]) {
+        for byte in bytes {
+            out.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    let mut out = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (valid, invalid) = match str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(e) => {
+                let valid = str::from_utf8(&rest[..e.valid_up_to()]).unwrap();
+                let invalid = e.error_len().unwrap_or(rest.len() - e.valid_up_to());
+                (valid, invalid)
+            }
+        };
+        for c in valid.chars() {
+            if c == '\\' {
+                out.push_str("\\\\");
+            } else if c.is_control() || c.is_whitespace() {
+                push_escaped(&mut out, c.encode_utf8(&mut [0; 4]).as_bytes());
+            } else {
+                out.push(c);
+            }
+        }
+        push_escaped(&mut out, &rest[valid.len()..valid.len() + invalid]);
+        rest = &rest[valid.len() + invalid..];
+    }
+    out
+}
+
+/// The raw bytes a name typed by the user stands for, undoing `escape_name`.
+/// A `\` that doesn't start `\\` or `\xNN` is kept as it is
+pub fn unescape_name(name: &str) -> Vec<u8> {
+    let bytes = name.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes.get(i + 1) == Some(&b'\\') {
+                out.push(b'\\');
+                i += 2;
+                continue;
+            }
+            let hex = bytes.get(i + 2..i + 4).unwrap_or(&[]);
+            if bytes.get(i + 1) == Some(&b'x')
+                && hex.len() == 2
+                && hex.iter().all(u8::is_ascii_hexdigit)
+            {
+                let hex = str::from_utf8(hex).unwrap();
+                out.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
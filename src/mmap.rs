@@ -0,0 +1,131 @@
+//! An image file mapped into memory, so only the pages that are touched get
+//! read from it. Derefs to the image's bytes, like the `Vec` `fs::read`
+//! gives, so the shell can mount either
+
+use crate::volume::BlockDevice;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+/// A whole image file mapped into memory
+pub struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+    /// Whether writes to the mapping go to the file
+    shared: bool,
+    /// Kept open for as long as the mapping, a shared mapping's lock goes
+    /// with it
+    _file: File,
+}
+
+impl Mapping {
+    /// Map the image at `path`. With `shared`, changes made through the
+    /// mapping are written back to the file; without it they are copy on
+    /// write, kept in memory and lost on unmapping, which needs no write
+    /// access to the file.
+    ///
+    /// A shared mapping takes an exclusive advisory lock (flock) on the file,
+    /// so two of them can't write over each other's changes. If someone
+    /// else has it the error is `io::ErrorKind::WouldBlock`
+    pub fn open(path: &Path, shared: bool) -> io::Result<Mapping> {
+        let file = OpenOptions::new().read(true).write(shared).open(path)?;
+        if shared && unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::WouldBlock {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "another process has it open for writing",
+                ));
+            }
+            return Err(e);
+        }
+        // a block device's size comes from seeking, not its metadata
+        let len = usize::try_from(BlockDevice::size(&file))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "image too large to map"))?;
+        if len == 0 {
+            // mmap refuses an empty mapping
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "image is empty",
+            ));
+        }
+        Mapping::map(file, len, shared)
+    }
+
+    fn map(file: File, len: usize, shared: bool) -> io::Result<Mapping> {
+        let flags = if shared {
+            libc::MAP_SHARED
+        } else {
+            libc::MAP_PRIVATE
+        };
+        // the shell writes to its image whichever way it was mapped
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            len,
+            shared,
+            _file: file,
+        })
+    }
+
+    /// Whether changes reach the file
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Wait for changes made so far to be written to the file. Nothing to
+    /// do for a private mapping
+    pub fn flush(&self) -> io::Result<()> {
+        if !self.shared {
+            return Ok(());
+        }
+        if unsafe { libc::msync(self.ptr as *mut libc::c_void, self.len, libc::MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl DerefMut for Mapping {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+// the mapping is owned like a Vec's buffer: the bytes are only written
+// through `&mut Mapping`, so sharing or sending one is as safe as a Vec
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Drop for Mapping {
+    // a shared mapping's changes still reach the file after this, the
+    // kernel writes them back in its own time. the lock is let go when the
+    // file is closed, after this
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
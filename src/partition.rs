@@ -0,0 +1,224 @@
+//! Finding the partitions of a whole-disk image, so the ext2 file system on
+//! one of them can be opened. The partition table is either an MBR, of
+//! which only the four primary entries are read, or a GPT behind a
+//! protective MBR. Sectors are taken to be 512 bytes, as they are in disk
+//! images
+
+use crate::error::Ext2Error;
+use crate::structs::{EXT2_END_OF_SUPERBLOCK, EXT2_MAGIC, EXT2_START_OF_SUPERBLOCK};
+use crate::volume::BlockDevice;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+const SECTOR_SIZE: u64 = 512;
+// where the MBR keeps its four entries, and the signature after them
+const MBR_ENTRIES: usize = 446;
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+// the one entry of the MBR in front of a GPT, covering the whole disk
+const GPT_PROTECTIVE: u8 = 0xEE;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+// more than this many GPT entries is taken to be garbage. 128 is usual
+const GPT_MAX_ENTRIES: u32 = 1024;
+// where the magic number is in the superblock
+const MAGIC_OFFSET: u64 = 56;
+
+/// What the partition table says is on a partition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// An MBR's one byte type, 0x83 for Linux
+    Mbr(u8),
+    /// A GPT's partition type GUID, as it is on disk
+    Gpt([u8; 16]),
+}
+
+impl fmt::Display for PartitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionKind::Mbr(kind) => write!(f, "type 0x{:02x}", kind),
+            // the first three fields of a GUID are little endian on disk
+            PartitionKind::Gpt(g) => write!(
+                f,
+                "type {:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+                g[3], g[2], g[1], g[0], g[5], g[4], g[7], g[6],
+                g[8], g[9], g[10], g[11], g[12], g[13], g[14], g[15]
+            ),
+        }
+    }
+}
+
+/// One entry of a partition table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition {
+    /// Counting from 1, the way Linux names them: sda1 is partition 1
+    pub number: usize,
+    /// Offset of the partition in bytes from the start of the device
+    pub start: u64,
+    /// Length in bytes
+    pub len: u64,
+    pub kind: PartitionKind,
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "partition {}: {} bytes at offset {}, {}",
+            self.number, self.len, self.start, self.kind
+        )
+    }
+}
+
+fn le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn le64(bytes: &[u8], at: usize) -> u64 {
+    le32(bytes, at) as u64 | (le32(bytes, at + 4) as u64) << 32
+}
+
+/// The partitions listed in the partition table at the start of `device`,
+/// none if it doesn't start with one. Empty slots are left out
+pub fn partitions<D: BlockDevice>(device: &D) -> Result<Vec<Partition>, Ext2Error> {
+    if device.size() < SECTOR_SIZE {
+        return Ok(Vec::new());
+    }
+    let mut mbr = [0; SECTOR_SIZE as usize];
+    device.read_at(0, &mut mbr)?;
+    if mbr[510..] != MBR_SIGNATURE {
+        return Ok(Vec::new());
+    }
+    let mut found = Vec::new();
+    for slot in 0..4 {
+        let entry = &mbr[MBR_ENTRIES + slot * 16..MBR_ENTRIES + (slot + 1) * 16];
+        let kind = entry[4];
+        let first = le32(entry, 8) as u64;
+        let sectors = le32(entry, 12) as u64;
+        if kind == GPT_PROTECTIVE {
+            return gpt_partitions(device);
+        }
+        // a boot sector with the signature but no table has junk here, an
+        // entry that doesn't start on the device isn't one
+        if kind == 0 || first == 0 || sectors == 0 || first * SECTOR_SIZE >= device.size() {
+            continue;
+        }
+        found.push(Partition {
+            number: slot + 1,
+            start: first * SECTOR_SIZE,
+            len: sectors * SECTOR_SIZE,
+            kind: PartitionKind::Mbr(kind),
+        });
+    }
+    Ok(found)
+}
+
+// the partitions of a GPT, whose header is in the second sector. the
+// header's and the entries' crcs aren't checked
+fn gpt_partitions<D: BlockDevice>(device: &D) -> Result<Vec<Partition>, Ext2Error> {
+    let mut header = [0; 92];
+    device.read_at(SECTOR_SIZE, &mut header)?;
+    if &header[..8] != GPT_SIGNATURE {
+        return Err(Ext2Error::Corrupt(format!(
+            "protective MBR but no GPT header at offset {}",
+            SECTOR_SIZE
+        )));
+    }
+    let table = le64(&header, 0x48);
+    let count = le32(&header, 0x50);
+    let entry_len = le32(&header, 0x54) as usize;
+    if count > GPT_MAX_ENTRIES || entry_len < 128 {
+        return Err(Ext2Error::Corrupt(format!(
+            "GPT with {} entries of {} bytes",
+            count, entry_len
+        )));
+    }
+    let Some(at) = table.checked_mul(SECTOR_SIZE) else {
+        return Err(Ext2Error::Corrupt(format!(
+            "GPT entries at sector {}",
+            table
+        )));
+    };
+    let mut entries = vec![0; count as usize * entry_len];
+    device.read_at(at, &mut entries)?;
+    let mut found = Vec::new();
+    for (i, entry) in entries.chunks(entry_len).enumerate() {
+        let mut kind = [0; 16];
+        kind.copy_from_slice(&entry[..16]);
+        let first = le64(entry, 32);
+        let last = le64(entry, 40);
+        if kind == [0; 16] || last < first || first >= device.size() / SECTOR_SIZE {
+            continue;
+        }
+        found.push(Partition {
+            number: i + 1,
+            start: first * SECTOR_SIZE,
+            len: (last - first + 1).saturating_mul(SECTOR_SIZE),
+            kind: PartitionKind::Gpt(kind),
+        });
+    }
+    Ok(found)
+}
+
+// whether there's an ext2 superblock magic number where there would be if
+// a file system started at `start`
+fn magic_at<D: BlockDevice>(device: &D, start: u64) -> bool {
+    let mut magic = [0; 2];
+    device
+        .read_at(
+            start + EXT2_START_OF_SUPERBLOCK as u64 + MAGIC_OFFSET,
+            &mut magic,
+        )
+        .is_ok()
+        && u16::from_le_bytes(magic) == EXT2_MAGIC
+}
+
+/// Whether `partition` looks like it holds an ext2 file system
+pub fn holds_ext2<D: BlockDevice>(device: &D, partition: &Partition) -> bool {
+    partition.len >= EXT2_END_OF_SUPERBLOCK as u64 && magic_at(device, partition.start)
+}
+
+/// The partition to open on `device`: partition `number` if one is asked
+/// for, otherwise the only one that holds an ext2 file system. None when
+/// the file system isn't on a partition, i.e. there's no table, or no
+/// partition was asked for and the device itself starts with one
+pub fn choose_partition<D: BlockDevice>(
+    device: &D,
+    number: Option<usize>,
+) -> Result<Option<Partition>, Ext2Error> {
+    let found = partitions(device)?;
+    if let Some(number) = number {
+        let Some(partition) = found.into_iter().find(|p| p.number == number) else {
+            return Err(Ext2Error::NotFound(format!("partition {}", number)));
+        };
+        if partition.start.saturating_add(partition.len) > device.size() {
+            return Err(Ext2Error::Corrupt(format!(
+                "partition {} runs past the end of the device",
+                number
+            )));
+        }
+        return Ok(Some(partition));
+    }
+    if magic_at(device, 0) {
+        return Ok(None);
+    }
+    let candidates: Vec<Partition> = found
+        .into_iter()
+        .filter(|partition| {
+            partition.start.saturating_add(partition.len) <= device.size()
+                && holds_ext2(device, partition)
+        })
+        .collect();
+    match candidates[..] {
+        [] => Ok(None),
+        [partition] => Ok(Some(partition)),
+        _ => Err(Ext2Error::Ambiguous(format!(
+            "partitions {} all hold an ext2 file system",
+            candidates
+                .iter()
+                .map(|partition| format!("{}", partition.number))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,222 @@
+//! Following `/` separated paths of raw names through the directories of a
+//! file system. `Volume` and the shell both resolve paths with this, each
+//! looking names up its own way through `Lookup`
+
+use crate::error::Ext2Error;
+use crate::metadata::escape_name;
+use crate::structs::InodeNo;
+use crate::volume::{BlockDevice, Volume};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// What resolving a path needs from a file system
+pub trait Lookup {
+    /// The inode the entry called `name` of directory `dir` points at
+    fn lookup(&self, dir: InodeNo, name: &[u8]) -> Result<InodeNo, Ext2Error>;
+
+    /// Whether `inode` is a directory
+    fn is_dir(&self, inode: InodeNo) -> Result<bool, Ext2Error>;
+
+    /// Whether names may be looked up in `dir` on the way along `path`,
+    /// which an error should name. Anything may by default
+    fn may_search(&self, _dir: InodeNo, _path: &[u8]) -> Result<(), Ext2Error> {
+        Ok(())
+    }
+
+    /// Called by `resolve` with each component of a path once it has been
+    /// followed, and the inode it led to. Nothing is done with it by default
+    fn step(&self, _component: &[u8], _inode: InodeNo) {}
+}
+
+impl<D: BlockDevice> Lookup for Volume<D> {
+    fn lookup(&self, dir: InodeNo, name: &[u8]) -> Result<InodeNo, Ext2Error> {
+        Volume::lookup(self, dir, name)
+    }
+
+    fn is_dir(&self, inode: InodeNo) -> Result<bool, Ext2Error> {
+        Ok(self.metadata(inode)?.is_dir())
+    }
+}
+
+/// Split a path into clean components: empty ones from repeated, leading or
+/// trailing slashes and `.` are dropped, and `..` cancels the component
+/// before it. `..` can't go above the root of an absolute path, but a
+/// relative path keeps its leading `..`s since they depend on where it is
+/// resolved from. This is purely by the text: `file/..` folds away here,
+/// where `resolve` finds `file` isn't a directory
+pub fn normalize_path(path: &[u8]) -> Vec<&[u8]> {
+    let absolute = path.starts_with(b"/");
+    let mut components: Vec<&[u8]> = Vec::new();
+    for component in path.split(|&c| c == b'/') {
+        match component {
+            b"" | b"." => {}
+            b".." => match components.last() {
+                Some(&last) if last != b".." => {
+                    components.pop();
+                }
+                _ if absolute => {}
+                _ => components.push(b".."),
+            },
+            name => components.push(name),
+        }
+    }
+    components
+}
+
+/// Resolve `path` to an inode number; absolute paths start at the root and
+/// relative ones at `base`. Any run of slashes is one separator, so a path
+/// of only slashes is the root, and a trailing slash only makes sense for a
+/// directory. The empty path is an error rather than `base`: it's what a
+/// missing argument looks like.
+///
+/// `.` and `..` fold away like `normalize_path` does, but only once the
+/// component before them is known to be a directory, so `file/..` is an
+/// error rather than the directory `file` is in. A `..` with nothing before
+/// it to cancel stays at the root of an absolute path, and is looked up in
+/// `base` for a relative one
+pub fn resolve<L: Lookup + ?Sized>(
+    fs: &L,
+    base: InodeNo,
+    path: &[u8],
+) -> Result<InodeNo, Ext2Error> {
+    if path.is_empty() {
+        return Err(Ext2Error::InvalidPath(String::new()));
+    }
+    let absolute = path.starts_with(b"/");
+    // the directories walked into, the last one is where the next component
+    // is looked up and `..` goes back to the one before it
+    let mut walked = vec![if absolute { InodeNo::ROOT } else { base }];
+    // where the component before this one ends, so an error can name the
+    // part of the path that isn't a directory rather than a name in it
+    let mut end = 0;
+    let mut start = 0;
+    for component in path.split(|&c| c == b'/') {
+        let component_end = start + component.len();
+        start = component_end + 1;
+        if component.is_empty() {
+            continue;
+        }
+        let current = *walked.last().unwrap();
+        if !fs.is_dir(current)? {
+            let name = if end == 0 { component } else { &path[..end] };
+            return Err(Ext2Error::NotADirectory(escape_name(name)));
+        }
+        fs.may_search(current, path)?;
+        match component {
+            b"." => {}
+            b".." if walked.len() > 1 => {
+                walked.pop();
+            }
+            b".." if absolute => {}
+            name => {
+                let inode = fs.lookup(current, name)?;
+                if name == b".." {
+                    walked[0] = inode;
+                } else {
+                    walked.push(inode);
+                }
+            }
+        }
+        fs.step(component, *walked.last().unwrap());
+        end = component_end;
+    }
+    let current = *walked.last().unwrap();
+    if path.ends_with(b"/") && !fs.is_dir(current)? {
+        return Err(Ext2Error::NotADirectory(escape_name(path)));
+    }
+    Ok(current)
+}
+
+/// Resolve everything but the last component of `path`, returning the
+/// directory it lives in along with that last component's name, which is
+/// what operations that create or remove entries need. Trailing slashes say
+/// the last component is a directory, so if it exists and isn't one that's
+/// an error. One that doesn't exist yet is up to the caller
+pub fn resolve_parent<'p, L: Lookup + ?Sized>(
+    fs: &L,
+    base: InodeNo,
+    path: &'p [u8],
+) -> Result<(InodeNo, &'p [u8]), Ext2Error> {
+    let (parent_path, name) = split_last(path);
+    // `.` and `..` always exist and can't be created or removed
+    if name.is_empty() || name == b"." || name == b".." {
+        return Err(Ext2Error::InvalidPath(escape_name(path)));
+    }
+    // parent_path is either empty or ends in '/', so resolve checks it is a
+    // directory
+    let parent = if parent_path.is_empty() {
+        base
+    } else {
+        resolve(fs, base, parent_path)?
+    };
+    if !fs.is_dir(parent)? {
+        return Err(Ext2Error::NotADirectory(escape_name(parent_path)));
+    }
+    if parent_path.len() + name.len() != path.len() {
+        match fs.lookup(parent, name) {
+            Ok(inode) if !fs.is_dir(inode)? => {
+                return Err(Ext2Error::NotADirectory(escape_name(path)));
+            }
+            Ok(_) | Err(Ext2Error::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((parent, name))
+}
+
+// the part of `path` before its last component, empty or ending in a slash,
+// and that component, without the slashes after it
+fn split_last(path: &[u8]) -> (&[u8], &[u8]) {
+    let end = path.iter().rposition(|&c| c != b'/').map_or(0, |i| i + 1);
+    let trimmed = &path[..end];
+    match trimmed.iter().rposition(|&c| c == b'/') {
+        Some(i) => trimmed.split_at(i + 1),
+        None => (&trimmed[..0], trimmed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(path: &str) -> Vec<&str> {
+        normalize_path(path.as_bytes())
+            .into_iter()
+            .map(|component| core::str::from_utf8(component).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn dots_are_dropped() {
+        assert!(normalized(".").is_empty());
+        assert_eq!(normalized("./a/./b/."), ["a", "b"]);
+        assert_eq!(normalized("/./a"), ["a"]);
+    }
+
+    #[test]
+    fn dot_dot_cancels_the_component_before_it() {
+        assert_eq!(normalized("a/b/../c"), ["a", "c"]);
+        assert_eq!(normalized("/a/b/../../c"), ["c"]);
+        assert!(normalized("a/..").is_empty());
+    }
+
+    #[test]
+    fn repeated_slashes_are_one() {
+        assert_eq!(normalized("//a///b//"), ["a", "b"]);
+        assert!(normalized("///").is_empty());
+        assert!(normalized("").is_empty());
+    }
+
+    // an absolute path stays at the root, a relative one keeps its `..`s
+    // for wherever it is resolved from
+    #[test]
+    fn dot_dot_above_the_root() {
+        assert!(normalized("/..").is_empty());
+        assert!(normalized("/../../a/..").is_empty());
+        assert_eq!(normalized("/../a"), ["a"]);
+        assert_eq!(normalized(".."), [".."]);
+        assert_eq!(normalized("../../a"), ["..", "..", "a"]);
+        assert_eq!(normalized("a/../../b"), ["..", "b"]);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::Ext2;
+use ext2::error::Ext2Error;
+use ext2::structs::{InodeNo, TypeIndicator};
+use std::fmt;
+
+/// One change to the file system. `rm` and `mv` plan what they do as a list
+/// of these first, which only reads, and then apply it; `--dry-run` prints
+/// the plan instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The entry `name` of directory `dir`, which points at `inode`, goes away
+    RemoveEntry {
+        dir: InodeNo,
+        name: String,
+        inode: InodeNo,
+    },
+    /// An entry `name` pointing at `inode` is added to directory `dir`
+    AddEntry {
+        dir: InodeNo,
+        name: String,
+        inode: InodeNo,
+        kind: TypeIndicator,
+    },
+    /// The `..` of directory `dir` is pointed at `parent`
+    Reparent { dir: InodeNo, parent: InodeNo },
+    /// An inode's link count goes from `from` to `to`
+    Links { inode: InodeNo, from: u16, to: u16 },
+    /// An inode is freed, and with it every block it holds
+    Free { inode: InodeNo, blocks: Vec<u32> },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::RemoveEntry { dir, name, inode } => write!(
+                f,
+                "remove entry {} (inode {}) from directory {}",
+                name, inode, dir
+            ),
+            Change::AddEntry {
+                dir, name, inode, ..
+            } => write!(
+                f,
+                "add entry {} (inode {}) to directory {}",
+                name, inode, dir
+            ),
+            Change::Reparent { dir, parent } => {
+                write!(f, "point .. of directory {} at {}", dir, parent)
+            }
+            Change::Links { inode, from, to } => write!(
+                f,
+                "change the link count of inode {} from {} to {}",
+                inode, from, to
+            ),
+            Change::Free { inode, blocks } if blocks.is_empty() => {
+                write!(f, "free inode {}, which holds no blocks", inode)
+            }
+            Change::Free { inode, blocks } => {
+                let noun = if blocks.len() == 1 { "block" } else { "blocks" };
+                write!(f, "free inode {} and {} ", inode, noun)?;
+                // runs of consecutive blocks are written first-last
+                let mut i = 0;
+                while i < blocks.len() {
+                    let mut end = i;
+                    while end + 1 < blocks.len() && blocks[end + 1] == blocks[end] + 1 {
+                        end += 1;
+                    }
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    if end == i {
+                        write!(f, "{}", blocks[i])?;
+                    } else {
+                        write!(f, "{}-{}", blocks[i], blocks[end])?;
+                    }
+                    i = end + 1;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Ext2 {
+    // what `unlink` does: remove the entry `name` from `parent`, freeing the
+    // inode once nothing links to it. directories have to be empty
+    pub fn plan_unlink(&self, parent: InodeNo, name: &str) -> Result<Vec<Change>, Ext2Error> {
+        if name == "." || name == ".." {
+            return Err(Ext2Error::InvalidPath(name.to_string()));
+        }
+        let child = self.lookup(parent, name)?;
+        self.check_dir_change(parent, name, false)?;
+        self.check_flags(child, name, false)?;
+        let is_dir = self.metadata(child)?.is_dir();
+        if is_dir && self.dir_entries(child)?.len() > 2 {
+            return Err(Ext2Error::DirectoryNotEmpty(name.to_string()));
+        }
+        let mut plan = vec![Change::RemoveEntry {
+            dir: parent,
+            name: name.to_string(),
+            inode: child,
+        }];
+        // a directory's own `.` goes with it, and its `..` no longer points
+        // at the parent. a file is only gone with its last link
+        let (counted, free) = if is_dir {
+            (parent, true)
+        } else {
+            let links = self.get_inode(child)?.hard_links.get();
+            (child, links <= 1)
+        };
+        let links = self.get_inode(counted)?.hard_links.get();
+        plan.push(Change::Links {
+            inode: counted,
+            from: links,
+            to: links.saturating_sub(1),
+        });
+        if free {
+            plan.push(Change::Free {
+                inode: child,
+                blocks: self.inode_blocks(child),
+            });
+        }
+        Ok(plan)
+    }
+
+    // whether directory `dir` is `ancestor` or somewhere below it, going up
+    // the `..`s. a corrupt `..` can make a loop that never reaches the root,
+    // so give up after as many steps as there are inodes
+    pub fn is_within(&self, dir: InodeNo, ancestor: InodeNo) -> Result<bool, Ext2Error> {
+        let mut current = dir;
+        let mut steps = 0;
+        loop {
+            if current == ancestor {
+                return Ok(true);
+            }
+            if current == InodeNo::ROOT {
+                return Ok(false);
+            }
+            steps += 1;
+            if steps > self.superblock().inodes_count.get() {
+                return Err(Ext2Error::Corrupt(format!(
+                    "`..` of inode {} never leads to the root",
+                    dir
+                )));
+            }
+            current = self.lookup(current, "..")?;
+        }
+    }
+
+    // what `rename` does: move the entry `src_name` of `src_dir` to `dst_dir`
+    // as `dst_name`. the inode stays where it is, only the entries change
+    pub fn plan_rename(
+        &self,
+        src_dir: InodeNo,
+        src_name: &str,
+        dst_dir: InodeNo,
+        dst_name: &str,
+    ) -> Result<Vec<Change>, Ext2Error> {
+        if src_name == "." || src_name == ".." {
+            return Err(Ext2Error::InvalidPath(src_name.to_string()));
+        }
+        let child = self.lookup(src_dir, src_name)?;
+        match self.lookup(dst_dir, dst_name) {
+            Ok(_) => return Err(Ext2Error::AlreadyExists(dst_name.to_string())),
+            Err(Ext2Error::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+        self.check_flags(child, src_name, false)?;
+        self.check_dir_change(src_dir, src_name, false)?;
+        self.check_dir_change(dst_dir, dst_name, true)?;
+        let metadata = self.metadata(child)?;
+        // a directory can't be moved inside itself
+        if metadata.is_dir() && self.is_within(dst_dir, child)? {
+            return Err(Ext2Error::InvalidPath(dst_name.to_string()));
+        }
+
+        let mut plan = vec![
+            Change::AddEntry {
+                dir: dst_dir,
+                name: dst_name.to_string(),
+                inode: child,
+                kind: metadata.file_type.type_indicator(),
+            },
+            Change::RemoveEntry {
+                dir: src_dir,
+                name: src_name.to_string(),
+                inode: child,
+            },
+        ];
+        if metadata.is_dir() && src_dir != dst_dir {
+            plan.push(Change::Reparent {
+                dir: child,
+                parent: dst_dir,
+            });
+            for (dir, delta) in [(src_dir, -1), (dst_dir, 1)] {
+                let links = self.get_inode(dir)?.hard_links.get();
+                plan.push(Change::Links {
+                    inode: dir,
+                    from: links,
+                    to: (links as i32 + delta).max(0) as u16,
+                });
+            }
+        }
+        Ok(plan)
+    }
+
+    // make the changes of a plan, in order. it has to have been planned
+    // against the file system as it is now
+    pub fn apply(&mut self, plan: &[Change]) -> Result<(), Ext2Error> {
+        for change in plan {
+            match change {
+                Change::RemoveEntry { dir, name, .. } => {
+                    self.remove_dir_entry(*dir, name)?;
+                }
+                Change::AddEntry {
+                    dir,
+                    name,
+                    inode,
+                    kind,
+                } => self.insert_dir_entry(*dir, name, *inode, *kind)?,
+                Change::Reparent { dir, parent } => self.set_dot_dot(*dir, *parent)?,
+                Change::Links { inode, from, to } => {
+                    self.adjust_links(*inode, *to as i32 - *from as i32)?
+                }
+                Change::Free { inode, .. } => self.free_inode(*inode)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,87 +1,99 @@
+use crate::error::Ext2Error;
+use alloc::format;
 use bitflags::bitflags;
-use null_terminated::NulStr;
+use core::fmt;
+use core::num::NonZeroU32;
+use zerocopy::byteorder::{LittleEndian, I16, I32, U16, U32};
+use zerocopy::{AsBytes, FromBytes, LayoutVerified, Unaligned};
+
+// ext2 stores every multi-byte field little-endian, so the on-disk structs
+// use these instead of native integers and convert on each access
+pub type Le16 = U16<LittleEndian>;
+pub type Le32 = U32<LittleEndian>;
+pub type LeI16 = I16<LittleEndian>;
+pub type LeI32 = I32<LittleEndian>;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, FromBytes, AsBytes, Unaligned)]
 // https://wiki.osdev.org/Ext2
 pub struct Superblock {
     // taken from https://wiki.osdev.org/Ext2
     /// Total number of inodes in file system
-    pub inodes_count: u32,
+    pub inodes_count: Le32,
     /// Total number of blocks in file system
-    pub blocks_count: u32,
+    pub blocks_count: Le32,
     /// Number of blocks reserved for superuser (see offset 80)
-    pub r_blocks_count: u32,
+    pub r_blocks_count: Le32,
     /// Total number of unallocated blocks
-    pub free_blocks_count: u32,
+    pub free_blocks_count: Le32,
     /// Total number of unallocated inodes
-    pub free_inodes_count: u32,
+    pub free_inodes_count: Le32,
     /// Block number of the block containing the superblock
-    pub first_data_block: u32,
+    pub first_data_block: Le32,
     /// log2 (block size) - 10. (In other words, the number to shift 1,024
     /// to the left by to obtain the block size)
-    pub log_block_size: u32,
+    pub log_block_size: Le32,
     /// log2 (fragment size) - 10. (In other words, the number to shift
     /// 1,024 to the left by to obtain the fragment size)
-    pub log_frag_size: i32,
+    pub log_frag_size: LeI32,
     /// Number of blocks in each block group
-    pub blocks_per_group: u32,
+    pub blocks_per_group: Le32,
     /// Number of fragments in each block group
-    pub frags_per_group: u32,
+    pub frags_per_group: Le32,
     /// Number of inodes in each block group
-    pub inodes_per_group: u32,
+    pub inodes_per_group: Le32,
     /// Last mount time (in POSIX time)
-    pub mtime: u32,
+    pub mtime: Le32,
     /// Last written time (in POSIX time)
-    pub wtime: u32,
+    pub wtime: Le32,
     /// Number of times the volume has been mounted since its last
     /// consistency check (fsck)
-    pub mnt_count: u16,
+    pub mnt_count: Le16,
     /// Number of mounts allowed before a consistency check (fsck) must be
     /// done
-    pub max_mnt_count: i16,
+    pub max_mnt_count: LeI16,
     /// Ext2 signature (0xef53), used to help confirm the presence of Ext2
     /// on a volume
-    pub magic: u16,
+    pub magic: Le16,
     /// File system state (see `FS_CLEAN` and `FS_ERR`)
-    pub state: u16,
+    pub state: Le16,
     /// What to do when an error is detected (see `ERR_IGNORE`, `ERR_RONLY` and
     /// `ERR_PANIC`)
-    pub errors: u16,
+    pub errors: Le16,
     /// Minor portion of version (combine with Major portion below to
     /// construct full version field)
-    pub rev_minor: u16,
+    pub rev_minor: Le16,
     /// POSIX time of last consistency check (fsck)
-    pub lastcheck: u32,
+    pub lastcheck: Le32,
     /// Interval (in POSIX time) between forced consistency checks (fsck)
-    pub checkinterval: u32,
+    pub checkinterval: Le32,
     /// Operating system ID from which the filesystem on this volume was
     /// created
-    pub creator_os: u32,
+    pub creator_os: Le32,
     /// Major portion of version (combine with Minor portion above to
     /// construct full version field)
-    pub rev_major: u32,
+    pub rev_major: Le32,
     /// User ID that can use reserved blocks
-    pub block_uid: u16,
+    pub block_uid: Le16,
     /// Group ID that can use reserved blocks
-    pub block_gid: u16,
+    pub block_gid: Le16,
 
     /// First non-reserved inode in file system.
-    pub first_inode: u32,
+    pub first_inode: Le32,
     /// Size of each inode structure in bytes. - only 128 bytes seem used
     /// but modern EXT filesystems seem to use 256 bytes for each inode
-    pub inode_size: u16,
+    pub inode_size: Le16,
     /// Block group that this superblock is part of (if backup copy)
-    pub block_group: u16,
+    pub block_group: Le16,
     /// Optional features present (features that are not required to read
     /// or write, but usually result in a performance increase)
-    pub features_opt: u32,
+    pub features_opt: Le32,
     /// Required features present (features that are required to be
     /// supported to read or write)
-    pub features_req: u32,
+    pub features_req: Le32,
     /// Features that if not supported, the volume must be mounted
     /// read-only)
-    pub features_ronly: u32,
+    pub features_ronly: Le32,
     /// File system ID (what is output by blkid)
     pub fs_id: [u8; 16],
     /// Volume name (C-style string: characters terminated by a 0 byte)
@@ -90,7 +102,7 @@ pub struct Superblock {
     /// terminated by a 0 byte)
     pub last_mnt_path: [u8; 64],
     /// Compression algorithms used (see Required features above)
-    pub compression: u32,
+    pub compression: Le32,
     /// Number of blocks to preallocate for files
     pub prealloc_blocks_files: u8,
     /// Number of blocks to preallocate for directories
@@ -100,105 +112,308 @@ pub struct Superblock {
     /// Journal ID (same style as the File system ID above)
     pub journal_id: [u8; 16],
     /// Journal inode
-    pub journal_inode: u32,
+    pub journal_inode: Le32,
     /// Journal device
-    pub journal_dev: u32,
+    pub journal_dev: Le32,
     /// Head of orphan inode list
-    pub journal_orphan_head: u32,
+    pub journal_orphan_head: Le32,
+}
+
+impl Superblock {
+    /// Size of a block in bytes. Only meaningful once `log_block_size` has
+    /// been checked, a corrupt value can overflow the shift
+    pub fn block_size(&self) -> usize {
+        MIN_BLOCK_SIZE << self.log_block_size.get()
+    }
+
+    /// Size of a slot in the inode tables. Revision 0 file systems don't
+    /// have the field and always use 128
+    pub fn inode_size(&self) -> usize {
+        if self.rev_major.get() >= 1 {
+            self.inode_size.get() as usize
+        } else {
+            128
+        }
+    }
+
+    /// Whether directory entries record the type of their inode, the
+    /// filetype feature. Without it the byte the type goes in is the high
+    /// byte of the name length, and has to be 0
+    pub fn has_file_types(&self) -> bool {
+        self.features_req.get() & INCOMPAT_FILETYPE != 0
+    }
+
+    /// The first inode that isn't reserved. Revision 0 file systems don't
+    /// have the field and always reserve the first 10
+    pub fn first_ino(&self) -> usize {
+        if self.rev_major.get() >= 1 {
+            self.first_inode.get() as usize
+        } else {
+            11
+        }
+    }
 }
 
+// the incompatible feature bit for file types in directory entries
+const INCOMPAT_FILETYPE: u32 = 0x2;
+
+/// The smallest block size, the one `log_block_size` is relative to
+pub const MIN_BLOCK_SIZE: usize = 1024;
+/// `Superblock::magic` of every ext2 file system
+pub const EXT2_MAGIC: u16 = 0xef53;
+/// Byte offset of the superblock from the start of the device, whatever the
+/// block size
+pub const EXT2_START_OF_SUPERBLOCK: usize = 1024;
+/// First byte after the superblock
+pub const EXT2_END_OF_SUPERBLOCK: usize = 2048;
+
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, FromBytes, AsBytes, Unaligned)]
 pub struct BlockGroupDescriptor {
     /// Block address of block usage bitmap
-    pub block_usage_addr: u32,
+    pub block_usage_addr: Le32,
     /// Block address of inode usage bitmap
-    pub inode_usage_addr: u32,
+    pub inode_usage_addr: Le32,
     /// Starting block address of inode table
-    pub inode_table_block: u32,
+    pub inode_table_block: Le32,
     /// Number of unallocated blocks in group
-    pub free_blocks_count: u16,
+    pub free_blocks_count: Le16,
     /// Number of unallocated inodes in group
-    pub free_inodes_count: u16,
+    pub free_inodes_count: Le16,
     /// Number of directories in group
-    pub dirs_count: u16,
+    pub dirs_count: Le16,
 
     _reserved: [u8; 14],
 }
 
+/// The first 128 bytes of an inode, which every inode size has. Bigger
+/// inodes keep extra fields and extended attributes after them, see
+/// `xattr`
 #[repr(C)]
+#[derive(FromBytes, AsBytes, Unaligned)]
 pub struct Inode {
     /// Type and Permissions (see below)
-    pub type_perm: TypePerm,
+    pub type_perm: Le16,
     /// User ID
-    pub uid: u16,
+    pub uid: Le16,
     /// Lower 32 bits of size in bytes
-    pub size_low: u32,
+    pub size_low: Le32,
     /// Last Access Time (in POSIX time)
-    pub atime: u32,
+    pub atime: Le32,
     /// Creation Time (in POSIX time)
-    pub ctime: u32,
+    pub ctime: Le32,
     /// Last Modification time (in POSIX time)
-    pub mtime: u32,
+    pub mtime: Le32,
     /// Deletion time (in POSIX time)
-    pub dtime: u32,
+    pub dtime: Le32,
     /// Group ID
-    pub gid: u16,
+    pub gid: Le16,
     /// Count of hard links (directory entries) to this inode. When this
     /// reaches 0, the data blocks are marked as unallocated.
-    pub hard_links: u16,
+    pub hard_links: Le16,
     /// Count of disk sectors (not Ext2 blocks) in use by this inode, not
     /// counting the actual inode structure nor directory entries linking
     /// to the inode.
-    pub sectors_count: u32,
-    /// Flags
-    pub flags: u32,
+    pub sectors_count: Le32,
+    /// Flags, see `InodeFlags`
+    pub flags: Le32,
     /// Operating System Specific value #1
     pub _os_specific_1: [u8; 4],
     /// Direct block pointers
-    pub direct_pointer: [u32; 12],
+    pub direct_pointer: [Le32; 12],
     /// Singly Indirect Block Pointer (Points to a block that is a list of
     /// block pointers to data)
-    pub indirect_pointer: u32,
+    pub indirect_pointer: Le32,
     /// Doubly Indirect Block Pointer (Points to a block that is a list of
     /// block pointers to Singly Indirect Blocks)
-    pub doubly_indirect: u32,
+    pub doubly_indirect: Le32,
     /// Triply Indirect Block Pointer (Points to a block that is a list of
     /// block pointers to Doubly Indirect Blocks)
-    pub triply_indirect: u32,
+    pub triply_indirect: Le32,
     /// Generation number (Primarily used for NFS)
-    pub gen_number: u32,
+    pub gen_number: Le32,
     /// In Ext2 version 0, this field is reserved. In version >= 1,
-    /// Extended attribute block (File ACL).
-    pub ext_attribute_block: u32,
+    /// Extended attribute block (File ACL), 0 for none. See `xattr`
+    pub ext_attribute_block: Le32,
     /// In Ext2 version 0, this field is reserved. In version >= 1, Upper
     /// 32 bits of file size (if feature bit set) if it's a file,
     /// Directory ACL if it's a directory
-    pub size_high: u32,
+    pub size_high: Le32,
     /// Block address of fragment
-    pub frag_block_addr: u32,
+    pub frag_block_addr: Le32,
     /// Operating System Specific Value #2
     pub _os_specific_2: [u8; 12],
-    _padding: [u8; 128], // TODO: handle inode sizes != 128 according to superblock
 }
 
+impl Inode {
+    /// Type and Permissions, decoded from the on-disk field
+    pub fn type_perm(&self) -> TypePerm {
+        TypePerm::from_bits_truncate(self.type_perm.get())
+    }
+
+    /// Flags, decoded from the on-disk field. Bits this crate has no
+    /// name for are dropped
+    pub fn flags(&self) -> InodeFlags {
+        InodeFlags::from_bits_truncate(self.flags.get())
+    }
+
+    /// Size in bytes. `size_high` is only the upper half of the size for
+    /// regular files; for directories and everything else it's the
+    /// directory ACL block, so it's left out. Linux reads it for regular
+    /// files whether or not the large file feature is set, and so does this
+    pub fn size(&self) -> u64 {
+        let low = self.size_low.get() as u64;
+        if self.type_perm.get() & 0xF000 == TypePerm::FILE.bits() {
+            ((self.size_high.get() as u64) << 32) + low
+        } else {
+            low
+        }
+    }
+}
+
+impl fmt::Debug for Inode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.size_low.get() == 0 && self.size_high.get() == 0 {
+            f.debug_struct("").finish()
+        } else {
+            f.debug_struct("Inode")
+                .field("type_perm", &self.type_perm())
+                .field("size_low", &self.size_low)
+                .field("direct_pointers", &self.direct_pointer)
+                .field("indirect_pointer", &self.indirect_pointer)
+                .finish()
+        }
+    }
+}
+
+/// A validated (1-indexed) inode number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InodeNo(NonZeroU32);
+
+impl InodeNo {
+    /// Inode of the root directory
+    pub const ROOT: InodeNo = match NonZeroU32::new(2) {
+        Some(n) => InodeNo(n),
+        None => unreachable!(),
+    };
+
+    /// Check that `n` names an inode of this file system (`1..=inodes_count`)
+    pub fn new(n: usize, superblock: &Superblock) -> Result<InodeNo, Ext2Error> {
+        if n == 0 || n > superblock.inodes_count.get() as usize {
+            return Err(Ext2Error::InodeOutOfRange(n, superblock.inodes_count.get()));
+        }
+        Ok(InodeNo(NonZeroU32::new(n as u32).unwrap()))
+    }
+
+    /// The raw inode number
+    pub fn get(self) -> u32 {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for InodeNo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The fixed part of a directory entry. The name follows it in the block and
+/// is *not* NUL-terminated, it is exactly `name_length` bytes and may be
+/// followed by leftovers of older entries, so read it through `name_at`
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, FromBytes, Unaligned)]
 pub struct DirectoryEntry {
     /// Inode
-    pub inode: u32,
+    pub inode: Le32,
     /// Total size of this entry (Including all subfields)
     /// (offset to start of next entry)
-    pub entry_size: u16,
+    pub entry_size: Le16,
     /// Name Length least-significant 8 bits
     pub name_length: u8,
-    /// Type indicator (only if the feature bit for "directory entries have file type byte" is set, else this is the most-significant 8 bits of the Name Length)
-    pub type_indicator: TypeIndicator,
+    /// Type indicator (only if the feature bit for "directory entries have file type byte" is set, else this is the most-significant 8 bits of the Name Length).
+    /// Kept as a raw byte since it comes from disk and can hold anything, read
+    /// it through `kind`
+    pub type_indicator: u8,
+}
+
+impl DirectoryEntry {
+    /// The type indicator, `Unknown` for anything that isn't a known type
+    pub fn kind(&self) -> TypeIndicator {
+        TypeIndicator::from_byte(self.type_indicator)
+    }
+
+    /// The name of the entry at `offset` of a directory block, read from the
+    /// bytes themselves: exactly `name_length` bytes, and nothing past the
+    /// end of `block`. None if the block ends before the name does
+    pub fn name_at(block: &[u8], offset: usize) -> Option<&[u8]> {
+        let name_length = *block.get(offset + 6)? as usize;
+        block.get(offset + 8..offset + 8 + name_length)
+    }
+
+    /// The entry at `offset` of a directory block, None if the block ends
+    /// before its fixed part does
+    pub fn at(block: &[u8], offset: usize) -> Option<&DirectoryEntry> {
+        let (entry, _) =
+            LayoutVerified::<_, DirectoryEntry>::new_unaligned_from_prefix(block.get(offset..)?)?;
+        Some(entry.into_ref())
+    }
+
+    /// Every entry of one directory block, unused ones included, as its
+    /// offset in the block, its fixed part and its name. `dir` and `start`,
+    /// how far into the directory the block is, only go into the error for
+    /// an entry that doesn't stay inside the block or has no room for its
+    /// name, which ends the walk
+    pub fn entries(block: &[u8], dir: InodeNo, start: usize) -> DirectoryEntries<'_> {
+        DirectoryEntries {
+            block,
+            dir,
+            start,
+            offset: 0,
+        }
+    }
+}
 
-    pub name: NulStr,
+/// See `DirectoryEntry::entries`
+pub struct DirectoryEntries<'a> {
+    block: &'a [u8],
+    dir: InodeNo,
+    start: usize,
+    offset: usize,
 }
 
-#[derive(Debug)]
+impl<'a> Iterator for DirectoryEntries<'a> {
+    type Item = Result<(usize, &'a DirectoryEntry, &'a [u8]), Ext2Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        if offset >= self.block.len() {
+            return None;
+        }
+        // the fields come from disk, check them before anything reads past
+        // them. the name is name_length bytes, there is no NUL after it
+        let entry = DirectoryEntry::at(self.block, offset)
+            .filter(|entry| {
+                let entry_size = entry.entry_size.get() as usize;
+                entry_size >= 8
+                    && offset + entry_size <= self.block.len()
+                    && 8 + entry.name_length as usize <= entry_size
+            })
+            .zip(DirectoryEntry::name_at(self.block, offset));
+        let Some((entry, name)) = entry else {
+            self.offset = self.block.len();
+            return Some(Err(Ext2Error::Corrupt(format!(
+                "bad directory entry at byte {} of inode {}",
+                self.start + offset,
+                self.dir
+            ))));
+        };
+        self.offset += entry.entry_size.get() as usize;
+        Some(Ok((offset, entry, name)))
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TypeIndicator {
     Unknown,
     Regular,
@@ -210,6 +425,23 @@ pub enum TypeIndicator {
     Symlink,
 }
 
+impl TypeIndicator {
+    /// Decode a type byte from disk. File systems without the filetype
+    /// feature leave it 0, and anything out of range is treated the same
+    pub fn from_byte(byte: u8) -> TypeIndicator {
+        match byte {
+            1 => TypeIndicator::Regular,
+            2 => TypeIndicator::Directory,
+            3 => TypeIndicator::Character,
+            4 => TypeIndicator::Block,
+            5 => TypeIndicator::Fifo,
+            6 => TypeIndicator::Socket,
+            7 => TypeIndicator::Symlink,
+            _ => TypeIndicator::Unknown,
+        }
+    }
+}
+
 bitflags! {
     pub struct TypePerm: u16 {
         /// FIFO
@@ -252,3 +484,28 @@ bitflags! {
         const SET_UID = 0x800;
     }
 }
+
+bitflags! {
+    pub struct InodeFlags: u32 {
+        /// Secure deletion (not implemented by Linux)
+        const SECURE_DELETE = 0x1;
+        /// Keep a copy of the data when deleted (not implemented by Linux)
+        const UNDELETE = 0x2;
+        /// File compression (not implemented by Linux)
+        const COMPRESSED = 0x4;
+        /// Changes are written to disk right away
+        const SYNC = 0x8;
+        /// Nothing about the file can change: its data, its metadata or,
+        /// for a directory, its entries. It can't be removed or renamed
+        const IMMUTABLE = 0x10;
+        /// Data can only be added at the end, and a directory can only
+        /// gain entries. It can't be removed or renamed
+        const APPEND_ONLY = 0x20;
+        /// Left out by dump
+        const NO_DUMP = 0x40;
+        /// Last access time is not updated
+        const NO_ATIME = 0x80;
+        /// Directory entries are hashed (htree)
+        const INDEX = 0x1000;
+    }
+}
@@ -0,0 +1,281 @@
+//! Writing a tree of a `Volume` out as a POSIX tar archive, headers made by
+//! hand. Names, link targets and sizes that don't fit a ustar header go in
+//! a pax extended header in front of it, which every tar since the 2000s
+//! reads
+
+use crate::error::Ext2Error;
+use crate::file::copy_file;
+use crate::metadata::{FileType, Metadata};
+use crate::structs::{Inode, InodeNo};
+use crate::volume::{BlockDevice, Volume};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+const BLOCK: usize = 512;
+
+// ustar type flags
+const REGULAR: u8 = b'0';
+const HARD_LINK: u8 = b'1';
+const SYMLINK: u8 = b'2';
+const CHAR_DEVICE: u8 = b'3';
+const BLOCK_DEVICE: u8 = b'4';
+const DIRECTORY: u8 = b'5';
+const FIFO: u8 = b'6';
+const PAX: u8 = b'x';
+
+/// Write the tree at `root` to `out` as a tar archive. Entries are named
+/// `name/...`, or relative to `root` if `name` is empty, and a `root` that
+/// isn't a directory becomes a single entry called `name`. Hard links are
+/// stored as tar hard links to the first name seen for the inode, and
+/// sockets, which tar can't hold, are left out
+pub fn write_tar<D: BlockDevice, W: Write>(
+    volume: &Volume<D>,
+    root: InodeNo,
+    name: &[u8],
+    out: W,
+) -> Result<(), Ext2Error> {
+    let mut writer = TarWriter {
+        volume,
+        out,
+        links: HashMap::new(),
+        visited: HashSet::new(),
+    };
+    writer.add(root, name.to_vec())?;
+    // an archive ends with two blocks of zeros
+    writer.out.write_all(&[0; 2 * BLOCK])?;
+    writer.out.flush()?;
+    Ok(())
+}
+
+struct TarWriter<'v, D, W> {
+    volume: &'v Volume<D>,
+    out: W,
+    /// First path written for each inode with more than one link
+    links: HashMap<u32, Vec<u8>>,
+    /// Directories already written, so a corrupt image that links a
+    /// directory into itself can't loop forever
+    visited: HashSet<u32>,
+}
+
+impl<D: BlockDevice, W: Write> TarWriter<'_, D, W> {
+    fn add(&mut self, inode: InodeNo, path: Vec<u8>) -> Result<(), Ext2Error> {
+        let node = self.volume.read_inode(inode)?;
+        let metadata = Metadata::from_inode(inode, &node);
+        let mut header = Header::new(&metadata);
+
+        if metadata.file_type != FileType::Directory && metadata.nlink > 1 {
+            if let Some(first) = self.links.get(&inode.get()) {
+                header.kind = HARD_LINK;
+                header.link = first.clone();
+                header.size = 0;
+                return self.write_header(&path, &header);
+            }
+            self.links.insert(inode.get(), path.clone());
+        }
+
+        match metadata.file_type {
+            FileType::Directory => {
+                if !self.visited.insert(inode.get()) {
+                    return Err(Ext2Error::Corrupt(format!(
+                        "directory inode {} is linked into its own subtree",
+                        inode
+                    )));
+                }
+                // the root of the archive only gets an entry if it has a name
+                if !path.is_empty() {
+                    header.kind = DIRECTORY;
+                    header.size = 0;
+                    let mut dir_path = path.clone();
+                    dir_path.push(b'/');
+                    self.write_header(&dir_path, &header)?;
+                }
+                for entry in self.volume.read_dir(inode)? {
+                    if entry.name_bytes == b"." || entry.name_bytes == b".." {
+                        continue;
+                    }
+                    let mut child = path.clone();
+                    if !child.is_empty() {
+                        child.push(b'/');
+                    }
+                    child.extend_from_slice(&entry.name_bytes);
+                    self.add(entry.inode, child)?;
+                }
+                Ok(())
+            }
+            FileType::Regular => {
+                header.kind = REGULAR;
+                self.write_header(&path, &header)?;
+                self.write_contents(inode, metadata.size)
+            }
+            FileType::Symlink => {
+                let mut target = vec![0; metadata.size as usize];
+                let len = self.volume.read_link(inode, &mut target)?;
+                target.truncate(len);
+                header.kind = SYMLINK;
+                header.link = target;
+                header.size = 0;
+                self.write_header(&path, &header)
+            }
+            FileType::CharDevice | FileType::BlockDevice => {
+                header.kind = if metadata.file_type == FileType::CharDevice {
+                    CHAR_DEVICE
+                } else {
+                    BLOCK_DEVICE
+                };
+                header.device = device_number(&node);
+                header.size = 0;
+                self.write_header(&path, &header)
+            }
+            FileType::Fifo => {
+                header.kind = FIFO;
+                header.size = 0;
+                self.write_header(&path, &header)
+            }
+            FileType::Socket | FileType::Unknown => Ok(()),
+        }
+    }
+
+    // a file's data, padded out to a whole number of tar blocks
+    fn write_contents(&mut self, inode: InodeNo, size: u64) -> Result<(), Ext2Error> {
+        copy_file(self.volume, inode, &mut self.out)?;
+        self.pad(size)
+    }
+
+    fn pad(&mut self, len: u64) -> Result<(), Ext2Error> {
+        let rest = (len % BLOCK as u64) as usize;
+        if rest != 0 {
+            self.out.write_all(&[0; BLOCK][rest..])?;
+        }
+        Ok(())
+    }
+
+    // a ustar header for `path`, with a pax header in front of it for
+    // whatever doesn't fit
+    fn write_header(&mut self, path: &[u8], header: &Header) -> Result<(), Ext2Error> {
+        let split = split_path(path);
+        let mut pax = Vec::new();
+        if split.is_none() {
+            pax_record(&mut pax, "path", path);
+        }
+        if header.link.len() > 100 {
+            pax_record(&mut pax, "linkpath", &header.link);
+        }
+        if header.size > MAX_OCTAL_11 {
+            pax_record(&mut pax, "size", header.size.to_string().as_bytes());
+        }
+        if !pax.is_empty() {
+            let mut pax_header = header.clone();
+            pax_header.kind = PAX;
+            pax_header.size = pax.len() as u64;
+            pax_header.link.clear();
+            self.out.write_all(&pax_header.encode(b"", b"PaxHeader"))?;
+            self.out.write_all(&pax)?;
+            self.pad(pax.len() as u64)?;
+        }
+        // what didn't fit is in the pax header, the ustar fields just get
+        // as much as they can hold
+        let (prefix, name) = split.unwrap_or((&b""[..], &path[path.len().saturating_sub(100)..]));
+        self.out.write_all(&header.encode(prefix, name))?;
+        Ok(())
+    }
+}
+
+// the largest number an 11 digit octal field holds
+const MAX_OCTAL_11: u64 = 0o77777777777;
+
+#[derive(Clone)]
+struct Header {
+    mode: u16,
+    uid: u16,
+    gid: u16,
+    size: u64,
+    mtime: u32,
+    kind: u8,
+    link: Vec<u8>,
+    device: (u32, u32),
+}
+
+impl Header {
+    fn new(metadata: &Metadata) -> Header {
+        Header {
+            mode: metadata.perms,
+            uid: metadata.uid,
+            gid: metadata.gid,
+            size: metadata.size,
+            mtime: metadata.mtime,
+            kind: REGULAR,
+            link: Vec::new(),
+            device: (0, 0),
+        }
+    }
+
+    fn encode(&self, prefix: &[u8], name: &[u8]) -> [u8; BLOCK] {
+        let mut block = [0; BLOCK];
+        put(&mut block[0..100], name);
+        octal(&mut block[100..108], self.mode as u64);
+        octal(&mut block[108..116], self.uid as u64);
+        octal(&mut block[116..124], self.gid as u64);
+        octal(&mut block[124..136], self.size.min(MAX_OCTAL_11));
+        octal(&mut block[136..148], self.mtime as u64);
+        block[156] = self.kind;
+        put(&mut block[157..257], &self.link);
+        put(&mut block[257..265], b"ustar\x0000");
+        octal(&mut block[329..337], self.device.0 as u64);
+        octal(&mut block[337..345], self.device.1 as u64);
+        put(&mut block[345..500], prefix);
+        // the checksum is taken with its own field as spaces
+        block[148..156].fill(b' ');
+        let sum: u32 = block.iter().map(|&b| b as u32).sum();
+        octal(&mut block[148..155], sum as u64);
+        block
+    }
+}
+
+// copy as much of `value` as fits into a field
+fn put(field: &mut [u8], value: &[u8]) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value[..len]);
+}
+
+// a zero padded octal number ending in a NUL, like tar writes them
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    put(field, digits.as_bytes());
+    field[field.len() - 1] = 0;
+}
+
+// split a path into a ustar prefix of up to 155 bytes and a name of up to
+// 100, at a `/`. None if it can't be done
+fn split_path(path: &[u8]) -> Option<(&[u8], &[u8])> {
+    if path.len() <= 100 {
+        return Some((b"", path));
+    }
+    // the slash itself goes in neither
+    (0..path.len())
+        .filter(|&i| path[i] == b'/' && i <= 155 && path.len() - i - 1 <= 100)
+        .map(|i| (&path[..i], &path[i + 1..]))
+        .find(|(_, name)| !name.is_empty())
+}
+
+// a pax record is "<length> <key>=<value>\n", where the length counts itself
+fn pax_record(pax: &mut Vec<u8>, key: &str, value: &[u8]) {
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + 1;
+    while len.to_string().len() + rest > len {
+        len += 1;
+    }
+    pax.extend_from_slice(format!("{} {}=", len, key).as_bytes());
+    pax.extend_from_slice(value);
+    pax.push(b'\n');
+}
+
+// the major and minor number of a device inode. Linux keeps them in the first
+// block pointer if they fit the old 8 bit encoding, and in the second otherwise
+fn device_number(node: &Inode) -> (u32, u32) {
+    let old = node.direct_pointer[0].get();
+    if old != 0 {
+        return ((old >> 8) & 0xff, old & 0xff);
+    }
+    let new = node.direct_pointer[1].get();
+    ((new & 0xfff00) >> 8, (new & 0xff) | ((new >> 12) & 0xfff00))
+}
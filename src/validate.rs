@@ -0,0 +1,131 @@
+use crate::error::Ext2Error;
+use crate::structs::{BlockGroupDescriptor, Superblock, EXT2_MAGIC, EXT2_START_OF_SUPERBLOCK};
+use alloc::format;
+use alloc::string::String;
+use core::mem;
+
+/// Reject superblocks whose fields would make mounting overflow, divide by
+/// zero or index past the end of a device of `device_len` bytes
+pub fn check_superblock(superblock: &Superblock, device_len: usize) -> Result<(), Ext2Error> {
+    let corrupt = |what: String| Err(Ext2Error::Corrupt(what));
+
+    if superblock.magic.get() != EXT2_MAGIC {
+        return corrupt(format!("bad magic number {:#x}", superblock.magic.get()));
+    }
+    // block sizes go from 1K (0) to 64K (6)
+    let log_block_size = superblock.log_block_size.get();
+    if log_block_size > 6 {
+        return corrupt(format!(
+            "log_block_size {} is out of range 0..=6",
+            log_block_size
+        ));
+    }
+    let block_size = superblock.block_size();
+    // each group's usage bitmaps are one block long
+    let bits_per_block = 8 * block_size as u32;
+
+    let blocks_per_group = superblock.blocks_per_group.get();
+    if blocks_per_group == 0 || blocks_per_group > bits_per_block {
+        return corrupt(format!(
+            "blocks_per_group {} is out of range 1..={}",
+            blocks_per_group, bits_per_block
+        ));
+    }
+    let inodes_per_group = superblock.inodes_per_group.get();
+    if inodes_per_group == 0 || inodes_per_group > bits_per_block {
+        return corrupt(format!(
+            "inodes_per_group {} is out of range 1..={}",
+            inodes_per_group, bits_per_block
+        ));
+    }
+
+    let blocks_count = superblock.blocks_count.get();
+    if blocks_count == 0 || blocks_count as usize > device_len / block_size {
+        return corrupt(format!(
+            "blocks_count {} does not match a device of {} blocks",
+            blocks_count,
+            device_len / block_size
+        ));
+    }
+    // the superblock always starts at byte 1024, so it is in block 1 with
+    // 1K blocks and in block 0 with anything bigger
+    let first_data_block = superblock.first_data_block.get();
+    let expected = (EXT2_START_OF_SUPERBLOCK / block_size) as u32;
+    if first_data_block != expected {
+        return corrupt(format!(
+            "first_data_block {} should be {} with {} byte blocks",
+            first_data_block, expected, block_size
+        ));
+    }
+    if blocks_count <= first_data_block {
+        return corrupt(format!(
+            "blocks_count {} leaves no block groups",
+            blocks_count
+        ));
+    }
+
+    let group_count = (blocks_count - first_data_block).div_ceil(blocks_per_group) as u64;
+    if superblock.inodes_count.get() as u64 != group_count * inodes_per_group as u64 {
+        return corrupt(format!(
+            "inodes_count {} does not match {} groups of {} inodes",
+            superblock.inodes_count.get(),
+            group_count,
+            inodes_per_group
+        ));
+    }
+
+    if superblock.rev_major.get() >= 1 {
+        let inode_size = superblock.inode_size.get() as usize;
+        if inode_size < 128 || !inode_size.is_power_of_two() || inode_size > block_size {
+            return corrupt(format!(
+                "inode_size {} is not a power of two between 128 and {}",
+                inode_size, block_size
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Every descriptor has to point at bitmaps and an inode table inside the
+/// file system, or reading inodes and allocating would go out of bounds
+pub fn check_block_groups(
+    superblock: &Superblock,
+    block_groups: &[BlockGroupDescriptor],
+) -> Result<(), Ext2Error> {
+    let blocks_count = superblock.blocks_count.get() as u64;
+    let block_size = superblock.block_size() as u64;
+    let table_len = superblock.inodes_per_group.get() as u64 * superblock.inode_size() as u64;
+
+    // nothing may sit on top of the superblock or the descriptor table,
+    // writing a bitmap or an inode there would wreck them
+    let first_free = superblock.first_data_block.get() as u64
+        + 1
+        + mem::size_of_val(block_groups).div_ceil(block_size as usize) as u64;
+
+    for (i, group) in block_groups.iter().enumerate() {
+        let block_bitmap = group.block_usage_addr.get() as u64;
+        let inode_bitmap = group.inode_usage_addr.get() as u64;
+        let inode_table = group.inode_table_block.get() as u64;
+        if block_bitmap < first_free || block_bitmap >= blocks_count {
+            return Err(Ext2Error::Corrupt(format!(
+                "block group {} has block bitmap at {}",
+                i, block_bitmap
+            )));
+        }
+        if inode_bitmap < first_free || inode_bitmap >= blocks_count {
+            return Err(Ext2Error::Corrupt(format!(
+                "block group {} has inode bitmap at {}",
+                i, inode_bitmap
+            )));
+        }
+        if inode_table < first_free
+            || inode_table * block_size + table_len > blocks_count * block_size
+        {
+            return Err(Ext2Error::Corrupt(format!(
+                "block group {} has inode table at {}",
+                i, inode_table
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,748 @@
+use crate::error::Ext2Error;
+use crate::metadata::{escape_name, DirEntry, FileType, Metadata};
+use crate::path;
+use crate::structs::{
+    BlockGroupDescriptor, DirectoryEntry, Inode, InodeNo, Superblock, TypeIndicator,
+    EXT2_END_OF_SUPERBLOCK, EXT2_START_OF_SUPERBLOCK,
+};
+use crate::validate::{check_block_groups, check_superblock};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use core::cell::RefCell;
+use core::cmp;
+use core::mem;
+use core::ops::DerefMut;
+use zerocopy::{AsBytes, FromBytes};
+
+/// Anything an ext2 file system can be read from: an image in memory, a
+/// partition behind a disk driver, ... Offsets are in bytes from the start
+/// of the file system
+pub trait BlockDevice {
+    /// Size of the device in bytes
+    fn size(&self) -> u64;
+
+    /// Fill all of `buf` with the bytes starting at `offset`
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error>;
+}
+
+impl BlockDevice for [u8] {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        let start = offset as usize;
+        match self.get(start..start + buf.len()) {
+            Some(bytes) => {
+                buf.copy_from_slice(bytes);
+                Ok(())
+            }
+            None => Err(Ext2Error::Device(format!(
+                "read of {} bytes at {} is past the end of the device",
+                buf.len(),
+                offset
+            ))),
+        }
+    }
+}
+
+/// An image the caller has handed over, e.g. one a web page read from a file
+impl BlockDevice for Vec<u8> {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        self[..].read_at(offset, buf)
+    }
+}
+
+impl<D: BlockDevice + ?Sized> BlockDevice for &D {
+    fn size(&self) -> u64 {
+        (**self).size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+/// A `BlockDevice` that can be written to as well
+pub trait BlockDeviceMut: BlockDevice {
+    /// Write all of `buf` to the device starting at `offset`
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), Ext2Error>;
+}
+
+impl BlockDeviceMut for [u8] {
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), Ext2Error> {
+        let start = offset as usize;
+        match self.get_mut(start..start + buf.len()) {
+            Some(bytes) => {
+                bytes.copy_from_slice(buf);
+                Ok(())
+            }
+            None => Err(Ext2Error::Device(format!(
+                "write of {} bytes at {} is past the end of the device",
+                buf.len(),
+                offset
+            ))),
+        }
+    }
+}
+
+impl BlockDeviceMut for Vec<u8> {
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), Ext2Error> {
+        self[..].write_at(offset, buf)
+    }
+}
+
+/// An image file or a device node on the host
+#[cfg(feature = "std")]
+impl BlockDevice for std::fs::File {
+    fn size(&self) -> u64 {
+        // the length of a block device node is 0, its size is found by
+        // seeking to the end
+        match self.metadata() {
+            Ok(metadata) if metadata.len() > 0 => metadata.len(),
+            _ => {
+                use std::io::Seek;
+                let mut file = self;
+                file.seek(std::io::SeekFrom::End(0)).unwrap_or(0)
+            }
+        }
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        use std::os::unix::fs::FileExt;
+        Ok(self.read_exact_at(buf, offset)?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl BlockDeviceMut for std::fs::File {
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), Ext2Error> {
+        use std::os::unix::fs::FileExt;
+        Ok(self.write_all_at(buf, offset)?)
+    }
+}
+
+// the little state reads keep between calls. with std it is behind a Mutex,
+// so a `Volume` can be read from several threads at once, without it behind
+// a RefCell
+#[cfg(feature = "std")]
+struct Lock<T>(std::sync::Mutex<T>);
+#[cfg(not(feature = "std"))]
+struct Lock<T>(RefCell<T>);
+
+impl<T> Lock<T> {
+    fn new(value: T) -> Lock<T> {
+        #[cfg(feature = "std")]
+        return Lock(std::sync::Mutex::new(value));
+        #[cfg(not(feature = "std"))]
+        return Lock(RefCell::new(value));
+    }
+
+    fn lock(&self) -> impl DerefMut<Target = T> + '_ {
+        // a panic while reading leaves nothing half changed that matters:
+        // both caches are only used once they are whole
+        #[cfg(feature = "std")]
+        return self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        #[cfg(not(feature = "std"))]
+        return self.0.borrow_mut();
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        #[cfg(feature = "std")]
+        return self
+            .0
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        #[cfg(not(feature = "std"))]
+        return self.0.get_mut();
+    }
+}
+
+/// An ext2 file system on a `BlockDevice`. It doesn't need the whole image
+/// in memory: it keeps the superblock and the descriptor table, and reads
+/// everything else when asked for it. On a `BlockDeviceMut` it can write
+/// them back as well, which is what the shell's `Ext2` does its changes
+/// with. With the `std` feature a `Volume` of a `Sync` device is `Sync`, the
+/// read state it keeps is behind a lock
+pub struct Volume<D> {
+    device: D,
+    superblock: Superblock,
+    block_groups: Vec<BlockGroupDescriptor>,
+    /// Size of a block in bytes
+    block_size: usize,
+    /// Size of an inode table slot, 128 on revision 0 file systems
+    inode_size: usize,
+    /// How many blocks `read_file` reads at once when a file is being read
+    /// from start to end, 0 to read only what's asked for
+    readahead: usize,
+    window: Lock<Readahead>,
+    /// The indirect block pointers were last read from at each level of
+    /// indirection (the one pointing at data first), and its contents
+    indirect: Lock<[(u32, Vec<u8>); 3]>,
+}
+
+/// Where a logical block of a file hangs under an inode's indirect
+/// pointers: the tree (1 for the indirect pointer, 2 doubly, 3 triply), the
+/// slots taken on the way down to the last indirect block, and the slot in
+/// that one
+pub type IndirectPath = (usize, Vec<usize>, usize);
+
+/// The data blocks of an inode in file order, as `(logical, block)`. Only
+/// the blocks its size covers are yielded and holes are skipped, a missing
+/// indirect block skipping everything under it. Indirect blocks are read
+/// through the volume's cache, so each is read once per run of pointers in
+/// it. After an error the iterator is done
+pub struct BlockPtrIter<'a, D> {
+    volume: &'a Volume<D>,
+    node: &'a Inode,
+    logical: u64,
+    end: u64,
+}
+
+impl<D: BlockDevice> Iterator for BlockPtrIter<'_, D> {
+    type Item = Result<(u64, u32), Ext2Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.logical < self.end {
+            let logical = self.logical;
+            match self.volume.locate(self.node, logical) {
+                Ok((block, span)) => {
+                    self.logical = logical.saturating_add(span);
+                    if block != 0 {
+                        return Some(Ok((logical, block)));
+                    }
+                }
+                Err(e) => {
+                    self.logical = self.end;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The default readahead, in blocks
+pub const DEFAULT_READAHEAD: usize = 32;
+
+// what read_file remembers between calls to spot sequential reads
+#[derive(Default)]
+struct Readahead {
+    // the inode read last and where a read continuing it would start
+    last: u32,
+    next: u64,
+    // blocks already read ahead: `data` holds the file's blocks from
+    // logical block `first` on
+    owner: u32,
+    first: u64,
+    data: Vec<u8>,
+}
+
+impl<D: BlockDevice> Volume<D> {
+    /// Read and check the superblock and descriptor table of `device`
+    pub fn open(device: D) -> Result<Volume<D>, Ext2Error> {
+        let device_len = usize::try_from(device.size()).unwrap_or(usize::MAX);
+        if device_len < EXT2_END_OF_SUPERBLOCK {
+            return Err(Ext2Error::Corrupt(format!(
+                "device is {} bytes, too small to hold a superblock",
+                device_len
+            )));
+        }
+        let superblock: Superblock = read_struct(&device, EXT2_START_OF_SUPERBLOCK as u64)?;
+        check_superblock(&superblock, device_len)?;
+
+        let block_size = superblock.block_size();
+        let first_data_block = superblock.first_data_block.get();
+        let group_count = (superblock.blocks_count.get() - first_data_block)
+            .div_ceil(superblock.blocks_per_group.get()) as usize;
+        // the descriptor table starts in the block after the superblock's
+        let table = (first_data_block as u64 + 1) * block_size as u64;
+        let table_len = (group_count * mem::size_of::<BlockGroupDescriptor>()) as u64;
+        if table + table_len > superblock.blocks_count.get() as u64 * block_size as u64 {
+            return Err(Ext2Error::Corrupt(format!(
+                "descriptor table for {} block groups does not fit on the device",
+                group_count
+            )));
+        }
+        let block_groups = (0..group_count)
+            .map(|i| {
+                let offset = table + (i * mem::size_of::<BlockGroupDescriptor>()) as u64;
+                read_struct(&device, offset)
+            })
+            .collect::<Result<Vec<BlockGroupDescriptor>, Ext2Error>>()?;
+        check_block_groups(&superblock, &block_groups)?;
+
+        let inode_size = superblock.inode_size();
+        Ok(Volume {
+            device,
+            superblock,
+            block_groups,
+            block_size,
+            inode_size,
+            readahead: DEFAULT_READAHEAD,
+            window: Lock::new(Readahead::default()),
+            indirect: Lock::new(Default::default()),
+        })
+    }
+
+    /// Set how many blocks a sequential `read_file` fetches at once, as long
+    /// as they're next to each other on the device. 0 turns readahead off
+    pub fn set_readahead(&mut self, blocks: usize) {
+        self.readahead = blocks;
+        *self.window.get_mut() = Readahead::default();
+    }
+
+    pub fn superblock(&self) -> &Superblock {
+        &self.superblock
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn block_groups(&self) -> &[BlockGroupDescriptor] {
+        &self.block_groups
+    }
+
+    /// Size of an inode table slot in bytes
+    pub fn inode_size(&self) -> usize {
+        self.inode_size
+    }
+
+    /// The device the file system is on
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// The device, for writing blocks to. What was read ahead or cached
+    /// from it is forgotten, so later reads see the changes. The superblock,
+    /// the descriptors and inodes are changed with the `update_` methods
+    /// instead, which keep the copies kept of them up to date
+    pub fn device_mut(&mut self) -> &mut D {
+        *self.window.get_mut() = Readahead::default();
+        *self.indirect.get_mut() = Default::default();
+        &mut self.device
+    }
+
+    /// Give back the device, changes and all
+    pub fn into_device(self) -> D {
+        self.device
+    }
+
+    /// Where on the device the inode table slot of `inode` starts
+    pub fn inode_offset(&self, inode: InodeNo) -> Result<u64, Ext2Error> {
+        // an InodeNo made for another file system can be out of range here
+        let inodes_count = self.superblock.inodes_count.get();
+        let out_of_range = Ext2Error::InodeOutOfRange(inode.get() as usize, inodes_count);
+        if inode.get() > inodes_count {
+            return Err(out_of_range);
+        }
+        let index = inode.get() as usize - 1;
+        let per_group = self.superblock.inodes_per_group.get() as usize;
+        let group = self
+            .block_groups
+            .get(index / per_group)
+            .ok_or(out_of_range)?;
+        Ok(
+            group.inode_table_block.get() as u64 * self.block_size as u64
+                + ((index % per_group) * self.inode_size) as u64,
+        )
+    }
+
+    /// A copy of an inode's first 128 bytes, whatever its size
+    pub fn read_inode(&self, inode: InodeNo) -> Result<Inode, Ext2Error> {
+        read_struct(&self.device, self.inode_offset(inode)?)
+    }
+
+    pub fn metadata(&self, inode: InodeNo) -> Result<Metadata, Ext2Error> {
+        Ok(Metadata::from_inode(inode, &self.read_inode(inode)?))
+    }
+
+    /// Where logical block `logical` of a file hangs under the indirect
+    /// pointers, see `IndirectPath`. None for the 12 direct blocks and for
+    /// blocks past what the triply indirect pointer reaches
+    pub fn indirect_path(&self, logical: u64) -> Option<IndirectPath> {
+        let per_block = (self.block_size / 4) as u64;
+        let mut index = logical.checked_sub(12)?;
+        if index < per_block {
+            return Some((1, vec![], index as usize));
+        }
+        index -= per_block;
+        if index < per_block * per_block {
+            let path = vec![(index / per_block) as usize];
+            return Some((2, path, (index % per_block) as usize));
+        }
+        index -= per_block * per_block;
+        if index < per_block * per_block * per_block {
+            let path = vec![
+                (index / per_block / per_block) as usize,
+                (index / per_block % per_block) as usize,
+            ];
+            return Some((3, path, (index % per_block) as usize));
+        }
+        None
+    }
+
+    /// The block holding logical block `logical` of `node`, 0 for a hole.
+    /// `block_ptrs` walks a whole file the same way
+    pub fn block_at(&self, node: &Inode, logical: u64) -> Result<u32, Ext2Error> {
+        Ok(self.locate(node, logical)?.0)
+    }
+
+    /// The data blocks of `node`, see `BlockPtrIter`
+    pub fn block_ptrs<'a>(&'a self, node: &'a Inode) -> BlockPtrIter<'a, D> {
+        BlockPtrIter {
+            volume: self,
+            node,
+            logical: 0,
+            end: node.size().div_ceil(self.block_size as u64),
+        }
+    }
+
+    // the block holding logical block `logical` of `node`, 0 for a hole,
+    // and how many logical blocks from `logical` on the answer is the same
+    // for: a missing indirect block is a hole for everything under it
+    fn locate(&self, node: &Inode, logical: u64) -> Result<(u32, u64), Ext2Error> {
+        if logical < 12 {
+            return Ok((node.direct_pointer[logical as usize].get(), 1));
+        }
+        let Some((tree, path, slot)) = self.indirect_path(logical) else {
+            // past what a triply indirect block can reach
+            return Ok((0, u64::MAX));
+        };
+        let per_block = (self.block_size / 4) as u64;
+        let roots = [
+            node.indirect_pointer.get(),
+            node.doubly_indirect.get(),
+            node.triply_indirect.get(),
+        ];
+        let mut block = roots[tree - 1];
+        // how many logical blocks `block` covers, and where ours is in them
+        let mut span = per_block.pow(tree as u32);
+        let index = path
+            .iter()
+            .chain([&slot])
+            .fold(0, |index, &slot| index * per_block + slot as u64);
+        for (level, &slot) in path.iter().chain([&slot]).enumerate() {
+            if block == 0 {
+                break;
+            }
+            span /= per_block;
+            block = self.pointer(tree - level, block, slot)?;
+        }
+        Ok((block, span - index % span))
+    }
+
+    // entry `index` of indirect block `block`, which is `depth` levels of
+    // indirection away from the data. the last indirect block read at each
+    // depth is kept, so walking a file doesn't read it again for every pointer
+    fn pointer(&self, depth: usize, block: u32, index: usize) -> Result<u32, Ext2Error> {
+        // a pointer from disk can be anything
+        if block >= self.superblock.blocks_count.get() {
+            return Err(Ext2Error::Corrupt(format!(
+                "block {} is past the end of the file system",
+                block
+            )));
+        }
+        let mut cache = self.indirect.lock();
+        let indirect = &mut cache[depth - 1];
+        if indirect.0 != block || indirect.1.is_empty() {
+            indirect.1.resize(self.block_size, 0);
+            // forget the old block first in case the read fails halfway
+            indirect.0 = 0;
+            self.device
+                .read_at(block as u64 * self.block_size as u64, &mut indirect.1)?;
+            indirect.0 = block;
+        }
+        let bytes = &indirect.1[index * 4..index * 4 + 4];
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    // read up to `readahead` blocks of a file starting at logical block
+    // `logical`, which is at `block` on the device, for as long as the
+    // following blocks come right after it on the device
+    fn fill_window(
+        &self,
+        node: &Inode,
+        inode: InodeNo,
+        logical: u64,
+        block: u32,
+        blocks_in_file: u64,
+    ) -> Result<(), Ext2Error> {
+        let mut run = 1;
+        while run < self.readahead as u64
+            && logical + run < blocks_in_file
+            && self.block_at(node, logical + run)? as u64 == block as u64 + run
+        {
+            run += 1;
+        }
+        let mut window = self.window.lock();
+        window.owner = 0;
+        window.data.resize(run as usize * self.block_size, 0);
+        self.device
+            .read_at(block as u64 * self.block_size as u64, &mut window.data)?;
+        window.owner = inode.get();
+        window.first = logical;
+        Ok(())
+    }
+
+    /// Read an inode's data starting at byte `offset` into `buf`, returning
+    /// how many bytes were read: less than `buf.len()` only at the end of
+    /// the file. Holes read as zeros.
+    ///
+    /// A read from the start of a file, or one that carries on where the
+    /// last read of the same file stopped, reads ahead: see `set_readahead`
+    pub fn read_file(
+        &self,
+        inode: InodeNo,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, Ext2Error> {
+        let node = self.read_inode(inode)?;
+        let size = Metadata::from_inode(inode, &node).size;
+        let len = cmp::min(buf.len() as u64, size.saturating_sub(offset)) as usize;
+        let block_size = self.block_size as u64;
+        let blocks_in_file = size.div_ceil(block_size);
+        let sequential = {
+            let window = self.window.lock();
+            self.readahead > 0
+                && (offset == 0 || (window.last == inode.get() && window.next == offset))
+        };
+        let mut done = 0;
+        while done < len {
+            let position = offset + done as u64;
+            let logical = position / block_size;
+            let in_block = (position % block_size) as usize;
+            let chunk = cmp::min(len - done, self.block_size - in_block);
+            let out = &mut buf[done..done + chunk];
+            if !self.copy_from_window(inode, logical, in_block, out) {
+                match self.block_at(&node, logical)? {
+                    0 => out.fill(0),
+                    block if sequential => {
+                        self.fill_window(&node, inode, logical, block, blocks_in_file)?;
+                        // another thread reading ahead can have taken the
+                        // window over since
+                        if !self.copy_from_window(inode, logical, in_block, out) {
+                            self.device
+                                .read_at(block as u64 * block_size + in_block as u64, out)?;
+                        }
+                    }
+                    block => self
+                        .device
+                        .read_at(block as u64 * block_size + in_block as u64, out)?,
+                }
+            }
+            done += chunk;
+        }
+        let mut window = self.window.lock();
+        window.last = inode.get();
+        window.next = offset + len as u64;
+        Ok(len)
+    }
+
+    // fill `out` from what was read ahead, if the block is there
+    fn copy_from_window(
+        &self,
+        inode: InodeNo,
+        logical: u64,
+        in_block: usize,
+        out: &mut [u8],
+    ) -> bool {
+        let window = self.window.lock();
+        let blocks = (window.data.len() / self.block_size) as u64;
+        if window.owner != inode.get() || logical < window.first || logical - window.first >= blocks
+        {
+            return false;
+        }
+        let start = (logical - window.first) as usize * self.block_size + in_block;
+        out.copy_from_slice(&window.data[start..start + out.len()]);
+        true
+    }
+
+    /// The sectors an inode's extended attribute block adds to its
+    /// `sectors_count`, on top of the blocks its pointers lead to
+    pub fn xattr_sectors(&self, node: &Inode) -> u32 {
+        if node.ext_attribute_block.get() != 0 {
+            (self.block_size / 512) as u32
+        } else {
+            0
+        }
+    }
+
+    /// Whether an inode's block pointers lead to blocks. Devices, fifos and
+    /// sockets have none, and a fast symlink keeps its target in them: it is
+    /// told from a slow one by having no sectors but its extended attribute
+    /// block's
+    pub fn has_block_pointers(&self, node: &Inode) -> bool {
+        match FileType::from_mode(node.type_perm.get()) {
+            FileType::Regular | FileType::Directory => true,
+            FileType::Symlink => node.sectors_count.get() != self.xattr_sectors(node),
+            _ => false,
+        }
+    }
+
+    /// Read the target of a symbolic link into `buf`, returning its length.
+    /// Short targets are kept in the inode itself instead of a data block
+    pub fn read_link(&self, inode: InodeNo, buf: &mut [u8]) -> Result<usize, Ext2Error> {
+        let node = self.read_inode(inode)?;
+        let metadata = Metadata::from_inode(inode, &node);
+        if !metadata.is_symlink() {
+            return Err(Ext2Error::InvalidPath(format!(
+                "inode {} is not a symlink",
+                inode
+            )));
+        }
+        if self.has_block_pointers(&node) {
+            return self.read_file(inode, 0, buf);
+        }
+        let target = node.direct_pointer.as_bytes();
+        let len = cmp::min(buf.len(), cmp::min(metadata.size as usize, target.len()));
+        buf[..len].copy_from_slice(&target[..len]);
+        Ok(len)
+    }
+
+    /// The blocks of directory `dir` in order. A directory has no holes, so
+    /// a missing block is an error, like a pointer past the end of the file
+    /// system
+    pub fn dir_blocks(&self, dir: InodeNo) -> Result<Vec<u32>, Ext2Error> {
+        let node = self.read_inode(dir)?;
+        if !Metadata::from_inode(dir, &node).is_dir() {
+            return Err(Ext2Error::NotADirectory(format!("inode {}", dir)));
+        }
+        let bad_pointer = |pointer| {
+            Ext2Error::Corrupt(format!(
+                "directory inode {} has bad block pointer {}",
+                dir, pointer
+            ))
+        };
+        let mut blocks = Vec::new();
+        for pointer in self.block_ptrs(&node) {
+            let (logical, block) = pointer?;
+            if logical != blocks.len() as u64 {
+                return Err(bad_pointer(0));
+            }
+            if block >= self.superblock.blocks_count.get() {
+                return Err(bad_pointer(block));
+            }
+            blocks.push(block);
+        }
+        if (blocks.len() as u64) * (self.block_size as u64) < node.size() {
+            return Err(bad_pointer(0));
+        }
+        Ok(blocks)
+    }
+
+    /// Call `f` with the inode, raw name and type of every live entry of a
+    /// directory, until it returns false
+    pub fn for_each_entry<F>(&self, dir: InodeNo, mut f: F) -> Result<(), Ext2Error>
+    where
+        F: FnMut(InodeNo, &[u8], TypeIndicator) -> bool,
+    {
+        let mut block = vec![0; self.block_size];
+        for (index, number) in self.dir_blocks(dir)?.into_iter().enumerate() {
+            self.device
+                .read_at(number as u64 * self.block_size as u64, &mut block)?;
+            for entry in DirectoryEntry::entries(&block, dir, index * self.block_size) {
+                let (_, entry, name) = entry?;
+                // an inode of 0 marks an unused entry
+                if entry.inode.get() != 0 {
+                    let inode = InodeNo::new(entry.inode.get() as usize, &self.superblock)?;
+                    if !f(inode, name, entry.kind()) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every live entry of a directory, `.` and `..` included
+    pub fn read_dir(&self, dir: InodeNo) -> Result<Vec<DirEntry>, Ext2Error> {
+        let mut entries = Vec::new();
+        self.for_each_entry(dir, |inode, name, kind| {
+            entries.push(DirEntry {
+                inode,
+                name: escape_name(name),
+                name_bytes: name.to_vec(),
+                kind,
+            });
+            true
+        })?;
+        Ok(entries)
+    }
+
+    /// The inode a directory entry called `name` points at
+    pub fn lookup(&self, dir: InodeNo, name: &[u8]) -> Result<InodeNo, Ext2Error> {
+        let mut found = None;
+        self.for_each_entry(dir, |inode, entry, _| {
+            if entry == name {
+                found = Some(inode);
+            }
+            found.is_none()
+        })?;
+        found.ok_or_else(|| Ext2Error::NotFound(escape_name(name)))
+    }
+
+    /// Follow a `/` separated path of raw names, see `path::resolve`. A
+    /// volume has no working directory, so a relative path starts at the
+    /// root as well
+    pub fn resolve(&self, path: &[u8]) -> Result<InodeNo, Ext2Error> {
+        path::resolve(self, InodeNo::ROOT, path)
+    }
+}
+
+impl<D: BlockDeviceMut> Volume<D> {
+    /// Change the superblock with `f` and write it back
+    pub fn update_superblock<F>(&mut self, f: F) -> Result<(), Ext2Error>
+    where
+        F: FnOnce(&mut Superblock),
+    {
+        f(&mut self.superblock);
+        self.device
+            .write_at(EXT2_START_OF_SUPERBLOCK as u64, self.superblock.as_bytes())
+    }
+
+    /// Change the descriptor of block group `group` with `f` and write it
+    /// back
+    pub fn update_block_group<F>(&mut self, group: usize, f: F) -> Result<(), Ext2Error>
+    where
+        F: FnOnce(&mut BlockGroupDescriptor),
+    {
+        let descriptor = &mut self.block_groups[group];
+        f(descriptor);
+        let table = (self.superblock.first_data_block.get() as u64 + 1) * self.block_size as u64;
+        let offset = table + (group * mem::size_of::<BlockGroupDescriptor>()) as u64;
+        self.device.write_at(offset, descriptor.as_bytes())
+    }
+
+    /// Change the first 128 bytes of an inode with `f` and write them back
+    pub fn update_inode<F>(&mut self, inode: InodeNo, f: F) -> Result<(), Ext2Error>
+    where
+        F: FnOnce(&mut Inode),
+    {
+        let offset = self.inode_offset(inode)?;
+        let mut node: Inode = read_struct(&self.device, offset)?;
+        f(&mut node);
+        self.device.write_at(offset, node.as_bytes())?;
+        // a cached indirect block can be one the inode just let go of
+        *self.indirect.get_mut() = Default::default();
+        *self.window.get_mut() = Readahead::default();
+        Ok(())
+    }
+}
+
+// copy a struct that any bytes are valid for out of the device
+fn read_struct<T: FromBytes, D: BlockDevice>(device: &D, offset: u64) -> Result<T, Ext2Error> {
+    let mut bytes = vec![0; mem::size_of::<T>()];
+    device.read_at(offset, &mut bytes)?;
+    Ok(T::read_from(&bytes[..]).unwrap())
+}
@@ -0,0 +1,116 @@
+use crate::Ext2;
+use ext2::error::Ext2Error;
+use ext2::metadata::DirEntry;
+use ext2::structs::InodeNo;
+
+impl Ext2 {
+    /// Every path below directory `dir`, which is at `path`, with the inode
+    /// it leads to, sorted by path. `.` and `..` aren't followed, and
+    /// neither is a directory that is one of its own ancestors. With the
+    /// `rayon` feature and more than one job the subdirectories are walked
+    /// in parallel on a pool of `jobs` threads. Either way the result is
+    /// the same
+    pub fn walk_tree(
+        &self,
+        dir: InodeNo,
+        path: &str,
+        jobs: usize,
+    ) -> Result<Vec<(String, InodeNo)>, Ext2Error> {
+        let mut found = if jobs > 1 {
+            self.walk_parallel(dir, path, jobs)?
+        } else {
+            self.walk_below(dir, path, &mut vec![dir])?
+        };
+        found.sort_unstable_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+        Ok(found)
+    }
+
+    // the entries of `dir` worth walking into or listing, with their paths
+    fn children(&self, dir: InodeNo, path: &str) -> Result<Vec<(String, DirEntry)>, Ext2Error> {
+        Ok(self
+            .dir_entries(dir)?
+            .into_iter()
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .map(|entry| {
+                (
+                    format!("{}/{}", path.trim_end_matches('/'), entry.name),
+                    entry,
+                )
+            })
+            .collect())
+    }
+
+    // one thread, depth first. `ancestors` are the directories on the way
+    // down to `dir`, itself included
+    fn walk_below(
+        &self,
+        dir: InodeNo,
+        path: &str,
+        ancestors: &mut Vec<InodeNo>,
+    ) -> Result<Vec<(String, InodeNo)>, Ext2Error> {
+        let mut found = Vec::new();
+        for (child_path, entry) in self.children(dir, path)? {
+            found.push((child_path.clone(), entry.inode));
+            if ancestors.contains(&entry.inode) || !self.metadata(entry.inode)?.is_dir() {
+                continue;
+            }
+            ancestors.push(entry.inode);
+            let below = self.walk_below(entry.inode, &child_path, ancestors);
+            ancestors.pop();
+            found.extend(below?);
+        }
+        Ok(found)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn walk_parallel(
+        &self,
+        dir: InodeNo,
+        path: &str,
+        jobs: usize,
+    ) -> Result<Vec<(String, InodeNo)>, Ext2Error> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .map_err(|e| Ext2Error::Unsupported(format!("starting {} threads: {}", jobs, e)))?;
+        pool.install(|| self.walk_below_parallel(dir, path, &[dir]))
+    }
+
+    // without rayon there is only the one thread
+    #[cfg(not(feature = "rayon"))]
+    fn walk_parallel(
+        &self,
+        dir: InodeNo,
+        path: &str,
+        _jobs: usize,
+    ) -> Result<Vec<(String, InodeNo)>, Ext2Error> {
+        self.walk_below(dir, path, &mut vec![dir])
+    }
+
+    // `walk_below` with a task for each entry, stolen by whichever thread
+    // of the pool is idle
+    #[cfg(feature = "rayon")]
+    fn walk_below_parallel(
+        &self,
+        dir: InodeNo,
+        path: &str,
+        ancestors: &[InodeNo],
+    ) -> Result<Vec<(String, InodeNo)>, Ext2Error> {
+        use rayon::prelude::*;
+        let found: Vec<Vec<(String, InodeNo)>> = self
+            .children(dir, path)?
+            .into_par_iter()
+            .map(|(child_path, entry)| {
+                let mut found = vec![(child_path.clone(), entry.inode)];
+                if ancestors.contains(&entry.inode) || !self.metadata(entry.inode)?.is_dir() {
+                    return Ok(found);
+                }
+                let mut ancestors = ancestors.to_vec();
+                ancestors.push(entry.inode);
+                found.extend(self.walk_below_parallel(entry.inode, &child_path, &ancestors)?);
+                Ok(found)
+            })
+            .collect::<Result<_, Ext2Error>>()?;
+        Ok(found.into_iter().flatten().collect())
+    }
+}
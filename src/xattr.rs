@@ -0,0 +1,132 @@
+//! Extended attributes: name and value pairs kept next to an inode, e.g.
+//! `user.comment` or the POSIX ACLs. They live in a block of their own,
+//! the one `Inode::ext_attribute_block` (i_file_acl) points at, and on
+//! file systems with inodes bigger than 128 bytes also in the space after
+//! the inode's extra fields. Both places hold the same kind of entries, see
+//! https://www.kernel.org/doc/html/latest/filesystems/ext4/attributes.html
+
+use crate::error::Ext2Error;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// Magic number at the start of an attribute block, and of the attributes
+/// in an inode
+pub const XATTR_MAGIC: u32 = 0xEA02_0000;
+// an attribute block's header, the entries come after it
+const BLOCK_HEADER_LEN: usize = 32;
+// an entry without its name
+const ENTRY_LEN: usize = 16;
+
+/// One extended attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xattr {
+    /// The whole name, with the prefix its namespace index stands for, e.g.
+    /// `user.comment`
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+// what each namespace index puts in front of the name kept on disk
+fn prefix(index: u8) -> &'static [u8] {
+    match index {
+        1 => b"user.",
+        2 => b"system.posix_acl_access",
+        3 => b"system.posix_acl_default",
+        4 => b"trusted.",
+        6 => b"security.",
+        7 => b"system.",
+        8 => b"system.richacl",
+        _ => b"",
+    }
+}
+
+fn le16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// The attributes in an attribute block
+pub fn parse_block(block: &[u8]) -> Result<Vec<Xattr>, Ext2Error> {
+    if block.len() < BLOCK_HEADER_LEN || le32(block, 0) != XATTR_MAGIC {
+        return Err(Ext2Error::Corrupt(
+            "extended attribute block has a bad magic number".to_string(),
+        ));
+    }
+    // h_blocks, Linux only ever makes blocks of one
+    if le32(block, 8) != 1 {
+        return Err(Ext2Error::Unsupported(format!(
+            "an extended attribute block spanning {} blocks",
+            le32(block, 8)
+        )));
+    }
+    // value offsets count from the start of the block
+    parse_entries(block, BLOCK_HEADER_LEN, 0)
+}
+
+/// Let go of one of the inodes sharing an attribute block, returning
+/// whether it was the last one and the block can be freed. A block without
+/// the magic number isn't one, and is left alone
+pub fn drop_reference(block: &mut [u8]) -> bool {
+    if block.len() < BLOCK_HEADER_LEN || le32(block, 0) != XATTR_MAGIC {
+        return false;
+    }
+    // h_refcount
+    let refcount = le32(block, 4);
+    if refcount > 1 {
+        block[4..8].copy_from_slice(&(refcount - 1).to_le_bytes());
+        return false;
+    }
+    true
+}
+
+/// The attributes kept in an inode, from `extra`: what's left of the inode
+/// after its first 128 bytes and the `i_extra_isize` after them. None there
+/// is fine, most inodes have none
+pub fn parse_in_inode(extra: &[u8]) -> Result<Vec<Xattr>, Ext2Error> {
+    if extra.len() < 4 || le32(extra, 0) != XATTR_MAGIC {
+        return Ok(Vec::new());
+    }
+    // value offsets count from the first entry, after the magic number
+    parse_entries(extra, 4, 4)
+}
+
+// the entries from `start` on, up to the 4 zero bytes that end them, with
+// their values at `base` plus their offsets
+fn parse_entries(region: &[u8], start: usize, base: usize) -> Result<Vec<Xattr>, Ext2Error> {
+    let corrupt = |what: &str| Err(Ext2Error::Corrupt(format!("extended attribute {}", what)));
+    let mut found = Vec::new();
+    let mut at = start;
+    while at + 4 <= region.len() && le32(region, at) != 0 {
+        if at + ENTRY_LEN > region.len() {
+            return corrupt("entry runs off the end");
+        }
+        let name_len = region[at] as usize;
+        let value_offset = le16(region, at + 2) as usize;
+        let value_inode = le32(region, at + 4);
+        let value_len = le32(region, at + 8) as usize;
+        let Some(short_name) = region.get(at + ENTRY_LEN..at + ENTRY_LEN + name_len) else {
+            return corrupt("name runs off the end");
+        };
+        if value_inode != 0 {
+            return Err(Ext2Error::Unsupported(
+                "extended attribute values kept in an inode of their own".to_string(),
+            ));
+        }
+        let Some(value) = region.get(base + value_offset..base + value_offset + value_len) else {
+            return corrupt("value runs off the end");
+        };
+        let mut name = prefix(region[at + 1]).to_vec();
+        name.extend_from_slice(short_name);
+        found.push(Xattr {
+            name,
+            value: value.to_vec(),
+        });
+        // entries are padded to 4 bytes
+        at += (ENTRY_LEN + name_len).next_multiple_of(4);
+    }
+    Ok(found)
+}
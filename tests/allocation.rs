@@ -0,0 +1,73 @@
+mod common;
+
+use common::{fixture, shell};
+
+// xorshift, so a failing run can be repeated exactly
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
+
+// a random mix of creating and removing files and directories, with an
+// fsck every `check_every` commands and one at the end. plenty of them fail
+// (removing what isn't there, creating in a missing directory), which is
+// part of the point: failures mustn't leak anything either
+fn script(seed: u64, operations: usize, check_every: usize) -> (String, usize) {
+    let mut rng = Rng(seed);
+    let mut script = String::new();
+    let mut checks = 0;
+    for i in 0..operations {
+        let dir = rng.below(6);
+        let name = rng.below(40);
+        let command = match rng.below(20) {
+            0..=2 => format!("mkdir /d{}", dir),
+            3..=9 => format!("touch /d{}/f{}", dir, name),
+            10..=11 => format!("mkdir /d{}/s{}", dir, name),
+            12..=16 => format!("rm /d{}/f{}", dir, name),
+            17 => format!("rm /d{}/s{} /d{}", dir, name, dir),
+            18 => format!("rm --trash /d{}/f{}", dir, name),
+            _ => "empty-trash".to_string(),
+        };
+        script.push_str(&command);
+        script.push('\n');
+        if i % check_every == check_every - 1 {
+            script.push_str("fsck\n");
+            checks += 1;
+        }
+    }
+    script.push_str("fsck\n");
+    (script, checks + 1)
+}
+
+#[test]
+fn free_counts_match_bitmaps() {
+    for (block_size, seed) in [(1024, 0x5eed), (4096, 0xdecade)] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let (script, checks) = script(seed, 3000, 100);
+        let stdout = shell(&fixture.image_path(), &script);
+
+        // fsck compares every group's free counts, and the superblock's,
+        // with the bitmaps and says so if they differ
+        let lines: Vec<&str> = stdout.lines().collect();
+        let problems: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| line.contains(" records ") && line.contains(" free "))
+            .collect();
+        assert!(problems.is_empty(), "{}", problems.join("\n"));
+        let clean = lines
+            .iter()
+            .filter(|line| **line == "no problems found")
+            .count();
+        assert_eq!(clean, checks, "{}", lines.join("\n"));
+    }
+}
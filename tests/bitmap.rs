@@ -0,0 +1,76 @@
+mod common;
+
+use common::{fixture, shell};
+use ext2::volume::Volume;
+
+// the used cells drawn for one bitmap, from its heading to its summary
+fn used_cells(stdout: &str, heading: &str) -> usize {
+    let start = stdout.find(heading).unwrap_or_else(|| panic!("{}", stdout));
+    stdout[start..]
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.starts_with("group "))
+        .map(|line| line.matches('#').count())
+        .sum()
+}
+
+// every group's used cells are what its descriptor's free count leaves,
+// for blocks and for inodes
+#[test]
+fn used_cells_match_the_free_counts() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let superblock = volume.superblock();
+    let groups = volume.block_groups();
+    assert!(groups.len() > 1);
+    let script: String = (0..groups.len())
+        .map(|g| format!("bitmap {0}\nbitmap -i {0}\n", g))
+        .collect();
+    let stdout = shell(&fixture.image_path(), &script);
+    assert!(!stdout.contains("descriptor says"), "{}", stdout);
+
+    let per_group = superblock.blocks_per_group.get() as usize;
+    let first_data_block = superblock.first_data_block.get() as usize;
+    let blocks_count = superblock.blocks_count.get() as usize;
+    let inodes = superblock.inodes_per_group.get() as usize;
+    for (g, descriptor) in groups.iter().enumerate() {
+        let first = first_data_block + g * per_group;
+        let blocks = (blocks_count - first).min(per_group);
+        let heading = format!(
+            "block bitmap of group {}, blocks {}-{}:",
+            g,
+            first,
+            first + blocks - 1
+        );
+        let used = used_cells(&stdout, &heading);
+        assert_eq!(used, blocks - descriptor.free_blocks_count.get() as usize);
+        assert!(stdout.contains(&format!("group {}: {} of {} blocks used", g, used, blocks)));
+
+        let heading = format!(
+            "inode bitmap of group {}, inodes {}-{}:",
+            g,
+            g * inodes + 1,
+            (g + 1) * inodes
+        );
+        let used = used_cells(&stdout, &heading);
+        assert_eq!(used, inodes - descriptor.free_inodes_count.get() as usize);
+    }
+}
+
+#[test]
+fn group_past_the_last_is_refused() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "bitmap 99\nbitmap -x\n");
+    assert!(
+        stdout.contains("bitmap: there is no group 99"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("usage: bitmap [-i] [group]"), "{}", stdout);
+}
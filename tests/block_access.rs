@@ -0,0 +1,69 @@
+mod common;
+
+use common::{fixture, shell, LEAF_CONTENTS, LINK_TARGET};
+use ext2::volume::Volume;
+use std::io::Write;
+
+// blocks are found by their number times the block size, the same with 1K
+// blocks, where block 0 holds the boot sector and the superblock is block
+// 1, as with 4K blocks, where both are in block 0
+#[test]
+fn same_behaviour_for_both_block_sizes() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let stdout = shell(
+            &fixture.image_path(),
+            &format!(
+                "ls /dir\ncat /{}\ncat /dir/sibling.txt\nfsck\n",
+                LINK_TARGET
+            ),
+        );
+        let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+        assert!(lines.contains(&"nested\tsibling.txt"), "{}", stdout);
+        assert!(
+            stdout.contains(std::str::from_utf8(LEAF_CONTENTS).unwrap()),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("next to nested\n"), "{}", stdout);
+        assert!(stdout.contains("no problems found"), "{}", stdout);
+        assert!(!stdout.contains("unable"), "{}", stdout);
+    }
+}
+
+// a directory block pointer past blocks_count is an error, not a panic,
+// even when the image file goes on after the file system
+#[test]
+fn pointer_past_the_last_block() {
+    for block_size in [1024, 4096] {
+        let Some(mut fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let blocks_count = Volume::open(&fixture.image[..])
+            .unwrap()
+            .superblock()
+            .blocks_count
+            .get();
+        // room for the bad block on the device, so only blocks_count stops it
+        let mut image = std::fs::OpenOptions::new()
+            .append(true)
+            .open(fixture.image_path())
+            .unwrap();
+        image.write_all(&vec![0; 4 * block_size]).unwrap();
+        fixture
+            .debugfs(&format!("sif /dir/nested block[0] {}", blocks_count + 1))
+            .unwrap();
+        let stdout = shell(&fixture.image_path(), "ls /dir/nested\nls /dir\n");
+        assert!(
+            stdout.contains(&format!("has bad block pointer {}", blocks_count + 1)),
+            "{}",
+            stdout
+        );
+        let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+        assert!(lines.contains(&"nested\tsibling.txt"), "{}", stdout);
+    }
+}
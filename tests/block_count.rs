@@ -0,0 +1,63 @@
+mod common;
+
+use common::{fixture_from, pattern, shell};
+use ext2::volume::Volume;
+use std::fs;
+
+// a file of three blocks holds three blocks' worth of 512 byte sectors,
+// which stat shows and fsck agrees with
+#[test]
+fn three_block_file() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture_from(block_size, "4M", &[], |tree| {
+            fs::write(tree.join("three"), pattern(3 * block_size)).unwrap();
+        }) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let volume = Volume::open(&fixture.image[..]).unwrap();
+        let three = volume.resolve(b"/three").unwrap();
+        let sectors = (3 * block_size / 512) as u32;
+        assert_eq!(volume.metadata(three).unwrap().blocks, sectors);
+
+        let stdout = shell(&fixture.image_path(), "stat three\nfsck\n");
+        assert!(
+            stdout.contains(&format!("Blocks: {}\t", sectors)),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("no problems found"), "{}", stdout);
+    }
+}
+
+// an extended attribute block counts towards the sectors as well, and a
+// count that is off is reported with what it should be
+#[test]
+fn attribute_block_and_a_wrong_count() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &["-I", "128"], |tree| {
+        fs::write(tree.join("file"), b"data").unwrap();
+        fs::write(tree.join("three"), pattern(3 * 1024)).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("ea_set /file user.comment hello").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    let stdout = shell(&fixture.image_path(), "stat file\nfsck\n");
+    assert!(stdout.contains("Blocks: 4\t"), "{}", stdout);
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    fixture.debugfs("sif /three blocks 2").unwrap();
+    let three = Volume::open(&fixture.image[..])
+        .unwrap()
+        .resolve(b"/three")
+        .unwrap();
+    let stdout = shell(&fixture.image_path(), "fsck\n");
+    assert!(
+        stdout.contains(&format!("inode {} records 2 sectors, it uses 6", three)),
+        "{}",
+        stdout
+    );
+}
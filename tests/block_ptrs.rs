@@ -0,0 +1,116 @@
+mod common;
+
+use common::{fixture_from, shell_bytes};
+use ext2::volume::Volume;
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::Command;
+
+const START: &[u8] = b"<<start>>";
+const END: &[u8] = b"<<end>>";
+
+// with 1024 byte blocks: the first direct block, the first under the
+// indirect pointer, the first under the doubly indirect one, and two under
+// the triply indirect one, in different indirect blocks. the last one is
+// cut short by the size
+const LOGICAL: [usize; 5] = [0, 12, 12 + 256, 12 + 256 + 65536, 12 + 256 + 65536 + 257];
+const LAST_LEN: usize = 500;
+
+fn find(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .unwrap()
+}
+
+// a different byte in each block, and holes everywhere else
+fn sparse_contents() -> Vec<u8> {
+    let mut contents = vec![0; LOGICAL[4] * 1024 + LAST_LEN];
+    for (i, logical) in LOGICAL.into_iter().enumerate() {
+        let block = &mut contents[logical * 1024..];
+        let len = block.len().min(1024);
+        block[..len].fill(i as u8 + 1);
+    }
+    contents
+}
+
+fn write_sparse(path: &Path) {
+    let mut file = fs::File::create(path).unwrap();
+    for (i, logical) in LOGICAL.into_iter().enumerate() {
+        let len = if i == LOGICAL.len() - 1 {
+            LAST_LEN
+        } else {
+            1024
+        };
+        file.seek(SeekFrom::Start((logical * 1024) as u64)).unwrap();
+        file.write_all(&vec![i as u8 + 1; len]).unwrap();
+    }
+}
+
+// what the shell printed between catting the two markers
+fn between_markers(stdout: &[u8]) -> &[u8] {
+    let after_start = find(stdout, START) + START.len();
+    &stdout[after_start..after_start + find(&stdout[after_start..], END)]
+}
+
+#[test]
+fn sparse_file_through_all_three_indirect_trees() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::write(tree.join("start"), START).unwrap();
+        fs::write(tree.join("end"), END).unwrap();
+        write_sparse(&tree.join("sparse"));
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let image = fixture.image_path();
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let inode = volume.resolve(b"/sparse").unwrap();
+    let node = volume.read_inode(inode).unwrap();
+    if node.triply_indirect.get() == 0 {
+        eprintln!("mke2fs didn't keep the holes, skipping");
+        return;
+    }
+
+    // the walk skips the holes, and agrees with looking each block up
+    let blocks: Vec<(u64, u32)> = volume.block_ptrs(&node).collect::<Result<_, _>>().unwrap();
+    let logical: Vec<u64> = blocks.iter().map(|&(logical, _)| logical).collect();
+    assert_eq!(logical, LOGICAL.map(|logical| logical as u64));
+    for (logical, block) in blocks {
+        assert_eq!(volume.block_at(&node, logical).unwrap(), block);
+        assert_eq!(volume.block_at(&node, logical + 1).unwrap(), 0);
+    }
+
+    let stdout = shell_bytes(&image, "cat start\ncat sparse\ncat end\n");
+    assert!(between_markers(&stdout) == sparse_contents());
+
+    let stdout = String::from_utf8(shell_bytes(&image, "frag sparse\n")).unwrap();
+    assert!(stdout.contains("sparse: 5 blocks in "), "{}", stdout);
+
+    // defragmenting walks the same blocks, and has to move all of them
+    let clone = fixture.tree_path().with_file_name("defragged.ext2");
+    let stdout = shell_bytes(
+        &image,
+        &format!("defrag sparse\nfrag sparse\nclone {}\n", clone.display()),
+    );
+    let stdout = String::from_utf8(stdout).unwrap();
+    assert!(
+        stdout.contains("sparse: 5 blocks in 1 fragments"),
+        "{}",
+        stdout
+    );
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let inode = volume.resolve(b"/sparse").unwrap();
+    let mut data = vec![0; sparse_contents().len()];
+    assert_eq!(volume.read_file(inode, 0, &mut data).unwrap(), data.len());
+    assert!(data == sparse_contents());
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&clone).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use common::{fixture_from, pattern, shell_bytes};
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const START: &[u8] = b"<<start>>";
+const END: &[u8] = b"<<end>>";
+
+fn find(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .unwrap()
+}
+
+// the exact bytes `cat name` writes, found between the markers catted
+// around it
+fn cat(image: &Path, name: &str) -> Vec<u8> {
+    let stdout = shell_bytes(image, &format!("cat start\ncat {}\ncat end\n", name));
+    let after_start = find(&stdout, START) + START.len();
+    stdout[after_start..after_start + find(&stdout[after_start..], END)].to_vec()
+}
+
+// the file as Linux reads it from the image, if it can be mounted here,
+// otherwise what mke2fs copied into it
+fn host_cat(image: &Path, tree: &Path, name: &str) -> Vec<u8> {
+    let mount_point = image.with_extension("mnt");
+    fs::create_dir_all(&mount_point).unwrap();
+    let mounted = Command::new("mount")
+        .args(["-o", "loop,ro"])
+        .arg(image)
+        .arg(&mount_point)
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !mounted {
+        return fs::read(tree.join(name)).unwrap();
+    }
+    let contents = fs::read(mount_point.join(name));
+    Command::new("umount").arg(&mount_point).status().unwrap();
+    contents.unwrap()
+}
+
+#[test]
+fn cat_writes_exactly_the_files_bytes() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("start"), START).unwrap();
+        fs::write(tree.join("end"), END).unwrap();
+        fs::write(tree.join("nul"), b"a\0b").unwrap();
+        // not text, no newline at the end, and not a whole number of blocks
+        fs::write(tree.join("odd"), pattern(2 * 1024 + 100)).unwrap();
+        fs::write(tree.join("empty"), b"").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    for name in ["nul", "odd", "empty"] {
+        assert_eq!(
+            cat(&fixture.image_path(), name),
+            host_cat(&fixture.image_path(), &fixture.tree_path(), name),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(cat(&fixture.image_path(), "nul"), b"a\0b");
+}
+
+// reading stops at the file's size: in the middle of the last block, at the
+// end of one, and before pointers a bigger earlier version of the file left
+#[test]
+fn cat_stops_at_the_files_size() {
+    for block_size in [1024, 4096] {
+        let Some(mut fixture) = fixture_from(block_size, "1M", &[], |tree| {
+            fs::write(tree.join("start"), START).unwrap();
+            fs::write(tree.join("end"), END).unwrap();
+            fs::write(tree.join("one"), b"x").unwrap();
+            fs::write(tree.join("block"), pattern(block_size)).unwrap();
+            fs::write(tree.join("block_and_one"), pattern(block_size + 1)).unwrap();
+            fs::write(tree.join("shrunk"), pattern(3 * block_size)).unwrap();
+        }) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        if fixture.debugfs("sif /shrunk size 1").is_none() {
+            eprintln!("debugfs not available, skipping");
+            return;
+        }
+        let image = fixture.image_path();
+        for (name, len) in [
+            ("one", 1),
+            ("block", block_size),
+            ("block_and_one", block_size + 1),
+            ("shrunk", 1),
+        ] {
+            let contents = cat(&image, name);
+            assert_eq!(contents.len(), len, "{}", name);
+            // the tree still has all of shrunk
+            if name == "shrunk" {
+                assert_eq!(contents, pattern(1));
+            } else {
+                assert_eq!(
+                    contents,
+                    host_cat(&image, &fixture.tree_path(), name),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+}
+
+// a symlink's mode has the regular file's bit set as well, but it isn't a
+// file and isn't catted, and neither is a directory
+#[test]
+fn cat_of_a_symlink_is_not_a_file() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("start"), START).unwrap();
+        fs::write(tree.join("end"), END).unwrap();
+        fs::write(tree.join("target"), b"contents\n").unwrap();
+        std::os::unix::fs::symlink("target", tree.join("link")).unwrap();
+        fs::create_dir(tree.join("dir")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    for name in ["link", "dir"] {
+        let stdout = String::from_utf8(cat(&fixture.image_path(), name)).unwrap();
+        assert!(
+            stdout.starts_with(&format!("not a file: {}\n", name)),
+            "{}",
+            stdout
+        );
+    }
+}
+
+// several files are written one after the other with nothing between them,
+// and one that can't be catted is reported in its place without stopping
+// the ones after it. the command as a whole still fails
+#[test]
+fn cat_of_three_files_concatenates_them() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("start"), START).unwrap();
+        fs::write(tree.join("end"), END).unwrap();
+        fs::write(tree.join("a"), b"first\n").unwrap();
+        // no newline at the end and more than a block
+        fs::write(tree.join("b"), pattern(1024 + 10)).unwrap();
+        fs::write(tree.join("c"), b"third").unwrap();
+        fs::create_dir(tree.join("dir")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let image = fixture.image_path();
+    let mut expected = b"first\n".to_vec();
+    expected.extend(pattern(1024 + 10));
+    expected.extend(b"third");
+    assert_eq!(cat(&image, "a b c"), expected);
+
+    let mut expected = b"first\ncat: missing: No such file or directory\n".to_vec();
+    expected.extend(b"not a file: dir\nthirdunable to cat file\n");
+    assert_eq!(
+        String::from_utf8(cat(&image, "a missing dir c")).unwrap(),
+        String::from_utf8(expected).unwrap()
+    );
+}
+
+// cat writes the bytes as they are, read_file_string is for when they are
+// wanted as text: a byte that isn't UTF-8 becomes U+FFFD and the rest stays
+#[test]
+fn read_file_string_replaces_invalid_utf8() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("latin1"), b"caf\xe9 au lait\n").unwrap();
+        fs::write(tree.join("truncated"), &"\u{e9}t\u{e9}".as_bytes()[..4]).unwrap();
+        fs::write(tree.join("utf8"), "d\u{e9}j\u{e0} vu\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let read = |name: &str| {
+        let inode = volume.resolve(format!("/{}", name).as_bytes()).unwrap();
+        volume.read_file_string(inode).unwrap()
+    };
+    assert_eq!(read("latin1"), "caf\u{fffd} au lait\n");
+    // the last character cut off halfway
+    assert_eq!(read("truncated"), "\u{e9}t\u{fffd}");
+    assert_eq!(read("utf8"), "d\u{e9}j\u{e0} vu\n");
+    let inode = volume.resolve(b"/latin1").unwrap();
+    assert_eq!(volume.read_file_bytes(inode).unwrap(), b"caf\xe9 au lait\n");
+}
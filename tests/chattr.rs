@@ -0,0 +1,112 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::structs::InodeFlags;
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn tree(tree: &Path) {
+    fs::write(tree.join("file"), b"contents\n").unwrap();
+    fs::create_dir(tree.join("dir")).unwrap();
+    fs::write(tree.join("dir/inside"), b"").unwrap();
+}
+
+#[test]
+fn immutable_file_cant_be_removed_until_cleared() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let flagged = fixture.tree_path().with_file_name("flagged.ext2");
+    let cleared = fixture.tree_path().with_file_name("cleared.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "chattr +i file\nrm file\nmv file moved\nclone {}\nchattr -i file\nrm file\nclone {}\n",
+            flagged.display(),
+            cleared.display()
+        ),
+    );
+    assert!(
+        stdout.contains("rm: file: Operation not permitted"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("mv: file: Operation not permitted"),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        stdout.matches("Operation not permitted").count(),
+        2,
+        "{}",
+        stdout
+    );
+
+    let image = fs::read(&flagged).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let file = volume.resolve(b"/file").unwrap();
+    assert_eq!(volume.metadata(file).unwrap().flags, InodeFlags::IMMUTABLE);
+    if let Ok(output) = Command::new("debugfs")
+        .arg("-R")
+        .arg("stat file")
+        .arg(&flagged)
+        .output()
+    {
+        let stat = String::from_utf8_lossy(&output.stdout);
+        assert!(stat.contains("Flags: 0x10"), "{}", stat);
+    }
+
+    let image = fs::read(&cleared).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert!(volume.resolve(b"/file").is_err());
+}
+
+// an immutable directory's entries can't change, an append-only one can
+// only gain them
+#[test]
+fn directory_flags_guard_its_entries() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "chattr +i dir\ntouch dir/new\nmkdir dir/sub\nrm dir/inside\nmv file dir/file\n\
+         chattr -i dir\nchattr +a dir\ntouch dir/added\nrm dir/added\nrm dir\nls dir\n",
+    );
+    for expected in [
+        "touch: new: Operation not permitted",
+        "mkdir: sub: Operation not permitted",
+        "rm: inside: Operation not permitted",
+        "mv: file: Operation not permitted",
+        "rm: added: Operation not permitted",
+        "rm: dir: Operation not permitted",
+    ] {
+        assert!(stdout.contains(expected), "{}\n{}", expected, stdout);
+    }
+    assert!(stdout.contains("added\t"), "{}", stdout);
+    assert!(!stdout.contains("new\t"), "{}", stdout);
+}
+
+#[test]
+fn chattr_usage() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "chattr +i\nchattr i file\nchattr +x file\n",
+    );
+    assert_eq!(
+        stdout.matches("usage: chattr +i|-i|+a|-a path ...").count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("chattr: unknown flag x"), "{}", stdout);
+}
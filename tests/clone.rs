@@ -0,0 +1,36 @@
+mod common;
+
+use common::{fixture, shell};
+use std::fs;
+
+#[test]
+fn clone_keeps_changes() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let clone = fixture.tree_path().with_file_name("clone.ext2");
+        let stdout = shell(
+            &fixture.image_path(),
+            &format!(
+                "mkdir /made\ntouch /made/before\nclone {0}\ntouch /after\nclone {0}\n",
+                clone.display()
+            ),
+        );
+        // the second clone mustn't overwrite the first
+        assert_eq!(
+            stdout.matches("unable to clone image").count(),
+            1,
+            "{}",
+            stdout
+        );
+
+        let stdout = shell(&clone, "ls /made\nls /\nfsck\n");
+        assert!(stdout.contains("before"), "{}", stdout);
+        assert!(!stdout.contains("after"), "{}", stdout);
+        assert!(stdout.contains("no problems found"), "{}", stdout);
+        // the image the shell was started on hasn't changed
+        assert_eq!(fs::read(fixture.image_path()).unwrap(), fixture.image);
+    }
+}
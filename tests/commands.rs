@@ -0,0 +1,98 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+fn fixture() -> Option<common::Fixture> {
+    fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("rom")).unwrap();
+        fs::write(tree.join("file"), b"contents\n").unwrap();
+        fs::write(tree.join("alog"), b"the wrong file\n").unwrap();
+    })
+}
+
+// the first word has to be a command, not just start with one: `cdrom`
+// isn't `cd rom`, `lsof` isn't `ls of` and `catalog file` isn't `cat alog`
+#[test]
+fn commands_are_matched_exactly() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "cdrom\nlsof\ncatalog file\nls\n");
+    for word in ["cdrom", "lsof", "catalog"] {
+        assert!(
+            stdout.contains(&format!("{}: command not found", word)),
+            "{}",
+            stdout
+        );
+    }
+    assert!(!stdout.contains("the wrong file"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+    // still in the root
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert!(lines.contains(&"alog\tfile\tlost+found\trom"), "{}", stdout);
+}
+
+// words can be separated by any run of whitespace
+#[test]
+fn extra_whitespace_between_words_is_ignored() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "  cat   file \n\n");
+    assert!(stdout.contains("contents\n"), "{}", stdout);
+    assert!(!stdout.contains("not found"), "{}", stdout);
+}
+
+// `rm -rf` used to remove a file called `-rf`, options it doesn't know
+// are refused now
+#[test]
+fn unknown_rm_options_are_refused() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "rm -rf rom\nls\n");
+    assert!(stdout.contains("rm: unknown option -rf"), "{}", stdout);
+    assert!(!stdout.contains("rm: freed"), "{}", stdout);
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert!(lines.contains(&"alog\tfile\tlost+found\trom"), "{}", stdout);
+}
+
+// a cwd that can't be read fails the commands that read it, and the
+// shell carries on rather than quitting before the next prompt
+#[test]
+fn unreadable_cwd_only_fails_what_reads_it() {
+    let Some(mut fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("sif /rom block[0] 99999").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd rom\nls\ntouch new\npwd\ncat /file\ncd /\nls\n",
+    );
+    assert!(!stdout.contains("unable to read cwd"), "{}", stdout);
+    assert_eq!(
+        stdout.matches("bad block pointer 99999").count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("unable to read directory in ls"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("unable to touch file"), "{}", stdout);
+    assert!(stdout.contains("\n/rom\n"), "{}", stdout);
+    assert!(stdout.contains("contents\n"), "{}", stdout);
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert!(lines.contains(&"alog\tfile\tlost+found\trom"), "{}", stdout);
+    assert!(stdout.ends_with("bye!\n"), "{}", stdout);
+}
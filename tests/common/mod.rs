@@ -0,0 +1,200 @@
+// fixtures for the integration tests: small images built by mke2fs from a
+// directory tree written here, so the expected contents are known exactly.
+// machines without e2fsprogs skip the tests instead of failing them
+
+use std::fs;
+#[cfg(feature = "std")]
+use std::io::Write;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+#[cfg(feature = "std")]
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::thread;
+
+/// Where the symlink in every fixture points
+pub const LINK_TARGET: &str = "dir/nested/deeper/leaf.txt";
+pub const LEAF_CONTENTS: &[u8] = b"a leaf three levels down\n";
+
+/// An image and the tree it was made from, removed again on drop
+pub struct Fixture {
+    #[allow(dead_code)]
+    pub block_size: usize,
+    pub image: Vec<u8>,
+    dir: PathBuf,
+}
+
+impl Fixture {
+    /// Where the image is on the host
+    pub fn image_path(&self) -> PathBuf {
+        self.dir.join("image.ext2")
+    }
+
+    /// The directory tree the image was made from
+    #[allow(dead_code)]
+    pub fn tree_path(&self) -> PathBuf {
+        self.dir.join("tree")
+    }
+
+    /// Run a debugfs request that writes to the image, and read it back in
+    pub fn debugfs(&mut self, request: &str) -> Option<()> {
+        let done = run(Command::new("debugfs")
+            .args(["-w", "-R", request])
+            .arg(self.image_path()))?;
+        assert!(done, "debugfs {} failed", request);
+        self.image = fs::read(self.image_path()).unwrap();
+        Some(())
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Bytes that differ from block to block, so a block read from the wrong
+/// place shows up
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i % 251) as u8 ^ (i / 4093) as u8)
+        .collect()
+}
+
+/// Length of a file that needs the doubly indirect block: past the 12
+/// direct blocks and the whole singly indirect block
+pub fn doubly_indirect_len(block_size: usize) -> usize {
+    (12 + block_size / 4 + 40) * block_size + 123
+}
+
+/// Build a fixture with `block_size` byte blocks, or None if mke2fs or
+/// debugfs can't be run here
+#[allow(dead_code)]
+pub fn fixture(block_size: usize) -> Option<Fixture> {
+    let mut fixture = fixture_from(block_size, "16M", &[], |tree| {
+        fs::create_dir_all(tree.join("dir/nested/deeper")).unwrap();
+        fs::write(tree.join(LINK_TARGET), LEAF_CONTENTS).unwrap();
+        fs::write(tree.join("dir/sibling.txt"), b"next to nested\n").unwrap();
+        fs::write(tree.join("one_byte"), b"x").unwrap();
+        fs::set_permissions(tree.join("one_byte"), fs::Permissions::from_mode(0o640)).unwrap();
+        fs::write(tree.join("twelve_blocks"), pattern(12 * block_size)).unwrap();
+        fs::write(
+            tree.join("doubly"),
+            pattern(doubly_indirect_len(block_size)),
+        )
+        .unwrap();
+        symlink(LINK_TARGET, tree.join("link")).unwrap();
+        fs::write(tree.join("hard_a"), b"one inode, two names\n").unwrap();
+        fs::hard_link(tree.join("hard_a"), tree.join("hard_b")).unwrap();
+        fs::write(tree.join("deleted.txt"), b"gone soon\n").unwrap();
+    })?;
+    fixture.debugfs("rm /deleted.txt")?;
+    Some(fixture)
+}
+
+/// Build an `image_size` image (as mke2fs takes it, "16M") from the tree
+/// `build` writes into the directory it's given, or None without mke2fs.
+/// `options` are passed on to mke2fs
+pub fn fixture_from(
+    block_size: usize,
+    image_size: &str,
+    options: &[&str],
+    build: impl FnOnce(&Path),
+) -> Option<Fixture> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "ext2-test-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let tree = dir.join("tree");
+    fs::create_dir_all(&tree).unwrap();
+    // the fixture is removed on drop from here on, whatever happens next
+    let mut fixture = Fixture {
+        block_size,
+        image: Vec::new(),
+        dir,
+    };
+    build(&tree);
+
+    let made = run(Command::new("mke2fs")
+        .args(["-q", "-F", "-t", "ext2", "-b", &block_size.to_string()])
+        .args(options)
+        .arg("-d")
+        .arg(&tree)
+        .arg(fixture.image_path())
+        .arg(image_size))?;
+    assert!(made, "mke2fs failed");
+    fixture.image = fs::read(fixture.image_path()).unwrap();
+    Some(fixture)
+}
+
+// run a command quietly, None if it isn't installed
+fn run(command: &mut Command) -> Option<bool> {
+    match command.output() {
+        Ok(output) => Some(output.status.success()),
+        Err(_) => None,
+    }
+}
+
+// run the shell on `image` with command line `flags` before it, typing in
+// `script`: whether it exited cleanly, and what it printed. the script is
+// fed in while the output is read, so a long one can't fill the pipes up,
+// and a shell that quits before reading it all is fine
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+fn shell_output(flags: &[&str], image: &Path, script: &str) -> (bool, Vec<u8>) {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_ext2"))
+        .args(flags)
+        .arg(image)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = shell.stdin.take().unwrap();
+    let script = script.to_string();
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(script.as_bytes());
+    });
+    let output = shell.wait_with_output().unwrap();
+    writer.join().unwrap();
+    (output.status.success(), output.stdout)
+}
+
+/// Whether the shell exited cleanly after `script` on `image` with command
+/// line `flags`, and what it printed. A shell that can't mount the image
+/// quits before reading any of the script
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+pub fn shell_status(flags: &[&str], image: &Path, script: &str) -> (bool, String) {
+    let (ok, stdout) = shell_output(flags, image, script);
+    (ok, String::from_utf8_lossy(&stdout).into_owned())
+}
+
+/// What the shell printed for `script` on `image` with command line
+/// `flags`. It has to exit cleanly
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+pub fn shell_with(flags: &[&str], image: &Path, script: &str) -> String {
+    let (ok, stdout) = shell_status(flags, image, script);
+    assert!(ok, "{}", stdout);
+    stdout
+}
+
+/// `shell_with` without any flags
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+pub fn shell(image: &Path, script: &str) -> String {
+    shell_with(&[], image, script)
+}
+
+/// The exact bytes `shell` prints, for output that needn't be text
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+pub fn shell_bytes(image: &Path, script: &str) -> Vec<u8> {
+    let (ok, stdout) = shell_output(&[], image, script);
+    assert!(ok, "{}", String::from_utf8_lossy(&stdout));
+    stdout
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::csum::{self, Checksummed};
+use std::fs;
+
+// a small tree on an image mke2fs gave checksums, with 128 and 256 byte
+// inodes since only the bigger ones keep all 32 bits of theirs
+fn checksummed(inode_size: &str) -> Option<common::Fixture> {
+    fixture_from(
+        1024,
+        "4M",
+        &["-O", "metadata_csum", "-I", inode_size],
+        |tree| {
+            fs::create_dir_all(tree.join("dir/nested")).unwrap();
+            fs::write(tree.join("dir/file"), b"checksummed\n").unwrap();
+            for i in 0..30 {
+                fs::write(tree.join(format!("dir/nested/{}", i)), b"x").unwrap();
+            }
+        },
+    )
+}
+
+#[test]
+fn fresh_image_validates() {
+    for inode_size in ["128", "256"] {
+        let Some(fixture) = checksummed(inode_size) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let report = csum::verify(&fixture.image[..]).unwrap();
+        assert!(report.is_valid(), "{:?}", report.mismatches);
+        // superblock, a descriptor, two bitmaps, the inodes in use and the
+        // directories' blocks at the least
+        assert!(report.checked > 20, "{}", report.checked);
+
+        let stdout = shell(&fixture.image_path(), "csum-check\n");
+        assert!(stdout.contains(" checksums checked, 0 wrong"), "{}", stdout);
+        assert!(!stdout.contains("checksums do not validate"), "{}", stdout);
+    }
+}
+
+#[test]
+fn corruption_is_reported() {
+    let Some(fixture) = checksummed("256") else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    // the superblock's volume name, and the root inode's mtime
+    let mut image = fixture.image.clone();
+    image[1024 + 0x78] ^= 0xFF;
+    let report = csum::verify(&image[..]).unwrap();
+    assert_eq!(report.mismatches.len(), 1, "{:?}", report.mismatches);
+    assert_eq!(report.mismatches[0].what, Checksummed::Superblock);
+
+    let volume = ext2::volume::Volume::open(&fixture.image[..]).unwrap();
+    let table = volume.block_groups()[0].inode_table_block.get() as usize;
+    let mut image = fixture.image.clone();
+    image[table * 1024 + 256 + 0x10] ^= 0x01;
+    let report = csum::verify(&image[..]).unwrap();
+    assert_eq!(report.mismatches.len(), 1, "{:?}", report.mismatches);
+    assert_eq!(report.mismatches[0].what, Checksummed::Inode(2));
+
+    let path = fixture.image_path();
+    fs::write(&path, &image).unwrap();
+    let stdout = shell(&path, "csum-check\n");
+    assert!(stdout.contains("inode 2: checksum is "), "{}", stdout);
+    assert!(stdout.contains("checksums do not validate"), "{}", stdout);
+}
+
+#[test]
+fn writes_are_refused() {
+    let Some(fixture) = checksummed("256") else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let path = fixture.image_path();
+    let stdout = shell(
+        &path,
+        "mkdir new\ntouch dir/file\nrm dir/file\nmv dir moved\nresizefs 8192\ncsum-check\n",
+    );
+    assert_eq!(
+        stdout.matches("metadata_csum checksums").count(),
+        5,
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(" checksums checked, 0 wrong"), "{}", stdout);
+    assert_eq!(fs::read(&path).unwrap(), fixture.image);
+}
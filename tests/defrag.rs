@@ -0,0 +1,140 @@
+mod common;
+
+use common::{doubly_indirect_len, fixture_from, pattern, shell};
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// what frag said about `name`, in order
+fn frag_lines<'a>(stdout: &'a str, name: &str) -> Vec<&'a str> {
+    stdout
+        .lines()
+        .filter(|line| line.starts_with(&format!("{}: ", name)) && line.contains("fragments"))
+        .collect()
+}
+
+// the clone has `path` holding `expected`, and e2fsck, if it's there, is
+// happy with it
+fn check_clone(clone: &Path, path: &str, expected: &[u8]) {
+    let image = fs::read(clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let inode = volume.resolve(path.as_bytes()).unwrap();
+    let mut data = vec![0; expected.len()];
+    assert_eq!(volume.read_file(inode, 0, &mut data).unwrap(), data.len());
+    assert!(data == expected);
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(clone).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+// the same holes as in tests/frag.rs: debugfs writes the file into the
+// two left by f2 and f4, and one after f6
+#[test]
+fn scattered_file_ends_up_in_one_fragment() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |tree| {
+        for i in 1..=6 {
+            fs::write(tree.join(format!("f{}", i)), pattern(1024)).unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("rm /f2").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    fixture.debugfs("rm /f4").unwrap();
+    let scattered = fixture.tree_path().join("../scattered");
+    fs::write(&scattered, pattern(3 * 1024)).unwrap();
+    fixture
+        .debugfs(&format!("write {} scattered", scattered.display()))
+        .unwrap();
+
+    let clone = fixture.tree_path().with_file_name("defragged.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "defrag scattered\nfrag scattered\ndefrag scattered\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(
+        stdout.contains("defrag: allocated 3 blocks; freed 3 blocks"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("scattered: 3 blocks in 1 fragments, largest gap 0 blocks"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("scattered: already in one fragment"),
+        "{}",
+        stdout
+    );
+    check_clone(&clone, "/scattered", &pattern(3 * 1024));
+}
+
+// mke2fs puts each indirect block in front of the data it points at, so a
+// file that needs the doubly indirect block is in several fragments. its
+// indirect blocks get rebuilt after the moved data
+#[test]
+fn indirect_blocks_are_rebuilt() {
+    let len = doubly_indirect_len(1024);
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::write(tree.join("big"), pattern(len)).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("defragged.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "frag big\ndefrag big\nfrag big\nclone {}\n",
+            clone.display()
+        ),
+    );
+    let blocks = len.div_ceil(1024);
+    let frag = frag_lines(&stdout, "big");
+    assert_eq!(frag.len(), 2, "{}", stdout);
+    assert!(!frag[0].contains(" in 1 fragments"), "{}", stdout);
+    assert!(stdout.contains("big: moved to blocks"), "{}", stdout);
+    assert_eq!(
+        frag[1],
+        format!(
+            "big: {} blocks in 1 fragments, largest gap 0 blocks",
+            blocks
+        )
+    );
+    check_clone(&clone, "/big", &pattern(len));
+}
+
+// a file bigger than the free space left can't go anywhere, and stays
+// where it was
+#[test]
+fn no_free_run_leaves_the_file_alone() {
+    let len = 600 * 1024;
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("big"), pattern(len)).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "frag big\ndefrag big\nfrag big\n");
+    let frag = frag_lines(&stdout, "big");
+    assert_eq!(frag.len(), 2, "{}", stdout);
+    assert_eq!(frag[0], frag[1]);
+    assert!(
+        stdout.contains("to move it to, leaving it as it is"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("defrag: allocated"), "{}", stdout);
+}
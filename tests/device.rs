@@ -0,0 +1,68 @@
+mod common;
+
+use common::{fixture, shell_status};
+use ext2::image;
+use std::path::Path;
+use std::process::Command;
+
+// /dev/null is a character device anyone can open, with nothing in it
+#[test]
+fn devices_are_read_only_unless_allowed() {
+    let null = Path::new("/dev/null");
+    assert!(image::open(null).unwrap().is_device());
+
+    let (ok, stdout) = shell_status(&[], null, "ls\n");
+    assert!(!ok);
+    assert!(stdout.contains("unable to mount file system"), "{}", stdout);
+
+    let (ok, stdout) = shell_status(&["--mmap-write"], null, "ls\n");
+    assert!(!ok);
+    assert!(
+        stdout.contains("is a device, so it is only read"),
+        "{}",
+        stdout
+    );
+    let (_, stdout) = shell_status(&["--mmap-write", "--allow-write-device"], null, "ls\n");
+    assert!(!stdout.contains("is a device"), "{}", stdout);
+}
+
+#[test]
+fn missing_images_are_reported() {
+    let (ok, stdout) = shell_status(&[], Path::new("/nonexistent/image.ext2"), "ls\n");
+    assert!(!ok);
+    assert!(
+        stdout.contains("unable to open /nonexistent/image.ext2"),
+        "{}",
+        stdout
+    );
+}
+
+// a real block device, where losetup can make one
+#[test]
+fn loop_device_is_read() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    assert!(!image::open(&fixture.image_path()).unwrap().is_device());
+    let attached = Command::new("losetup")
+        .args(["--find", "--show", "--read-only"])
+        .arg(fixture.image_path())
+        .output();
+    let device = match attached {
+        Ok(output) if output.status.success() => {
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        }
+        _ => {
+            eprintln!("no loop devices here, skipping");
+            return;
+        }
+    };
+    let (ok, stdout) = shell_status(&[], Path::new(&device), "cat one_byte\n");
+    Command::new("losetup")
+        .args(["-d", &device])
+        .status()
+        .unwrap();
+    assert!(ok);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::fixture_from;
+use ext2::diff::{diff, Change, DiffOptions};
+use ext2::volume::Volume;
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::Path;
+use std::process::Command;
+
+// the second tree is the first one with a little of everything changed
+fn build(tree: &Path, changed: bool) {
+    let write = |name: &str, contents: &[u8]| fs::write(tree.join(name), contents).unwrap();
+    write("same.txt", b"unchanged\n");
+    write("changed.txt", if changed { b"bbbb\n" } else { b"aaaa\n" });
+    write(
+        "grown.txt",
+        if changed { b"longer now\n" } else { b"short\n" },
+    );
+    write("mode.txt", b"mode\n");
+    let mode = if changed { 0o755 } else { 0o644 };
+    fs::set_permissions(tree.join("mode.txt"), fs::Permissions::from_mode(mode)).unwrap();
+    write("x", b"linked\n");
+    fs::hard_link(tree.join("x"), tree.join(if changed { "z" } else { "y" })).unwrap();
+    symlink(if changed { "u" } else { "t" }, tree.join("sym")).unwrap();
+    if changed {
+        write("new.txt", b"new\n");
+        write("kind", b"a file now\n");
+    } else {
+        write("gone.txt", b"gone\n");
+        fs::create_dir(tree.join("kind")).unwrap();
+        write("kind/inside", b"inside\n");
+    }
+}
+
+#[test]
+fn differences_are_listed_by_path() {
+    let (Some(a), Some(b)) = (
+        fixture_from(1024, "1M", &[], |tree| build(tree, false)),
+        fixture_from(4096, "1M", &[], |tree| build(tree, true)),
+    ) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let output = Command::new(env!("CARGO_BIN_EXE_ext2diff"))
+        .arg(a.image_path())
+        .arg(b.image_path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout,
+        "M / links 4 -> 3\n\
+         C /changed.txt\n\
+         - /gone.txt\n\
+         S /grown.txt 6 -> 11\n\
+         T /kind dir -> file\n\
+         - /kind/inside\n\
+         M /mode.txt mode 0644 -> 0755\n\
+         + /new.txt\n\
+         C /sym\n\
+         R /y -> /z\n"
+    );
+
+    // --content leaves out the metadata, --metadata-only the contents
+    let output = Command::new(env!("CARGO_BIN_EXE_ext2diff"))
+        .arg("--content")
+        .arg(a.image_path())
+        .arg(b.image_path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("M "), "{}", stdout);
+    assert!(stdout.contains("C /changed.txt"), "{}", stdout);
+    let output = Command::new(env!("CARGO_BIN_EXE_ext2diff"))
+        .arg("--metadata-only")
+        .arg(a.image_path())
+        .arg(b.image_path())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("C "), "{}", stdout);
+    assert!(stdout.contains("M /mode.txt"), "{}", stdout);
+
+    // an image against itself, and against the same tree in another image
+    let volume_a = Volume::open(&a.image[..]).unwrap();
+    assert_eq!(
+        diff(&volume_a, &volume_a, DiffOptions::default()).unwrap(),
+        []
+    );
+    let Some(again) = fixture_from(4096, "2M", &[], |tree| build(tree, false)) else {
+        return;
+    };
+    let volume_again = Volume::open(&again.image[..]).unwrap();
+    assert_eq!(
+        diff(&volume_a, &volume_again, DiffOptions::default()).unwrap(),
+        []
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_ext2diff"))
+        .arg(a.image_path())
+        .arg(again.image_path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    // a renamed link is only the rename, not a change to the file
+    let volume_b = Volume::open(&b.image[..]).unwrap();
+    let differences = diff(&volume_a, &volume_b, DiffOptions::default()).unwrap();
+    let x: Vec<&Change> = differences
+        .iter()
+        .filter(|d| d.path == b"/x" || d.path == b"/y" || d.path == b"/z")
+        .map(|d| &d.change)
+        .collect();
+    assert_eq!(x, [&Change::Renamed(b"/z".to_vec())]);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{fixture_from, shell_with};
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+
+// the shell with `--time`, which prints the directory blocks each command
+// read and wrote
+fn shell(image: &Path, script: &str) -> String {
+    shell_with(&["--time"], image, script)
+}
+
+// listings that were read before a change show the change right after it
+#[test]
+fn listings_follow_changes() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/file"), b"data\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "ls dir\nmkdir dir/new\nls dir\nmv dir/file dir/renamed\nls dir\nrm dir/renamed\nls dir\n\
+         cd dir/new\ncd ..\npwd\n",
+    );
+    let listings: Vec<&str> = stdout.lines().filter(|line| line.ends_with('\t')).collect();
+    assert_eq!(
+        listings,
+        ["file\t", "file\tnew\t", "new\trenamed\t", "new\t"],
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("\n/dir\n"), "{}", stdout);
+}
+
+// looking up a thousand names in one directory reads its blocks once,
+// and the root's once on the way
+#[test]
+fn one_directory_is_read_once() {
+    let Some(fixture) = fixture_from(4096, "8M", &[], |tree| {
+        fs::create_dir(tree.join("big")).unwrap();
+        for i in 0..1000 {
+            fs::write(tree.join(format!("big/f{:03}", i)), b"").unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let blocks = |path: &[u8]| {
+        let inode = volume.resolve(path).unwrap();
+        volume.metadata(inode).unwrap().size as usize / 4096
+    };
+    let expected = blocks(b"/") + blocks(b"/big");
+    assert!(blocks(b"/big") > 1);
+
+    let paths: Vec<String> = (0..1000).map(|i| format!("big/f{:03}", i)).collect();
+    let stdout = shell(&fixture.image_path(), &format!("ls {}\n", paths.join(" ")));
+    assert!(stdout.contains("\nbig/f999\n"), "{}", stdout);
+    let time = stdout
+        .lines()
+        .find(|line| line.starts_with("time: ls took "))
+        .expect(&stdout);
+    assert!(
+        time.ends_with(&format!(", read {} directory blocks", expected)),
+        "{}",
+        time
+    );
+}
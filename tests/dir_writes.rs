@@ -0,0 +1,161 @@
+mod common;
+
+use common::{fixture_from, shell_with};
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+
+// the shell with `--time`, which prints the directory blocks each command
+// read and wrote
+fn shell(image: &Path, script: &str) -> String {
+    shell_with(&["--time"], image, script)
+}
+
+// the `time:` line of each command, in order
+fn times(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .filter(|line| line.starts_with("time: "))
+        .collect()
+}
+
+// adding an entry to a directory of many blocks writes back the one block
+// it goes in, whether that's one with room left or a new one
+#[test]
+fn insert_writes_one_block() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::create_dir(tree.join("big")).unwrap();
+        for i in 0..300 {
+            fs::write(
+                tree.join(format!("big/entry_with_a_long_name_{:03}", i)),
+                b"",
+            )
+            .unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let big = volume.resolve(b"/big").unwrap();
+    let blocks = volume.metadata(big).unwrap().size as usize / 1024;
+    assert!(blocks > 5, "{} blocks", blocks);
+
+    let clone = fixture.tree_path().with_file_name("added.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "touch big/new\nmkdir big/sub\nls big\nclone {}\n",
+            clone.display()
+        ),
+    );
+    let times = times(&stdout);
+    assert!(
+        times[0].ends_with(", wrote 1 directory block"),
+        "{}",
+        stdout
+    );
+    assert!(
+        times[1].ends_with(", wrote 1 directory block"),
+        "{}",
+        stdout
+    );
+    assert!(!times[2].contains("wrote"), "{}", stdout);
+
+    // everything that was there is still there, with the new ones
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let entries = volume.read_dir(big).unwrap();
+    assert_eq!(entries.len(), 2 + 300 + 2);
+    assert!(entries.iter().any(|entry| entry.name == "new"));
+    assert!(entries.iter().any(|entry| entry.name == "sub"));
+    assert!(entries
+        .iter()
+        .any(|entry| entry.name == "entry_with_a_long_name_299"));
+}
+
+// filling a directory past its first block still writes one block per
+// entry, the new block included
+#[test]
+fn growing_directory_writes_one_block_each() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::create_dir(tree.join("small")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let script: String = (0..100)
+        .map(|i| format!("touch small/f{:03}\n", i))
+        .collect();
+    let clone = fixture.tree_path().with_file_name("grown.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("{}clone {}\n", script, clone.display()),
+    );
+    let times = times(&stdout);
+    assert_eq!(
+        times
+            .iter()
+            .filter(|line| line.ends_with(", wrote 1 directory block"))
+            .count(),
+        100,
+        "{}",
+        stdout
+    );
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let small = volume.resolve(b"/small").unwrap();
+    assert_eq!(volume.metadata(small).unwrap().size, 2048);
+    assert_eq!(volume.read_dir(small).unwrap().len(), 102);
+}
+
+// taking an entry out of a directory of many blocks writes back only the
+// block it was in, whether it merges into the one before it or is first
+#[test]
+fn remove_writes_one_block() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::create_dir(tree.join("big")).unwrap();
+        for i in 0..300 {
+            fs::write(
+                tree.join(format!("big/entry_with_a_long_name_{:03}", i)),
+                b"",
+            )
+            .unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let big = volume.resolve(b"/big").unwrap();
+    let entries = volume.read_dir(big).unwrap();
+    let names: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.name.starts_with("entry"))
+        .map(|entry| entry.name.clone())
+        .collect();
+    let (first, last) = (&names[0], &names[names.len() - 1]);
+
+    let clone = fixture.tree_path().with_file_name("removed.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "rm big/{}\nrm big/{}\nclone {}\n",
+            last,
+            first,
+            clone.display()
+        ),
+    );
+    let times = times(&stdout);
+    for time in &times[..2] {
+        assert!(time.ends_with(", wrote 1 directory block"), "{}", stdout);
+    }
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let entries = volume.read_dir(big).unwrap();
+    assert_eq!(entries.len(), 2 + 298);
+    assert!(!entries.iter().any(|entry| &entry.name == first));
+    assert!(!entries.iter().any(|entry| &entry.name == last));
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::{fixture, shell};
+use ext2::volume::Volume;
+
+// size_high of a directory is its ACL block, so a directory with one set
+// is still as big as size_low says. for a regular file it's the upper half
+// of the size
+#[test]
+fn size_high_only_counts_for_files() {
+    for block_size in [1024, 4096] {
+        let Some(mut fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        fixture.debugfs("sif /dir size_hi 7").unwrap();
+        fixture.debugfs("sif /one_byte size_hi 1").unwrap();
+
+        let volume = Volume::open(&fixture.image[..]).unwrap();
+        let dir = volume.resolve(b"/dir").unwrap();
+        assert_eq!(volume.metadata(dir).unwrap().size, block_size as u64);
+        assert_eq!(volume.read_dir(dir).unwrap().len(), 4);
+        let file = volume.resolve(b"/one_byte").unwrap();
+        assert_eq!(volume.metadata(file).unwrap().size, (1 << 32) + 1);
+
+        let stdout = shell(&fixture.image_path(), "ls /dir\nstat /dir\n");
+        assert!(stdout.contains("sibling.txt"), "{}", stdout);
+        assert!(
+            stdout.contains(&format!("Size: {}", block_size)),
+            "{}",
+            stdout
+        );
+    }
+}
+
+// a new entry goes in the space a removed one left, so a directory that
+// has been churned doesn't keep growing
+#[test]
+fn freed_entry_space_is_reused() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    // `.` and `..` take 24 bytes, and 62 entries with 8 byte names take 16
+    // each, leaving 8 bytes: too few for another
+    let mut script = "mkdir full\n".to_string();
+    for i in 0..62 {
+        script.push_str(&format!("touch full/f{:07}\n", i));
+    }
+    script.push_str("rm full/f0000030\ntouch full/g0000030\nstat full\ntouch full/h\n");
+    let stdout = shell(&fixture.image_path(), &script);
+    let touches: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("touch: allocated"))
+        .collect();
+    assert_eq!(touches.len(), 64, "{}", stdout);
+    assert!(
+        touches[..63]
+            .iter()
+            .all(|line| line.ends_with(", 0 blocks")),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Size: 1024\t"), "{}", stdout);
+    // with no gap left the directory has to grow
+    assert!(touches[63].ends_with(", 1 block"), "{}", stdout);
+}
+
+// the bytes of a directory that grew past a block are the entries the
+// shell wrote, in order, each block's last entry running to its end
+#[test]
+fn directory_blocks_read_back_as_written() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("written.ext2");
+    // after `.` and `..`, 50 entries of 20 bytes fill the first block
+    // exactly, so the second starts with entry 50
+    let mut script = "mkdir grown\n".to_string();
+    for i in 0..60 {
+        script.push_str(&format!("touch grown/entry_{:03}\n", i));
+    }
+    script.push_str(&format!("clone {}\n", clone.display()));
+    shell(&fixture.image_path(), &script);
+
+    let image = std::fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let grown = volume.resolve(b"/grown").unwrap();
+    let entry = |inode: u32, rec_len: usize, name: &str, kind: u8| {
+        let mut bytes = vec![0; rec_len];
+        bytes[..4].copy_from_slice(&inode.to_le_bytes());
+        bytes[4..6].copy_from_slice(&(rec_len as u16).to_le_bytes());
+        bytes[6] = name.len() as u8;
+        bytes[7] = kind;
+        bytes[8..8 + name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    };
+    let mut expected = entry(grown.get(), 12, ".", 2);
+    expected.extend(entry(volume.resolve(b"/").unwrap().get(), 12, "..", 2));
+    for i in 0..60 {
+        let name = format!("entry_{:03}", i);
+        let inode = volume.lookup(grown, name.as_bytes()).unwrap();
+        let rec_len = if i == 59 { 1024 - 20 * 9 } else { 20 };
+        expected.extend(entry(inode.get(), rec_len, &name, 1));
+    }
+    assert_eq!(expected.len(), 2048);
+
+    let mut actual = vec![0; 2048];
+    assert_eq!(volume.read_file(grown, 0, &mut actual).unwrap(), 2048);
+    assert!(actual == expected, "{:?}", actual);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{fixture_from, shell_with};
+use std::fs;
+
+// the listings the shell prints for `ls`, each name followed by a tab
+fn listings(stdout: &str) -> Vec<&str> {
+    stdout.lines().filter(|line| line.ends_with('\t')).collect()
+}
+
+fn fixture() -> Option<common::Fixture> {
+    fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("file"), vec![b'x'; 3000]).unwrap();
+    })
+}
+
+#[test]
+fn rm_dry_run_reports_and_changes_nothing() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell_with(
+        &[],
+        &fixture.image_path(),
+        "ls\nrm --dry-run file dir\nls\nstat file\n",
+    );
+    assert!(
+        stdout.contains("file:\n  would remove entry file (inode"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(") from directory 2\n"), "{}", stdout);
+    assert!(
+        stdout.contains("would change the link count of inode"),
+        "{}",
+        stdout
+    );
+    // 3000 bytes in 1024 byte blocks
+    assert!(stdout.contains("would free inode"), "{}", stdout);
+    assert!(stdout.contains(" and blocks "), "{}", stdout);
+    // the directory's `..` was a link to the root
+    assert!(
+        stdout.contains("would change the link count of inode 2 from 4 to 3"),
+        "{}",
+        stdout
+    );
+    let listings = listings(&stdout);
+    assert_eq!(listings.len(), 2, "{}", stdout);
+    assert_eq!(listings[0], listings[1]);
+    assert!(listings[0].contains("file"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
+
+#[test]
+fn mv_dry_run_reports_and_changes_nothing() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell_with(
+        &[],
+        &fixture.image_path(),
+        "mv --dry-run file dir\nls\nls dir\n",
+    );
+    assert!(stdout.contains("would add entry file (inode"), "{}", stdout);
+    assert!(
+        stdout.contains("would remove entry file (inode"),
+        "{}",
+        stdout
+    );
+    // file is still where it was, and only there
+    let listings = listings(&stdout);
+    assert_eq!(listings, ["dir\tfile\tlost+found\t"], "{}", stdout);
+}
+
+// with --dry-run on the command line it's on for the whole session, and
+// what can't do a dry run refuses to run at all
+#[test]
+fn dry_run_for_the_whole_session() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell_with(
+        &["--dry-run"],
+        &fixture.image_path(),
+        "rm file\nmkdir new\nls\n",
+    );
+    assert!(stdout.contains("would free inode"), "{}", stdout);
+    assert!(
+        stdout.contains(
+            "mkdir: this is a dry run, and only rm and mv can say what they would change"
+        ),
+        "{}",
+        stdout
+    );
+    let listings = listings(&stdout);
+    assert!(listings[0].contains("file"), "{}", stdout);
+    assert!(!listings[0].contains("new"), "{}", stdout);
+}
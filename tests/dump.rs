@@ -0,0 +1,83 @@
+mod common;
+
+use common::{fixture, shell};
+use ext2::dump::{dump, TreeEntry};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+
+fn child<'a>(entry: &'a TreeEntry, name: &str) -> &'a TreeEntry {
+    entry
+        .children
+        .as_ref()
+        .unwrap()
+        .iter()
+        .find(|child| child.name == name)
+        .unwrap_or_else(|| panic!("no {} in {}", name, entry.name))
+}
+
+#[test]
+fn dump_mirrors_the_file_system() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let volume = Volume::open(&fixture.image[..]).unwrap();
+        let superblock = volume.superblock();
+
+        let without_tree = dump(&volume, None).unwrap();
+        assert!(without_tree.tree.is_none());
+        let dumped = dump(&volume, Some((InodeNo::ROOT, b"/"))).unwrap();
+        assert_eq!(dumped.superblock.block_size, block_size);
+        assert_eq!(
+            dumped.superblock.inodes_count,
+            superblock.inodes_count.get()
+        );
+        assert_eq!(
+            dumped.superblock.free_blocks_count,
+            superblock.free_blocks_count.get()
+        );
+        assert_eq!(dumped.superblock.uuid.len(), 36);
+        assert_eq!(dumped.groups.len(), volume.block_groups().len());
+        let free: u32 = dumped
+            .groups
+            .iter()
+            .map(|group| group.free_blocks_count as u32)
+            .sum();
+        assert_eq!(free, superblock.free_blocks_count.get());
+
+        let root = dumped.tree.as_ref().unwrap();
+        assert_eq!(root.inode, 2);
+        assert_eq!(root.file_type, "dir");
+        let leaf = child(
+            child(child(child(root, "dir"), "nested"), "deeper"),
+            "leaf.txt",
+        );
+        assert_eq!(leaf.file_type, "file");
+        assert_eq!(leaf.size, common::LEAF_CONTENTS.len() as u64);
+        assert!(leaf.children.is_none());
+        assert_eq!(child(root, "link").file_type, "symlink");
+        assert_eq!(child(root, "hard_a").inode, child(root, "hard_b").inode);
+        assert!(root
+            .children
+            .as_ref()
+            .unwrap()
+            .iter()
+            .all(|child| child.name != "." && child.name != ".."));
+
+        // and the shell prints it as a single line of JSON
+        let stdout = shell(&fixture.image_path(), "dump --json --tree /dir\ndump\n");
+        let json = stdout
+            .lines()
+            .find(|line| line.starts_with('{'))
+            .unwrap_or_else(|| panic!("no JSON in\n{}", stdout));
+        assert!(
+            json.starts_with("{\"superblock\":{\"inodes_count\":"),
+            "{}",
+            json
+        );
+        assert!(json.contains("\"tree\":{\"name\":\"dir\""), "{}", json);
+        assert!(json.contains("\"name\":\"sibling.txt\""), "{}", json);
+        assert!(stdout.contains("usage: dump --json"), "{}", stdout);
+    }
+}
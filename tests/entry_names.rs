@@ -0,0 +1,223 @@
+mod common;
+
+use common::{fixture_from, shell, Fixture};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// the first block of the root directory in `image`, and where each of
+// its entries starts
+fn root_block(image: &[u8]) -> (usize, Vec<usize>) {
+    let volume = Volume::open(image).unwrap();
+    let block_size = volume.block_size();
+    let block = volume.read_inode(InodeNo::ROOT).unwrap().direct_pointer[0].get() as usize;
+    let data = &image[block * block_size..(block + 1) * block_size];
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while offset < block_size {
+        offsets.push(offset);
+        offset += u16::from_le_bytes([data[offset + 4], data[offset + 5]]) as usize;
+    }
+    (block * block_size, offsets)
+}
+
+fn tree(tree: &Path) {
+    fs::write(tree.join("abc"), b"").unwrap();
+}
+
+// a copy of the image with a non-zero byte in the padding after the name
+// `abc`, the way a reused entry can leave it
+fn garbage_after_abc(fixture: &Fixture) -> PathBuf {
+    let mut image = fixture.image.clone();
+    let (start, offsets) = root_block(&image);
+    let abc = offsets
+        .into_iter()
+        .map(|offset| start + offset)
+        .find(|&entry| &image[entry + 8..entry + 11] == b"abc")
+        .unwrap();
+    // the byte padding the name out to 12 bytes
+    image[abc + 11] = b'X';
+    let crafted = fixture.tree_path().with_file_name("crafted.ext2");
+    fs::write(&crafted, &image).unwrap();
+    crafted
+}
+
+// names aren't NUL terminated: the padding after one can hold anything and
+// is never part of it
+#[test]
+fn name_stops_at_its_length() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let crafted = garbage_after_abc(&fixture);
+    let stdout = shell(&crafted, "ls\nstat abc\n");
+    assert!(stdout.contains("abc\t"), "{}", stdout);
+    assert!(stdout.contains("  File: abc"), "{}", stdout);
+    assert!(!stdout.contains("abcX"), "{}", stdout);
+}
+
+// `abcdefgh` deleted and `abc` put in its slot, the way a directory reuses
+// an entry: the rest of the old name is still there after the new one
+#[test]
+fn shorter_name_in_a_reused_slot() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("abcdefgh"), b"").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let mut image = fixture.image.clone();
+    let (start, offsets) = root_block(&image);
+    let entry = offsets
+        .into_iter()
+        .map(|offset| start + offset)
+        .find(|&entry| &image[entry + 8..entry + 16] == b"abcdefgh")
+        .unwrap();
+    image[entry + 6] = 3;
+    let crafted = fixture.tree_path().with_file_name("crafted.ext2");
+    fs::write(&crafted, &image).unwrap();
+
+    let stdout = shell(&crafted, "ls\nstat abc\nstat abcdefgh\n");
+    assert!(stdout.contains("abc\t"), "{}", stdout);
+    assert!(stdout.contains("  File: abc\n"), "{}", stdout);
+    assert!(!stdout.contains("abcdefgh\t"), "{}", stdout);
+    assert!(!stdout.contains("  File: abcdefgh"), "{}", stdout);
+}
+
+// the last entry of the block claims a name longer than what is left of
+// the block. it is reported, and nothing after the block is read as a name
+#[test]
+fn name_past_the_block_is_corrupt() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let mut image = fixture.image.clone();
+    let (start, offsets) = root_block(&image);
+    // stretch the second last entry up to 12 bytes before the end of the
+    // block and move the last one there, with a 200 byte name
+    let second_last = start + offsets[offsets.len() - 2];
+    let last = start + offsets[offsets.len() - 1];
+    let moved = start + 1024 - 12;
+    let mut entry = image[last..last + 8].to_vec();
+    entry[4..6].copy_from_slice(&12u16.to_le_bytes());
+    entry[6] = 200;
+    image[moved..moved + 8].copy_from_slice(&entry);
+    image[moved + 8..moved + 12].copy_from_slice(b"abcd");
+    let stretched = (moved - second_last) as u16;
+    image[second_last + 4..second_last + 6].copy_from_slice(&stretched.to_le_bytes());
+    let crafted = fixture.tree_path().with_file_name("crafted.ext2");
+    fs::write(&crafted, &image).unwrap();
+
+    let stdout = shell(&crafted, "ls\n");
+    assert!(
+        stdout.contains("bad directory entry at byte 1012 of inode 2"),
+        "{}",
+        stdout
+    );
+}
+
+// names are bytes, not text: a Latin-1 name and one with a control
+// character in it are listed with those bytes as `\xNN`, never dropped or
+// turned into U+FFFD, and can be typed back in that way
+#[test]
+fn names_that_are_not_utf8_are_escaped() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join(OsStr::from_bytes(b"caf\xe9")), b"latin-1\n").unwrap();
+        fs::write(tree.join(OsStr::from_bytes(b"tab\tname")), b"").unwrap();
+        fs::write(tree.join("back\\slash"), b"").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("escaped.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "ls\nstat caf\\xe9\ncat caf\\xe9\nmkdir new\\xff\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(stdout.contains("caf\\xe9\t"), "{}", stdout);
+    assert!(stdout.contains("tab\\x09name\t"), "{}", stdout);
+    assert!(stdout.contains("back\\\\slash\t"), "{}", stdout);
+    assert!(!stdout.contains('\u{fffd}'), "{}", stdout);
+    assert!(stdout.contains("  File: caf\\xe9\n"), "{}", stdout);
+    assert!(stdout.contains("latin-1\n"), "{}", stdout);
+
+    // the escaped name made the raw byte
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let new = volume.resolve(b"/new\xff").unwrap();
+    assert!(volume.metadata(new).unwrap().is_dir());
+}
+
+// the shell splits arguments on whitespace, so a space in a name is shown
+// as `\x20` too, and the name typed that way gets into the directory and
+// removes the file
+#[test]
+fn names_with_spaces_are_escaped() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("a b"), b"spaced\n").unwrap();
+        fs::create_dir(tree.join("dir name")).unwrap();
+        fs::write(tree.join("dir name/inner"), b"").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("spaces.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "ls\ncat a\\x20b\ncd dir\\x20name\npwd\nls\ncd /\nrm a\\x20b\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(stdout.contains("a\\x20b\t"), "{}", stdout);
+    assert!(stdout.contains("dir\\x20name\t"), "{}", stdout);
+    assert!(stdout.contains("spaced\n"), "{}", stdout);
+    assert!(stdout.contains("/dir\\x20name\n"), "{}", stdout);
+    assert!(stdout.contains("inner\t"), "{}", stdout);
+    assert!(!stdout.contains("No such file"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert!(volume.resolve(b"/a b").is_err());
+    assert!(volume.resolve(b"/dir name/inner").is_ok());
+}
+
+// an entry whose record is shorter than its own name rounded up to 4 bytes
+// leaves no room after it, it doesn't wrap around to a huge gap. the entry
+// after it is garbage, which is what gets reported
+#[test]
+fn record_shorter_than_its_name_is_no_gap() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("abcde"), b"").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let mut image = fixture.image.clone();
+    let (start, offsets) = root_block(&image);
+    let entry = offsets
+        .into_iter()
+        .map(|offset| start + offset)
+        .find(|&entry| &image[entry + 8..entry + 13] == b"abcde")
+        .unwrap();
+    // enough for the 8 byte header and the name, less than the 16 it takes
+    image[entry + 4..entry + 6].copy_from_slice(&13u16.to_le_bytes());
+    let crafted = fixture.tree_path().with_file_name("crafted.ext2");
+    fs::write(&crafted, &image).unwrap();
+
+    let stdout = shell(&crafted, "touch new\nmkdir other\n");
+    assert_eq!(
+        stdout.matches("bad directory entry at byte").count(),
+        2,
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::{fixture, shell};
+use ext2::structs::{InodeNo, TypeIndicator};
+use ext2::volume::Volume;
+use std::fs;
+
+// set the type byte of the root's entry called `name`
+fn set_entry_type(image: &mut [u8], name: &[u8], kind: u8) {
+    let volume = Volume::open(&image[..]).unwrap();
+    let block_size = volume.block_size();
+    let block = volume.read_inode(InodeNo::ROOT).unwrap().direct_pointer[0].get() as usize;
+    let data = &mut image[block * block_size..(block + 1) * block_size];
+    let mut offset = 0;
+    while offset < block_size {
+        let rec_len = u16::from_le_bytes([data[offset + 4], data[offset + 5]]) as usize;
+        let name_len = data[offset + 6] as usize;
+        if &data[offset + 8..offset + 8 + name_len] == name {
+            data[offset + 7] = kind;
+            return;
+        }
+        offset += rec_len;
+    }
+    panic!("no entry {:?} in the root", name);
+}
+
+// an entry that says directory for a regular file is reported, with both
+// types, and nothing is said about the entries that are right
+#[test]
+fn mismatched_entry_type_is_reported() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "fsck\n");
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    let mut image = fixture.image.clone();
+    set_entry_type(&mut image, b"one_byte", 2);
+    let one_byte = Volume::open(&image[..])
+        .unwrap()
+        .resolve(b"/one_byte")
+        .unwrap();
+    let crafted = fixture.tree_path().with_file_name("crafted.ext2");
+    fs::write(&crafted, &image).unwrap();
+    let stdout = shell(&crafted, "fsck\n");
+    let expected = format!(
+        "entry \"one_byte\" in directory inode 2 is marked as a directory, inode {} is a regular file",
+        one_byte
+    );
+    assert!(stdout.contains(&expected), "{}", stdout);
+    assert_eq!(stdout.matches("is marked as a").count(), 1, "{}", stdout);
+}
+
+// every type mknod makes is what stat says it is, in the inode's mode and
+// in the entry pointing at it
+#[test]
+fn mknod_makes_each_type() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let types = [
+        ("fifo", "fifo", TypeIndicator::Fifo),
+        ("char", "character special file", TypeIndicator::Character),
+        ("block", "block special file", TypeIndicator::Block),
+        ("socket", "socket", TypeIndicator::Socket),
+    ];
+    let clone = fixture.tree_path().with_file_name("nodes.ext2");
+    let script: String = types
+        .iter()
+        .map(|(kind, _, _)| format!("mknod node_{0} {0}\nstat node_{0}\n", kind))
+        .collect();
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("{}clone {}\n", script, clone.display()),
+    );
+    assert!(!stdout.contains("unable to create inode"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let entries = volume.read_dir(InodeNo::ROOT).unwrap();
+    for (kind, name, indicator) in types {
+        let path = format!("node_{}", kind);
+        let stat = &stdout[stdout.find(&format!("  File: {}\n", path)).unwrap()..];
+        let size_line = stat.lines().nth(1).unwrap();
+        assert!(size_line.ends_with(&format!("\t{}", name)), "{}", stdout);
+        let entry = entries.iter().find(|entry| entry.name == path).unwrap();
+        assert_eq!(entry.kind, indicator);
+        assert_eq!(
+            volume.metadata(entry.inode).unwrap().file_type.to_string(),
+            name
+        );
+    }
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{fixture, fixture_from, pattern, shell};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn run(command: &mut Command, script: &str) -> String {
+    let mut shell = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = shell.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+// `len` bytes at `offset` of the file at `path`
+fn read_at(path: &Path, offset: u64, len: usize) -> Vec<u8> {
+    let mut file = fs::File::open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    let mut data = vec![0; len];
+    file.read_exact(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn export_copies_the_file() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let out = fixture.tree_path().with_file_name("doubly");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "export doubly {0}\nexport one_byte {0}\nexport dir {1}\n",
+            out.display(),
+            fixture.tree_path().with_file_name("dir").display()
+        ),
+    );
+    assert_eq!(
+        fs::read(&out).unwrap(),
+        fs::read(fixture.tree_path().join("doubly")).unwrap()
+    );
+    // never over an existing file
+    assert!(stdout.contains("export: File exists"), "{}", stdout);
+    assert!(
+        stdout.contains("export: not supported: reading inode"),
+        "{}",
+        stdout
+    );
+    assert!(!fixture.tree_path().with_file_name("dir").exists());
+}
+
+// a 2G file, nearly all of it a hole, comes out whole with the shell
+// limited to 256M of heap. reading it into memory first couldn't
+#[test]
+fn export_bigger_than_memory() {
+    const SIZE: u64 = 2 << 30;
+    let Some(fixture) = fixture_from(4096, "64M", &[], |tree| {
+        let mut file = fs::File::create(tree.join("sparse")).unwrap();
+        file.set_len(SIZE).unwrap();
+        for (offset, marker) in [(0, b"start"), (SIZE / 2, b"mdle!"), (SIZE - 5, b"end!!")] {
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(marker).unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let out = fixture.tree_path().with_file_name("sparse");
+    let tar = fixture.tree_path().with_file_name("sparse.tar");
+    let stdout = run(
+        Command::new("sh")
+            .arg("-c")
+            .arg("ulimit -d 262144 && exec \"$0\" \"$@\"")
+            .arg(env!("CARGO_BIN_EXE_ext2"))
+            .arg(fixture.image_path()),
+        &format!(
+            "export sparse {}\ntar sparse {}\n",
+            out.display(),
+            tar.display()
+        ),
+    );
+    assert!(!stdout.contains("unable"), "{}", stdout);
+    assert_eq!(fs::metadata(&out).unwrap().len(), SIZE);
+    assert_eq!(read_at(&out, 0, 5), b"start");
+    assert_eq!(read_at(&out, SIZE / 2 - 1, 7), b"\0mdle!\0");
+    assert_eq!(read_at(&out, SIZE - 5, 5), b"end!!");
+    // the data follows the ustar header and a pax one for the size
+    let tar_len = fs::metadata(&tar).unwrap().len();
+    assert!(tar_len > SIZE, "{}", tar_len);
+    assert_eq!(read_at(&tar, tar_len - 1024 - 5, 5), b"end!!");
+}
+
+// the same hashes as sha256sum gives the files the image was made from
+#[test]
+fn sha256_matches_sha256sum() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::write(tree.join("empty"), b"").unwrap();
+        fs::write(tree.join("small"), b"hello\n").unwrap();
+        fs::write(tree.join("big"), pattern(300 * 1024 + 7)).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "sha256 empty small big\n");
+    if !cfg!(feature = "sha256") {
+        assert!(
+            stdout.contains("sha256: built without the sha256 feature"),
+            "{}",
+            stdout
+        );
+        return;
+    }
+    let Ok(expected) = Command::new("sha256sum")
+        .args(["empty", "small", "big"])
+        .current_dir(fixture.tree_path())
+        .output()
+    else {
+        eprintln!("sha256sum not available, skipping");
+        return;
+    };
+    for line in String::from_utf8_lossy(&expected.stdout).lines() {
+        assert!(
+            stdout.lines().any(|got| got == line),
+            "{}\n{}",
+            line,
+            stdout
+        );
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::{fixture, fixture_from, shell, Fixture};
+use std::fs;
+use std::time::Instant;
+
+// `count` directories of `count` files each, some of them a level deeper
+fn wide_tree(count: usize) -> Option<Fixture> {
+    fixture_from(1024, "32M", &["-N", "8192"], |tree| {
+        for dir in 0..count {
+            let dir = tree.join(format!("d{:03}", dir));
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            for file in 0..count {
+                fs::write(dir.join(format!("f{:03}", file)), b"").unwrap();
+                fs::write(dir.join("sub").join(format!("g{:03}", file)), b"").unwrap();
+            }
+        }
+    })
+}
+
+#[test]
+fn find_lists_every_path_sorted() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "find dir\nfind one_byte\n");
+    let lines: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("dir") || line.starts_with("one_byte"))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "dir",
+            "dir/nested",
+            "dir/nested/deeper",
+            "dir/nested/deeper/leaf.txt",
+            "dir/sibling.txt",
+            "one_byte"
+        ],
+        "{}",
+        stdout
+    );
+}
+
+// however many threads walk it, the output is byte for byte the same
+#[test]
+fn find_with_jobs_matches_one_thread() {
+    let Some(fixture) = wide_tree(30) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let serial = shell(&fixture.image_path(), "find /\n");
+    assert!(serial.contains("\n/d029/sub/g029\n"), "{}", serial);
+    for jobs in [2, 4, 8] {
+        let parallel = shell(&fixture.image_path(), &format!("find --jobs {} /\n", jobs));
+        let parallel = parallel.replace(
+            "find: built without the rayon feature, walking with one thread\n",
+            "",
+        );
+        assert!(parallel == serial, "{} jobs differ", jobs);
+    }
+}
+
+// a few thousand files. run with
+// `cargo test --features rayon --test find -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn find_with_jobs_is_faster() {
+    let Some(fixture) = wide_tree(60) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let time = |script: &str| {
+        let start = Instant::now();
+        shell(&fixture.image_path(), script);
+        start.elapsed()
+    };
+    // the same start up and shutdown, so the difference is the walk
+    let baseline = time("\n");
+    let serial = time("find /\n").saturating_sub(baseline);
+    let parallel = time("find --jobs 4 /\n").saturating_sub(baseline);
+    eprintln!("one thread {:?}, four {:?}", serial, parallel);
+    if cfg!(feature = "rayon") && std::thread::available_parallelism().map_or(1, |n| n.get()) > 1 {
+        assert!(parallel < serial, "{:?} {:?}", serial, parallel);
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{fixture_from, pattern, shell};
+use std::fs;
+
+// mke2fs lays one block files out back to back. removing every other one
+// leaves one block holes, which debugfs's write fills first
+#[test]
+fn scattered_file_has_a_fragment_per_hole() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |tree| {
+        for i in 1..=6 {
+            fs::write(tree.join(format!("f{}", i)), pattern(1024)).unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "frag f1\nfrag /\n");
+    assert!(
+        stdout.contains("f1: 1 blocks in 1 fragments, largest gap 0 blocks"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("/: 1 blocks in 1 fragments"), "{}", stdout);
+
+    if fixture.debugfs("rm /f2").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    fixture.debugfs("rm /f4").unwrap();
+    let scattered = fixture.tree_path().join("../scattered");
+    fs::write(&scattered, pattern(3 * 1024)).unwrap();
+    fixture
+        .debugfs(&format!("write {} scattered", scattered.display()))
+        .unwrap();
+    let stdout = shell(&fixture.image_path(), "frag scattered\n");
+    // one block in each hole, and the last one after f6
+    assert!(
+        stdout.contains("scattered: 3 blocks in 3 fragments, largest gap 2 blocks"),
+        "{}",
+        stdout
+    );
+}
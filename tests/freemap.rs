@@ -0,0 +1,54 @@
+mod common;
+
+use common::{fixture_from, shell};
+
+// with every block marked used but a few runs, freemap counts them and
+// finds the longest, the first one of a tie
+#[test]
+fn largest_run_of_a_crafted_bitmap() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("setb 1 1023").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    for (first, len) in [(100, 5), (300, 20), (600, 1), (700, 20)] {
+        fixture
+            .debugfs(&format!("freeb {} {}", first, len))
+            .unwrap();
+    }
+    let stdout = shell(&fixture.image_path(), "freemap\nfreemap 0\n");
+    assert!(
+        stdout.contains("group 0: 46 free blocks, largest run 20 (blocks 300-319)\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("largest free run: 20 blocks, in group 0\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("usage: freemap"), "{}", stdout);
+}
+
+// a full file system has no run at all
+#[test]
+fn no_free_blocks() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("setb 1 1023").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    let stdout = shell(&fixture.image_path(), "freemap\n");
+    assert!(stdout.contains("group 0: no free blocks\n"), "{}", stdout);
+    assert!(
+        stdout.contains("largest free run: none, the file system is full"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{fixture, shell};
+use ext2::volume::Volume;
+
+// the findings fsck printed under `heading`, one per line
+fn findings<'a>(stdout: &'a str, heading: &str) -> Vec<&'a str> {
+    let mut lines = stdout.lines().skip_while(|line| !line.ends_with(heading));
+    lines.next();
+    lines
+        .take_while(|line| line.starts_with("  "))
+        .map(str::trim)
+        .collect()
+}
+
+// a file system straight from mke2fs has nothing wrong with it
+#[test]
+fn clean_image_has_no_findings() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let stdout = shell(&fixture.image_path(), "fsck\n");
+        assert!(stdout.contains("no problems found"), "{}", stdout);
+        assert!(!stdout.contains("errors:"), "{}", stdout);
+        assert!(!stdout.contains("warnings:"), "{}", stdout);
+    }
+}
+
+// each hand-made inconsistency is reported as what it is, with the inode
+// or block numbers, under its severity
+#[test]
+fn corruptions_are_reported() {
+    let Some(original) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&original.image[..]).unwrap();
+    let one_byte = volume.resolve(b"/one_byte").unwrap();
+    let block = volume
+        .block_at(&volume.read_inode(one_byte).unwrap(), 0)
+        .unwrap();
+    let sibling = volume.resolve(b"/dir/sibling.txt").unwrap();
+    let twelve_blocks = volume.resolve(b"/twelve_blocks").unwrap();
+    let hard_a = volume.resolve(b"/hard_a").unwrap();
+    let free_blocks = volume.superblock().free_blocks_count.get();
+
+    let cases = [
+        (
+            "sif /hard_a links_count 5".to_string(),
+            "errors:",
+            format!("inode {} has link count 5, 2 entries reference it", hard_a),
+        ),
+        (
+            "sif /twelve_blocks size 100".to_string(),
+            "errors:",
+            format!("inode {} has size 100 but 12 data blocks", twelve_blocks),
+        ),
+        (
+            format!("freeb {}", block),
+            "errors:",
+            format!(
+                "block {} is used by inode {} but free in the bitmap",
+                block, one_byte
+            ),
+        ),
+        (
+            format!("sif /dir/sibling.txt block[0] {}", block),
+            "errors:",
+            format!("block {} is claimed by inode", block),
+        ),
+        (
+            "freei /dir/sibling.txt".to_string(),
+            "errors:",
+            format!("points at free inode {}", sibling),
+        ),
+        (
+            format!("ssv free_blocks_count {}", free_blocks + 7),
+            "warnings:",
+            format!(
+                "superblock records {} free blocks, the groups have {}",
+                free_blocks + 7,
+                free_blocks
+            ),
+        ),
+    ];
+    for (request, heading, expected) in cases {
+        let Some(mut fixture) = fixture(1024) else {
+            return;
+        };
+        fixture.debugfs(&request).unwrap();
+        let stdout = shell(&fixture.image_path(), "fsck\n");
+        assert!(
+            findings(&stdout, heading)
+                .iter()
+                .any(|finding| finding.contains(&expected)),
+            "{}: no {:?} under {}\n{}",
+            request,
+            expected,
+            heading,
+            stdout
+        );
+        assert!(!stdout.contains("no problems found"), "{}", stdout);
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+use std::path::Path;
+
+// what one command printed, a line each
+fn run(image: &Path, command: &str) -> Vec<String> {
+    shell(image, &format!("{}\n", command))
+        .lines()
+        .map(|line| line.trim_start_matches(":> ").trim_end().to_string())
+        .filter(|line| line != "bye!")
+        .collect()
+}
+
+// `ls` of a file prints its path, so listing a glob prints what it
+// expanded to, in name order
+#[test]
+fn patterns_expand_to_matching_names() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        for name in [
+            "a.txt",
+            "b.txt",
+            "c.log",
+            "file1",
+            "file2",
+            "file10",
+            ".hidden.txt",
+        ] {
+            fs::write(tree.join(name), name).unwrap();
+        }
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/x.txt"), b"x").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let image = fixture.image_path();
+    // `*` doesn't match a leading `.`
+    assert_eq!(run(&image, "ls *.txt"), ["a.txt", "b.txt"]);
+    assert_eq!(run(&image, "ls *1"), ["file1"]);
+    assert_eq!(run(&image, "ls *1*"), ["file1", "file10"]);
+    // `?` is exactly one character
+    assert_eq!(run(&image, "ls file?"), ["file1", "file2"]);
+    assert_eq!(run(&image, "ls file??"), ["file10"]);
+    // classes, ranges and negated classes
+    assert_eq!(run(&image, "ls [ac].*"), ["a.txt", "c.log"]);
+    assert_eq!(run(&image, "ls [a-b].txt"), ["a.txt", "b.txt"]);
+    assert_eq!(run(&image, "ls [!a]*.txt"), ["b.txt"]);
+    // a pattern starting with `.` matches dotfiles, never `.` and `..`
+    assert_eq!(run(&image, "ls .*"), [".hidden.txt"]);
+    // the directory part is kept in front of each match
+    assert_eq!(run(&image, "ls dir/*.txt"), ["dir/x.txt"]);
+    // like a shell, a pattern matching nothing is passed on as it is
+    assert_eq!(
+        run(&image, "ls *.none"),
+        [
+            "ls: *.none: No such file or directory",
+            "unable to read directory in ls"
+        ]
+    );
+}
+
+// commands work on every match, and only on the matches
+#[test]
+fn commands_run_on_each_match() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        for name in ["tmp1", "tmp2", "tmp10", "keep"] {
+            fs::write(tree.join(name), name).unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "rm tmp?\nls\n");
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert!(lines.contains(&"keep\tlost+found\ttmp10"), "{}", stdout);
+}
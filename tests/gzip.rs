@@ -0,0 +1,74 @@
+mod common;
+
+use common::{fixture, shell_status};
+use ext2::image::{self, ImageFile};
+use ext2::structs::InodeNo;
+use ext2::volume::{BlockDevice, Volume};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// the fixture's image gzipped next to it
+fn gzipped(image: &[u8], path: &Path) -> PathBuf {
+    let path = path.with_extension("ext2.gz");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(image).unwrap();
+    fs::write(&path, encoder.finish().unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn gzipped_images_read_the_same() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let path = gzipped(&fixture.image, &fixture.image_path());
+    // small enough for memory, and spilled to a file with no room there
+    for (limit, in_memory) in [(image::IN_MEMORY_LIMIT, true), (0, false)] {
+        let opened = image::open_with(&path, limit).unwrap();
+        assert!(opened.is_compressed());
+        assert_eq!(matches!(opened, ImageFile::Memory(_)), in_memory);
+        assert_eq!(opened.size(), fixture.image.len() as u64);
+        let volume = Volume::open(&opened).unwrap();
+        let inode = volume.resolve(b"/one_byte").unwrap();
+        let mut byte = [0; 1];
+        assert_eq!(volume.read_file(inode, 0, &mut byte).unwrap(), 1);
+        assert_eq!(&byte, b"x");
+        assert_eq!(volume.read_dir(InodeNo::ROOT).unwrap().len(), {
+            let plain = Volume::open(&fixture.image[..]).unwrap();
+            plain.read_dir(InodeNo::ROOT).unwrap().len()
+        });
+        assert_eq!(opened.into_bytes().unwrap(), fixture.image);
+    }
+    let plain = image::open(&fixture.image_path()).unwrap();
+    assert!(!plain.is_compressed());
+}
+
+#[test]
+fn shell_and_diff_open_gzipped_images() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let path = gzipped(&fixture.image, &fixture.image_path());
+    let (ok, stdout) = shell_status(&[], &path, "cat one_byte\nmkdir new\nls\n");
+    assert!(ok);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+    assert!(stdout.contains("new"), "{}", stdout);
+
+    // there's no writing back to a compressed file
+    let (ok, stdout) = shell_status(&["--mmap-write"], &path, "ls\n");
+    assert!(!ok);
+    assert!(stdout.contains("is compressed"), "{}", stdout);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_ext2diff"))
+        .arg(&path)
+        .arg(fixture.image_path())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::fixture_from;
+use ext2::error::Ext2Error;
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// byte offsets of superblock fields in the image
+const INODES_COUNT: usize = 1024;
+const BLOCKS_COUNT: usize = 1024 + 4;
+const LOG_BLOCK_SIZE: usize = 1024 + 24;
+const BLOCKS_PER_GROUP: usize = 1024 + 32;
+const INODES_PER_GROUP: usize = 1024 + 40;
+const MAGIC: usize = 1024 + 56;
+const INODE_SIZE: usize = 1024 + 88;
+
+fn set_u32(image: &mut [u8], offset: usize, value: u32) {
+    image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn get_u32(image: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(image[offset..offset + 4].try_into().unwrap())
+}
+
+// the shell refuses the image with the library's reason, rather than
+// panicking or starting on it
+fn shell_refuses(dir: &Path, image: &[u8], reason: &str) {
+    let path = dir.join("broken.ext2");
+    fs::write(&path, image).unwrap();
+    // it quits before reading any commands
+    let output = Command::new(env!("CARGO_BIN_EXE_ext2"))
+        .arg(&path)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(
+        stdout.contains("unable to mount file system: ") && stdout.contains(reason),
+        "{}",
+        stdout
+    );
+}
+
+// each broken superblock is refused with what is wrong with it, by the
+// library and by the shell, and never panics either of them
+#[test]
+fn broken_headers_are_refused() {
+    let mut corpus: Vec<(&str, Vec<u8>, &str)> = Vec::new();
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture_from(block_size, "1M", &[], |tree| {
+            fs::write(tree.join("file"), b"file\n").unwrap();
+        }) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let image = &fixture.image;
+        corpus.push((
+            "truncated superblock",
+            image[..1500].to_vec(),
+            "too small to hold a superblock",
+        ));
+        let mut bad_magic = image.clone();
+        bad_magic[MAGIC..MAGIC + 2].copy_from_slice(&0x1234u16.to_le_bytes());
+        corpus.push(("bad magic", bad_magic, "bad magic number 0x1234"));
+        let mut huge_blocks = image.clone();
+        set_u32(&mut huge_blocks, LOG_BLOCK_SIZE, 200);
+        corpus.push(("log_block_size 200", huge_blocks, "log_block_size 200"));
+        let mut no_blocks_per_group = image.clone();
+        set_u32(&mut no_blocks_per_group, BLOCKS_PER_GROUP, 0);
+        corpus.push((
+            "blocks_per_group 0",
+            no_blocks_per_group,
+            "blocks_per_group 0",
+        ));
+        let mut small_inodes = image.clone();
+        small_inodes[INODE_SIZE..INODE_SIZE + 2].copy_from_slice(&100u16.to_le_bytes());
+        corpus.push(("inode_size 100", small_inodes, "inode_size 100"));
+    }
+
+    let Some(fixture) = fixture_from(4096, "1M", &[], |_| {}) else {
+        return;
+    };
+    // a 4K image read as 1K blocks has groups too big for one block's
+    // bitmap
+    let mut zero_log = fixture.image.clone();
+    set_u32(&mut zero_log, LOG_BLOCK_SIZE, 0);
+    corpus.push((
+        "log_block_size 0 on 4K blocks",
+        zero_log,
+        "blocks_per_group 32768 is out of range",
+    ));
+
+    // one block group of one block: its descriptors would be in the block
+    // after the end of the file system
+    let Some(small) = fixture_from(1024, "1M", &[], |_| {}) else {
+        return;
+    };
+    let mut past_the_end = small.image.clone();
+    set_u32(&mut past_the_end, BLOCKS_COUNT, 2);
+    set_u32(&mut past_the_end, BLOCKS_PER_GROUP, 1);
+    let inodes_per_group = get_u32(&past_the_end, INODES_PER_GROUP);
+    set_u32(&mut past_the_end, INODES_COUNT, inodes_per_group);
+    corpus.push((
+        "descriptor table past the end",
+        past_the_end,
+        "does not fit on the device",
+    ));
+
+    for (what, image, reason) in &corpus {
+        match Volume::open(&image[..]) {
+            Err(Ext2Error::Corrupt(message)) => {
+                assert!(message.contains(reason), "{}: {}", what, message)
+            }
+            Err(e) => panic!("{}: {}", what, e),
+            Ok(_) => panic!("{}: opened", what),
+        }
+        shell_refuses(&small.tree_path(), image, reason);
+    }
+}
@@ -0,0 +1,205 @@
+mod common;
+
+use common::{doubly_indirect_len, fixture, pattern, Fixture, LEAF_CONTENTS, LINK_TARGET};
+use ext2::error::Ext2Error;
+use ext2::metadata::FileType;
+use ext2::structs::{InodeNo, TypeIndicator};
+use ext2::volume::Volume;
+
+// run `test` on a 1K and a 4K block fixture, or on nothing if they can't be made
+fn with_fixtures(test: impl Fn(&Fixture, &Volume<&[u8]>)) {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let volume = Volume::open(&fixture.image[..]).unwrap();
+        assert_eq!(volume.block_size(), block_size);
+        test(&fixture, &volume);
+    }
+}
+
+fn read_all(volume: &Volume<&[u8]>, path: &str) -> Vec<u8> {
+    let inode = volume.resolve(path.as_bytes()).unwrap();
+    let mut data = vec![0; volume.metadata(inode).unwrap().size as usize];
+    let read = volume.read_file(inode, 0, &mut data).unwrap();
+    assert_eq!(read, data.len());
+    data
+}
+
+fn names(volume: &Volume<&[u8]>, path: &str) -> Vec<String> {
+    let dir = volume.resolve(path.as_bytes()).unwrap();
+    let mut names: Vec<String> = volume
+        .read_dir(dir)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn root_listing() {
+    with_fixtures(|_, volume| {
+        assert_eq!(
+            names(volume, "/"),
+            [
+                ".",
+                "..",
+                "dir",
+                "doubly",
+                "hard_a",
+                "hard_b",
+                "link",
+                "lost+found",
+                "one_byte",
+                "twelve_blocks"
+            ]
+        );
+    });
+}
+
+#[test]
+fn nested_directories() {
+    with_fixtures(|_, volume| {
+        assert_eq!(names(volume, "/dir"), [".", "..", "nested", "sibling.txt"]);
+        assert_eq!(names(volume, "/dir/nested/deeper"), [".", "..", "leaf.txt"]);
+        assert_eq!(
+            read_all(volume, "/dir/nested/deeper/leaf.txt"),
+            LEAF_CONTENTS
+        );
+
+        // `..` leads back up, and each directory has one link per subdirectory
+        let dir = volume.resolve(b"/dir").unwrap();
+        assert_eq!(volume.resolve(b"/dir/nested/..").unwrap(), dir);
+        assert_eq!(volume.resolve(b"/dir/..").unwrap(), InodeNo::ROOT);
+        let metadata = volume.metadata(dir).unwrap();
+        assert_eq!(metadata.file_type, FileType::Directory);
+        assert_eq!(metadata.nlink, 3);
+
+        let entry = volume
+            .read_dir(dir)
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.name == "nested")
+            .unwrap();
+        assert_eq!(entry.kind, TypeIndicator::Directory);
+    });
+}
+
+#[test]
+fn one_byte_file() {
+    with_fixtures(|_, volume| {
+        assert_eq!(read_all(volume, "/one_byte"), b"x");
+        let metadata = volume
+            .metadata(volume.resolve(b"/one_byte").unwrap())
+            .unwrap();
+        assert_eq!(metadata.file_type, FileType::Regular);
+        assert_eq!(metadata.perms, 0o640);
+        assert_eq!(metadata.mode_string(), "-rw-r-----");
+        assert_eq!(metadata.nlink, 1);
+    });
+}
+
+#[test]
+fn exactly_twelve_blocks() {
+    with_fixtures(|fixture, volume| {
+        let data = read_all(volume, "/twelve_blocks");
+        assert_eq!(data.len(), 12 * fixture.block_size);
+        assert_eq!(data, pattern(12 * fixture.block_size));
+    });
+}
+
+#[test]
+fn doubly_indirect_file() {
+    with_fixtures(|fixture, volume| {
+        let len = doubly_indirect_len(fixture.block_size);
+        assert_eq!(read_all(volume, "/doubly"), pattern(len));
+
+        // a read that starts in the doubly indirect part and runs off the end
+        let inode = volume.resolve(b"/doubly").unwrap();
+        let offset = len - 100;
+        let mut tail = [0; 1000];
+        assert_eq!(
+            volume.read_file(inode, offset as u64, &mut tail).unwrap(),
+            100
+        );
+        assert_eq!(tail[..100], pattern(len)[offset..]);
+    });
+}
+
+#[test]
+fn symlink() {
+    with_fixtures(|_, volume| {
+        let inode = volume.resolve(b"/link").unwrap();
+        let metadata = volume.metadata(inode).unwrap();
+        assert!(metadata.is_symlink());
+        assert_eq!(metadata.size, LINK_TARGET.len() as u64);
+        let mut target = [0; 64];
+        let len = volume.read_link(inode, &mut target).unwrap();
+        assert_eq!(&target[..len], LINK_TARGET.as_bytes());
+    });
+}
+
+#[test]
+fn hard_links() {
+    with_fixtures(|_, volume| {
+        let a = volume.resolve(b"/hard_a").unwrap();
+        let b = volume.resolve(b"/hard_b").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(volume.metadata(a).unwrap().nlink, 2);
+        assert_eq!(read_all(volume, "/hard_b"), b"one inode, two names\n");
+    });
+}
+
+#[test]
+fn deleted_entry() {
+    with_fixtures(|_, volume| {
+        assert!(!names(volume, "/").contains(&"deleted.txt".to_string()));
+        assert!(matches!(
+            volume.resolve(b"/deleted.txt"),
+            Err(Ext2Error::NotFound(_))
+        ));
+    });
+}
+
+#[test]
+fn not_a_directory() {
+    with_fixtures(|_, volume| {
+        assert!(matches!(
+            volume.resolve(b"/one_byte/x"),
+            Err(Ext2Error::NotADirectory(_))
+        ));
+    });
+}
+
+#[test]
+fn inode_numbers_out_of_range() {
+    with_fixtures(|_, volume| {
+        let superblock = volume.superblock();
+        let count = superblock.inodes_count.get();
+        assert!(matches!(
+            InodeNo::new(0, superblock),
+            Err(Ext2Error::InodeOutOfRange(0, c)) if c == count
+        ));
+        assert!(matches!(
+            InodeNo::new(count as usize + 1, superblock),
+            Err(Ext2Error::InodeOutOfRange(_, _))
+        ));
+        let last = InodeNo::new(count as usize, superblock).unwrap();
+        assert!(volume.read_inode(last).is_ok());
+    });
+}
+
+#[test]
+fn owned_image() {
+    with_fixtures(|fixture, _| {
+        // what an embedder that read the image itself does
+        let volume = Volume::open(fixture.image.clone()).unwrap();
+        let leaf = volume.resolve(LINK_TARGET.as_bytes()).unwrap();
+        let mut contents = vec![0; LEAF_CONTENTS.len()];
+        volume.read_file(leaf, 0, &mut contents).unwrap();
+        assert_eq!(contents, LEAF_CONTENTS);
+    });
+}
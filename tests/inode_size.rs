@@ -0,0 +1,118 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::metadata::FileType;
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::fs;
+
+const FILES: usize = 40;
+
+fn contents(i: usize) -> String {
+    format!("file number {}\n", i)
+}
+
+// inode table slots are the superblock's inode size apart, not
+// size_of::<Inode>(). with enough files the later inodes land well past
+// where the wrong stride would look for them
+#[test]
+fn inode_table_stride() {
+    for inode_size in [128, 256] {
+        let Some(fixture) = fixture_from(1024, "4M", &["-I", &inode_size.to_string()], |tree| {
+            fs::create_dir(tree.join("sub")).unwrap();
+            for i in 0..FILES {
+                fs::write(tree.join(format!("f{}", i)), contents(i)).unwrap();
+            }
+        }) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+
+        let volume = Volume::open(&fixture.image[..]).unwrap();
+        assert_eq!(volume.superblock().inode_size(), inode_size);
+        let root = volume.metadata(InodeNo::ROOT).unwrap();
+        assert_eq!(root.file_type, FileType::Directory);
+        // ., .. from lost+found and sub, and its own name
+        assert_eq!(root.nlink, 4);
+        for i in 0..FILES {
+            let inode = volume.resolve(format!("/f{}", i).as_bytes()).unwrap();
+            let mut data = [0; 64];
+            let len = volume.read_file(inode, 0, &mut data).unwrap();
+            assert_eq!(&data[..len], contents(i).as_bytes());
+        }
+
+        // and the shell's get_inode
+        let script: String = (0..FILES).map(|i| format!("cat /f{}\n", i)).collect();
+        let stdout = shell(&fixture.image_path(), &script);
+        for i in 0..FILES {
+            assert!(
+                stdout.contains(&contents(i)),
+                "{} byte inodes, f{} missing from\n{}",
+                inode_size,
+                i,
+                stdout
+            );
+        }
+    }
+}
+
+// the shell's get_inode, for inodes in the 2nd and the 20th block of the
+// inode table: the first slot of each block, and the last, where an inode
+// ends right at the end of the block. with 4096 byte blocks the 2nd block
+// is past the reserved inodes, and FILLER files reach the 20th
+const FILLER: usize = 700;
+
+#[test]
+fn inodes_deep_in_the_table() {
+    for inode_size in [128, 256] {
+        let Some(fixture) = fixture_from(
+            4096,
+            "8M",
+            &["-I", &inode_size.to_string(), "-N", "1024"],
+            |tree| {
+                for i in 0..FILLER {
+                    fs::write(tree.join(format!("f{}", i)), vec![b'x'; i + 1]).unwrap();
+                }
+            },
+        ) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let volume = Volume::open(&fixture.image[..]).unwrap();
+        let per_block = 4096 / inode_size;
+        let wanted: Vec<usize> = [1, 19]
+            .iter()
+            .flat_map(|block| [block * per_block + 1, (block + 1) * per_block])
+            .collect();
+        // which file got each of the wanted inodes
+        let mut files = Vec::new();
+        for i in 0..FILLER {
+            let inode = volume.resolve(format!("/f{}", i).as_bytes()).unwrap();
+            if wanted.contains(&(inode.get() as usize)) {
+                files.push((i, inode.get()));
+            }
+        }
+        assert_eq!(files.len(), wanted.len(), "{:?}", files);
+
+        let script: String = files
+            .iter()
+            .map(|(i, _)| format!("stat /f{}\n", i))
+            .collect();
+        let stdout = shell(&fixture.image_path(), &script);
+        for (i, inode) in files {
+            assert!(
+                stdout.contains(&format!("Inode: {}\t", inode)),
+                "{}",
+                stdout
+            );
+            assert!(
+                stdout.contains(&format!("Size: {}\t", i + 1)),
+                "{} byte inodes, f{} (inode {}) in\n{}",
+                inode_size,
+                i,
+                inode,
+                stdout
+            );
+        }
+    }
+}
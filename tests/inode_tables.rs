@@ -0,0 +1,83 @@
+mod common;
+
+use common::fixture_from;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::Instant;
+
+fn run(image: &Path, script: &str) -> Output {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_ext2"))
+        .arg(image)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    shell.wait_with_output().unwrap()
+}
+
+// 10,000 files spread over every group's inode table, each stat'ed by
+// lsinodes. the time is only printed, it depends too much on the machine
+#[test]
+fn stat_ten_thousand_inodes() {
+    let Some(fixture) = fixture_from(1024, "16M", &["-N", "12000"], |tree| {
+        // 100 to a directory keeps each one within its direct blocks
+        for dir in 0..100 {
+            let dir = tree.join(format!("d{:02}", dir));
+            fs::create_dir(&dir).unwrap();
+            for file in 0..100 {
+                fs::write(dir.join(format!("f{:02}", file)), b"").unwrap();
+            }
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let start = Instant::now();
+    let output = run(&fixture.image_path(), "lsinodes\n");
+    eprintln!("lsinodes took {:?}", start.elapsed());
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let files = stdout
+        .lines()
+        .filter(|line| line.split('\t').nth(1) == Some("file"))
+        .count();
+    assert_eq!(files, 10_000, "{}", stdout);
+}
+
+// an inode table past the end of the device is caught at mount, before
+// any inode is read out of it
+#[test]
+fn inode_table_out_of_range_fails_mount() {
+    let Some(fixture) = fixture_from(1024, "16M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let mut image = fixture.image.clone();
+    // 1K blocks: the descriptors are in block 2, 32 bytes each, with the
+    // inode table at byte 8. this is group 1's
+    let field = 2 * 1024 + 32 + 8;
+    image[field..field + 4].copy_from_slice(&0xFF_FFFFu32.to_le_bytes());
+    let broken = fixture.tree_path().with_file_name("broken.ext2");
+    fs::write(&broken, &image).unwrap();
+    let output = run(&broken, "ls\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("unable to mount file system: corrupt file system: "),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("block group 1 has inode table at 16777215"),
+        "{}",
+        stdout
+    );
+}
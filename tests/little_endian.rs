@@ -0,0 +1,121 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::fs;
+
+// the raw blocks of a directory
+fn dir_blocks<'a>(volume: &Volume<&[u8]>, image: &'a [u8], dir: InodeNo) -> Vec<&'a [u8]> {
+    let block_size = volume.block_size();
+    let node = volume.read_inode(dir).unwrap();
+    let blocks = volume.metadata(dir).unwrap().size as usize / block_size;
+    node.direct_pointer[..blocks]
+        .iter()
+        .map(|pointer| {
+            let start = pointer.get() as usize * block_size;
+            &image[start..start + block_size]
+        })
+        .collect()
+}
+
+// the record lengths of a block's entries, read little and big endian
+fn rec_lens(block: &[u8], read: fn([u8; 2]) -> u16) -> Vec<usize> {
+    let mut lens = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= block.len() {
+        let len = read([block[offset + 4], block[offset + 5]]) as usize;
+        if len == 0 {
+            break;
+        }
+        lens.push(len);
+        offset += len;
+    }
+    lens
+}
+
+// what the shell writes into directory blocks is little endian whatever
+// the host is: a moved directory's `..`, the record lengths of entries it
+// adds and of a block it adds, all read back the same by the library, and
+// none of them make sense read big endian
+#[test]
+fn directory_records_are_little_endian() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        fs::create_dir_all(tree.join("a/sub")).unwrap();
+        fs::create_dir(tree.join("b")).unwrap();
+        fs::create_dir(tree.join("small")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let touches: String = (0..100)
+        .map(|i| format!("touch small/f{:03}\n", i))
+        .collect();
+    let clone = fixture.tree_path().with_file_name("written.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("mv a/sub b\n{}clone {}\n", touches, clone.display()),
+    );
+    assert!(!stdout.contains("unable"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let b = volume.resolve(b"/b").unwrap();
+    let sub = volume.resolve(b"/b/sub").unwrap();
+    assert_eq!(volume.lookup(sub, b"..").unwrap(), b);
+    let first = dir_blocks(&volume, &image, sub)[0];
+    let dot_dot: [u8; 4] = first[12..16].try_into().unwrap();
+    assert_eq!(u32::from_le_bytes(dot_dot), b.get());
+    assert_ne!(u32::from_be_bytes(dot_dot), b.get());
+
+    let small = volume.resolve(b"/small").unwrap();
+    assert_eq!(volume.read_dir(small).unwrap().len(), 102);
+    let blocks = dir_blocks(&volume, &image, small);
+    assert_eq!(blocks.len(), 2);
+    for block in blocks {
+        let little = rec_lens(block, u16::from_le_bytes);
+        assert_eq!(little.iter().sum::<usize>(), 1024);
+        let big = rec_lens(block, u16::from_be_bytes);
+        assert_ne!(big, little);
+    }
+}
+
+// fields are read little endian whatever the host is: bytes written in
+// little endian order into the superblock, a group descriptor and an inode
+// read back as the value they encode, and not as its byte swap
+#[test]
+fn struct_fields_are_little_endian() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("file"), b"contents\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let original = Volume::open(&fixture.image[..]).unwrap();
+    let inode = original.resolve(b"/file").unwrap();
+    let offset = original.inode_offset(inode).unwrap() as usize;
+    let mut image = fixture.image.clone();
+    let superblock = 1024;
+    // with 1024 byte blocks the descriptors are in block 2
+    let descriptor = 2 * 1024;
+    // superblock mtime and wtime, dirs_count of group 0, and an inode's
+    // uid and mtime
+    image[superblock + 44..superblock + 48].copy_from_slice(&0x0403_0201u32.to_le_bytes());
+    image[superblock + 48..superblock + 52].copy_from_slice(&0x0807_0605u32.to_le_bytes());
+    image[descriptor + 16..descriptor + 18].copy_from_slice(&0x0102u16.to_le_bytes());
+    image[offset + 2..offset + 4].copy_from_slice(&0x0304u16.to_le_bytes());
+    image[offset + 16..offset + 20].copy_from_slice(&0x0a0b_0c0du32.to_le_bytes());
+
+    let volume = Volume::open(&image[..]).unwrap();
+    let superblock = volume.superblock();
+    assert_eq!(superblock.mtime.get(), 0x0403_0201);
+    assert_eq!(superblock.wtime.get(), 0x0807_0605);
+    assert_eq!(superblock.magic.get(), 0xef53);
+    assert_eq!(volume.block_groups()[0].dirs_count.get(), 0x0102);
+    let node = volume.read_inode(inode).unwrap();
+    assert_eq!(node.uid.get(), 0x0304);
+    assert_eq!(node.mtime.get(), 0x0a0b_0c0d);
+    let metadata = volume.metadata(inode).unwrap();
+    assert_eq!((metadata.uid, metadata.mtime), (0x0304, 0x0a0b_0c0d));
+    assert_eq!(metadata.size, 9);
+}
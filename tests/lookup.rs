@@ -0,0 +1,95 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::volume::Volume;
+use std::fs;
+
+// lookup goes from a name to its inode and rlookup back, to both names of
+// a hard linked file
+#[test]
+fn names_and_inodes_both_ways() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/a"), b"linked\n").unwrap();
+        fs::hard_link(tree.join("dir/a"), tree.join("dir/b")).unwrap();
+        fs::write(tree.join("dir/other"), b"other\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let dir = volume.resolve(b"/dir").unwrap();
+    let a = volume.resolve(b"/dir/a").unwrap();
+    assert_eq!(volume.resolve(b"/dir/b").unwrap(), a);
+
+    // each answer is on lines of its own, the first one too
+    let stdout = "\n".to_string()
+        + &shell(
+            &fixture.image_path(),
+            &format!(
+                "lookup dir a\nlookup / dir\nrlookup dir {a}\nrlookup / <{dir}>\ncd dir\n\
+                 lookup . b\nrlookup . {a}\n"
+            ),
+        );
+    assert_eq!(
+        stdout.matches(&format!("\n{}\n", a)).count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(&format!("\n{}\n", dir)), "{}", stdout);
+    assert_eq!(stdout.matches("\na\nb\n").count(), 2, "{}", stdout);
+    assert!(stdout.contains("\ndir\n"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
+
+// a missing name, an inode nothing in the directory points at, and a file
+// given as the directory are all reported
+#[test]
+fn misses_are_reported() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("file"), b"data\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "lookup / missing\nlookup file x\nrlookup / 100\nrlookup file 2\nrlookup / x\n\
+         lookup /\n",
+    );
+    assert!(
+        stdout.contains("lookup: missing: No such file or directory"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("lookup: x: Not a directory"), "{}", stdout);
+    assert!(
+        stdout.contains("rlookup: no entry of / points at inode 100"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("rlookup: file: Not a directory"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("rlookup: x is not an inode number"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("usage: lookup dir name"), "{}", stdout);
+    assert_eq!(
+        stdout.matches("unable to look up name").count(),
+        3,
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        stdout.matches("unable to look up inode").count(),
+        3,
+        "{}",
+        stdout
+    );
+}
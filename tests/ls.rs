@@ -0,0 +1,99 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+// the lines the shell printed for one command, without the prompts and
+// the tab after each name
+fn lines(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .map(|line| line.trim_start_matches(":> ").trim_end())
+        .filter(|line| *line != "bye!")
+        .collect()
+}
+
+// each directory gets a `path:` header and its listing, then its
+// subdirectories follow in name order, each under its full path. `.` and
+// `..` show with -a but are never listed again
+#[test]
+fn recursive_listing_has_a_header_per_directory() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir_all(tree.join("top/b/deep")).unwrap();
+        fs::create_dir(tree.join("top/a")).unwrap();
+        fs::write(tree.join("top/file"), b"file\n").unwrap();
+        fs::write(tree.join("top/b/inner"), b"inner\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "ls -R top\n");
+    assert_eq!(
+        lines(&stdout),
+        [
+            "top:",
+            "a\tb\tfile",
+            "",
+            "top/a:",
+            "",
+            "",
+            "top/b:",
+            "deep\tinner",
+            "",
+            "top/b/deep:",
+            "",
+        ],
+        "{}",
+        stdout
+    );
+
+    let stdout = shell(&fixture.image_path(), "ls -aR top/b/\n");
+    assert_eq!(
+        lines(&stdout),
+        ["top/b/:", ".\t..\tdeep\tinner", "", "top/b/deep:", ".\t..",],
+        "{}",
+        stdout
+    );
+
+    let stdout = shell(&fixture.image_path(), "ls -R --depth=1 top\n");
+    let headers: Vec<&str> = lines(&stdout)
+        .into_iter()
+        .filter(|line| line.ends_with(':'))
+        .collect();
+    assert_eq!(headers, ["top:", "top/a:", "top/b:"], "{}", stdout);
+}
+
+// entries sort by name, by size or mtime with ties left in name order, and
+// -r turns whichever order around
+#[test]
+fn sort_orders() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("sorted")).unwrap();
+        for (name, size) in [("a", 100), ("b", 300), ("c", 200), ("d", 200)] {
+            fs::write(tree.join("sorted").join(name), vec![b'x'; size]).unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    for (name, mtime) in [("a", 100), ("b", 200), ("c", 300), ("d", 300)] {
+        if fixture
+            .debugfs(&format!("sif /sorted/{} mtime {}", name, mtime))
+            .is_none()
+        {
+            eprintln!("debugfs not available, skipping");
+            return;
+        }
+    }
+    for (flags, order) in [
+        ("", "a\tb\tc\td"),
+        ("-S", "b\tc\td\ta"),
+        ("-t", "c\td\tb\ta"),
+        ("-r", "d\tc\tb\ta"),
+        ("-Sr", "a\td\tc\tb"),
+        ("-tr", "a\tb\td\tc"),
+    ] {
+        let stdout = shell(&fixture.image_path(), &format!("ls {} sorted\n", flags));
+        assert_eq!(lines(&stdout), [order], "ls {}: {}", flags, stdout);
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+// the table lines of the listing that starts with the header at `from`
+fn rows(stdout: &str, from: usize) -> Vec<&str> {
+    stdout[from..]
+        .lines()
+        .skip(1)
+        .take_while(|line| line.as_bytes().first().is_some_and(u8::is_ascii_digit))
+        .collect()
+}
+
+#[test]
+fn used_inodes_are_listed() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("file"), b"four").unwrap();
+        fs::create_dir(tree.join("dir")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "lsinodes\nlsinodes --all\n");
+    let headers: Vec<usize> = stdout
+        .match_indices("inode\ttype\tsize\tlinks\n")
+        .map(|(at, _)| at)
+        .collect();
+    assert_eq!(headers.len(), 2, "{}", stdout);
+
+    let used = rows(&stdout, headers[0]);
+    // the root, lost+found and the two made by mke2fs, in inode order
+    assert_eq!(used.len(), 4, "{:?}", used);
+    assert!(used[0].starts_with("2\tdir\t1024\t"), "{:?}", used);
+    assert!(used[1].starts_with("11\tdir\t"), "{:?}", used);
+    assert!(used.contains(&"12\tfile\t4\t1") || used.contains(&"13\tfile\t4\t1"));
+
+    // with --all the reserved inodes come first, the bad blocks inode too
+    let all = rows(&stdout, headers[1]);
+    assert!(all.len() > used.len(), "{:?}", all);
+    assert!(all[0].starts_with("1\t"), "{:?}", all);
+    assert!(used.iter().all(|row| all.contains(row)));
+
+    let stdout = shell(&fixture.image_path(), "lsinodes --bad\n");
+    assert!(stdout.contains("usage: lsinodes [--all]"), "{}", stdout);
+    assert!(stdout.contains("unable to list inodes"), "{}", stdout);
+}
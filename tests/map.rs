@@ -0,0 +1,104 @@
+mod common;
+
+use common::{fixture_from, pattern, shell};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// what debugfs prints for `request`, without its banner
+fn debugfs(image: &Path, request: &str) -> Option<String> {
+    let output = Command::new("debugfs")
+        .arg("-R")
+        .arg(request)
+        .arg(image)
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// bmap and imap against debugfs's own, over a file that reaches into its
+// doubly indirect block and a directory of files spread over the groups
+#[test]
+fn bmap_and_imap_agree_with_debugfs() {
+    for block_size in [1024, 4096] {
+        let per_block = block_size / 4;
+        let len = (12 + per_block + 3) * block_size;
+        let Some(fixture) = fixture_from(block_size, "16M", &[], |tree| {
+            fs::write(tree.join("big"), pattern(len)).unwrap();
+            fs::create_dir(tree.join("many")).unwrap();
+            for i in 0..40 {
+                fs::write(tree.join(format!("many/{}", i)), b"x").unwrap();
+            }
+        }) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let image = fixture.image_path();
+        // the last direct block, the first few reached through each kind of
+        // indirect block and one past the end of the file
+        let logical = [
+            0,
+            11,
+            12,
+            13,
+            12 + per_block - 1,
+            12 + per_block,
+            12 + per_block + 2,
+        ];
+        let inodes = [1, 2, 11, 12, 13, 40, 52];
+        let mut script = String::new();
+        for n in logical.iter().chain([len / block_size + 5].iter()) {
+            script.push_str(&format!("bmap /big {}\n", n));
+        }
+        for inode in inodes {
+            script.push_str(&format!("imap <{}>\n", inode));
+        }
+        let stdout = shell(&image, &script);
+
+        let Some(mut expected) = debugfs(&image, "bmap /big 0") else {
+            eprintln!("debugfs not available, skipping");
+            return;
+        };
+        for n in logical[1..].iter().chain([len / block_size + 5].iter()) {
+            expected += &debugfs(&image, &format!("bmap /big {}", n)).unwrap();
+        }
+        for inode in inodes {
+            expected += &debugfs(&image, &format!("imap <{}>", inode)).unwrap();
+        }
+        assert!(
+            stdout.contains(&expected),
+            "{}\nwanted\n{}",
+            stdout,
+            expected
+        );
+        // the file has no hole, so only the block past its end is 0
+        assert_eq!(expected.matches("\n0\n").count(), 1, "{}", expected);
+    }
+}
+
+#[test]
+fn bad_arguments_are_refused() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("file"), b"data").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "bmap /file\nbmap /file x\nbmap /missing 0\nbmap /file 99999999999\n\
+         imap\nimap 0\nimap 100000\nimap <x>\n",
+    );
+    assert_eq!(
+        stdout.matches("unable to map block").count(),
+        4,
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        stdout.matches("unable to map inode").count(),
+        4,
+        "{}",
+        stdout
+    );
+}
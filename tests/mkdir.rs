@@ -0,0 +1,173 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+
+// the inode numbers stat printed, in order
+fn stat_inodes(stdout: &str) -> Vec<u32> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Inode: "))
+        .map(|rest| rest.split('\t').next().unwrap().parse().unwrap())
+        .collect()
+}
+
+// a new directory's `.` is itself and its `..` the directory it was made
+// in, which isn't the root here so the two can't be mixed up
+#[test]
+fn dot_and_dot_dot_of_a_new_directory() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture_from(block_size, "4M", &[], |_| {}) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let clone = fixture.tree_path().with_file_name("made.ext2");
+        let stdout = shell(
+            &fixture.image_path(),
+            &format!(
+                "mkdir p\nmkdir p/d\nstat p\nstat p/d\ncd p/d\nstat .\ncd .\nstat .\n\
+                 cd ..\nstat .\nclone {}\n",
+                clone.display()
+            ),
+        );
+        assert!(!stdout.contains("unable"), "{}", stdout);
+        let inodes = stat_inodes(&stdout);
+        let [p, d, in_d, after_dot, after_dot_dot] = inodes[..] else {
+            panic!("{}", stdout);
+        };
+        assert_ne!(p, d);
+        assert_eq!(in_d, d);
+        assert_eq!(after_dot, d);
+        assert_eq!(after_dot_dot, p);
+
+        // the block itself: `.` takes 12 bytes and `..` the rest
+        let image = std::fs::read(&clone).unwrap();
+        let volume = Volume::open(&image[..]).unwrap();
+        let dir = volume.resolve(b"/p/d").unwrap();
+        assert_eq!(dir.get(), d);
+        let mut block = vec![0; block_size];
+        assert_eq!(volume.read_file(dir, 0, &mut block).unwrap(), block_size);
+        let le32 = |at: usize| u32::from_le_bytes(block[at..at + 4].try_into().unwrap());
+        let le16 = |at: usize| u16::from_le_bytes([block[at], block[at + 1]]) as usize;
+        assert_eq!((le32(0), le16(4), &block[6..9]), (d, 12, &[1, 2, b'.'][..]));
+        assert_eq!(
+            (le32(12), le16(16), &block[18..22]),
+            (p, block_size - 12, &[2, 2, b'.', b'.'][..])
+        );
+    }
+}
+
+// making the same directory twice fails the second time, without a second
+// entry or a leaked inode, and the new directory lists only `.` and `..`
+#[test]
+fn mkdir_twice_says_file_exists() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("made.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "mkdir foo\nmkdir foo\ncd foo\nls -a\ncd foo\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(stdout.contains("mkdir: foo: File exists"), "{}", stdout);
+    assert!(stdout.contains(".\t..\t\n"), "{}", stdout);
+    assert!(
+        stdout.contains("cd: foo: No such file or directory"),
+        "{}",
+        stdout
+    );
+
+    let image = std::fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let root = volume.read_dir(InodeNo::ROOT).unwrap();
+    let foos = root.iter().filter(|entry| entry.name == "foo").count();
+    assert_eq!(foos, 1);
+    let original = Volume::open(&fixture.image[..]).unwrap();
+    assert_eq!(
+        volume.superblock().free_inodes_count.get(),
+        original.superblock().free_inodes_count.get() - 1
+    );
+}
+
+// `mkdir -p` makes every missing directory along the path, goes through
+// the ones that are there, and only fails on something that isn't a
+// directory
+#[test]
+fn mkdir_p_makes_the_missing_parents() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |tree| {
+        std::fs::create_dir(tree.join("have")).unwrap();
+        std::fs::write(tree.join("have/file"), b"file\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let original = Volume::open(&fixture.image[..]).unwrap();
+    let have = original.resolve(b"/have").unwrap();
+    let clone = fixture.tree_path().with_file_name("made.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "mkdir -p have/one/two/three\nmkdir -p have/one/two\nmkdir -p have/file/sub\n\
+             fsck\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(
+        stdout.contains("mkdir: file: Not a directory"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("File exists"), "{}", stdout);
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    let image = std::fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert_eq!(volume.resolve(b"/have").unwrap(), have);
+    let one = volume.resolve(b"/have/one").unwrap();
+    let two = volume.resolve(b"/have/one/two").unwrap();
+    let three = volume.resolve(b"/have/one/two/three").unwrap();
+    assert_eq!(volume.lookup(one, b"..").unwrap(), have);
+    assert_eq!(volume.lookup(two, b"..").unwrap(), one);
+    assert_eq!(volume.lookup(three, b"..").unwrap(), two);
+    assert!(volume.metadata(three).unwrap().is_dir());
+    assert_eq!(
+        volume.superblock().free_inodes_count.get(),
+        original.superblock().free_inodes_count.get() - 3
+    );
+}
+
+// a fresh directory has 2 links, its entry and its `.`, and each
+// subdirectory adds one with its `..`: stat and the inode both say 4 after
+// two of them
+#[test]
+fn subdirectories_add_to_the_parents_links() {
+    let Some(fixture) = fixture_from(1024, "4M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("made.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "mkdir p\nstat p\nmkdir p/a\nmkdir p/b\nstat p\nfsck\nclone {}\n",
+            clone.display()
+        ),
+    );
+    let links: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Inode: "))
+        .map(|rest| rest.split("Links: ").nth(1).unwrap())
+        .collect();
+    assert_eq!(links, ["2", "4"], "{}", stdout);
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    let image = std::fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let p = volume.resolve(b"/p").unwrap();
+    assert_eq!(volume.metadata(p).unwrap().nlink, 4);
+}
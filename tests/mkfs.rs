@@ -0,0 +1,61 @@
+mod common;
+
+use common::shell;
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn made_images_are_empty_and_usable() {
+    let dir = std::env::temp_dir().join(format!("ext2-mkfs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (block_size, size) in [("1024", "8M"), ("4096", "200M")] {
+        let image = dir.join(format!("made-{}.ext2", block_size));
+        let _ = fs::remove_file(&image);
+        let mkfs = Command::new(env!("CARGO_BIN_EXE_ext2"))
+            .args(["mkfs", "--size", size, "--block-size", block_size])
+            .args(["--label", "made"])
+            .arg(&image)
+            .output()
+            .unwrap();
+        assert!(mkfs.status.success());
+        // an existing file is left alone
+        let again = Command::new(env!("CARGO_BIN_EXE_ext2"))
+            .arg("mkfs")
+            .arg(&image)
+            .output()
+            .unwrap();
+        assert!(!again.status.success());
+
+        let bytes = fs::read(&image).unwrap();
+        let volume = Volume::open(&bytes[..]).unwrap();
+        let superblock = volume.superblock();
+        assert_eq!(superblock.block_size().to_string(), block_size);
+        assert_eq!(&superblock.volume_name[..5], b"made\0");
+        assert_ne!(superblock.fs_id, [0; 16]);
+        assert_eq!(
+            superblock.inodes_count.get() - superblock.free_inodes_count.get(),
+            11
+        );
+        let names: Vec<Vec<u8>> = volume
+            .read_dir(InodeNo::ROOT)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name_bytes)
+            .collect();
+        assert_eq!(names, [&b"."[..], b"..", b"lost+found"]);
+        assert_eq!(volume.resolve(b"/lost+found").unwrap().get(), 11);
+
+        if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&image).output() {
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stdout)
+            );
+        }
+        let stdout = shell(&image, "mkdir /a\ntouch /a/b\nls /a\nfsck\n");
+        assert!(stdout.contains("no problems found"), "{}", stdout);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
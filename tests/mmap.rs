@@ -0,0 +1,144 @@
+mod common;
+
+use common::{fixture, shell_with, LEAF_CONTENTS, LINK_TARGET};
+use ext2::mmap::Mapping;
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+#[test]
+fn mapped_image_reads_like_a_read_one() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let mapping = Mapping::open(&fixture.image_path(), false).unwrap();
+        assert!(mapping[..] == fixture.image[..]);
+        let volume = Volume::open(&mapping[..]).unwrap();
+        let names: Vec<String> = volume
+            .read_dir(InodeNo::ROOT)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert!(names.iter().any(|name| name == "dir"), "{:?}", names);
+        let leaf = volume.resolve(LINK_TARGET.as_bytes()).unwrap();
+        let mut contents = vec![0; LEAF_CONTENTS.len()];
+        volume.read_file(leaf, 0, &mut contents).unwrap();
+        assert_eq!(contents, LEAF_CONTENTS);
+
+        let stdout = shell_with(&["--mmap"], &fixture.image_path(), "ls\n");
+        assert!(stdout.contains("dir"), "{}", stdout);
+        assert!(!stdout.contains("unable to map"), "{}", stdout);
+    }
+}
+
+#[test]
+fn only_shared_mappings_change_the_file() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let image = fixture.image_path();
+    let stdout = shell_with(&["--mmap"], &image, "mkdir /private\nls\n");
+    assert!(stdout.contains("private"), "{}", stdout);
+    assert!(fs::read(&image).unwrap() == fixture.image);
+
+    shell_with(
+        &["--mmap-write"],
+        &image,
+        "mkdir /shared\ntouch /shared/file\n",
+    );
+    let bytes = fs::read(&image).unwrap();
+    let volume = Volume::open(&bytes[..]).unwrap();
+    volume.resolve(b"/shared/file").unwrap();
+    assert!(volume.resolve(b"/private").is_err());
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&image).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+#[test]
+fn empty_file_falls_back_to_reading() {
+    let dir = std::env::temp_dir().join(format!("ext2-mmap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let empty = dir.join("empty.ext2");
+    fs::write(&empty, b"").unwrap();
+    assert!(Mapping::open(&empty, false).is_err());
+    let output = Command::new(env!("CARGO_BIN_EXE_ext2"))
+        .arg("--mmap")
+        .arg(&empty)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("reading it instead"), "{}", stdout);
+    assert!(stdout.contains("unable to mount file system"), "{}", stdout);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn only_one_shell_writes_back_at_a_time() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let image = fixture.image_path();
+    let first = Mapping::open(&image, true).unwrap();
+    let second = Mapping::open(&image, true);
+    assert_eq!(
+        second.err().map(|e| e.kind()),
+        Some(std::io::ErrorKind::WouldBlock)
+    );
+    // a private mapping writes nothing, so it doesn't need the lock
+    Mapping::open(&image, false).unwrap();
+
+    // a shell that can't have the lock says so and keeps its changes in
+    // memory
+    let stdout = shell_with(&["--mmap-write"], &image, "mkdir /blocked\nls\n");
+    assert!(stdout.contains("is locked"), "{}", stdout);
+    assert!(stdout.contains("blocked"), "{}", stdout);
+    drop(first);
+    let bytes = fs::read(&image).unwrap();
+    assert!(bytes == fixture.image);
+
+    // and once the lock is let go the next one writes
+    let stdout = shell_with(&["--mmap-write"], &image, "mkdir /written\n");
+    assert!(!stdout.contains("is locked"), "{}", stdout);
+    let bytes = fs::read(&image).unwrap();
+    Volume::open(&bytes[..])
+        .unwrap()
+        .resolve(b"/written")
+        .unwrap();
+}
+
+// blocks are sliced out of the mapping when they are asked for, so a big
+// image that is mostly holes mounts without anything being read or built
+// per block. the time is only printed
+#[test]
+fn big_sparse_image_mounts_mapped() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let big = fixture.tree_path().with_file_name("big.ext2");
+    let made = Command::new("mke2fs")
+        .args(["-q", "-F", "-t", "ext2", "-b", "1024", "-N", "2048"])
+        .arg(&big)
+        .arg("2G")
+        .output()
+        .unwrap();
+    assert!(made.status.success());
+    let start = Instant::now();
+    let stdout = shell_with(&["--mmap"], &big, "ls /\n");
+    eprintln!("2G image mounted and listed in {:?}", start.elapsed());
+    assert!(!stdout.contains("reading it instead"), "{}", stdout);
+    assert!(stdout.contains("lost+found"), "{}", stdout);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{fixture_from, shell_with, Fixture};
+use std::fs;
+use std::time::{Duration, Instant};
+
+// one directory, `big`, of `count` empty files
+fn big_dir(count: usize) -> Option<Fixture> {
+    let inodes = (count + 100).to_string();
+    fixture_from(1024, "64M", &["-N", &inodes], |tree| {
+        let big = tree.join("big");
+        fs::create_dir(&big).unwrap();
+        for file in 0..count {
+            fs::write(big.join(format!("f{:05}", file)), b"").unwrap();
+        }
+    })
+}
+
+// `lookups` names in `big`, every `count / lookups`th, and one that
+// isn't there
+fn lookups(count: usize, lookups: usize) -> String {
+    let mut script: String = (0..count)
+        .step_by(count / lookups)
+        .map(|file| format!("lookup big f{:05}\n", file))
+        .collect();
+    script.push_str("lookup big missing\n");
+    script
+}
+
+// the hash finds exactly what the scan does, by lookup, by path and by glob
+#[test]
+fn name_index_matches_scan() {
+    let Some(fixture) = big_dir(2000) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let mut script = lookups(2000, 500);
+    script.push_str("stat big/f01999\nls big/f0001?\ncd big\nlookup . f00000\n");
+    let indexed = shell_with(&[], &fixture.image_path(), &script);
+    assert!(indexed.contains("lookup: "), "{}", indexed);
+    assert!(indexed.contains("\nbig/f00019\n"), "{}", indexed);
+    let scanned = shell_with(&["--no-name-index"], &fixture.image_path(), &script);
+    assert!(indexed == scanned, "{}\n{}", indexed, scanned);
+}
+
+// a removed name can't be found through a hash built before the removal,
+// and a new one can be found through one built before it was made
+#[test]
+fn name_index_follows_changes() {
+    let Some(fixture) = big_dir(100) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell_with(
+        &[],
+        &fixture.image_path(),
+        "lookup big f00050\nrm big/f00050\nlookup big f00050\n\
+         lookup big new\nmkdir big/new\nlookup big new\n",
+    );
+    let answers: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("lookup: ") || line.parse::<usize>().is_ok())
+        .collect();
+    assert_eq!(answers.len(), 4, "{}", stdout);
+    assert!(answers[0].parse::<usize>().is_ok(), "{}", stdout);
+    assert!(answers[1].starts_with("lookup: "), "{}", stdout);
+    assert!(answers[2].starts_with("lookup: "), "{}", stdout);
+    assert!(answers[3].parse::<usize>().is_ok(), "{}", stdout);
+}
+
+// 10,000 lookups in a directory of 50,000 names. run with
+// `cargo test --test name_index -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn name_index_is_faster() {
+    let Some(fixture) = big_dir(50_000) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let script = lookups(50_000, 10_000);
+    let time = |flags: &[&str]| -> (Duration, String) {
+        let start = Instant::now();
+        let stdout = shell_with(flags, &fixture.image_path(), &script);
+        (start.elapsed(), stdout)
+    };
+    let (indexed, indexed_out) = time(&[]);
+    let (scanned, scanned_out) = time(&["--no-name-index"]);
+    eprintln!("hashed {:?}, scanned {:?}", indexed, scanned);
+    assert!(indexed_out == scanned_out);
+    assert!(indexed < scanned, "{:?} {:?}", indexed, scanned);
+}
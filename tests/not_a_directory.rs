@@ -0,0 +1,169 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+// going through a file as if it were a directory fails, names the file,
+// and doesn't act on the directory the path started from
+#[test]
+fn paths_through_a_file_fail() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/file"), b"contents\n").unwrap();
+        fs::write(tree.join("dir/other"), b"other\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd dir\ncat file/deeper\ncd file/deeper\nls\nls file/deeper\nrm file/deeper\n\
+         stat /dir/file/deeper/still\nls\n",
+    );
+    assert!(stdout.contains("cat: file: Not a directory"), "{}", stdout);
+    assert!(stdout.contains("unable to cat file"), "{}", stdout);
+    assert!(stdout.contains("cd: file: Not a directory"), "{}", stdout);
+    assert!(stdout.contains("ls: file: Not a directory"), "{}", stdout);
+    assert!(stdout.contains("rm: file/: Not a directory"), "{}", stdout);
+    assert!(
+        stdout.contains("stat: /dir/file: Not a directory"),
+        "{}",
+        stdout
+    );
+    // nothing of the directory's was printed as if it were the target, and
+    // both listings are still of dir
+    assert!(!stdout.contains("other\n"), "{}", stdout);
+    assert_eq!(stdout.matches("file\tother").count(), 2, "{}", stdout);
+}
+
+// `..` and `.` after a file don't fold it away: the file is no more a
+// directory to come back out of than to go into
+#[test]
+fn dot_dot_after_a_file_fails() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/file"), b"contents\n").unwrap();
+        fs::write(tree.join("dir/other"), b"other\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd dir\ncat file/..\ncd file/..\ncd file/.\nstat /dir/file/../other\nls\n",
+    );
+    assert!(stdout.contains("cat: file: Not a directory"), "{}", stdout);
+    assert!(!stdout.contains("not a file: file/.."), "{}", stdout);
+    assert_eq!(
+        stdout.matches("cd: file: Not a directory").count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("stat: /dir/file: Not a directory"),
+        "{}",
+        stdout
+    );
+    // still in dir
+    assert!(stdout.contains("file\tother"), "{}", stdout);
+}
+
+// cd onto a file, or a symlink to one, fails and stays where it was, so the
+// commands after it still run in the old directory
+#[test]
+fn cd_onto_a_file_keeps_the_cwd() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/file"), b"contents\n").unwrap();
+        std::os::unix::fs::symlink("file", tree.join("dir/link")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd dir\ncd file\npwd\ncd link\npwd\ncd /dir/file/\nls\ncat file\n",
+    );
+    assert!(stdout.contains("cd: file: Not a directory"), "{}", stdout);
+    assert!(stdout.contains("cd: link: Not a directory"), "{}", stdout);
+    assert!(
+        stdout.contains("cd: /dir/file/: Not a directory"),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        stdout.matches("unable to read directory in cd").count(),
+        3,
+        "{}",
+        stdout
+    );
+    assert_eq!(stdout.matches("/dir\n").count(), 2, "{}", stdout);
+    assert!(stdout.contains("file\tlink\t"), "{}", stdout);
+    assert!(stdout.contains("contents\n"), "{}", stdout);
+}
+
+// ls of a file prints its name, or its -l line, the way coreutils does,
+// and a missing name is an error rather than a listing of anything
+#[test]
+fn ls_of_a_file() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/file"), b"contents\n").unwrap();
+        fs::write(tree.join("dir/other"), b"other\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "ls dir/file\nls -l dir/file\nls dir/missing\n",
+    );
+    assert!(stdout.lines().any(|line| line == "dir/file"), "{}", stdout);
+    let long = stdout
+        .lines()
+        .find(|line| line.starts_with("-rw"))
+        .expect(&stdout);
+    assert!(long.contains(" 9 "), "{}", long);
+    assert!(long.ends_with(" dir/file"), "{}", long);
+    assert_eq!(stdout.matches(" dir/file\n").count(), 1, "{}", stdout);
+    assert!(
+        stdout.contains("ls: missing: No such file or directory"),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        stdout.matches("unable to read directory in ls").count(),
+        1,
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("file\tother"), "{}", stdout);
+}
+
+// a socket's mode has the directory's bit set as well, but it is no more a
+// directory than a file is: it can't be the cwd or be gone through, and ls
+// lists it by name
+#[test]
+fn socket_is_not_a_directory() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        std::os::unix::net::UnixListener::bind(tree.join("dir/sock")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd dir\ncd sock\nls sock/deeper\nls sock\nmkdir sock/new\n",
+    );
+    assert!(stdout.contains("cd: sock: Not a directory"), "{}", stdout);
+    assert!(stdout.contains("ls: sock: Not a directory"), "{}", stdout);
+    assert!(stdout.lines().any(|line| line == "sock"), "{}", stdout);
+    assert!(
+        stdout.contains("mkdir: sock/: Not a directory"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("new"), "{}", stdout);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+// debugfs's unlink takes the entry out of the directory and leaves the
+// inode alone, which is the leak orphans is there to find
+#[test]
+fn unlinked_inodes_are_reported() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir_all(tree.join("dir/sub")).unwrap();
+        fs::write(tree.join("dir/leaked"), b"leaked\n").unwrap();
+        fs::write(tree.join("dir/sub/kept"), b"kept\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "orphans\n");
+    assert!(stdout.contains("0 orphaned inodes"), "{}", stdout);
+    assert!(
+        !stdout.contains("file system has orphaned inodes"),
+        "{}",
+        stdout
+    );
+
+    let stdout = shell(&fixture.image_path(), "stat dir/leaked\n");
+    let inode = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Inode: "))
+        .and_then(|rest| rest.split('\t').next())
+        .expect(&stdout)
+        .to_string();
+    if fixture.debugfs("unlink /dir/leaked").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    // a whole subtree cut off shows up too, the directory and its file
+    fixture.debugfs("unlink /dir/sub").unwrap();
+    let stdout = shell(&fixture.image_path(), "orphans\n");
+    assert!(
+        stdout.contains(&format!(
+            "inode {}: file, 7 bytes, 1 links, not in any directory",
+            inode
+        )),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(": dir, 1024 bytes, 2 links,"), "{}", stdout);
+    assert!(stdout.contains(": file, 5 bytes, 1 links,"), "{}", stdout);
+    assert!(stdout.contains("3 orphaned inodes"), "{}", stdout);
+    assert!(
+        stdout.contains("file system has orphaned inodes"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn orphans_are_reconnected_into_lost_found() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir_all(tree.join("dir/sub")).unwrap();
+        fs::write(tree.join("dir/leaked"), b"leaked\n").unwrap();
+        fs::write(tree.join("dir/sub/kept"), b"kept\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "stat dir/leaked\nstat dir/sub\n");
+    let inodes: Vec<String> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Inode: "))
+        .filter_map(|rest| rest.split('\t').next())
+        .map(str::to_string)
+        .collect();
+    let [leaked, sub] = &inodes[..] else {
+        panic!("{}", stdout);
+    };
+    if fixture.debugfs("unlink /dir/leaked").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    // debugfs leaves dir's link count alone, so it still counts the `..`
+    // of sub until sub is reconnected somewhere else
+    fixture.debugfs("unlink /dir/sub").unwrap();
+
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "fsck --reconnect\ncat /lost+found/#{}\ncat /lost+found/#{}/kept\ncd /lost+found/#{}\ncd ..\nls\norphans\nfsck\n",
+            leaked, sub, sub
+        ),
+    );
+    assert!(
+        stdout.contains(&format!(
+            "reconnected inode {} as /lost+found/#{}",
+            leaked, leaked
+        )),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!(
+            "reconnected inode {} as /lost+found/#{}",
+            sub, sub
+        )),
+        "{}",
+        stdout
+    );
+    // kept came along inside sub, it doesn't get an entry of its own
+    assert_eq!(stdout.matches("reconnected inode").count(), 2, "{}", stdout);
+    assert!(stdout.contains("leaked\n"), "{}", stdout);
+    assert!(stdout.contains("kept\n"), "{}", stdout);
+    assert!(stdout.contains("0 orphaned inodes"), "{}", stdout);
+    // the `..` of sub leads to lost+found now, and the link counts add up
+    assert!(stdout.contains(&format!("#{}", leaked)), "{}", stdout);
+    assert!(!stdout.contains("file system has errors"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
+
+// more orphans than fit in one block of a lost+found that only has one
+#[test]
+fn reconnecting_grows_a_full_lost_found() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &["-N", "256"], |tree| {
+        for i in 0..100 {
+            fs::write(tree.join(format!("file{}", i)), b"").unwrap();
+        }
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("rmdir /lost+found").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    for i in 0..100 {
+        fixture.debugfs(&format!("unlink /file{}", i)).unwrap();
+    }
+    let stdout = shell(
+        &fixture.image_path(),
+        "fsck --reconnect\nstat /lost+found\norphans\nfsck\n",
+    );
+    assert_eq!(
+        stdout.matches("reconnected inode").count(),
+        100,
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Size: 2048"), "{}", stdout);
+    assert!(stdout.contains("0 orphaned inodes"), "{}", stdout);
+    assert!(!stdout.contains("file system has errors"), "{}", stdout);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::{fixture_from, shell_status, Fixture};
+use ext2::error::Ext2Error;
+use ext2::partition::{choose_partition, holds_ext2, partitions, PartitionKind};
+use std::fs;
+
+// whether the shell exited happily, and what it printed
+// a 1M file system holding one file, `name`
+fn file_system(name: &str) -> Option<Fixture> {
+    fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join(name), name.as_bytes()).unwrap();
+    })
+}
+
+const MIB: usize = 1024 * 1024;
+// Linux file system data, 0fc63daf-8483-4772-8e79-3d69d8477de4, as it is
+// on disk
+const LINUX_GUID: [u8; 16] = [
+    0xaf, 0x3d, 0xc6, 0x0f, 0x83, 0x84, 0x72, 0x47, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d, 0xe4,
+];
+
+// a disk with `contents` in partitions from 1M on, one every 2M, listed in
+// an MBR with the given types
+fn mbr_disk(contents: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut disk = vec![0; (1 + 2 * contents.len()) * MIB];
+    for (slot, (kind, data)) in contents.iter().enumerate() {
+        let start = (1 + 2 * slot) * MIB;
+        disk[start..start + data.len()].copy_from_slice(data);
+        let entry = &mut disk[446 + slot * 16..446 + (slot + 1) * 16];
+        entry[4] = *kind;
+        entry[8..12].copy_from_slice(&((start / 512) as u32).to_le_bytes());
+        entry[12..16].copy_from_slice(&((2 * MIB / 512) as u32).to_le_bytes());
+    }
+    disk[510] = 0x55;
+    disk[511] = 0xaa;
+    disk
+}
+
+// the same with a GPT, its entries in sectors 2 to 33
+fn gpt_disk(contents: &[&[u8]]) -> Vec<u8> {
+    let mut disk = mbr_disk(&[(0xee, &[])]);
+    disk.resize((1 + 2 * contents.len()) * MIB, 0);
+    disk[512..520].copy_from_slice(b"EFI PART");
+    disk[512 + 0x48..512 + 0x50].copy_from_slice(&2u64.to_le_bytes());
+    disk[512 + 0x50..512 + 0x54].copy_from_slice(&128u32.to_le_bytes());
+    disk[512 + 0x54..512 + 0x58].copy_from_slice(&128u32.to_le_bytes());
+    for (i, data) in contents.iter().enumerate() {
+        let start = (1 + 2 * i) * MIB;
+        disk[start..start + data.len()].copy_from_slice(data);
+        let entry = 1024 + i * 128;
+        disk[entry..entry + 16].copy_from_slice(&LINUX_GUID);
+        let first = (start / 512) as u64;
+        let last = first + (2 * MIB / 512) as u64 - 1;
+        disk[entry + 32..entry + 40].copy_from_slice(&first.to_le_bytes());
+        disk[entry + 40..entry + 48].copy_from_slice(&last.to_le_bytes());
+    }
+    disk
+}
+
+#[test]
+fn mbr_partitions_are_found() {
+    let Some(fixture) = file_system("first") else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let disk = mbr_disk(&[(0x0c, &[0xff; 4096]), (0x83, &fixture.image)]);
+    let device = &disk[..];
+    let found = partitions(&device).unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[1].number, 2);
+    assert_eq!(found[1].start, 3 * MIB as u64);
+    assert_eq!(found[1].len, 2 * MIB as u64);
+    assert_eq!(found[1].kind, PartitionKind::Mbr(0x83));
+    assert!(!holds_ext2(&device, &found[0]));
+    assert!(holds_ext2(&device, &found[1]));
+    // the only ext2 partition is picked without asking
+    assert_eq!(choose_partition(&device, None).unwrap(), Some(found[1]));
+    assert_eq!(choose_partition(&device, Some(1)).unwrap(), Some(found[0]));
+    assert!(matches!(
+        choose_partition(&device, Some(3)),
+        Err(Ext2Error::NotFound(_))
+    ));
+    // a bare file system has no table and is opened whole
+    assert_eq!(choose_partition(&&fixture.image[..], None).unwrap(), None);
+}
+
+#[test]
+fn gpt_partitions_are_found() {
+    let (Some(first), Some(second)) = (file_system("first"), file_system("second")) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let disk = gpt_disk(&[&first.image, &second.image]);
+    let device = &disk[..];
+    let found = partitions(&device).unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].start, MIB as u64);
+    assert_eq!(
+        found[0].kind.to_string(),
+        "type 0fc63daf-8483-4772-8e79-3d69d8477de4"
+    );
+    assert!(found.iter().all(|partition| holds_ext2(&device, partition)));
+    assert!(matches!(
+        choose_partition(&device, None),
+        Err(Ext2Error::Ambiguous(_))
+    ));
+    assert_eq!(choose_partition(&device, Some(2)).unwrap(), Some(found[1]));
+}
+
+#[test]
+fn shell_mounts_a_partition() {
+    let (Some(first), Some(second)) = (file_system("first"), file_system("second")) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let path = first.image_path();
+    fs::write(
+        &path,
+        mbr_disk(&[(0x83, &first.image), (0x83, &second.image)]),
+    )
+    .unwrap();
+
+    let (ok, stdout) = shell_status(&[], &path, "ls\n");
+    assert!(!ok);
+    assert!(stdout.contains("partitions 1, 2 all hold"), "{}", stdout);
+    assert!(
+        stdout.contains("partition 2: 2097152 bytes at offset 3145728"),
+        "{}",
+        stdout
+    );
+
+    let (ok, stdout) = shell_status(&["--partition", "2"], &path, "cat second\n");
+    assert!(ok);
+    assert!(stdout.contains("second"), "{}", stdout);
+    assert!(!stdout.contains("unable to cat file"), "{}", stdout);
+
+    // with only one file system on the disk it's found by itself
+    fs::write(&path, mbr_disk(&[(0x0c, &[]), (0x83, &second.image)])).unwrap();
+    let (ok, stdout) = shell_status(&[], &path, "cat second\n");
+    assert!(ok);
+    assert!(!stdout.contains("unable to cat file"), "{}", stdout);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{fixture, shell, LINK_TARGET};
+use ext2::volume::Volume;
+
+// a file three directories down is found from its inode number, and found
+// again where it went after it's moved
+#[test]
+fn nested_file_back_to_its_path() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let leaf = volume
+        .resolve(format!("/{}", LINK_TARGET).as_bytes())
+        .unwrap();
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "ncheck {0}\nncheck <{0}>\nmv /{1} /dir/moved.txt\nncheck {0}\n",
+            leaf, LINK_TARGET
+        ),
+    );
+    let lines: Vec<&str> = stdout.lines().collect();
+    let found = format!("{}\t/{}", leaf, LINK_TARGET);
+    assert_eq!(
+        lines.iter().filter(|&&l| l == found).count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(
+        lines.contains(&format!("{}\t/dir/moved.txt", leaf).as_str()),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn pwd_follows_cd() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "pwd\ncd dir/nested/deeper\npwd\ncd ..\npwd\n",
+    );
+    let lines: Vec<&str> = stdout.lines().collect();
+    let printed: Vec<&str> = lines
+        .iter()
+        .copied()
+        .filter(|l| l.starts_with('/'))
+        .collect();
+    assert_eq!(
+        printed,
+        ["/", "/dir/nested/deeper", "/dir/nested"],
+        "{}",
+        stdout
+    );
+}
+
+// inode 1 holds the bad blocks, no directory links to it
+#[test]
+fn unreachable_inode() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "ncheck 1\nncheck x\n");
+    assert!(
+        stdout.contains("ncheck: inode 1 can't be reached from the root"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("ncheck: x is not an inode number"),
+        "{}",
+        stdout
+    );
+}
+
+// `resolve` prints every component it looks up, intermediate directories
+// and `..` included, with the inode each led to
+#[test]
+fn resolve_traces_each_step() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let dir = volume.resolve(b"/dir").unwrap();
+    let nested = volume.resolve(b"/dir/nested").unwrap();
+    let sibling = volume.resolve(b"/dir/sibling.txt").unwrap();
+    let stdout = shell(
+        &fixture.image_path(),
+        "resolve dir/nested\nresolve /dir/nested/../sibling.txt\n",
+    );
+    let lines: Vec<&str> = stdout
+        .lines()
+        .map(|l| l.trim_start_matches(":> "))
+        .collect();
+    let expected = [
+        ".\t2\tdirectory".to_string(),
+        format!("dir\t{}\tdirectory", dir),
+        format!("nested\t{}\tdirectory", nested),
+        format!("=> inode {}", nested),
+        "/\t2\tdirectory".to_string(),
+        format!("dir\t{}\tdirectory", dir),
+        format!("nested\t{}\tdirectory", nested),
+        format!("..\t{}\tdirectory", dir),
+        format!("sibling.txt\t{}\tregular file", sibling),
+        format!("=> inode {}", sibling),
+    ];
+    for window in [&expected[..4], &expected[4..]] {
+        assert!(
+            lines.windows(window.len()).any(|lines| lines == window),
+            "{}",
+            stdout
+        );
+    }
+}
+
+// the trace is of resolve itself, so a path it refuses ends in the failure
+// after the last step it took, and never in an inode
+#[test]
+fn resolve_trace_stops_where_resolve_fails() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let one_byte = volume.resolve(b"/one_byte").unwrap();
+    let stdout = shell(&fixture.image_path(), "resolve one_byte/..\n");
+    let lines: Vec<&str> = stdout
+        .lines()
+        .map(|l| l.trim_start_matches(":> "))
+        .collect();
+    let expected = [
+        ".\t2\tdirectory".to_string(),
+        format!("one_byte\t{}\tregular file", one_byte),
+        "=> failed: one_byte: Not a directory".to_string(),
+    ];
+    assert!(
+        lines.windows(expected.len()).any(|lines| lines == expected),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("=> inode"), "{}", stdout);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{fixture_from, shell_with};
+use std::fs;
+use std::os::unix::fs::{chown, PermissionsExt};
+use std::path::Path;
+
+// `path` under `tree`, owned by `uid`:`gid` with mode `mode`
+fn own(tree: &Path, path: &str, uid: u32, gid: u32, mode: u32) {
+    let path = tree.join(path);
+    chown(&path, Some(uid), Some(gid)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn tree(tree: &Path) {
+    for name in ["secret", "shared", "not_for_owner"] {
+        fs::write(tree.join(name), format!("{} contents\n", name)).unwrap();
+    }
+    own(tree, "secret", 1000, 1000, 0o600);
+    own(tree, "shared", 1000, 100, 0o640);
+    own(tree, "not_for_owner", 1000, 1000, 0o044);
+    fs::create_dir(tree.join("private")).unwrap();
+    fs::write(tree.join("private/inside"), b"inside\n").unwrap();
+    own(tree, "private", 1000, 1000, 0o700);
+    fs::create_dir(tree.join("read_only")).unwrap();
+    fs::write(tree.join("read_only/kept"), b"kept\n").unwrap();
+    own(tree, "read_only", 1000, 1000, 0o555);
+}
+
+// files can only be set up with other owners by root, and mke2fs has to
+// keep them
+fn fixture() -> Option<common::Fixture> {
+    let fixture = std::panic::catch_unwind(|| fixture_from(1024, "1M", &[], tree)).ok()??;
+    Some(fixture)
+}
+
+fn can_read(stdout: &str, name: &str) -> bool {
+    stdout.contains(&format!("{} contents\n", name))
+}
+
+#[test]
+fn reading_follows_the_mode_bits() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs or chown not available, skipping");
+        return;
+    };
+    let script = "cat secret\ncat shared\ncat not_for_owner\n";
+    let owner = shell_with(
+        &["--uid", "1000", "--gid", "1000"],
+        &fixture.image_path(),
+        script,
+    );
+    assert!(can_read(&owner, "secret"), "{}", owner);
+    assert!(can_read(&owner, "shared"), "{}", owner);
+    // only the owner's bits count for the owner
+    assert!(!can_read(&owner, "not_for_owner"), "{}", owner);
+    assert!(
+        owner.contains("cat: not_for_owner: Permission denied"),
+        "{}",
+        owner
+    );
+
+    let group = shell_with(
+        &["--uid", "1001", "--gid", "100"],
+        &fixture.image_path(),
+        script,
+    );
+    assert!(!can_read(&group, "secret"), "{}", group);
+    assert!(
+        group.contains("cat: secret: Permission denied"),
+        "{}",
+        group
+    );
+    assert!(can_read(&group, "shared"), "{}", group);
+    // group 100 isn't the file's group, so everyone else's bits count
+    assert!(can_read(&group, "not_for_owner"), "{}", group);
+
+    let other = shell_with(&["--uid", "1001"], &fixture.image_path(), script);
+    assert!(!can_read(&other, "secret"), "{}", other);
+    assert!(!can_read(&other, "shared"), "{}", other);
+    assert!(can_read(&other, "not_for_owner"), "{}", other);
+
+    // root, and no identity at all, read everything
+    for flags in [&["--uid", "0"][..], &[]] {
+        let stdout = shell_with(flags, &fixture.image_path(), script);
+        for name in ["secret", "shared", "not_for_owner"] {
+            assert!(can_read(&stdout, name), "{:?}\n{}", flags, stdout);
+        }
+    }
+}
+
+#[test]
+fn directories_need_search_and_write_permission() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs or chown not available, skipping");
+        return;
+    };
+    let script = "cat private/inside\nls private\ntouch read_only/new\n\
+                  rm read_only/kept\ntouch secret\ncd private\n";
+    let stranger = shell_with(
+        &["--uid", "1001", "--gid", "1001"],
+        &fixture.image_path(),
+        script,
+    );
+    for denied in [
+        "cat: private/inside: Permission denied",
+        "ls: private: Permission denied",
+        "cd: private: Permission denied",
+        "touch: new: Permission denied",
+        "rm: kept: Permission denied",
+        "touch: secret: Permission denied",
+    ] {
+        assert!(stranger.contains(denied), "{}\n{}", denied, stranger);
+    }
+
+    // the owner gets into private, but read_only is read only for them too
+    let owner = shell_with(
+        &["--uid", "1000", "--gid", "1000"],
+        &fixture.image_path(),
+        script,
+    );
+    assert!(owner.contains("inside\n"), "{}", owner);
+    assert!(!owner.contains("private: Permission denied"), "{}", owner);
+    assert!(owner.contains("touch: new: Permission denied"), "{}", owner);
+    assert!(owner.contains("rm: kept: Permission denied"), "{}", owner);
+    assert!(!owner.contains("touch: secret"), "{}", owner);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{fixture_from, shell};
+
+// a directory made with --blocks has them from the start, and entries fill
+// them without the directory growing
+#[test]
+fn preallocated_directory_takes_entries_without_allocating() {
+    let Some(fixture) = fixture_from(1024, "1M", &["-N", "256"], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let mut script = "mkdir --blocks 4 big\nstat big\n".to_string();
+    // about 80 of these fit in a 1024 byte block
+    for i in 0..200 {
+        script.push_str(&format!("touch big/file{}\n", i));
+    }
+    script.push_str("stat big\nls big\nmkdir --blocks 13 toobig\n");
+    let stdout = shell(&fixture.image_path(), &script);
+
+    assert!(stdout.contains("mkdir: allocated inode "), "{}", stdout);
+    assert!(stdout.contains(", 4 blocks\n"), "{}", stdout);
+    assert_eq!(
+        stdout.matches("Size: 4096\tBlocks: 8\tdirectory").count(),
+        2,
+        "{}",
+        stdout
+    );
+    let touches: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("touch: "))
+        .collect();
+    assert_eq!(touches.len(), 200, "{}", stdout);
+    assert!(
+        touches.iter().all(|line| line.ends_with(", 0 blocks")),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("file0\t"), "{}", stdout);
+    assert!(stdout.contains("file199\t"), "{}", stdout);
+    assert!(
+        stdout.contains("mkdir: --blocks takes a number from 1 to 12"),
+        "{}",
+        stdout
+    );
+}
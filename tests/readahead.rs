@@ -0,0 +1,106 @@
+mod common;
+
+use common::{fixture_from, pattern};
+use ext2::error::Ext2Error;
+use ext2::volume::{BlockDevice, Volume, DEFAULT_READAHEAD};
+use std::cell::Cell;
+use std::fs;
+
+const LEN: usize = 10 << 20;
+
+// an image in memory that counts how often it's read
+struct Counting<'a> {
+    image: &'a [u8],
+    reads: Cell<usize>,
+}
+
+impl BlockDevice for Counting<'_> {
+    fn size(&self) -> u64 {
+        self.image.size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<(), Ext2Error> {
+        self.reads.set(self.reads.get() + 1);
+        self.image.read_at(offset, buf)
+    }
+}
+
+// read all of /big in `chunk` byte reads and return how many device reads
+// that took
+fn sequential(image: &[u8], readahead: usize, chunk: usize) -> usize {
+    let device = Counting {
+        image,
+        reads: Cell::new(0),
+    };
+    let mut volume = Volume::open(&device).unwrap();
+    volume.set_readahead(readahead);
+    let inode = volume.resolve(b"/big").unwrap();
+    let before = device.reads.get();
+    let mut data = vec![0; LEN];
+    for start in (0..LEN).step_by(chunk) {
+        let end = (start + chunk).min(LEN);
+        let read = volume
+            .read_file(inode, start as u64, &mut data[start..end])
+            .unwrap();
+        assert_eq!(read, end - start);
+    }
+    assert!(
+        data == pattern(LEN),
+        "wrong data with readahead {}",
+        readahead
+    );
+    device.reads.get() - before
+}
+
+// read scattered bits of /big, never carrying on from the last read, and
+// return how many device reads that took
+fn scattered(image: &[u8], readahead: usize) -> usize {
+    let device = Counting {
+        image,
+        reads: Cell::new(0),
+    };
+    let mut volume = Volume::open(&device).unwrap();
+    volume.set_readahead(readahead);
+    let inode = volume.resolve(b"/big").unwrap();
+    let before = device.reads.get();
+    let expected = pattern(LEN);
+    let mut state = 0x2545_f491_u64;
+    for _ in 0..200 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let offset = 1 + (state % (LEN as u64 - 1000)) as usize;
+        let mut buf = [0; 700];
+        volume.read_file(inode, offset as u64, &mut buf).unwrap();
+        assert_eq!(buf[..], expected[offset..offset + 700]);
+    }
+    device.reads.get() - before
+}
+
+#[test]
+fn sequential_reads_read_ahead() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture_from(block_size, "16M", &[], |tree| {
+            fs::write(tree.join("big"), pattern(LEN)).unwrap();
+        }) else {
+            eprintln!("mke2fs not available, skipping");
+            return;
+        };
+        let image = &fixture.image[..];
+
+        let without = sequential(image, 0, 64 << 10);
+        let with = sequential(image, DEFAULT_READAHEAD, 64 << 10);
+        eprintln!(
+            "{} byte blocks: {} device reads without readahead, {} with",
+            block_size, without, with
+        );
+        assert!(with * 4 < without, "{} vs {}", with, without);
+        // a read of a block at a time gets the same data, and a window of a
+        // single block does too
+        sequential(image, DEFAULT_READAHEAD, block_size);
+        sequential(image, 1, 3000);
+
+        // random reads don't read anything ahead
+        assert_eq!(scattered(image, DEFAULT_READAHEAD), scattered(image, 0));
+    }
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+fn fixture() -> Option<common::Fixture> {
+    fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir_all(tree.join("a/b/sub")).unwrap();
+        fs::create_dir_all(tree.join("a/b/sibling")).unwrap();
+        fs::create_dir_all(tree.join("a/b/x")).unwrap();
+        fs::write(tree.join("a/b/sibling/file"), b"sibling\n").unwrap();
+        fs::write(tree.join("a/b/x/only_in_x"), b"x\n").unwrap();
+        // same name one level up, so starting from the wrong place shows
+        fs::write(tree.join("a/file"), b"wrong\n").unwrap();
+    })
+}
+
+// relative paths start at the current directory however deep it is, and
+// `..` goes up from there rather than from the root
+#[test]
+fn relative_paths_start_at_the_cwd() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd a/b/sub\ncat ../sibling/file\nls ./../x/../x\n\
+         cd ../x\ncat ./../sibling/file\nls ./../x/.././x\n\
+         cd ../../..\ncat ./a/b/sibling/../sibling/file\nls ./a/b/x/../x\n",
+    );
+    // count whole lines, less the tab ls ends each name with
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert_eq!(
+        lines.iter().filter(|&&l| l == "sibling").count(),
+        3,
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        lines.iter().filter(|&&l| l == "only_in_x").count(),
+        3,
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("wrong"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
+
+// an absolute path ignores the current directory
+#[test]
+fn absolute_paths_start_at_the_root() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd a/b/x\ncat /a/b/sibling/file\ncat /a/file\nls /a/b/x/../x\ncat /../../a/file\n",
+    );
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert!(lines.contains(&"sibling"), "{}", stdout);
+    assert_eq!(
+        lines.iter().filter(|&&l| l == "wrong").count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(lines.contains(&"only_in_x"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
+
+// any run of slashes is one, wherever it is, and only slashes is the root.
+// `cd ` with a trailing space is a bare `cd`, which goes to the root too
+#[test]
+fn repeated_slashes_are_one_separator() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cd a//b\ncd //\nls\ncat a//b///sibling//file\ncat //a/b//sibling/file\nls /\n\
+         cd a\ncd ///\nls\ncd a\ncd \nls\n",
+    );
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    // the root's listing, after `cd //`, for `ls /`, after `cd ///` and
+    // after `cd `
+    assert_eq!(
+        lines.iter().filter(|&&l| l == "a\tlost+found").count(),
+        4,
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        lines.iter().filter(|&&l| l == "sibling").count(),
+        2,
+        "{}",
+        stdout
+    );
+    assert!(!lines.contains(&"b\tfile"), "{}", stdout);
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{fixture_from, pattern, shell};
+use ext2::volume::Volume;
+use std::fs;
+use std::process::Command;
+
+// a 1024 block file system in groups of 512 blocks and 32 inodes, nearly
+// filled by one file, grown to 4000 blocks. the directories made after
+// that need inodes and blocks from the new groups
+#[test]
+fn grown_groups_are_allocated_from() {
+    let Some(fixture) = fixture_from(
+        1024,
+        "1024",
+        &["-O", "^resize_inode", "-g", "512", "-N", "64"],
+        |tree| fs::write(tree.join("big"), pattern(900_000)).unwrap(),
+    ) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let before = Volume::open(&fixture.image[..]).unwrap();
+    let old_inodes = before.superblock().inodes_count.get();
+    let old_blocks = before.superblock().blocks_count.get();
+    assert_eq!(before.block_groups().len(), 2);
+
+    let clone = fixture.tree_path().with_file_name("grown.ext2");
+    let mut script = String::from("resizefs 100\nresizefs 4000\n");
+    for i in 0..120 {
+        script.push_str(&format!("mkdir /n{}\n", i));
+    }
+    script.push_str(&format!("fsck\nclone {}\n", clone.display()));
+    let stdout = shell(&fixture.image_path(), &script);
+    // shrinking isn't possible
+    assert_eq!(
+        stdout.matches("unable to resize file system").count(),
+        1,
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let superblock = volume.superblock();
+    assert_eq!(superblock.blocks_count.get(), 4000);
+    assert_eq!(volume.block_groups().len(), 8);
+    assert_eq!(superblock.inodes_count.get(), 8 * old_inodes / 2);
+    let free: u32 = volume
+        .block_groups()
+        .iter()
+        .map(|group| group.free_blocks_count.get() as u32)
+        .sum();
+    assert_eq!(free, superblock.free_blocks_count.get());
+
+    let last = volume.resolve(b"/n119").unwrap();
+    assert!(last.get() > old_inodes);
+    let block = volume.read_inode(last).unwrap().direct_pointer[0].get();
+    assert!(block >= old_blocks, "block {} is in an old group", block);
+    let mut data = vec![0; 900_000];
+    let big = volume.resolve(b"/big").unwrap();
+    volume.read_file(big, 0, &mut data).unwrap();
+    assert!(data == pattern(900_000));
+
+    // e2fsck has the last word, if it's there
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&clone).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
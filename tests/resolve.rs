@@ -0,0 +1,121 @@
+mod common;
+
+use common::fixture;
+use ext2::error::Ext2Error;
+use ext2::path::{resolve, resolve_parent};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+
+// the path shapes resolve has to get right, against the tree every fixture
+// has: /dir/nested/deeper/leaf.txt, /dir/sibling.txt and the file /one_byte
+#[test]
+fn path_shapes() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let at = |base, path: &str| resolve(&volume, base, path.as_bytes());
+    let root = InodeNo::ROOT;
+    let dir = at(root, "/dir").unwrap();
+    let nested = at(root, "/dir/nested").unwrap();
+    let sibling = at(root, "/dir/sibling.txt").unwrap();
+
+    // slashes: only slashes is the root, runs of them are one, and a
+    // trailing one is fine on a directory
+    assert_eq!(at(dir, "/").unwrap(), root);
+    assert_eq!(at(dir, "//").unwrap(), root);
+    assert_eq!(at(root, "//dir///nested").unwrap(), nested);
+    assert_eq!(at(root, "/dir/nested/").unwrap(), nested);
+    // `..` can't go above the root, and folds away the name before it
+    assert_eq!(at(nested, "/..").unwrap(), root);
+    assert_eq!(at(root, "/../../dir").unwrap(), dir);
+    assert_eq!(at(root, "/dir/nested/../sibling.txt").unwrap(), sibling);
+    // relative paths start at the base, `.` is where they are and a leading
+    // `..` is looked up there
+    assert_eq!(at(dir, ".").unwrap(), dir);
+    assert_eq!(at(dir, "./././").unwrap(), dir);
+    assert_eq!(at(dir, "nested/deeper/..").unwrap(), nested);
+    assert_eq!(at(nested, "..").unwrap(), dir);
+    assert_eq!(at(nested, "../sibling.txt").unwrap(), sibling);
+
+    assert!(matches!(at(dir, ""), Err(Ext2Error::InvalidPath(_))));
+    assert!(matches!(
+        at(root, "/dir/missing"),
+        Err(Ext2Error::NotFound(name)) if name == "missing"
+    ));
+    assert!(matches!(
+        at(root, "/one_byte/"),
+        Err(Ext2Error::NotADirectory(path)) if path == "/one_byte/"
+    ));
+    assert!(matches!(
+        at(root, "/one_byte/x"),
+        Err(Ext2Error::NotADirectory(path)) if path == "/one_byte"
+    ));
+    // `..` and `.` only fold away after a directory
+    for path in ["/one_byte/..", "/one_byte/.", "/one_byte/../dir"] {
+        assert!(
+            matches!(
+                at(root, path),
+                Err(Ext2Error::NotADirectory(name)) if name == "/one_byte"
+            ),
+            "{}",
+            path
+        );
+    }
+    assert!(matches!(
+        at(root, "one_byte/.."),
+        Err(Ext2Error::NotADirectory(name)) if name == "one_byte"
+    ));
+    assert!(matches!(
+        at(root, "/dir/sibling.txt/../nested"),
+        Err(Ext2Error::NotADirectory(name)) if name == "/dir/sibling.txt"
+    ));
+}
+
+#[test]
+fn parent_shapes() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let parent = |base, path: &str| {
+        resolve_parent(&volume, base, path.as_bytes())
+            .map(|(parent, name)| (parent, String::from_utf8(name.to_vec()).unwrap()))
+    };
+    let root = InodeNo::ROOT;
+    let dir = resolve(&volume, root, b"/dir").unwrap();
+    let nested = resolve(&volume, root, b"/dir/nested").unwrap();
+
+    assert_eq!(parent(dir, "new").unwrap(), (dir, "new".to_string()));
+    assert_eq!(parent(root, "/dir//new").unwrap(), (dir, "new".to_string()));
+    // the name can be missing or a directory with trailing slashes
+    assert_eq!(parent(root, "dir/new//").unwrap(), (dir, "new".to_string()));
+    assert_eq!(parent(dir, "nested/").unwrap(), (dir, "nested".to_string()));
+    assert_eq!(
+        parent(nested, "../sibling.txt").unwrap(),
+        (dir, "sibling.txt".to_string())
+    );
+
+    // there is no name to make or remove in these
+    for path in ["", "/", "//", ".", "dir/..", "/.."] {
+        assert!(
+            matches!(parent(root, path), Err(Ext2Error::InvalidPath(_))),
+            "{:?}",
+            path
+        );
+    }
+    assert!(matches!(
+        parent(root, "/dir/sibling.txt/"),
+        Err(Ext2Error::NotADirectory(path)) if path == "/dir/sibling.txt/"
+    ));
+    assert!(matches!(
+        parent(root, "/one_byte/new"),
+        Err(Ext2Error::NotADirectory(path)) if path == "/one_byte/"
+    ));
+    assert!(matches!(
+        parent(root, "/missing/new"),
+        Err(Ext2Error::NotFound(name)) if name == "missing"
+    ));
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::path::Path;
+
+// the image that comes with the repository is a dynamic revision one with
+// 256 byte inodes
+#[test]
+fn bundled_image() {
+    let image = Path::new(env!("CARGO_MANIFEST_DIR")).join("myfs.ext2");
+    let stdout = shell(&image, "rev\nrev now\n");
+    assert!(
+        stdout.contains(
+            "revision: 1 (dynamic)\nminor revision: 0\nfirst inode: 11\ninode size: 256\n"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("usage: rev"), "{}", stdout);
+}
+
+// revision 0 has neither field, whatever the superblock holds there
+#[test]
+fn good_old_revision() {
+    let Some(fixture) = fixture_from(1024, "1M", &["-r", "0"], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(&fixture.image_path(), "rev\n");
+    assert!(
+        stdout.contains(
+            "revision: 0 (good old)\nminor revision: 0\nfirst inode: 11\ninode size: 128\n"
+        ),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::structs::{InodeNo, TypeIndicator};
+use ext2::volume::Volume;
+use std::fs;
+use std::process::Command;
+
+// a revision 0 file system reads like any other, new inodes come after
+// the 10 reserved ones, and the entries for them say nothing about their
+// type since there's no filetype feature to say it with
+#[test]
+fn revision_0_image() {
+    let Some(fixture) = fixture_from(1024, "1M", &["-r", "0"], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+        fs::write(tree.join("dir/file"), b"hello\n").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    assert_eq!(volume.superblock().rev_major.get(), 0);
+    assert!(!volume.superblock().has_file_types());
+
+    let clone = fixture.tree_path().with_file_name("written.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "ls\ncat dir/file\nmkdir new\ntouch new/x\nfsck\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(stdout.contains("dir\tlost+found\t"), "{}", stdout);
+    assert!(stdout.contains("hello\n"), "{}", stdout);
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let new = volume.resolve(b"/new").unwrap();
+    let x = volume.resolve(b"/new/x").unwrap();
+    assert!(new.get() >= 11 && x.get() >= 11);
+    assert!(volume.metadata(new).unwrap().is_dir());
+    assert_eq!(volume.metadata(x).unwrap().size, 0);
+    for dir in [InodeNo::ROOT, new] {
+        for entry in volume.read_dir(dir).unwrap() {
+            assert_eq!(entry.kind, TypeIndicator::Unknown, "{}", entry.name);
+        }
+    }
+
+    // e2fsck has the last word, if it's there
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&clone).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
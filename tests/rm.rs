@@ -0,0 +1,249 @@
+mod common;
+
+use common::{fixture_from, shell};
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn tree(tree: &Path) {
+    fs::create_dir_all(tree.join("a/b/c")).unwrap();
+    fs::write(tree.join("a/b/file"), b"contents\n").unwrap();
+    fs::write(tree.join("file"), b"top\n").unwrap();
+}
+
+// a directory with something in it only goes with rm -r. rm and rmdir
+// refuse it and change nothing at all
+#[test]
+fn only_rm_r_removes_a_populated_directory() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let refused = fixture.tree_path().with_file_name("refused.ext2");
+    let removed = fixture.tree_path().with_file_name("removed.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "rm a\nrmdir a\nrmdir file\nclone {}\nrm -r a\nfsck\nclone {}\n",
+            refused.display(),
+            removed.display()
+        ),
+    );
+    assert!(stdout.contains("rm: a: Directory not empty"), "{}", stdout);
+    assert!(
+        stdout.contains("rmdir: a: Directory not empty"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("rmdir: file: Not a directory"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+    assert!(fs::read(&refused).unwrap() == fixture.image);
+
+    let image = fs::read(&removed).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert!(volume.resolve(b"/a").is_err());
+    assert!(volume.resolve(b"/file").is_ok());
+    let original = Volume::open(&fixture.image[..]).unwrap();
+    // a, b, c and the file in b
+    assert_eq!(
+        volume.superblock().free_inodes_count.get(),
+        original.superblock().free_inodes_count.get() + 4
+    );
+}
+
+// the current directory and the ones above it can't be removed, however
+// they are named
+#[test]
+fn the_current_directory_stays() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let clone = fixture.tree_path().with_file_name("kept.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "cd a/b/c\nrmdir .\nrm -r /a\nrm -r ../../../a\nrmdir /a/b/c\nls\nclone {}\n",
+            clone.display()
+        ),
+    );
+    assert!(stdout.contains("rmdir: invalid path: \".\""), "{}", stdout);
+    assert!(
+        stdout.contains("rm: /a: Device or resource busy"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("rm: ../../../a: Device or resource busy"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("rmdir: /a/b/c: Device or resource busy"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("freed"), "{}", stdout);
+    assert!(fs::read(&clone).unwrap() == fixture.image);
+}
+
+// once removals leave the last block of a directory with nothing in it,
+// the block goes back to the free blocks and the directory shrinks. the
+// first block stays even with everything removed
+#[test]
+fn emptied_last_directory_block_is_freed() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir(tree.join("dir")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let touch: String = (0..100).map(|i| format!("touch dir/f{:03}\n", i)).collect();
+    let rm: String = (0..100).map(|i| format!("rm dir/f{:03}\n", i)).collect();
+    let grown = fixture.tree_path().with_file_name("grown.ext2");
+    let emptied = fixture.tree_path().with_file_name("emptied.ext2");
+    shell(
+        &fixture.image_path(),
+        &format!(
+            "{}clone {}\n{}clone {}\n",
+            touch,
+            grown.display(),
+            rm,
+            emptied.display()
+        ),
+    );
+
+    let image = fs::read(&grown).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let dir = volume.resolve(b"/dir").unwrap();
+    let metadata = volume.metadata(dir).unwrap();
+    assert_eq!(metadata.size, 2048);
+    assert_eq!(metadata.blocks, 4);
+    let free = volume.superblock().free_blocks_count.get();
+
+    let image = fs::read(&emptied).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let metadata = volume.metadata(dir).unwrap();
+    assert_eq!(metadata.size, 1024);
+    assert_eq!(metadata.blocks, 2);
+    assert_eq!(volume.superblock().free_blocks_count.get(), free + 1);
+    assert_eq!(volume.read_dir(dir).unwrap().len(), 2);
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&emptied).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+// `rm --trash` only moves a file into /.trash, a second one of the same
+// name gets a suffix, and `mv` puts both back as they were
+#[test]
+fn trashed_files_come_back_with_mv() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], tree) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let nested = volume.resolve(b"/a/b/file").unwrap();
+    let top = volume.resolve(b"/file").unwrap();
+
+    let trashed = fixture.tree_path().with_file_name("trashed.ext2");
+    let restored = fixture.tree_path().with_file_name("restored.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "rm --trash a/b/file\nrm --trash file\nclone {}\n\
+             mv /.trash/file a/b/file\nmv /.trash/file.1 file\nfsck\nclone {}\n",
+            trashed.display(),
+            restored.display()
+        ),
+    );
+    assert!(!stdout.contains("unable"), "{}", stdout);
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+
+    let image = fs::read(&trashed).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert!(volume.resolve(b"/a/b/file").is_err());
+    assert!(volume.resolve(b"/file").is_err());
+    assert_eq!(volume.resolve(b"/.trash/file").unwrap(), nested);
+    assert_eq!(volume.resolve(b"/.trash/file.1").unwrap(), top);
+
+    let image = fs::read(&restored).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    for (path, inode, contents) in [
+        (&b"/a/b/file"[..], nested, &b"contents\n"[..]),
+        (b"/file", top, b"top\n"),
+    ] {
+        assert_eq!(volume.resolve(path).unwrap(), inode);
+        let mut data = vec![0; 64];
+        let len = volume.read_file(inode, 0, &mut data).unwrap();
+        assert_eq!(&data[..len], contents);
+        assert_eq!(volume.metadata(inode).unwrap().nlink, 1);
+    }
+    let trash = volume.resolve(b"/.trash").unwrap();
+    let names: Vec<String> = volume
+        .read_dir(trash)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    assert_eq!(names, [".", ".."]);
+}
+
+// a fast symlink's target is in its block pointers even when it has an
+// attribute block, so removing it frees the attribute block and nothing
+// else, and so does removing a file with one
+#[test]
+fn removing_frees_the_attribute_block() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &["-I", "128"], |tree| {
+        fs::write(tree.join("file"), b"data").unwrap();
+        std::os::unix::fs::symlink("file", tree.join("link")).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let free_blocks = |image: &[u8]| {
+        Volume::open(image)
+            .unwrap()
+            .superblock()
+            .free_blocks_count
+            .get()
+    };
+    let before = free_blocks(&fixture.image);
+    if fixture.debugfs("ea_set /link user.comment one").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    fixture.debugfs("ea_set /file user.comment two").unwrap();
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let link = volume
+        .read_inode(volume.resolve(b"/link").unwrap())
+        .unwrap();
+    assert_ne!(link.ext_attribute_block.get(), 0);
+    assert!(!volume.has_block_pointers(&link));
+    assert_eq!(free_blocks(&fixture.image), before - 2);
+
+    let clone = fixture.tree_path().with_file_name("removed.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("rm link\nrm file\nfsck\nclone {}\n", clone.display()),
+    );
+    assert!(stdout.contains("no problems found"), "{}", stdout);
+    let image = fs::read(&clone).unwrap();
+    // the file's data block is gone too
+    assert_eq!(free_blocks(&image), before + 1);
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(&clone).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+use std::process::Command;
+
+// `cargo +stable <args>` on this crate, into a target directory of its own
+// so it doesn't wait on the one running the tests. None if rustup or the
+// stable toolchain isn't there to run it
+fn cargo_stable(args: &[&str]) -> Option<(bool, String)> {
+    let installed = Command::new("cargo")
+        .args(["+stable", args[0], "--help"])
+        .output()
+        .ok()?;
+    if !installed.status.success() {
+        return None;
+    }
+    let output = Command::new("cargo")
+        .arg("+stable")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("stable"),
+        )
+        .output()
+        .unwrap();
+    Some((
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
+}
+
+// nothing nightly-only is used: the crate, its tests and its benches build
+// on stable. the dependencies are the ones this test was built with, so it
+// runs offline. clippy is left to CI, a lint is no reason for the tests to
+// fail
+#[test]
+fn builds_on_stable() {
+    let args = ["build", "--offline", "--all-targets"];
+    let Some((success, stderr)) = cargo_stable(&args) else {
+        eprintln!("no stable toolchain to run cargo build with, skipping");
+        return;
+    };
+    assert!(success, "cargo +stable {}:\n{}", args.join(" "), stderr);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{fixture, shell};
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::process::Command;
+
+// run the shell on the fixture's image with `script` as input
+// extract `archive` with the host's tar into a new directory `into`
+fn extract(archive: &Path, into: &Path) -> Option<()> {
+    fs::create_dir(into).unwrap();
+    let output = Command::new("tar")
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(into)
+        .output()
+        .ok()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(())
+}
+
+// check that `extracted` holds the same files, links and modes as `tree`
+fn same_tree(tree: &Path, extracted: &Path) {
+    for entry in fs::read_dir(tree).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name();
+        let original = entry.path();
+        let copy = extracted.join(&name);
+        let metadata = fs::symlink_metadata(&original).unwrap();
+        let copied =
+            fs::symlink_metadata(&copy).unwrap_or_else(|_| panic!("{} is missing", copy.display()));
+        assert_eq!(
+            metadata.file_type(),
+            copied.file_type(),
+            "{}",
+            copy.display()
+        );
+        if metadata.is_symlink() {
+            assert_eq!(
+                fs::read_link(&original).unwrap(),
+                fs::read_link(&copy).unwrap()
+            );
+            continue;
+        }
+        assert_eq!(
+            metadata.permissions().mode() & 0o7777,
+            copied.permissions().mode() & 0o7777,
+            "{}",
+            copy.display()
+        );
+        if metadata.is_dir() {
+            same_tree(&original, &copy);
+        } else {
+            assert_eq!(fs::read(&original).unwrap(), fs::read(&copy).unwrap());
+            assert_eq!(copied.nlink(), metadata.nlink(), "{}", copy.display());
+        }
+    }
+}
+
+#[test]
+fn tar_round_trips_through_host_tar() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        // the fixture's tree still has the file that was deleted from the image
+        let tree = fixture.tree_path();
+        fs::remove_file(tree.join("deleted.txt")).unwrap();
+
+        let work = tree.parent().unwrap();
+        let whole = work.join("whole.tar");
+        let subtree = work.join("subtree.tar");
+        let script = format!(
+            "tar / {}\ntar /dir/ {}\n",
+            whole.display(),
+            subtree.display()
+        );
+        let stdout = shell(&fixture.image_path(), &script);
+        assert!(!stdout.contains("tar:"), "{}", stdout);
+
+        let Some(()) = extract(&whole, &work.join("whole")) else {
+            eprintln!("tar not available, skipping");
+            return;
+        };
+        same_tree(&tree, &work.join("whole"));
+        assert!(work.join("whole/lost+found").is_dir());
+
+        // a subtree is named after its last component
+        extract(&subtree, &work.join("subtree")).unwrap();
+        same_tree(&tree.join("dir"), &work.join("subtree/dir"));
+        let entries: Vec<_> = fs::read_dir(work.join("subtree")).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+}
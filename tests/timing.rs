@@ -0,0 +1,28 @@
+mod common;
+
+use common::{fixture, shell_with};
+
+#[test]
+fn time_follows_each_command() {
+    let Some(fixture) = fixture(1024) else {
+        eprintln!("mke2fs/debugfs not available, skipping");
+        return;
+    };
+    for (args, timed) in [(&["--time"][..], true), (&[][..], false)] {
+        let stdout = shell_with(args, &fixture.image_path(), "ls\ncd dir\nls\n");
+        let times: Vec<&str> = stdout
+            .lines()
+            .filter(|line| line.starts_with("time: "))
+            .collect();
+        if !timed {
+            assert!(times.is_empty(), "{}", stdout);
+            continue;
+        }
+        assert_eq!(times.len(), 3, "{}", stdout);
+        assert!(times[0].starts_with("time: ls took "), "{}", stdout);
+        assert!(times[1].starts_with("time: cd took "), "{}", stdout);
+        // the listing comes before its time
+        let listing = stdout.find("nested").unwrap();
+        assert!(listing < stdout.rfind("time: ls took ").unwrap());
+    }
+}
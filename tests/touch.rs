@@ -0,0 +1,57 @@
+mod common;
+
+use common::{fixture_from, pattern, shell};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use std::fs;
+
+// touching a file only moves its times forward, its data stays where it
+// was; `touch -c` on a name that isn't there makes nothing
+#[test]
+fn touch_changes_only_the_times() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("file"), pattern(5000)).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("sif /file mtime 100").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let inode = volume.resolve(b"/file").unwrap();
+    let before = volume.metadata(inode).unwrap();
+    let blocks_before: Vec<_> = volume
+        .block_ptrs(&volume.read_inode(inode).unwrap())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let free_before = volume.superblock().free_blocks_count.get();
+    let entries_before = volume.read_dir(InodeNo::ROOT).unwrap().len();
+    assert_eq!(before.mtime, 100);
+
+    let clone = fixture.tree_path().with_file_name("touched.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("touch -c missing\ntouch file\nclone {}\n", clone.display()),
+    );
+    assert!(!stdout.contains("touch:"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert!(volume.resolve(b"/missing").is_err());
+    assert_eq!(
+        volume.read_dir(InodeNo::ROOT).unwrap().len(),
+        entries_before
+    );
+    let after = volume.metadata(inode).unwrap();
+    assert!(after.mtime > 100, "mtime is still {}", after.mtime);
+    assert_eq!(after.size, before.size);
+    assert_eq!(after.blocks, before.blocks);
+    let blocks_after: Vec<_> = volume
+        .block_ptrs(&volume.read_inode(inode).unwrap())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(blocks_after, blocks_before);
+    assert_eq!(volume.superblock().free_blocks_count.get(), free_before);
+}
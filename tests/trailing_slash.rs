@@ -0,0 +1,74 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+fn fixture() -> Option<common::Fixture> {
+    fixture_from(1024, "1M", &[], |tree| {
+        fs::create_dir_all(tree.join("dir/sub")).unwrap();
+        fs::create_dir(tree.join("empty")).unwrap();
+        fs::write(tree.join("dir/inside"), b"inside\n").unwrap();
+        fs::write(tree.join("file"), b"file\n").unwrap();
+    })
+}
+
+// one trailing slash or several, a directory is found just the same
+#[test]
+fn trailing_slashes_on_directories_are_ignored() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "ls dir/\nls dir///\ncd dir/\nls\ncd /\ncd dir///sub//\ncd ../\nls\ncd /\n\
+         stat empty/\nrm empty///\nmv dir// moved/\nls\nmkdir new//\nmknod made/ dir\nls\n",
+    );
+    assert_eq!(stdout.matches("inside\tsub\t").count(), 4, "{}", stdout);
+    assert!(stdout.contains("  File: empty/\n"), "{}", stdout);
+    assert!(
+        stdout.contains("\nfile\tlost+found\tmoved\t\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("\nfile\tlost+found\tmade\tmoved\tnew\t\n"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("unable"), "{}", stdout);
+}
+
+// a trailing slash says the path is a directory, so on anything else it's
+// an error, and nothing is changed or made
+#[test]
+fn trailing_slashes_on_files_are_not_a_directory() {
+    let Some(fixture) = fixture() else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "cat file/\ncat file//\ncd file/\nstat file/\nrm file/\nrm file///\nmv file/ other\n\
+         mv file other/\ntouch new/\nmknod new// file\nls\n",
+    );
+    for error in [
+        "cat: file/: Not a directory",
+        "cat: file//: Not a directory",
+        "cd: file/: Not a directory",
+        "stat: file/: Not a directory",
+        "rm: file/: Not a directory",
+        "rm: file///: Not a directory",
+        "mv: file/: Not a directory",
+        "mv: other/: Not a directory",
+        "touch: new/: Not a directory",
+        "mknod: new//: Not a directory",
+    ] {
+        assert!(stdout.contains(error), "{}: {}", error, stdout);
+    }
+    assert!(
+        stdout.contains("\ndir\tempty\tfile\tlost+found\t\n"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::fixture;
+use ext2::error::Ext2Error;
+use ext2::volume::Volume;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// an image cut short, at a block boundary or in the middle of a block, is
+// refused when it's opened instead of reading past its end later
+#[test]
+fn truncated_image_is_refused() {
+    for block_size in [1024, 4096] {
+        let Some(fixture) = fixture(block_size) else {
+            eprintln!("mke2fs/debugfs not available, skipping");
+            return;
+        };
+        let whole = fixture.image.len();
+        for len in [whole / 2, whole / 2 + 100, whole - 1] {
+            let cut = &fixture.image[..len];
+            match Volume::open(cut) {
+                Err(Ext2Error::Corrupt(message)) => {
+                    assert!(message.contains("blocks_count"), "{}", message)
+                }
+                Err(e) => panic!("{} bytes: {}", len, e),
+                Ok(_) => panic!("{} of {} bytes opened", len, whole),
+            }
+
+            let image = fixture.tree_path().with_file_name("truncated.ext2");
+            fs::write(&image, cut).unwrap();
+            let mut shell = Command::new(env!("CARGO_BIN_EXE_ext2"))
+                .arg(&image)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            shell
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(b"cat /doubly\n")
+                .unwrap();
+            let output = shell.wait_with_output().unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert_eq!(output.status.code(), Some(1), "{}", stdout);
+            assert!(
+                stdout.contains("unable to mount file system: ")
+                    && stdout.contains("does not match a device of"),
+                "{}",
+                stdout
+            );
+        }
+    }
+}
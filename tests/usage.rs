@@ -0,0 +1,46 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+// each command that allocates or frees something says what, and one that
+// doesn't says nothing
+#[test]
+fn commands_report_what_they_allocate_and_free() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |tree| {
+        fs::write(tree.join("file"), vec![b'x'; 3000]).unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let stdout = shell(
+        &fixture.image_path(),
+        "touch new\ntouch new\nstat new\nmkdir dir\nrm file\nls\n",
+    );
+    let reports: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.contains("allocated") || line.contains("freed"))
+        .collect();
+    let inode = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Inode: "))
+        .and_then(|rest| rest.split('\t').next())
+        .expect(&stdout);
+    assert_eq!(
+        reports[0],
+        format!("touch: allocated inode {}, 0 blocks", inode),
+        "{}",
+        stdout
+    );
+    // the second touch only sets times
+    assert!(
+        reports[1].starts_with("mkdir: allocated inode "),
+        "{}",
+        stdout
+    );
+    assert!(reports[1].ends_with(", 1 block"), "{}", stdout);
+    // 3000 bytes in 1024 byte blocks
+    assert!(reports[2].starts_with("rm: freed inode "), "{}", stdout);
+    assert!(reports[2].ends_with(", 3 blocks"), "{}", stdout);
+    assert_eq!(reports.len(), 3, "{}", stdout);
+}
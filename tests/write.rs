@@ -0,0 +1,110 @@
+mod common;
+
+use common::{doubly_indirect_len, fixture_from, pattern, shell};
+use ext2::volume::Volume;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// the clone has `path` holding `expected`, and e2fsck, if it's there, is
+// happy with it
+fn check_clone(clone: &Path, path: &str, expected: &[u8]) {
+    let image = fs::read(clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let inode = volume.resolve(path.as_bytes()).unwrap();
+    assert_eq!(volume.metadata(inode).unwrap().size, expected.len() as u64);
+    let mut data = vec![0; expected.len()];
+    assert_eq!(volume.read_file(inode, 0, &mut data).unwrap(), data.len());
+    assert!(data == expected);
+    if let Ok(output) = Command::new("e2fsck").arg("-fn").arg(clone).output() {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+// 15 blocks: the 12 direct ones and 3 under the indirect pointer
+#[test]
+fn file_past_the_direct_blocks() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let host = fixture.tree_path().with_file_name("fifteen");
+    let contents = pattern(15 * 1024 - 10);
+    fs::write(&host, &contents).unwrap();
+    let clone = fixture.tree_path().with_file_name("written.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!(
+            "write {} fifteen\nclone {}\n",
+            host.display(),
+            clone.display()
+        ),
+    );
+    assert!(!stdout.contains("unable"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let inode = volume.resolve(b"/fifteen").unwrap();
+    let node = volume.read_inode(inode).unwrap();
+    assert_ne!(node.indirect_pointer.get(), 0);
+    assert_eq!(node.doubly_indirect.get(), 0);
+    // 15 data blocks and the indirect one
+    assert_eq!(volume.metadata(inode).unwrap().blocks, 16 * 2);
+    check_clone(&clone, "/fifteen", &contents);
+}
+
+#[test]
+fn file_past_the_indirect_block() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let host = fixture.tree_path().with_file_name("big");
+    let contents = pattern(doubly_indirect_len(1024));
+    fs::write(&host, &contents).unwrap();
+    let clone = fixture.tree_path().with_file_name("written.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("write {} big\nclone {}\n", host.display(), clone.display()),
+    );
+    assert!(!stdout.contains("unable"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    let node = volume.read_inode(volume.resolve(b"/big").unwrap()).unwrap();
+    assert_ne!(node.doubly_indirect.get(), 0);
+    check_clone(&clone, "/big", &contents);
+}
+
+// a file that doesn't fit leaves nothing behind
+#[test]
+fn file_too_big_is_not_created() {
+    let Some(fixture) = fixture_from(1024, "1M", &[], |_| {}) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let host = fixture.tree_path().with_file_name("huge");
+    fs::write(&host, pattern(2 * 1024 * 1024)).unwrap();
+    let volume = Volume::open(&fixture.image[..]).unwrap();
+    let free = volume.superblock().free_blocks_count.get();
+    let clone = fixture.tree_path().with_file_name("written.ext2");
+    let stdout = shell(
+        &fixture.image_path(),
+        &format!("write {} huge\nclone {}\n", host.display(), clone.display()),
+    );
+    assert!(
+        stdout.contains("write: No space left on device"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("unable to write file"), "{}", stdout);
+
+    let image = fs::read(&clone).unwrap();
+    let volume = Volume::open(&image[..]).unwrap();
+    assert!(volume.resolve(b"/huge").is_err());
+    assert_eq!(volume.superblock().free_blocks_count.get(), free);
+}
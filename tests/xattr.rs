@@ -0,0 +1,64 @@
+mod common;
+
+use common::{fixture_from, shell};
+use std::fs;
+
+// 128 byte inodes have no room for attributes, so debugfs puts them all in
+// an attribute block
+#[test]
+fn attributes_in_a_block() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &["-I", "128"], |tree| {
+        fs::write(tree.join("file"), b"data").unwrap();
+        fs::write(tree.join("plain"), b"data").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    if fixture.debugfs("ea_set /file user.comment hello").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    fixture.debugfs("ea_set /file trusted.tag 42").unwrap();
+    let stdout = shell(
+        &fixture.image_path(),
+        "getxattr file\ngetxattr plain\ngetxattr missing\ngetxattr\n",
+    );
+    assert!(stdout.contains("user.comment=\"hello\"\n"), "{}", stdout);
+    assert!(stdout.contains("trusted.tag=\"42\"\n"), "{}", stdout);
+    assert_eq!(stdout.matches("=\"").count(), 2, "{}", stdout);
+    assert!(stdout.contains("usage: getxattr path"), "{}", stdout);
+    assert_eq!(
+        stdout.matches("unable to read extended attributes").count(),
+        2,
+        "{}",
+        stdout
+    );
+}
+
+// with 256 byte inodes a small value stays in the inode and one too big
+// for it goes to the block
+#[test]
+fn attributes_in_the_inode_and_a_block() {
+    let Some(mut fixture) = fixture_from(1024, "1M", &["-I", "256"], |tree| {
+        fs::write(tree.join("file"), b"data").unwrap();
+    }) else {
+        eprintln!("mke2fs not available, skipping");
+        return;
+    };
+    let big = "v".repeat(300);
+    let value = fixture.tree_path().join("value");
+    fs::write(&value, &big).unwrap();
+    if fixture.debugfs("ea_set /file user.small tiny").is_none() {
+        eprintln!("debugfs not available, skipping");
+        return;
+    }
+    fixture
+        .debugfs(&format!("ea_set -f {} /file user.big", value.display()))
+        .unwrap();
+    let stdout = shell(&fixture.image_path(), "getxattr /file\n");
+    let small = stdout.find("user.small=\"tiny\"\n").expect(&stdout);
+    let in_block = stdout
+        .find(&format!("user.big=\"{}\"\n", big))
+        .expect(&stdout);
+    assert!(small < in_block, "{}", stdout);
+}
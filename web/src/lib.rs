@@ -0,0 +1,126 @@
+//! The read-only `Volume` for JavaScript, through wasm-bindgen. The page
+//! hands over the bytes of an image and asks for directories, files and
+//! metadata by path. Paths are names as `escape_name` shows them, which is
+//! how `list_dir` gives them out, joined by `/`
+
+use ext2::error::Ext2Error;
+use ext2::metadata::{escape_name, unescape_name, Metadata};
+use ext2::structs::InodeNo;
+use ext2::volume::Volume;
+use js_sys::{Array, Object, Reflect};
+use std::cmp;
+use wasm_bindgen::prelude::*;
+
+// how much `read_file` reads at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// An image loaded from the page
+#[wasm_bindgen]
+pub struct Image {
+    volume: Volume<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl Image {
+    /// Mount the image in `bytes`, e.g. a `Uint8Array` of a dropped file
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: Vec<u8>) -> Result<Image, JsError> {
+        Ok(Image {
+            volume: Volume::open(bytes).map_err(js_error)?,
+        })
+    }
+
+    /// The entries of the directory at `path`, `.` and `..` left out, as an
+    /// array of objects shaped like `stat`'s with a `name` as well
+    pub fn list_dir(&self, path: &str) -> Result<JsValue, JsError> {
+        let dir = self.resolve(path)?;
+        let entries = Array::new();
+        for entry in self.volume.read_dir(dir).map_err(js_error)? {
+            if entry.name_bytes == b"." || entry.name_bytes == b".." {
+                continue;
+            }
+            let metadata = self.volume.metadata(entry.inode).map_err(js_error)?;
+            let object = stat_object(&metadata);
+            set(&object, "name", entry.name.into());
+            entries.push(&object);
+        }
+        Ok(entries.into())
+    }
+
+    /// The contents of the regular file at `path`, a `Uint8Array` on the
+    /// JavaScript side
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, JsError> {
+        let inode = self.resolve(path)?;
+        let metadata = self.volume.metadata(inode).map_err(js_error)?;
+        if !metadata.is_file() {
+            return Err(JsError::new(&format!("{} is not a regular file", path)));
+        }
+        // the size comes from the image and can be anything, so nothing is
+        // allocated for it up front: the buffer grows a chunk at a time as
+        // reads succeed
+        let mut data = Vec::new();
+        let mut chunk = vec![0; CHUNK_SIZE];
+        while (data.len() as u64) < metadata.size {
+            match self.volume.read_file(inode, data.len() as u64, &mut chunk) {
+                Ok(0) => break,
+                Ok(n) => data.extend_from_slice(&chunk[..n]),
+                Err(e) => return Err(js_error(e)),
+            }
+        }
+        Ok(data)
+    }
+
+    /// What the inode at `path` says about itself: `inode`, `type` (file,
+    /// dir, symlink...), `mode` (as `ls -l` shows it), `perms`, `nlink`,
+    /// `uid`, `gid`, `size`, `atime`, `mtime` and `ctime`
+    pub fn stat(&self, path: &str) -> Result<JsValue, JsError> {
+        let inode = self.resolve(path)?;
+        let metadata = self.volume.metadata(inode).map_err(js_error)?;
+        Ok(stat_object(&metadata).into())
+    }
+
+    /// The target of the symlink at `path`
+    pub fn read_link(&self, path: &str) -> Result<String, JsError> {
+        let inode = self.resolve(path)?;
+        let metadata = self.volume.metadata(inode).map_err(js_error)?;
+        // a target is never longer than a block, whatever the size says
+        let mut target = vec![0; cmp::min(metadata.size, self.volume.block_size() as u64) as usize];
+        let len = self
+            .volume
+            .read_link(inode, &mut target)
+            .map_err(js_error)?;
+        Ok(escape_name(&target[..len]))
+    }
+}
+
+impl Image {
+    fn resolve(&self, path: &str) -> Result<InodeNo, JsError> {
+        self.volume.resolve(&unescape_name(path)).map_err(js_error)
+    }
+}
+
+fn stat_object(metadata: &Metadata) -> Object {
+    let object = Object::new();
+    set(&object, "inode", metadata.ino.get().into());
+    set(&object, "type", metadata.file_type.name().into());
+    set(&object, "mode", metadata.mode_string().into());
+    set(&object, "perms", metadata.perms.into());
+    set(&object, "nlink", metadata.nlink.into());
+    set(&object, "uid", metadata.uid.into());
+    set(&object, "gid", metadata.gid.into());
+    // a JavaScript number holds sizes up to 2^53 exactly, plenty for ext2
+    set(&object, "size", (metadata.size as f64).into());
+    set(&object, "atime", metadata.atime.into());
+    set(&object, "mtime", metadata.mtime.into());
+    set(&object, "ctime", metadata.ctime.into());
+    object
+}
+
+fn set(object: &Object, key: &str, value: JsValue) {
+    // only fails for frozen objects and proxies, which these aren't
+    Reflect::set(object, &key.into(), &value).unwrap();
+}
+
+fn js_error(e: Ext2Error) -> JsError {
+    JsError::new(&e.to_string())
+}